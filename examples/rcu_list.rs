@@ -0,0 +1,164 @@
+//! A sorted singly linked set of integers, protected by nothing but an `RcuDomain`'s raw
+//! primitives: readers walk it inside `RcuDomain::read_lock` through
+//! `DomainReadGuard::dereference`, while writers (serialized by a mutex) link nodes in with
+//! `RcuDomain::assign` and free unlinked ones with `RcuDomain::retire`.
+//!
+//! Run with `cargo run --release --example rcu_list`.
+
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering::Relaxed};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use rcu_rust::RcuDomain;
+
+struct Node {
+    value: u64,
+    next: AtomicPtr<Node>,
+}
+
+/// Hands nodes to `RcuDomain::retire`.
+unsafe fn free_node(node: *mut Node) {
+    drop(Box::from_raw(node));
+}
+
+struct List {
+    domain: RcuDomain,
+    head: AtomicPtr<Node>,
+    /// Only one writer may relink nodes at a time, readers never take it
+    writer: Mutex<()>,
+}
+
+impl List {
+    fn new() -> Self {
+        Self {
+            domain: RcuDomain::new(),
+            head: AtomicPtr::new(ptr::null_mut()),
+            writer: Mutex::new(()),
+        }
+    }
+
+    fn contains(&self, value: u64) -> bool {
+        let guard = self.domain.read_lock();
+        // Safety: every node is published with `assign` and only freed with `retire`
+        let mut node = unsafe { guard.dereference(&self.head) };
+        while let Some(cur) = node {
+            if cur.value >= value {
+                return cur.value == value;
+            }
+            node = unsafe { guard.dereference(&cur.next) };
+        }
+        false
+    }
+
+    /// Sum and length of the elements, from a single walk.
+    fn sum(&self) -> (u64, usize) {
+        let guard = self.domain.read_lock();
+        let (mut sum, mut len) = (0, 0);
+        // Safety: as in `contains`
+        let mut node = unsafe { guard.dereference(&self.head) };
+        while let Some(cur) = node {
+            sum += cur.value;
+            len += 1;
+            node = unsafe { guard.dereference(&cur.next) };
+        }
+        (sum, len)
+    }
+
+    /// The slot pointing to the first node not smaller than `value`, and that node. Only for
+    /// writers: without a read lock the nodes are only safe to touch while holding `self.writer`.
+    fn find(&self, value: u64) -> (&AtomicPtr<Node>, *mut Node) {
+        let mut slot = &self.head;
+        loop {
+            let node = slot.load(Relaxed);
+            // Safety: nodes are only retired by writers, and we are the only one
+            match unsafe { node.as_ref() } {
+                Some(cur) if cur.value < value => slot = &cur.next,
+                _ => return (slot, node),
+            }
+        }
+    }
+
+    fn insert(&self, value: u64) -> bool {
+        let _writer = self.writer.lock().unwrap();
+        let (slot, next) = self.find(value);
+        // Safety: as in `find`
+        if unsafe { next.as_ref() }.is_some_and(|next| next.value == value) {
+            return false;
+        }
+        let node = Box::into_raw(Box::new(Node {
+            value,
+            next: AtomicPtr::new(next),
+        }));
+        self.domain.assign(slot, node);
+        true
+    }
+
+    fn remove(&self, value: u64) -> bool {
+        let writer = self.writer.lock().unwrap();
+        let (slot, node) = self.find(value);
+        // Safety: as in `find`
+        let Some(cur) = (unsafe { node.as_ref() }).filter(|cur| cur.value == value) else {
+            return false;
+        };
+        self.domain.assign(slot, cur.next.load(Relaxed));
+        // Retiring may wait for readers, no need to hold up other writers meanwhile
+        drop(writer);
+        // Safety: just unlinked, and only this writer could have done that
+        unsafe { self.domain.retire(node, free_node) };
+        true
+    }
+}
+
+impl Drop for List {
+    fn drop(&mut self) {
+        let mut node = *self.head.get_mut();
+        while !node.is_null() {
+            // Safety: no readers or writers are left, and unlinked nodes were already retired
+            let mut cur = unsafe { Box::from_raw(node) };
+            node = *cur.next.get_mut();
+        }
+    }
+}
+
+fn main() {
+    let list = List::new();
+    // The even numbers stay put, the odd ones come and go
+    for value in (0..1000).step_by(2) {
+        list.insert(value);
+    }
+    let even_sum: u64 = (0..1000).step_by(2).sum();
+    let stop = AtomicBool::new(false);
+    let walks = AtomicU64::new(0);
+
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                while !stop.load(Relaxed) {
+                    let (sum, len) = list.sum();
+                    // Every even element is always there, odd ones add to it
+                    assert!(sum >= even_sum && len >= 500, "lost elements: sum {sum}, len {len}");
+                    assert!(list.contains(500));
+                    walks.fetch_add(1, Relaxed);
+                }
+            });
+        }
+        for writer in 0..2u64 {
+            let (list, stop) = (&list, &stop);
+            s.spawn(move || {
+                let mut value = 1 + 2 * writer;
+                while !stop.load(Relaxed) {
+                    list.insert(value);
+                    list.remove(value);
+                    value = (value + 4) % 1000;
+                }
+            });
+        }
+        thread::sleep(Duration::from_secs(1));
+        stop.store(true, Relaxed);
+    });
+
+    let (sum, len) = list.sum();
+    println!("{} walks, {len} elements left summing to {sum}", walks.into_inner());
+}
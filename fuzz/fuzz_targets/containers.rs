@@ -0,0 +1,50 @@
+//! Interprets the input as writes to the crate's other containers, `LeftRight`, `TripleBuffer` and
+//! `RcuSeq`, made by one writer thread while reader threads keep checking every value they see.
+//! Writes run in input order, so a crashing input replays the same writes on every run.
+
+#![no_main]
+
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+
+use libfuzzer_sys::fuzz_target;
+use rcu_rust::{LeftRight, RcuSeq, TripleBuffer};
+
+/// Reader threads per container
+const READERS: usize = 2;
+
+/// A value that shows tearing: its seed repeated.
+fn is_uniform(value: &[u8]) -> bool {
+    value.iter().all(|b| *b == value[0])
+}
+
+fuzz_target!(|data: &[u8]| {
+    let lr = LeftRight::new(vec![0u8; 32]);
+    let seq = RcuSeq::new([0u8; 24]);
+    let (mut producer, consumer) = TripleBuffer::new(vec![0u8; 32]).split();
+    let done = AtomicBool::new(false);
+    std::thread::scope(|s| {
+        for _ in 0..READERS {
+            let (lr, seq, done) = (&lr, &seq, &done);
+            let mut consumer = consumer.clone();
+            s.spawn(move || {
+                while !done.load(Relaxed) {
+                    assert!(lr.read_with(|value| is_uniform(value)));
+                    assert!(is_uniform(&seq.read()));
+                    assert!(is_uniform(consumer.latest()));
+                }
+            });
+        }
+        let mut writer = lr.writer().unwrap();
+        for bytes in data.chunks_exact(2) {
+            let arg = bytes[1];
+            match bytes[0] % 4 {
+                0 => writer.apply(|value| value.iter_mut().for_each(|b| *b = arg)),
+                1 => seq.write([arg; 24]),
+                2 => producer.publish(vec![arg; 32]),
+                _ => producer.publish_with(|value| value.iter_mut().for_each(|b| *b = arg)),
+            }
+        }
+        drop(writer);
+        done.store(true, Relaxed);
+    });
+});
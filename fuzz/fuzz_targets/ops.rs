@@ -0,0 +1,102 @@
+//! Interprets the input as a sequence of operations on a single `Rcu<Vec<u8>>`, dealt out round
+//! robin to a few threads. Each thread runs its share in input order, so a crashing input replays
+//! the same operations on every run, only their interleaving across threads varies.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rcu_rust::{Rcu, RcuSubscriber};
+
+/// Threads the operations are dealt out to
+const THREADS: usize = 3;
+/// Subscribers a single thread may hold at once
+const MAX_SUBSCRIBERS: usize = 16;
+
+/// One operation, decoded from two input bytes: which one, and its argument.
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    Read,
+    ReadWith,
+    Update(u8),
+    Set(u8),
+    UpdateWith(u8),
+    Subscribe,
+    ReadSubscriber,
+    DropSubscriber,
+    Clone,
+    /// Publishes a value the invariant refuses
+    Reject,
+}
+
+impl Op {
+    fn decode(bytes: &[u8]) -> Self {
+        let arg = bytes[1];
+        match bytes[0] % 10 {
+            0 => Op::Read,
+            1 => Op::ReadWith,
+            2 => Op::Update(arg),
+            3 => Op::Set(arg),
+            4 => Op::UpdateWith(arg),
+            5 => Op::Subscribe,
+            6 => Op::ReadSubscriber,
+            7 => Op::DropSubscriber,
+            8 => Op::Clone,
+            _ => Op::Reject,
+        }
+    }
+}
+
+/// The value published for `arg`: its own length seed repeated, so any torn or freed value shows.
+fn payload(arg: u8) -> Vec<u8> {
+    vec![arg; arg as usize % 64]
+}
+
+fn is_payload(value: &[u8]) -> bool {
+    value.is_empty() || (value.len() == value[0] as usize % 64 && value.iter().all(|b| *b == value[0]))
+}
+
+fn run(rcu: &Rcu<Vec<u8>>, ops: impl Iterator<Item = Op>) {
+    let mut subscribers: Vec<RcuSubscriber<'_, Vec<u8>>> = Vec::new();
+    for op in ops {
+        match op {
+            Op::Read => assert!(is_payload(&rcu.read())),
+            Op::ReadWith => assert!(rcu.read_with(|value| is_payload(value))),
+            Op::Update(arg) => {
+                rcu.update(payload(arg));
+            }
+            Op::Set(arg) => rcu.set(payload(arg)).unwrap(),
+            Op::UpdateWith(arg) => {
+                let published = rcu
+                    .update_with(|cur| payload(cur.first().copied().unwrap_or(0).wrapping_add(arg)))
+                    .unwrap();
+                assert!(is_payload(&published));
+            }
+            Op::Subscribe if subscribers.len() < MAX_SUBSCRIBERS => subscribers.push(rcu.subscribe()),
+            Op::Subscribe => {}
+            Op::ReadSubscriber => {
+                if let Some(subscriber) = subscribers.last() {
+                    assert!(is_payload(&subscriber.read()));
+                }
+            }
+            Op::DropSubscriber => drop(subscribers.pop()),
+            Op::Clone => {
+                let value = rcu.read();
+                assert_eq!(value.clone(), value);
+            }
+            Op::Reject => assert!(rcu.set(vec![1, 2]).is_err()),
+        }
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let ops: Vec<Op> = data.chunks_exact(2).map(Op::decode).collect();
+    let rcu = Rcu::new(Vec::new());
+    rcu.set_invariant(|value| is_payload(value).then_some(()).ok_or_else(|| "not a payload".to_string()));
+    std::thread::scope(|s| {
+        for thread in 0..THREADS {
+            let (rcu, ops) = (&rcu, &ops);
+            s.spawn(move || run(rcu, ops.iter().copied().skip(thread).step_by(THREADS)));
+        }
+    });
+    assert!(is_payload(&rcu.into_box()));
+});
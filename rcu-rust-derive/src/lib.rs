@@ -0,0 +1,183 @@
+//! `#[derive(RcuFields)]`, re-exported by `rcu_rust` with its `derive` feature.
+//!
+//! For a struct with named fields, generates a `<Name>Rcu` struct holding every field in its own
+//! `rcu_rust::Rcu`, so fields can be read and published independently of each other. See the
+//! re-export, `rcu_rust::RcuFields`, for an example.
+//!
+//! Per field, `<Name>Rcu` gets a getter returning a clone of the current value, a `set_<field>`
+//! setter and a `<field>_rcu` accessor to the `Rcu` itself (for subscribing, hooks, invariants...).
+//! It also gets `snapshot()`, assembling a whole struct from the current values, and a `From`
+//! impl for the original struct.
+//!
+//! Attributes:
+//! - `#[rcu(name = "...")]` on the struct names the generated struct.
+//! - `#[rcu(rename = "...")]` on a field names its accessors.
+//! - `#[rcu(skip)]` on a field keeps it out of any `Rcu`: it is stored as a plain, immutable value
+//!   with no accessors, and cloned into snapshots.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitStr};
+
+#[proc_macro_derive(RcuFields, attributes(rcu))]
+pub fn derive_rcu_fields(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+struct Field {
+    ident: Ident,
+    ty: syn::Type,
+    /// Base name of the accessors
+    name: Ident,
+    skip: bool,
+}
+
+const NOT_A_STRUCT: &str = "RcuFields can only be derived for structs";
+
+/// Parses the identifier in a `name = "..."` or `rename = "..."` attribute.
+fn ident_value(meta: &syn::meta::ParseNestedMeta) -> syn::Result<Ident> {
+    let value = meta.value()?.parse::<LitStr>()?;
+    value
+        .parse()
+        .map_err(|_| syn::Error::new(value.span(), format!("`{}` isn't an identifier", value.value())))
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let data = match &input.data {
+        Data::Struct(data) => data,
+        Data::Enum(data) => return Err(syn::Error::new(data.enum_token.span, NOT_A_STRUCT)),
+        Data::Union(data) => return Err(syn::Error::new(data.union_token.span, NOT_A_STRUCT)),
+    };
+    let Fields::Named(named) = &data.fields else {
+        let span = match &data.fields {
+            Fields::Unit => data.semi_token.map_or_else(Span::call_site, |semi| semi.span),
+            fields => fields.span(),
+        };
+        return Err(syn::Error::new(span, "RcuFields needs a struct with named fields"));
+    };
+
+    let mut rcu_name = format_ident!("{}Rcu", input.ident);
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("rcu")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                rcu_name = ident_value(&meta)?;
+                Ok(())
+            } else {
+                Err(meta.error("expected `name = \"...\"`"))
+            }
+        })?;
+    }
+
+    let mut fields = Vec::new();
+    for field in &named.named {
+        let ident = field.ident.clone().expect("named field");
+        let mut name = ident.clone();
+        let mut skip = false;
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("rcu")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    skip = true;
+                    Ok(())
+                } else if meta.path.is_ident("rename") {
+                    name = ident_value(&meta)?;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `skip` or `rename = \"...\"`"))
+                }
+            })?;
+        }
+        fields.push(Field {
+            ident,
+            ty: field.ty.clone(),
+            name,
+            skip,
+        });
+    }
+
+    let vis = &input.vis;
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    // `Rcu` requires `Clone`, and so does cloning skipped fields into snapshots
+    let mut where_clause = where_clause.cloned().unwrap_or_else(|| syn::parse_quote!(where));
+    for field in &fields {
+        let ty = &field.ty;
+        where_clause.predicates.push(syn::parse_quote!(#ty: ::core::clone::Clone));
+    }
+
+    let storage = fields.iter().map(|Field { ident, ty, skip, .. }| {
+        if *skip {
+            quote!(#ident: #ty)
+        } else {
+            quote!(#ident: ::rcu_rust::Rcu<#ty>)
+        }
+    });
+    let accessors = fields.iter().filter(|field| !field.skip).map(|Field { ident, ty, name, .. }| {
+        let setter = format_ident!("set_{}", name);
+        let rcu = format_ident!("{}_rcu", name);
+        let get_doc = format!("Returns a clone of the current `{ident}`.");
+        let set_doc = format!("Publishes a new `{ident}`, see `Rcu::set`.");
+        let rcu_doc = format!("The `Rcu` holding `{ident}`.");
+        quote! {
+            #[doc = #get_doc]
+            #vis fn #name(&self) -> #ty {
+                self.#ident.read()
+            }
+            #[doc = #set_doc]
+            #vis fn #setter(&self, value: #ty) -> ::core::result::Result<(), ::rcu_rust::PublishError<#ty>> {
+                self.#ident.set(value)
+            }
+            #[doc = #rcu_doc]
+            #vis fn #rcu(&self) -> &::rcu_rust::Rcu<#ty> {
+                &self.#ident
+            }
+        }
+    });
+    let snapshot = fields.iter().map(|Field { ident, skip, .. }| {
+        if *skip {
+            quote!(#ident: ::core::clone::Clone::clone(&self.#ident))
+        } else {
+            quote!(#ident: self.#ident.read())
+        }
+    });
+    let from = fields.iter().map(|Field { ident, skip, .. }| {
+        if *skip {
+            quote!(#ident: value.#ident)
+        } else {
+            quote!(#ident: ::rcu_rust::Rcu::new(value.#ident))
+        }
+    });
+
+    let struct_doc = format!("`{ident}` with every field in its own `Rcu`, generated by `#[derive(RcuFields)]`.");
+    let snapshot_doc = format!(
+        "Assembles a `{ident}` from the current value of every field. Fields are read one after \
+         the other, so the snapshot is only consistent per field: a field published concurrently \
+         may or may not be seen, independently of the other fields."
+    );
+    Ok(quote! {
+        #[doc = #struct_doc]
+        #vis struct #rcu_name #impl_generics #where_clause {
+            #(#storage,)*
+        }
+
+        impl #impl_generics #rcu_name #ty_generics #where_clause {
+            #(#accessors)*
+            #[doc = #snapshot_doc]
+            #vis fn snapshot(&self) -> #ident #ty_generics {
+                #ident {
+                    #(#snapshot,)*
+                }
+            }
+        }
+
+        impl #impl_generics ::core::convert::From<#ident #ty_generics> for #rcu_name #ty_generics #where_clause {
+            fn from(value: #ident #ty_generics) -> Self {
+                Self {
+                    #(#from,)*
+                }
+            }
+        }
+    })
+}
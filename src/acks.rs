@@ -0,0 +1,135 @@
+use std::sync::atomic::{fence, AtomicU64, Ordering::{Relaxed, SeqCst}};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+use std::time::Duration;
+
+use crate::error::LaggingSubscribers;
+use crate::notify::Notify;
+use crate::staleness::{Staleness, View};
+
+/// The subscribers of one `Rcu` created with `subscribe_acked`, for publishers to wait for.
+#[derive(Default)]
+pub(crate) struct Acks {
+    /// Every live acked subscriber's view, by id
+    views: Mutex<Vec<(u64, Weak<View>)>>,
+    next_id: AtomicU64,
+    /// Wakes publishers waiting for subscribers to catch up
+    observed: Notify,
+}
+
+impl Acks {
+    /// Starts waiting for the subscriber reading through `view`, returning its id.
+    pub(crate) fn register(&self, view: &Arc<View>) -> u64 {
+        let id = self.next_id.fetch_add(1, Relaxed);
+        self.views.lock().unwrap_or_else(PoisonError::into_inner).push((id, Arc::downgrade(view)));
+        id
+    }
+
+    /// Stops waiting for the subscriber `id`, which is being dropped.
+    pub(crate) fn unregister(&self, id: u64) {
+        self.views.lock().unwrap_or_else(PoisonError::into_inner).retain(|(other, _)| *other != id);
+        self.observed.notify();
+    }
+
+    /// To be called after an acked subscriber recorded a read in its view.
+    pub(crate) fn observed(&self) {
+        // Orders the view's relaxed store before the check for waiters, as the fence in `lagging`
+        // orders the waiter's registration before its loads of the views
+        fence(SeqCst);
+        self.observed.notify();
+    }
+
+    /// The id and staleness of every live acked subscriber whose last read returned a version older
+    /// than `version`, in creation order.
+    fn lagging(&self, version: u64) -> Vec<(u64, Staleness)> {
+        fence(SeqCst);
+        let views = self.views.lock().unwrap_or_else(PoisonError::into_inner);
+        views
+            .iter()
+            .filter_map(|(id, view)| Some((*id, view.upgrade()?.staleness())))
+            .filter(|(_, staleness)| staleness.version < version)
+            .collect()
+    }
+
+    /// Blocks until every live acked subscriber read `version` or a newer one, or `timeout` elapsed.
+    pub(crate) fn wait(&self, version: u64, timeout: Duration) -> Result<(), LaggingSubscribers> {
+        if self.observed.wait_until(|| self.lagging(version).is_empty(), Some(timeout)) {
+            return Ok(());
+        }
+        let behind = self.lagging(version);
+        if behind.is_empty() {
+            // Caught up right after the deadline
+            return Ok(());
+        }
+        Err(LaggingSubscribers { version, behind })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use crate::Rcu;
+
+    /// How long the slow subscriber takes to get around to reading.
+    const SLOW: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn waits_end_once_the_slow_subscriber_caught_up() {
+        let rcu = Rcu::new(0);
+        let fast = rcu.subscribe_acked();
+        let slow = rcu.subscribe_acked();
+        rcu.set(1).unwrap();
+        let start = Instant::now();
+        thread::scope(|s| {
+            s.spawn(|| assert_eq!(fast.read(), 1));
+            s.spawn(|| {
+                thread::sleep(SLOW);
+                assert_eq!(slow.read(), 1);
+            });
+            rcu.wait_until_observed(rcu.version(), Duration::from_secs(10)).unwrap();
+            assert!(start.elapsed() >= SLOW, "went on before the slow subscriber read");
+        });
+        // Already observed, by both
+        rcu.wait_until_observed(1, Duration::ZERO).unwrap();
+    }
+
+    #[test]
+    fn waits_time_out_listing_only_the_subscribers_behind() {
+        let rcu = Rcu::new(0);
+        let fast = rcu.subscribe_acked();
+        let slow = rcu.subscribe_acked();
+        rcu.set(1).unwrap();
+        rcu.set(2).unwrap();
+        assert_eq!(fast.read(), 2);
+        let start = Instant::now();
+        let lagging = rcu.wait_until_observed(2, SLOW).unwrap_err();
+        assert!(start.elapsed() >= SLOW, "gave up after {:?}", start.elapsed());
+        assert_eq!(lagging.version, 2);
+        assert_eq!(lagging.behind.len(), 1, "{lagging}");
+        let (id, staleness) = lagging.behind[0];
+        assert_eq!(Some(id), slow.ack_id());
+        assert_eq!(staleness.version, 0);
+        assert_eq!(staleness.versions_behind, 2);
+        // Plain subscribers are never waited for
+        let _plain = rcu.subscribe();
+        drop(slow);
+        rcu.wait_until_observed(2, Duration::ZERO).unwrap();
+    }
+
+    #[test]
+    fn dropping_a_lagging_subscriber_releases_the_wait() {
+        let rcu = Rcu::new(0);
+        let slow = rcu.subscribe_acked();
+        rcu.set(1).unwrap();
+        let start = Instant::now();
+        thread::scope(|s| {
+            s.spawn(move || {
+                thread::sleep(SLOW);
+                drop(slow);
+            });
+            rcu.wait_until_observed(1, Duration::from_secs(10)).unwrap();
+        });
+        assert!(start.elapsed() < Duration::from_secs(5), "the wait outlived the subscriber");
+    }
+}
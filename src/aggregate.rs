@@ -0,0 +1,165 @@
+use std::marker::PhantomData;
+
+use crate::{PublishError, Rcu};
+
+/// Folds samples of type `T` into a running aggregate, see [`RcuAggregate`].
+pub trait Aggregator<T>: Clone {
+    /// Folds `sample` into the aggregate.
+    fn record(&mut self, sample: &T);
+}
+
+/// The count, sum, minimum and maximum of numeric samples, from which their mean follows.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Summary {
+    /// How many samples were recorded
+    pub count: u64,
+    /// Their sum
+    pub sum: f64,
+    /// The smallest one, None before the first
+    pub min: Option<f64>,
+    /// The largest one, None before the first
+    pub max: Option<f64>,
+}
+
+impl Summary {
+    /// The mean of the samples, None before the first.
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+impl<T: Copy + Into<f64>> Aggregator<T> for Summary {
+    fn record(&mut self, sample: &T) {
+        let sample = (*sample).into();
+        self.count += 1;
+        self.sum += sample;
+        self.min = Some(self.min.map_or(sample, |min| min.min(sample)));
+        self.max = Some(self.max.map_or(sample, |max| max.max(sample)));
+    }
+}
+
+/// An aggregate of samples recorded by any number of threads, behind an `Rcu`: every recording
+/// publishes an updated copy of the aggregate, starting over from the newer one when another
+/// recording got there first, so no sample is ever lost, and readers always see an aggregate of
+/// whole recordings. What is aggregated is up to `A`, [`Summary`] by default.
+///
+/// Recordings of all threads go through the one `Rcu`, so under heavy contention they retry often;
+/// [`RcuAggregate::record_all`] folds many samples in with a single publish.
+///
+/// ```
+/// # use rcu_rust::RcuAggregate;
+/// # let elapsed_us = 250;
+/// let latencies: RcuAggregate<u32> = RcuAggregate::new();
+/// latencies.record(elapsed_us)?;
+/// println!("mean {:?}", latencies.snapshot().mean());
+/// # Ok::<(), rcu_rust::PublishError<rcu_rust::Summary>>(())
+/// ```
+pub struct RcuAggregate<T, A: Aggregator<T> = Summary> {
+    rcu: Rcu<A>,
+    _samples: PhantomData<fn(&T)>,
+}
+
+impl<T, A: Aggregator<T>> RcuAggregate<T, A> {
+    /// Creates an aggregate of no samples.
+    pub fn new() -> Self
+    where
+        A: Default,
+    {
+        Self::from_aggregate(A::default())
+    }
+    /// Publishes `aggregate` as the initial one, which further samples are folded into.
+    pub fn from_aggregate(aggregate: A) -> Self {
+        Self {
+            rcu: Rcu::new(aggregate),
+            _samples: PhantomData,
+        }
+    }
+    /// The `Rcu` holding the aggregate, for everything else an `Rcu` does: subscribing, waiting
+    /// for changes, freezing...
+    pub fn as_rcu(&self) -> &Rcu<A> {
+        &self.rcu
+    }
+    /// Folds `sample` into the aggregate. Fails, handing back the aggregate that would have been
+    /// published, when an invariant of the `Rcu` refuses it or the `Rcu` is frozen.
+    #[track_caller]
+    pub fn record(&self, sample: T) -> Result<(), PublishError<A>> {
+        self.rcu.update_mut(|aggregate| aggregate.record(&sample))
+    }
+    /// Folds every sample of `samples` into the aggregate with a single publish, so readers see
+    /// all of them or none.
+    #[track_caller]
+    pub fn record_all(&self, samples: impl IntoIterator<Item = T>) -> Result<(), PublishError<A>> {
+        let samples: Vec<T> = samples.into_iter().collect();
+        self.rcu.update_mut(|aggregate| samples.iter().for_each(|sample| aggregate.record(sample)))
+    }
+    /// A copy of the current aggregate.
+    #[track_caller]
+    pub fn snapshot(&self) -> A {
+        self.rcu.read()
+    }
+}
+
+impl<T, A: Aggregator<T> + Default> Default for RcuAggregate<T, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+    use std::thread;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::{Aggregator, RcuAggregate, Summary};
+    use crate::{Rcu, SpinYield};
+
+    #[test]
+    fn concurrent_recordings_match_a_single_threaded_fold() {
+        const THREADS: u64 = 20;
+        const SAMPLES: usize = 1000;
+        let stats = RcuAggregate::<i32> {
+            rcu: Rcu::new(Summary::default()).with_wait_strategy(SpinYield::default()),
+            _samples: PhantomData,
+        };
+        let samples: Vec<i32> = thread::scope(|s| {
+            let recorders: Vec<_> = (0..THREADS)
+                .map(|seed| {
+                    let stats = &stats;
+                    s.spawn(move || {
+                        let mut rng = StdRng::seed_from_u64(seed);
+                        let samples: Vec<i32> = (0..SAMPLES).map(|_| rng.gen_range(-100..=100)).collect();
+                        // Half the threads record one sample at a time, the others in batches
+                        if seed % 2 == 0 {
+                            for &sample in &samples {
+                                stats.record(sample).unwrap();
+                            }
+                        } else {
+                            for batch in samples.chunks(100) {
+                                stats.record_all(batch.iter().copied()).unwrap();
+                            }
+                        }
+                        samples
+                    })
+                })
+                .collect();
+            s.spawn(|| {
+                let mut last = 0;
+                while last < THREADS * SAMPLES as u64 {
+                    let count = stats.snapshot().count;
+                    assert!(count >= last, "went back from {last} samples to {count}");
+                    last = count;
+                    thread::yield_now();
+                }
+            });
+            recorders.into_iter().flat_map(|recorder| recorder.join().unwrap()).collect()
+        });
+        let mut expected = Summary::default();
+        for sample in &samples {
+            expected.record(sample);
+        }
+        assert_eq!(stats.snapshot(), expected, "the aggregate lost samples");
+    }
+}
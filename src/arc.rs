@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use crate::{PublishError, Rcu};
+
+impl<T: Clone> Rcu<Arc<T>> {
+    /// Publishes a new `Arc` of a copy of the current payload with `f` applied, returning it.
+    ///
+    /// Cloning the payload can't be avoided here: the `Rcu` itself always holds the current `Arc`,
+    /// and readers may be inside its payload at any time, so it is never mutated in place and every
+    /// attempt clones the payload exactly once, shared with outstanding reads or not. As in
+    /// [`Rcu::update_with`], losing a race to another writer starts over from the newer value, so
+    /// `f` may be called several times. To mutate without any clone while nothing else can reach
+    /// the value, see [`Rcu::make_mut`].
+    #[track_caller]
+    pub fn update_make_mut(&self, mut f: impl FnMut(&mut T)) -> Result<Arc<T>, PublishError<Arc<T>>> {
+        self.update_with(|current| {
+            let mut next = T::clone(current);
+            f(&mut next);
+            Arc::new(next)
+        })
+    }
+    /// The payload of the current value, borrowed exclusively, None if the `Rcu` is frozen. The
+    /// payload is only cloned if `Arc`s of it handed out by reads are still alive, like with
+    /// [`Arc::make_mut`]. Changes made through it aren't a publish: the version doesn't change, and
+    /// neither hooks nor invariants run.
+    pub fn make_mut(&mut self) -> Option<&mut T> {
+        if self.is_frozen() {
+            return None;
+        }
+        Some(Arc::make_mut(self.raw.get_mut()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    use std::sync::Arc;
+
+    use crate::Rcu;
+
+    /// Counts its clones.
+    #[derive(Debug)]
+    struct Payload {
+        value: u64,
+        clones: Arc<AtomicUsize>,
+    }
+
+    impl Clone for Payload {
+        fn clone(&self) -> Self {
+            self.clones.fetch_add(1, SeqCst);
+            Payload {
+                value: self.value,
+                clones: Arc::clone(&self.clones),
+            }
+        }
+    }
+
+    fn counted() -> (Rcu<Arc<Payload>>, Arc<AtomicUsize>) {
+        let clones = Arc::new(AtomicUsize::new(0));
+        let payload = Payload {
+            value: 0,
+            clones: Arc::clone(&clones),
+        };
+        (Rcu::new(Arc::new(payload)), clones)
+    }
+
+    #[test]
+    fn update_make_mut_clones_the_payload_once_whether_shared_or_not() {
+        let (rcu, clones) = counted();
+        let published = rcu.update_make_mut(|payload| payload.value += 1).unwrap();
+        assert_eq!((published.value, clones.load(SeqCst)), (1, 1));
+        drop(published);
+
+        let held = rcu.read();
+        let published = rcu.update_make_mut(|payload| payload.value += 1).unwrap();
+        assert_eq!((published.value, clones.load(SeqCst)), (2, 2));
+        // What was read before stays as it was
+        assert_eq!(held.value, 1);
+    }
+
+    #[test]
+    fn make_mut_clones_the_payload_only_while_reads_share_it() {
+        let (mut rcu, clones) = counted();
+        rcu.make_mut().unwrap().value = 1;
+        assert_eq!(clones.load(SeqCst), 0);
+
+        let held = rcu.read();
+        rcu.make_mut().unwrap().value = 2;
+        assert_eq!(clones.load(SeqCst), 1);
+        assert_eq!(held.value, 1);
+        // Unshared again, the clone being the only one left
+        rcu.make_mut().unwrap().value = 3;
+        assert_eq!((rcu.read().value, clones.load(SeqCst)), (3, 1));
+    }
+}
@@ -0,0 +1,142 @@
+//! Zero-copy reads of published rkyv archives, with the `rkyv` feature.
+
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use rkyv::api::high::HighValidator;
+use rkyv::bytecheck::CheckBytes;
+use rkyv::rancor::Error;
+use rkyv::util::AlignedVec;
+use rkyv::Archive;
+
+use crate::raw::RawReadGuard;
+use crate::{Rcu, RcuReadGuard};
+
+/// An `Rcu` of the bytes of an rkyv archive of an `A`, read in place: a read is a pointer into
+/// the currently published bytes, never a deserialization nor a copy.
+///
+/// Bytes are validated once when they are published, so reads don't have to, whatever the size
+/// of the archive.
+pub struct ArchivedRcu<A> {
+    bytes: Rcu<AlignedVec>,
+    _archive: PhantomData<fn() -> A>,
+}
+
+impl<A: Archive> ArchivedRcu<A>
+where
+    A::Archived: for<'v> CheckBytes<HighValidator<'v, Error>>,
+{
+    /// Publishes `bytes`, which must hold an archived `A`, e.g. as produced by `rkyv::to_bytes`.
+    pub fn new(bytes: AlignedVec) -> Result<Self, Error> {
+        rkyv::access::<A::Archived, Error>(&bytes)?;
+        Ok(Self {
+            bytes: Rcu::new(bytes),
+            _archive: PhantomData,
+        })
+    }
+    /// The currently published archive, read in place, see [`ArchivedGuard`].
+    #[track_caller]
+    pub fn read_archived(&self) -> ArchivedGuard<'_, A> {
+        let bytes = self.bytes.raw.read_guard();
+        // Safety: only bytes validated as an archived `A` are ever published
+        let archived = unsafe { rkyv::access_unchecked::<A::Archived>(&bytes) };
+        ArchivedGuard {
+            archived: archived as *const _,
+            _bytes: bytes,
+        }
+    }
+    /// The currently published bytes, as a guard like [`Rcu::read_guard`] returns.
+    #[track_caller]
+    pub fn read_bytes(&self) -> RcuReadGuard<'_, AlignedVec> {
+        self.bytes.read_guard()
+    }
+    /// Publishes `bytes` in place of the current archive, waiting for readers of the replaced one
+    /// like [`Rcu::set`] does. Fails, publishing nothing, if `bytes` don't hold an archived `A`.
+    pub fn publish_archived(&self, bytes: AlignedVec) -> Result<(), Error> {
+        rkyv::access::<A::Archived, Error>(&bytes)?;
+        self.bytes.set(bytes).expect("never frozen and without invariants");
+        Ok(())
+    }
+    /// See [`Rcu::version`].
+    pub fn version(&self) -> u64 {
+        self.bytes.version()
+    }
+}
+
+/// An archived `A` read in place from an [`ArchivedRcu`]. The bytes it points into stay alive until
+/// the guard is dropped, with the same caveats as [`RcuReadGuard`].
+pub struct ArchivedGuard<'a, A: Archive> {
+    /// Into the bytes `_bytes` keeps alive, which don't move with the guard
+    archived: *const A::Archived,
+    _bytes: RawReadGuard<'a, AlignedVec>,
+}
+
+impl<A: Archive> Deref for ArchivedGuard<'_, A> {
+    type Target = A::Archived;
+
+    fn deref(&self) -> &A::Archived {
+        // Safety: `self._bytes` keeps the bytes alive, and they are never written to once published
+        unsafe { &*self.archived }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rkyv::{Archive, Serialize};
+
+    use super::*;
+
+    #[derive(Archive, Serialize)]
+    struct Route {
+        name: String,
+        ports: Vec<u16>,
+        weight: u32,
+    }
+
+    fn archive(name: &str, ports: &[u16], weight: u32) -> AlignedVec {
+        let route = Route {
+            name: name.into(),
+            ports: ports.to_vec(),
+            weight,
+        };
+        rkyv::to_bytes::<Error>(&route).unwrap()
+    }
+
+    #[test]
+    fn fields_are_read_in_place_from_the_published_bytes() {
+        let rcu = ArchivedRcu::<Route>::new(archive("api", &[80, 443], 3)).unwrap();
+        let route = rcu.read_archived();
+        assert_eq!(route.name.as_str(), "api");
+        assert_eq!(route.ports.iter().map(|port| port.to_native()).collect::<Vec<_>>(), [80, 443]);
+        assert_eq!(route.weight.to_native(), 3);
+        // Pointing into the bytes, nothing was deserialized nor copied
+        let bytes = rcu.read_bytes();
+        assert!(bytes.as_ptr_range().contains(&route.name.as_str().as_ptr()));
+    }
+
+    #[test]
+    fn guards_keep_reading_the_archive_they_were_taken_from() {
+        let rcu = ArchivedRcu::<Route>::new(archive("api", &[80], 1)).unwrap();
+        let old = rcu.read_archived();
+        // The counted engine waits for `old`, so publish from elsewhere and drop it meanwhile
+        std::thread::scope(|s| {
+            let publisher = s.spawn(|| rcu.publish_archived(archive("web", &[8080, 8443], 2)));
+            assert_eq!(old.name.as_str(), "api");
+            drop(old);
+            publisher.join().unwrap().unwrap();
+        });
+        let new = rcu.read_archived();
+        assert_eq!((new.name.as_str(), new.weight.to_native()), ("web", 2));
+        assert_eq!(rcu.version(), 1);
+    }
+
+    #[test]
+    fn bytes_that_are_no_archive_are_refused() {
+        let mut garbage = AlignedVec::<16>::new();
+        garbage.extend_from_slice(&[0xff; 3]);
+        assert!(ArchivedRcu::<Route>::new(garbage.clone()).is_err());
+        let rcu = ArchivedRcu::<Route>::new(archive("api", &[80], 1)).unwrap();
+        assert!(rcu.publish_archived(garbage).is_err());
+        assert_eq!((rcu.version(), rcu.read_archived().name.as_str()), (0, "api"));
+    }
+}
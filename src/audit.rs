@@ -0,0 +1,217 @@
+//! A bounded log of an `Rcu`'s recent publishes, with the `audit` feature.
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, ThreadId};
+use std::time::SystemTime;
+
+use crate::raw::RawRcu;
+use crate::Rcu;
+
+/// Longest value summary recorded, in characters
+const SUMMARY_CHARS: usize = 256;
+
+/// One publish recorded by [`Rcu::audit_log`].
+#[derive(Clone, Debug)]
+pub struct AuditEntry {
+    /// The version the value was published as, see [`Rcu::version`]
+    pub version: u64,
+    /// When it was published
+    pub at: SystemTime,
+    /// The publishing thread
+    pub thread_id: ThreadId,
+    /// Name of the publishing thread, if it has one
+    pub thread_name: Option<String>,
+    /// The value's `Debug` rendering, cut off after 256 characters, if enabled with
+    /// [`Rcu::enable_audit_log_with_summaries`]
+    pub summary: Option<String>,
+}
+
+/// The last `slots.len()` publishes. Publishes never run concurrently, so recording only contends
+/// with threads reading the log, and only on the one slot being overwritten.
+struct Ring<T> {
+    slots: Box<[Mutex<Option<AuditEntry>>]>,
+    /// Publishes recorded so far, the next one goes to `slots[recorded % slots.len()]`
+    recorded: AtomicU64,
+    render: Option<fn(&T) -> String>,
+}
+
+fn render<T: Debug>(value: &T) -> String {
+    let mut summary = format!("{value:?}");
+    if let Some((cut, _)) = summary.char_indices().nth(SUMMARY_CHARS) {
+        summary.truncate(cut);
+        summary.push('…');
+    }
+    summary
+}
+
+/// The audit log of an `Rcu`, None while disabled. Published through RCU like the hook list, so
+/// recording never blocks a thread enabling or disabling the log.
+pub(crate) struct AuditLog<T> {
+    ring: RawRcu<Option<Arc<Ring<T>>>>,
+}
+
+impl<T> AuditLog<T> {
+    pub(crate) fn new() -> Self {
+        Self { ring: RawRcu::new(Box::default()) }
+    }
+
+    fn enable(&self, capacity: usize, render: Option<fn(&T) -> String>) {
+        assert!(capacity > 0, "audit log capacity must be positive");
+        let ring = Arc::new(Ring {
+            slots: (0..capacity).map(|_| Mutex::new(None)).collect(),
+            recorded: AtomicU64::new(0),
+            render,
+        });
+        self.ring.modify(|_| Some(Arc::clone(&ring)));
+    }
+
+    fn current(&self) -> Option<Arc<Ring<T>>> {
+        self.ring.read_with(Option::clone)
+    }
+
+    /// Records the publish of `value` as `version`, if enabled.
+    pub(crate) fn record(&self, value: &T, version: u64) {
+        let Some(ring) = self.current() else {
+            return;
+        };
+        let current = thread::current();
+        let entry = AuditEntry {
+            version,
+            at: SystemTime::now(),
+            thread_id: current.id(),
+            thread_name: current.name().map(String::from),
+            summary: ring.render.map(|render| render(value)),
+        };
+        let slot = ring.recorded.fetch_add(1, Relaxed) % ring.slots.len() as u64;
+        *ring.slots[slot as usize].lock().unwrap_or_else(PoisonError::into_inner) = Some(entry);
+    }
+}
+
+impl<T: Clone> Rcu<T> {
+    /// Starts recording the last `capacity` publishes, see [`Rcu::audit_log`]. Replaces, and
+    /// clears, a log enabled before.
+    ///
+    /// # Panics
+    /// If `capacity` is 0.
+    pub fn enable_audit_log(&self, capacity: usize) {
+        self.audit.enable(capacity, None);
+    }
+    /// Like [`Rcu::enable_audit_log`], also recording a summary of each published value.
+    pub fn enable_audit_log_with_summaries(&self, capacity: usize)
+    where
+        T: Debug,
+    {
+        self.audit.enable(capacity, Some(render::<T>));
+    }
+    /// Stops recording publishes and drops the log.
+    pub fn disable_audit_log(&self) {
+        self.audit.ring.modify(|_| None);
+    }
+    /// The publishes recorded since the log was enabled or last cleared, oldest first, at most its
+    /// capacity of them. Empty while disabled.
+    pub fn audit_log(&self) -> Vec<AuditEntry> {
+        let Some(ring) = self.audit.current() else {
+            return Vec::new();
+        };
+        let mut entries: Vec<_> = ring
+            .slots
+            .iter()
+            .filter_map(|slot| slot.lock().unwrap_or_else(PoisonError::into_inner).clone())
+            .collect();
+        entries.sort_by_key(|entry| entry.version);
+        entries
+    }
+    /// Forgets the publishes recorded so far, the log stays enabled.
+    pub fn clear_audit_log(&self) {
+        if let Some(ring) = self.audit.current() {
+            for slot in ring.slots.iter() {
+                *slot.lock().unwrap_or_else(PoisonError::into_inner) = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+
+    use super::*;
+
+    const CAPACITY: usize = 16;
+    const PUBLISHERS: usize = 4;
+    const PUBLISHES: usize = 500;
+
+    /// The publisher and its publish, from the summary of a `(publisher, publish)` value.
+    fn parse(summary: &str) -> (usize, usize) {
+        let (publisher, publish) = summary.trim_matches(['(', ')']).split_once(", ").unwrap();
+        (publisher.parse().unwrap(), publish.parse().unwrap())
+    }
+
+    #[test]
+    fn the_log_keeps_the_last_publishes_oldest_first() {
+        let rcu = Rcu::new(0);
+        rcu.set(-1).unwrap();
+        rcu.enable_audit_log_with_summaries(4);
+        for value in 0..10 {
+            rcu.set(value).unwrap();
+        }
+        let log = rcu.audit_log();
+        let versions: Vec<_> = log.iter().map(|entry| entry.version).collect();
+        assert_eq!(versions, (rcu.version() - 3..=rcu.version()).collect::<Vec<_>>());
+        let summaries: Vec<_> = log.iter().map(|entry| entry.summary.as_deref().unwrap()).collect();
+        assert_eq!(summaries, ["6", "7", "8", "9"]);
+        assert!(log.iter().all(|entry| entry.thread_id == thread::current().id()));
+    }
+
+    #[test]
+    fn the_log_stays_bounded_and_ordered_under_a_publish_storm() {
+        let rcu = Rcu::new((0, 0));
+        rcu.enable_audit_log_with_summaries(CAPACITY);
+        let done = AtomicBool::new(false);
+        thread::scope(|s| {
+            s.spawn(|| {
+                while !done.load(SeqCst) {
+                    let oldest = rcu.version().saturating_sub(CAPACITY as u64);
+                    let log = rcu.audit_log();
+                    assert!(log.len() <= CAPACITY, "{} entries in a log of {CAPACITY}", log.len());
+                    assert!(log.windows(2).all(|pair| pair[0].version < pair[1].version), "out of order");
+                    assert!(log.iter().all(|entry| entry.version >= oldest), "older than the last publishes");
+                    thread::yield_now();
+                }
+            });
+            let publishers: Vec<_> = (0..PUBLISHERS)
+                .map(|publisher| {
+                    let rcu = &rcu;
+                    thread::Builder::new()
+                        .name(format!("publisher-{publisher}"))
+                        .spawn_scoped(s, move || {
+                            for publish in 1..=PUBLISHES {
+                                rcu.set((publisher, publish)).unwrap();
+                                if publish % 7 == 0 {
+                                    thread::yield_now();
+                                }
+                            }
+                        })
+                        .unwrap()
+                })
+                .collect();
+            publishers.into_iter().for_each(|publisher| publisher.join().unwrap());
+            done.store(true, SeqCst);
+        });
+        let log = rcu.audit_log();
+        let versions: Vec<_> = log.iter().map(|entry| entry.version).collect();
+        let last = rcu.version();
+        assert_eq!(versions, (last - CAPACITY as u64 + 1..=last).collect::<Vec<_>>());
+        // Each entry is the publish of the thread it names, each thread's publishes in order
+        let mut latest = [0; PUBLISHERS];
+        for entry in &log {
+            let (publisher, publish) = parse(entry.summary.as_deref().unwrap());
+            assert_eq!(entry.thread_name, Some(format!("publisher-{publisher}")));
+            assert!(publish > latest[publisher], "publisher {publisher} logged out of order");
+            latest[publisher] = publish;
+        }
+        assert_eq!(parse(log.last().unwrap().summary.as_deref().unwrap()), rcu.read());
+    }
+}
@@ -0,0 +1,353 @@
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::sync::Arc;
+use std::time::Instant;
+
+#[cfg(feature = "metrics")]
+use crate::metrics::Failure;
+#[cfg(feature = "async")]
+use crate::raw::YieldNow;
+use crate::reclaim::Retired;
+use crate::{PublishError, Rcu};
+
+/// The values displaced from an `Rcu` that are still waiting to be freed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Backlog {
+    /// How many there are
+    pub count: usize,
+    /// Their estimated size in bytes, 0 unless sized with [`Rcu::with_max_pending_bytes`]
+    pub bytes: usize,
+}
+
+/// What a publish does when it finds the backlog of an `Rcu` past one of its limits, see
+/// [`Rcu::with_max_pending_retired`].
+#[derive(Clone, Default)]
+pub enum OnBacklog {
+    /// Waits until enough of the backlog was freed, which takes the readers of the displaced values
+    /// to finish. The default.
+    #[default]
+    Block,
+    /// Fails with [`PublishError::Backpressure`], publishing nothing.
+    Fail,
+    /// Calls the callback with the backlog on the publishing thread, then publishes anyway.
+    Call(Arc<dyn Fn(Backlog) + Send + Sync>),
+}
+
+impl fmt::Debug for OnBacklog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OnBacklog::Block => f.write_str("Block"),
+            OnBacklog::Fail => f.write_str("Fail"),
+            OnBacklog::Call(_) => f.write_str("Call(..)"),
+        }
+    }
+}
+
+/// The limits on the backlog of one `Rcu`.
+#[derive(Default)]
+pub(crate) struct Limits {
+    max_count: Option<usize>,
+    max_bytes: Option<usize>,
+    on_backlog: OnBacklog,
+}
+
+impl Limits {
+    /// Whether a limit was set, with `Rcu::with_max_pending_retired` or `Rcu::with_max_pending_bytes`.
+    pub(crate) fn is_set(&self) -> bool {
+        self.max_count.is_some() || self.max_bytes.is_some()
+    }
+}
+
+/// Keeps count of the size of the values displaced from one `RawRcu` until they are freed, while
+/// they are sized.
+pub(crate) struct RetiredBytes<T> {
+    size_of: Option<fn(&T) -> usize>,
+    /// Shared with the values themselves, which may be freed after the `RawRcu` is gone
+    total: Arc<AtomicUsize>,
+}
+
+/// A displaced value whose size is counted in `total` until it is freed.
+struct Weighed {
+    retired: Retired,
+    size: usize,
+    total: Arc<AtomicUsize>,
+}
+
+/// Frees the value, then stops counting it.
+unsafe fn untrack(weighed: *mut Weighed) {
+    let weighed = Box::from_raw(weighed);
+    weighed.retired.reclaim();
+    weighed.total.fetch_sub(weighed.size, Relaxed);
+}
+
+impl<T> RetiredBytes<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            size_of: None,
+            total: Arc::default(),
+        }
+    }
+
+    pub(crate) fn set(&mut self, size_of: fn(&T) -> usize) {
+        self.size_of = Some(size_of);
+    }
+
+    /// The size of the displaced values not freed yet.
+    pub(crate) fn total(&self) -> usize {
+        self.total.load(Relaxed)
+    }
+
+    /// The size of the displaced `value`, None unless sized.
+    pub(crate) fn size(&self, value: &T) -> Option<usize> {
+        self.size_of.map(|size_of| size_of(value))
+    }
+
+    /// Wraps `retired`, which frees a displaced value of `size`, see `size`, to count it until then.
+    pub(crate) fn track(&self, size: Option<usize>, retired: Retired) -> Retired {
+        let Some(size) = size else {
+            return retired;
+        };
+        self.total.fetch_add(size, Relaxed);
+        let weighed = Weighed {
+            retired,
+            size,
+            total: Arc::clone(&self.total),
+        };
+        Retired::with_deleter(Box::into_raw(Box::new(weighed)), untrack)
+    }
+}
+
+impl<T: Clone> Rcu<T> {
+    /// Limits the displaced values waiting to be freed to `count`: a publish finding more of them
+    /// than that first tries to get them freed, and if that doesn't bring them under the limit
+    /// does what [`Rcu::with_backpressure`] says, blocking by default. Bounds the memory readers
+    /// holding on for long can keep alive behind a stream of publishes.
+    ///
+    /// The backlog counts what the engine hasn't freed yet and what [`Rcu::with_retire_batching`]
+    /// holds back. The `reclaim-counted` engine frees in-line, so without batching or deadlines
+    /// its backlog stays empty. Values of an [`RcuDomain`](crate::RcuDomain) count the backlog of
+    /// the whole domain. One publish may overshoot a limit by one value.
+    ///
+    /// ```
+    /// # use std::collections::BTreeMap;
+    /// # use rcu_rust::{OnBacklog, Rcu};
+    /// let index = Rcu::new(BTreeMap::<u64, String>::new())
+    ///     .with_max_pending_retired(16)
+    ///     .with_backpressure(OnBacklog::Fail);
+    /// index.update_with(|index| {
+    ///     let mut index = index.clone();
+    ///     index.insert(1, "one".into());
+    ///     index
+    /// })?;
+    /// # Ok::<(), rcu_rust::PublishError<BTreeMap<u64, String>>>(())
+    /// ```
+    pub fn with_max_pending_retired(mut self, count: usize) -> Self {
+        self.limits.max_count = Some(count);
+        self
+    }
+    /// Like [`Rcu::with_max_pending_retired`], limiting the size of the displaced values waiting
+    /// to be freed to `bytes`, as estimated by `size_of` once per value when it is displaced.
+    ///
+    /// ```
+    /// # use rcu_rust::Rcu;
+    /// let blob = Rcu::new(Vec::new()).with_max_pending_bytes(64 << 20, |blob: &Vec<u8>| blob.capacity());
+    /// blob.set(vec![0; 1024])?;
+    /// # Ok::<(), rcu_rust::PublishError<Vec<u8>>>(())
+    /// ```
+    pub fn with_max_pending_bytes(mut self, bytes: usize, size_of: fn(&T) -> usize) -> Self {
+        self.raw.set_retired_size(size_of);
+        self.limits.max_bytes = Some(bytes);
+        self
+    }
+    /// Makes publishes finding the backlog past a limit do as `on_backlog` says instead of
+    /// blocking.
+    pub fn with_backpressure(mut self, on_backlog: OnBacklog) -> Self {
+        self.limits.on_backlog = on_backlog;
+        self
+    }
+    /// Number of displaced values waiting to be freed, see [`Rcu::with_max_pending_retired`] and
+    /// [`Rcu::reclaim_now`].
+    pub fn pending_retired(&self) -> usize {
+        self.raw.pending_retired()
+    }
+    /// Estimated size of the displaced values waiting to be freed, 0 unless sized with
+    /// [`Rcu::with_max_pending_bytes`].
+    pub fn pending_retired_bytes(&self) -> usize {
+        self.raw.retired_bytes()
+    }
+    /// The backlog if it is past a limit.
+    fn over_limit(&self) -> Option<Backlog> {
+        let backlog = Backlog {
+            count: self.raw.pending_retired(),
+            bytes: self.raw.retired_bytes(),
+        };
+        let over = |max: Option<usize>, cur| max.is_some_and(|max| cur > max);
+        (over(self.limits.max_count, backlog.count) || over(self.limits.max_bytes, backlog.bytes)).then_some(backlog)
+    }
+    /// Lets a publish go ahead as the limits say. Returns false if blocking for the backlog went
+    /// past `deadline`, or the backlog past a limit if the publish should fail.
+    pub(crate) fn admit_before(&self, deadline: Option<Instant>) -> Result<bool, Backlog> {
+        if !self.limits.is_set() || self.over_limit().is_none() {
+            return Ok(true);
+        }
+        if let OnBacklog::Block = self.limits.on_backlog {
+            let mut attempt = 0;
+            loop {
+                self.raw.flush_retired_before(deadline);
+                if self.over_limit().is_none() {
+                    return Ok(true);
+                }
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    return Ok(false);
+                }
+                self.raw.waiter().wait(attempt);
+                attempt = attempt.wrapping_add(1);
+            }
+        }
+        // Whatever can be freed without waiting
+        self.raw.flush_retired_before(Some(Instant::now()));
+        let Some(backlog) = self.over_limit() else {
+            return Ok(true);
+        };
+        match &self.limits.on_backlog {
+            OnBacklog::Call(f) => {
+                f(backlog);
+                Ok(true)
+            }
+            _ => Err(backlog),
+        }
+    }
+    /// `admit_before` without a deadline.
+    pub(crate) fn admit(&self) -> Result<(), Backlog> {
+        self.admit_before(None).map(|_| ())
+    }
+    /// Like `admit`, yielding instead of blocking.
+    #[cfg(feature = "async")]
+    pub(crate) async fn admit_async(&self) -> Result<(), Backlog> {
+        while !self.admit_before(Some(Instant::now()))? {
+            YieldNow::default().await;
+        }
+        Ok(())
+    }
+    /// Records a call failed by the limits, returning the error to report.
+    pub(crate) fn backpressure(&self, backlog: Backlog, value: T) -> PublishError<T> {
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = self.raw.metrics() {
+            metrics.failed(Failure::Backpressure);
+        }
+        PublishError::Backpressure { backlog, value }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{Backlog, OnBacklog};
+    use crate::{PublishError, Rcu};
+
+    /// Long enough for a publish that wrongly ignores the limit to have gone ahead.
+    const SETTLE: Duration = Duration::from_millis(50);
+
+    /// Backlogs go past 3 values, or 250 bytes, on the fifth publish of 100 bytes.
+    fn limited(on_backlog: OnBacklog) -> Rcu<Vec<u8>> {
+        // Batching keeps the displaced values pending with every engine, even the one freeing in-line
+        Rcu::new(vec![0; 100])
+            .with_retire_batching(1000, Duration::from_secs(3600))
+            .with_max_pending_retired(3)
+            .with_max_pending_bytes(250, Vec::len)
+            .with_backpressure(on_backlog)
+    }
+
+    /// Runs `f` with a reader of `rcu` pinned on another thread, which exits once `f` returns.
+    fn with_reader(rcu: &Rcu<Vec<u8>>, f: impl FnOnce()) {
+        let (entered, wait_entered) = mpsc::channel();
+        let (exit, wait_exit) = mpsc::channel::<()>();
+        thread::scope(|s| {
+            s.spawn(move || {
+                rcu.read_with(|_| {
+                    entered.send(()).unwrap();
+                    let _ = wait_exit.recv();
+                })
+            });
+            wait_entered.recv().unwrap();
+            f();
+            drop(exit);
+        });
+    }
+
+    #[test]
+    fn blocking_publishes_wait_for_the_backlog_to_be_freed() {
+        let rcu = limited(OnBacklog::Block);
+        let published = AtomicBool::new(false);
+        thread::scope(|s| {
+            with_reader(&rcu, || {
+                for i in 1..=4 {
+                    rcu.set(vec![i; 100]).unwrap();
+                }
+                assert_eq!(rcu.pending_retired(), 3);
+                assert_eq!(rcu.pending_retired_bytes(), 300);
+                s.spawn(|| {
+                    rcu.set(vec![5; 100]).unwrap();
+                    published.store(true, SeqCst);
+                });
+                thread::sleep(SETTLE);
+                assert!(!published.load(SeqCst), "published past the limit under a reader");
+                // Not a read, which may be held back behind the blocked publish
+                assert_eq!(rcu.version(), 4);
+            });
+        });
+        assert!(published.load(SeqCst), "never published once the reader exited");
+        assert_eq!(rcu.read_with(|value| value[0]), 5);
+        assert!(rcu.pending_retired() <= 3, "{} still pending", rcu.pending_retired());
+        assert!(rcu.pending_retired_bytes() <= 250, "{} bytes still pending", rcu.pending_retired_bytes());
+    }
+
+    #[test]
+    fn failing_publishes_hand_back_their_value_and_the_backlog() {
+        let rcu = limited(OnBacklog::Fail);
+        with_reader(&rcu, || {
+            for i in 1..=4 {
+                rcu.set(vec![i; 100]).unwrap();
+            }
+            match rcu.set(vec![5; 100]) {
+                Err(PublishError::Backpressure { backlog, value }) => {
+                    assert_eq!(backlog, Backlog { count: 3, bytes: 300 });
+                    assert_eq!(value, vec![5; 100], "handed back another value");
+                }
+                other => panic!("published past the limit: {:?}", other.map_err(|err| err.to_string())),
+            }
+            assert_eq!(rcu.read_with(|value| value[0]), 4, "failed publish changed the value");
+            assert_eq!(rcu.version(), 4);
+            assert_eq!(rcu.pending_retired(), 3);
+            assert_eq!(rcu.pending_retired_bytes(), 300);
+        });
+        rcu.reclaim_now();
+        assert_eq!(rcu.pending_retired(), 0);
+        assert_eq!(rcu.pending_retired_bytes(), 0);
+        rcu.set(vec![5; 100]).unwrap();
+    }
+
+    #[test]
+    fn calling_publishes_report_the_backlog_and_go_ahead() {
+        let backlogs = Arc::new(Mutex::new(Vec::new()));
+        let reported = Arc::clone(&backlogs);
+        let rcu = limited(OnBacklog::Call(Arc::new(move |backlog| reported.lock().unwrap().push(backlog))));
+        with_reader(&rcu, || {
+            for i in 1..=6 {
+                rcu.set(vec![i; 100]).unwrap();
+            }
+            assert_eq!(rcu.read_with(|value| value[0]), 6);
+            assert_eq!(rcu.pending_retired(), 5);
+            assert_eq!(rcu.pending_retired_bytes(), 500);
+        });
+        assert_eq!(
+            *backlogs.lock().unwrap(),
+            [Backlog { count: 3, bytes: 300 }, Backlog { count: 4, bytes: 400 }],
+            "not called once per publish past the limit",
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::reclaim::Retired;
+
+/// Values displaced from one `RawRcu`, held back from its engine until enough of them accumulated
+/// to be reclaimed by a single grace period, see `Rcu::with_retire_batching`.
+pub(crate) struct RetireBatch {
+    /// The batch is due once it holds this many values, 0 while batching is off
+    max_len: usize,
+    /// The batch is due once its oldest value waited this long
+    max_age: Duration,
+    /// The length of `batch.retired`, read without the lock
+    len: AtomicUsize,
+    batch: Mutex<Batch>,
+}
+
+#[derive(Default)]
+struct Batch {
+    retired: Vec<Retired>,
+    /// When the oldest value of `retired` was added
+    since: Option<Instant>,
+}
+
+impl RetireBatch {
+    /// Batching turned off.
+    pub(crate) fn new() -> Self {
+        Self {
+            max_len: 0,
+            max_age: Duration::ZERO,
+            len: AtomicUsize::new(0),
+            batch: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn set(&mut self, max_len: usize, max_age: Duration) {
+        self.max_len = max_len.max(1);
+        self.max_age = max_age;
+    }
+
+    pub(crate) fn is_on(&self) -> bool {
+        self.max_len > 0
+    }
+
+    /// Adds `retired` to the batch, returning the whole batch, `retired` included, if it is due.
+    pub(crate) fn push(&self, retired: Retired) -> Option<Vec<Retired>> {
+        let mut batch = self.batch.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        let since = *batch.since.get_or_insert(now);
+        batch.retired.push(retired);
+        if batch.retired.len() < self.max_len && now.duration_since(since) < self.max_age {
+            self.len.store(batch.retired.len(), Relaxed);
+            return None;
+        }
+        Some(self.take_locked(&mut batch))
+    }
+
+    /// Takes the whole batch, however small.
+    pub(crate) fn take(&self) -> Vec<Retired> {
+        self.take_locked(&mut self.batch.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn take_locked(&self, batch: &mut Batch) -> Vec<Retired> {
+        self.len.store(0, Relaxed);
+        batch.since = None;
+        mem::take(&mut batch.retired)
+    }
+
+    /// Number of values in the batch.
+    pub(crate) fn len(&self) -> usize {
+        self.len.load(Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::RetireBatch;
+    use crate::reclaim::Retired;
+    use crate::Rcu;
+
+    /// Counts its drops in the counter it shares with the test.
+    #[derive(Clone)]
+    struct Tracked(Arc<AtomicUsize>);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, SeqCst);
+        }
+    }
+
+    fn retired(drops: &Arc<AtomicUsize>) -> Retired {
+        Retired::new(Box::into_raw(Box::new(Tracked(Arc::clone(drops)))))
+    }
+
+    /// Frees a taken batch, as the engine would once nothing observes it.
+    fn reclaim(batch: Vec<Retired>) {
+        for retired in batch {
+            // Safety: never published
+            unsafe { retired.reclaim() }
+        }
+    }
+
+    #[test]
+    fn batches_are_handed_over_whole_once_full() {
+        let mut batch = RetireBatch::new();
+        batch.set(4, Duration::from_secs(3600));
+        let drops = Arc::new(AtomicUsize::new(0));
+        for round in 0..3 {
+            for len in 1..4 {
+                assert!(batch.push(retired(&drops)).is_none(), "handed over before it was full");
+                assert_eq!(batch.len(), len);
+            }
+            let full = batch.push(retired(&drops)).expect("not handed over once full");
+            assert_eq!(full.len(), 4);
+            assert_eq!(batch.len(), 0);
+            reclaim(full);
+            assert_eq!(drops.load(SeqCst), 4 * (round + 1));
+        }
+    }
+
+    #[test]
+    fn batches_are_handed_over_once_their_oldest_value_is_old_enough() {
+        let mut batch = RetireBatch::new();
+        batch.set(1000, Duration::from_millis(20));
+        let drops = Arc::new(AtomicUsize::new(0));
+        assert!(batch.push(retired(&drops)).is_none());
+        thread::sleep(Duration::from_millis(30));
+        let due = batch.push(retired(&drops)).expect("not handed over once old enough");
+        assert_eq!(due.len(), 2);
+        reclaim(due);
+        // The age restarts with the next batch
+        assert!(batch.push(retired(&drops)).is_none(), "the next batch inherited the age");
+        assert_eq!(batch.len(), 1);
+        reclaim(batch.take());
+        assert_eq!(drops.load(SeqCst), 3);
+    }
+
+    #[test]
+    fn flush_leaves_the_batch_empty_and_frees_it() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let rcu = Rcu::new(Tracked(Arc::clone(&drops))).with_retire_batching(100, Duration::from_secs(3600));
+        for i in 1..=10 {
+            assert!(rcu.set(Tracked(Arc::clone(&drops))).is_ok());
+            // The first publish only displaces into the previous value
+            assert_eq!(rcu.retire_batch_len(), i - 1);
+        }
+        assert_eq!(drops.load(SeqCst), 0, "freed while batched");
+        rcu.flush();
+        assert_eq!(rcu.retire_batch_len(), 0, "flush left values in the batch");
+        rcu.barrier();
+        assert_eq!(drops.load(SeqCst), 9, "the flushed batch wasn't freed");
+        assert_eq!(rcu.pending_retired(), 0);
+        rcu.flush();
+        assert_eq!(rcu.retire_batch_len(), 0, "flushing nothing");
+    }
+
+    #[test]
+    fn racing_publishes_never_overfill_the_batch() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let rcu = Rcu::new(Tracked(Arc::clone(&drops))).with_retire_batching(8, Duration::from_secs(3600));
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        assert!(rcu.set(Tracked(Arc::clone(&drops))).is_ok());
+                        assert!(rcu.retire_batch_len() < 8, "{} batched", rcu.retire_batch_len());
+                        thread::yield_now();
+                    }
+                });
+            }
+        });
+        // 399 displaced, in full batches of 8 but for what is left
+        assert_eq!(rcu.retire_batch_len(), 399 % 8);
+        rcu.flush();
+        rcu.barrier();
+        assert_eq!(drops.load(SeqCst), 399, "displaced values lost");
+    }
+}
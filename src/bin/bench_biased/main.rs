@@ -0,0 +1,89 @@
+//! Measures what a read costs on the thread that created the `Rcu`: through the full protocol with
+//! `Rcu::new`, through the bias with `Rcu::new_biased`, through the full protocol again once another
+//! thread revoked that bias, and, as the floor to compare them with, a bare pointer load plus the
+//! same clone. Prints one CSV row per read. Run with `cargo run --release --bin bench_biased -- --help`.
+
+use std::hint::black_box;
+use std::process;
+use std::sync::atomic::{AtomicPtr, Ordering::Acquire};
+use std::thread;
+use std::time::Instant;
+
+use rcu_rust::Rcu;
+
+const USAGE: &str = "usage: bench_biased [--reads N] [--payload-bytes N]";
+
+struct Config {
+    reads: u64,
+    payload_bytes: usize,
+}
+
+impl Config {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut config = Config {
+            reads: 10_000_000,
+            payload_bytes: 8,
+        };
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                return Err(String::new());
+            }
+            let value = args.next().ok_or_else(|| format!("{arg} needs a value"))?;
+            let bad = || format!("bad value for {arg}: {value}");
+            match arg.as_str() {
+                "--reads" => config.reads = value.parse().map_err(|_| bad())?,
+                "--payload-bytes" => config.payload_bytes = value.parse().map_err(|_| bad())?,
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+        if config.reads == 0 {
+            return Err("--reads must be positive".into());
+        }
+        Ok(config)
+    }
+}
+
+/// Times `reads` calls of `read`, returning the mean nanoseconds per call.
+fn time<R>(reads: u64, mut read: impl FnMut() -> R) -> f64 {
+    let start = Instant::now();
+    for _ in 0..reads {
+        black_box(read());
+    }
+    start.elapsed().as_nanos() as f64 / reads as f64
+}
+
+fn main() {
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(err) if err.is_empty() => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            process::exit(2);
+        }
+    };
+    let payload = vec![0u8; config.payload_bytes];
+    let reads = config.reads;
+    println!("read,payload_bytes,ns_per_read");
+    let row = |name: &str, per_read: f64| println!("{name},{},{per_read:.2}", config.payload_bytes);
+
+    let value = Box::into_raw(Box::new(payload.clone()));
+    let ptr = AtomicPtr::new(value);
+    // Safety: `value` is only freed below, once done reading it
+    row("pointer_load", time(reads, || unsafe { (*ptr.load(Acquire)).clone() }));
+    // Safety: from `Box::into_raw` above, and nothing reads it anymore
+    drop(unsafe { Box::from_raw(value) });
+
+    let rcu = Rcu::new(payload.clone());
+    row("new", time(reads, || rcu.read()));
+
+    let rcu = Rcu::new_biased(payload);
+    row("new_biased", time(reads, || rcu.read()));
+    // Any other thread touching it revokes the bias for good
+    thread::scope(|s| {
+        s.spawn(|| rcu.read());
+    });
+    row("new_biased_revoked", time(reads, || rcu.read()));
+}
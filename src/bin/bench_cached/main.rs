@@ -0,0 +1,143 @@
+//! Compares the steady-state cost of reading a field of a value behind an `Rcu` through
+//! `Rcu::cached`, through `Rcu::read` of an `Rcu<Arc<T>>` cloning the `Arc`, and through
+//! `Rcu::read_with`, for various reader counts, optionally with a writer publishing meanwhile.
+//! Prints one CSV row per read mode and reader count. Run with
+//! `cargo run --release --bin bench_cached -- --help`.
+
+use std::hint::black_box;
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering::Relaxed};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rcu_rust::Rcu;
+
+const USAGE: &str = "usage: bench_cached [--readers 1,2,4,...] [--writes-per-sec N] [--duration MS]";
+
+struct Config {
+    readers: Vec<usize>,
+    /// Publishes per second of the writer, 0 for none at all
+    writes_per_sec: u64,
+    duration: Duration,
+}
+
+impl Config {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut config = Config {
+            readers: vec![1, 2, 4, 8, 16],
+            writes_per_sec: 0,
+            duration: Duration::from_millis(1000),
+        };
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                return Err(String::new());
+            }
+            let value = args.next().ok_or_else(|| format!("{arg} needs a value"))?;
+            let bad = || format!("bad value for {arg}: {value}");
+            match arg.as_str() {
+                "--readers" => {
+                    config.readers = value.split(',').map(str::parse).collect::<Result<_, _>>().map_err(|_| bad())?;
+                }
+                "--writes-per-sec" => config.writes_per_sec = value.parse().map_err(|_| bad())?,
+                "--duration" => config.duration = Duration::from_millis(value.parse().map_err(|_| bad())?),
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+        if config.readers.contains(&0) {
+            return Err("--readers must be positive".into());
+        }
+        Ok(config)
+    }
+}
+
+/// A value read extremely often, of which reads only need a field.
+#[derive(Clone, Debug)]
+struct Settings {
+    limit: u64,
+    _names: Vec<String>,
+}
+
+impl Settings {
+    fn new(limit: u64) -> Self {
+        Settings {
+            limit,
+            _names: (0..16).map(|i| format!("name-{i}")).collect(),
+        }
+    }
+}
+
+/// Runs `readers` threads calling `read`, and a writer calling `write` at the configured rate,
+/// returning the mean nanoseconds per read.
+fn run(config: &Config, readers: usize, read: impl Fn() -> u64 + Sync, mut write: impl FnMut(u64) + Send) -> f64 {
+    let stop = AtomicBool::new(false);
+    let reads = AtomicU64::new(0);
+    let busy = AtomicU64::new(0);
+    thread::scope(|s| {
+        for _ in 0..readers {
+            s.spawn(|| {
+                let start = Instant::now();
+                let mut n = 0;
+                while !stop.load(Relaxed) {
+                    black_box(read());
+                    n += 1;
+                }
+                reads.fetch_add(n, Relaxed);
+                busy.fetch_add(start.elapsed().as_nanos() as u64, Relaxed);
+            });
+        }
+        if config.writes_per_sec > 0 {
+            let stop = &stop;
+            s.spawn(move || {
+                let pause = Duration::from_secs(1) / config.writes_per_sec as u32;
+                let mut limit = 0;
+                while !stop.load(Relaxed) {
+                    limit += 1;
+                    write(limit);
+                    thread::sleep(pause);
+                }
+            });
+        }
+        thread::sleep(config.duration);
+        stop.store(true, Relaxed);
+    });
+    busy.into_inner() as f64 / reads.into_inner().max(1) as f64
+}
+
+fn main() {
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(err) if err.is_empty() => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            process::exit(2);
+        }
+    };
+    println!("read_mode,readers,writes_per_sec,ns_per_read");
+    for &readers in &config.readers {
+        let row = |mode: &str, per_read: f64| {
+            println!("{mode},{readers},{},{per_read:.1}", config.writes_per_sec);
+        };
+
+        let rcu = Rcu::new(Settings::new(0));
+        let per_read = run(&config, readers, || rcu.cached(|settings| settings.limit), |limit| {
+            rcu.set(Settings::new(limit)).expect("nothing refuses the value");
+        });
+        row("cached", per_read);
+
+        let rcu = Rcu::new(Arc::new(Settings::new(0)));
+        let per_read = run(&config, readers, || rcu.read().limit, |limit| {
+            rcu.set(Arc::new(Settings::new(limit))).expect("nothing refuses the value");
+        });
+        row("read_arc", per_read);
+
+        let rcu = Rcu::new(Settings::new(0));
+        let per_read = run(&config, readers, || rcu.read_with(|settings| settings.limit), |limit| {
+            rcu.set(Settings::new(limit)).expect("nothing refuses the value");
+        });
+        row("read_with", per_read);
+    }
+}
@@ -0,0 +1,134 @@
+//! Compares what an update of a large collection behind an `Rcu` costs with std collections, which
+//! every update copies whole, and with the persistent collections of the `im` feature, which only
+//! copy what changes. Prints one CSV row per collection. Run with
+//! `cargo run --release --features im --bin bench_collections -- --help`.
+
+use std::hint::black_box;
+use std::process;
+use std::time::{Duration, Instant};
+
+use rcu_rust::{RcuBTreeMap, RcuHashMap, RcuImHashMap, RcuImOrdMap, RcuImVector, RcuVec};
+
+const USAGE: &str = "usage: bench_collections [--entries N] [--updates N] [--reads N]";
+
+struct Config {
+    entries: u64,
+    updates: u64,
+    reads: u64,
+}
+
+impl Config {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut config = Config {
+            entries: 1_000_000,
+            updates: 100,
+            reads: 1_000_000,
+        };
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                return Err(String::new());
+            }
+            let value = args.next().ok_or_else(|| format!("{arg} needs a value"))?;
+            let parsed = value.parse().map_err(|_| format!("bad value for {arg}: {value}"))?;
+            match arg.as_str() {
+                "--entries" => config.entries = parsed,
+                "--updates" => config.updates = parsed,
+                "--reads" => config.reads = parsed,
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+        if config.entries == 0 || config.updates == 0 || config.reads == 0 {
+            return Err("--entries, --updates and --reads must be positive".into());
+        }
+        Ok(config)
+    }
+}
+
+/// Spreads the `i`th access over the keys, so neither updates nor reads hit the same entry.
+fn key(i: u64, entries: u64) -> u64 {
+    i.wrapping_mul(0x9e37_79b9_7f4a_7c15) % entries
+}
+
+/// Times `count` calls of `f`, returning the mean per call.
+fn time(count: u64, mut f: impl FnMut(u64)) -> Duration {
+    let start = Instant::now();
+    for i in 0..count {
+        f(i);
+    }
+    start.elapsed() / count as u32
+}
+
+/// Builds a collection of `config.entries` entries with `build`, then times updates and reads of
+/// it, printing the row.
+fn bench<C>(
+    name: &str,
+    config: &Config,
+    build: impl FnOnce(u64) -> C,
+    mut update: impl FnMut(&C, u64),
+    mut read: impl FnMut(&C, u64) -> Option<u64>,
+) {
+    let entries = config.entries;
+    let collection = build(entries);
+    let update = time(config.updates, |i| update(&collection, key(i, entries)));
+    let read = time(config.reads, |i| {
+        black_box(read(&collection, key(i, entries)));
+    });
+    println!("{name},{entries},{},{}", update.as_nanos(), read.as_nanos());
+}
+
+fn main() {
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(err) if err.is_empty() => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            process::exit(2);
+        }
+    };
+    println!("collection,entries,update_ns,read_ns");
+    bench(
+        "std::HashMap",
+        &config,
+        |entries| RcuHashMap::<u64, u64>::from_map((0..entries).map(|i| (i, i)).collect()),
+        |map, key| drop(map.insert(key, key + 1)),
+        |map, key| map.get(&key),
+    );
+    bench(
+        "im::HashMap",
+        &config,
+        |entries| RcuImHashMap::<u64, u64>::from_map((0..entries).map(|i| (i, i)).collect()),
+        |map, key| drop(map.insert(key, key + 1)),
+        |map, key| map.get(&key),
+    );
+    bench(
+        "std::BTreeMap",
+        &config,
+        |entries| RcuBTreeMap::from_map((0..entries).map(|i| (i, i)).collect()),
+        |map, key| drop(map.insert(key, key + 1)),
+        |map, key| map.get(&key),
+    );
+    bench(
+        "im::OrdMap",
+        &config,
+        |entries| RcuImOrdMap::from_map((0..entries).map(|i| (i, i)).collect()),
+        |map, key| drop(map.insert(key, key + 1)),
+        |map, key| map.get(&key),
+    );
+    bench(
+        "std::Vec",
+        &config,
+        |entries| RcuVec::from_list((0..entries).collect()),
+        |list, index| drop(list.set(index as usize, index + 1)),
+        |list, index| list.get(index as usize),
+    );
+    bench(
+        "im::Vector",
+        &config,
+        |entries| RcuImVector::from_list((0..entries).collect()),
+        |list, index| drop(list.set(index as usize, index + 1)),
+        |list, index| list.get(index as usize),
+    );
+}
@@ -0,0 +1,96 @@
+//! Measures what a read of an `Rcu` costs once frozen, against the same read before freezing it
+//! and a bare pointer load, for various reader counts: `read_guard` of a frozen value skips the
+//! read-side critical section, and `frozen_ref` only loads the pointer. Prints one CSV row per read
+//! and reader count. Run with `cargo run --release --bin bench_frozen -- --help`.
+
+use std::hint::black_box;
+use std::process;
+use std::sync::atomic::{AtomicPtr, AtomicU64, Ordering::{Acquire, Relaxed}};
+use std::thread;
+use std::time::Instant;
+
+use rcu_rust::Rcu;
+
+const USAGE: &str = "usage: bench_frozen [--readers 1,2,4,...] [--reads N]";
+
+struct Config {
+    readers: Vec<usize>,
+    /// Reads per reader
+    reads: u64,
+}
+
+impl Config {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut config = Config {
+            readers: vec![1, 2, 4, 8, 16],
+            reads: 10_000_000,
+        };
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                return Err(String::new());
+            }
+            let value = args.next().ok_or_else(|| format!("{arg} needs a value"))?;
+            let bad = || format!("bad value for {arg}: {value}");
+            match arg.as_str() {
+                "--readers" => {
+                    config.readers = value.split(',').map(str::parse).collect::<Result<_, _>>().map_err(|_| bad())?;
+                }
+                "--reads" => config.reads = value.parse().map_err(|_| bad())?,
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+        if config.reads == 0 || config.readers.contains(&0) {
+            return Err("--reads and --readers must be positive".into());
+        }
+        Ok(config)
+    }
+}
+
+/// Runs `readers` threads making `reads` calls of `read` each, returning the mean nanoseconds per call.
+fn time(readers: usize, reads: u64, read: impl Fn() -> u64 + Sync) -> f64 {
+    let busy = AtomicU64::new(0);
+    thread::scope(|s| {
+        for _ in 0..readers {
+            s.spawn(|| {
+                let start = Instant::now();
+                for _ in 0..reads {
+                    black_box(read());
+                }
+                busy.fetch_add(start.elapsed().as_nanos() as u64, Relaxed);
+            });
+        }
+    });
+    busy.into_inner() as f64 / (readers as u64 * reads) as f64
+}
+
+fn main() {
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(err) if err.is_empty() => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            process::exit(2);
+        }
+    };
+    let reads = config.reads;
+    println!("read,readers,ns_per_read");
+    for &readers in &config.readers {
+        let row = |name: &str, per_read: f64| println!("{name},{readers},{per_read:.2}");
+
+        let value = Box::into_raw(Box::new(1u64));
+        let ptr = AtomicPtr::new(value);
+        // Safety: `value` is only freed below, once done reading it
+        row("pointer_load", time(readers, reads, || unsafe { *ptr.load(Acquire) }));
+        // Safety: from `Box::into_raw` above, and nothing reads it anymore
+        drop(unsafe { Box::from_raw(value) });
+
+        let rcu = Rcu::new(1u64);
+        row("read_guard", time(readers, reads, || *rcu.read_guard()));
+        rcu.freeze();
+        row("read_guard_frozen", time(readers, reads, || *rcu.read_guard()));
+        row("frozen_ref", time(readers, reads, || *rcu.frozen_ref().expect("frozen above")));
+    }
+}
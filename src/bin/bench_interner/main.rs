@@ -0,0 +1,151 @@
+//! Compares lookups of interned strings in an `RcuInterner` with lookups in a
+//! `Mutex<HashMap<String, u32>>`, for various reader counts, optionally with a writer interning new
+//! strings meanwhile. Prints one CSV row per structure and reader count. Run with
+//! `cargo run --release --bin bench_interner -- --help`.
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering::Relaxed};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rcu_rust::RcuInterner;
+
+const USAGE: &str =
+    "usage: bench_interner [--readers 1,2,4,...] [--strings N] [--interns-per-sec N] [--duration MS]";
+
+struct Config {
+    readers: Vec<usize>,
+    /// Strings interned before the readers start, which they look up in turn
+    strings: usize,
+    /// New strings the writer interns per second, 0 for no writer at all
+    interns_per_sec: u64,
+    duration: Duration,
+}
+
+impl Config {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut config = Config {
+            readers: vec![1, 2, 4, 8, 16, 32],
+            strings: 1024,
+            interns_per_sec: 0,
+            duration: Duration::from_millis(1000),
+        };
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                return Err(String::new());
+            }
+            let value = args.next().ok_or_else(|| format!("{arg} needs a value"))?;
+            let bad = || format!("bad value for {arg}: {value}");
+            match arg.as_str() {
+                "--readers" => {
+                    config.readers = value.split(',').map(str::parse).collect::<Result<_, _>>().map_err(|_| bad())?;
+                }
+                "--strings" => config.strings = value.parse().map_err(|_| bad())?,
+                "--interns-per-sec" => config.interns_per_sec = value.parse().map_err(|_| bad())?,
+                "--duration" => config.duration = Duration::from_millis(value.parse().map_err(|_| bad())?),
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+        if config.strings == 0 || config.readers.contains(&0) {
+            return Err("--strings and --readers must be positive".into());
+        }
+        Ok(config)
+    }
+}
+
+/// Runs `readers` threads calling `lookup` with the strings in turn, and a writer calling `intern`
+/// with new strings at the configured rate, returning the lookups made per second.
+fn run(
+    config: &Config,
+    readers: usize,
+    strings: &[String],
+    lookup: impl Fn(&str) -> Option<u32> + Sync,
+    intern: impl Fn(&str) + Sync,
+) -> f64 {
+    let stop = AtomicBool::new(false);
+    let lookups = AtomicU64::new(0);
+    let start = Instant::now();
+    thread::scope(|s| {
+        for reader in 0..readers {
+            let (stop, lookups, lookup) = (&stop, &lookups, &lookup);
+            s.spawn(move || {
+                let mut n = 0;
+                // Readers start apart, not to all look up the same string at once
+                for string in strings.iter().cycle().skip(reader * strings.len() / readers) {
+                    if stop.load(Relaxed) {
+                        break;
+                    }
+                    black_box(lookup(string));
+                    n += 1;
+                }
+                lookups.fetch_add(n, Relaxed);
+            });
+        }
+        if config.interns_per_sec > 0 {
+            s.spawn(|| {
+                let pause = Duration::from_secs(1) / config.interns_per_sec as u32;
+                let mut n = 0;
+                while !stop.load(Relaxed) {
+                    intern(&format!("new-{n}"));
+                    n += 1;
+                    thread::sleep(pause);
+                }
+            });
+        }
+        thread::sleep(config.duration);
+        stop.store(true, Relaxed);
+    });
+    lookups.into_inner() as f64 / start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(err) if err.is_empty() => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            process::exit(2);
+        }
+    };
+    let strings: Vec<String> = (0..config.strings).map(|i| format!("string-{i}")).collect();
+    println!("structure,readers,strings,interns_per_sec,lookups_per_sec");
+    for &readers in &config.readers {
+        let row = |structure: &str, lookups: f64| {
+            println!("{structure},{readers},{},{},{lookups:.0}", config.strings, config.interns_per_sec);
+        };
+
+        let interner = RcuInterner::new();
+        interner.intern_all(strings.iter().map(String::as_str));
+        let lookups = run(
+            &config,
+            readers,
+            &strings,
+            |string| interner.get(string).map(|symbol| symbol.index()),
+            |string| {
+                interner.intern(string);
+            },
+        );
+        row("RcuInterner", lookups);
+
+        let map: Mutex<HashMap<String, u32>> =
+            Mutex::new(strings.iter().enumerate().map(|(i, string)| (string.clone(), i as u32)).collect());
+        let lookups = run(
+            &config,
+            readers,
+            &strings,
+            |string| map.lock().unwrap().get(string).copied(),
+            |string| {
+                let mut map = map.lock().unwrap();
+                let next = map.len() as u32;
+                map.entry(string.to_owned()).or_insert(next);
+            },
+        );
+        row("Mutex<HashMap>", lookups);
+    }
+}
@@ -0,0 +1,129 @@
+//! Compares `LeftRight` with `Rcu` under one writer changing a vector as fast as it can while
+//! readers read from it, for various reader counts. A `LeftRight` write applies the change twice in
+//! place, an `Rcu` one copies the vector into a new allocation. Prints one CSV row per structure
+//! and reader count. Run with `cargo run --release --bin bench_left_right -- --help`.
+
+use std::hint::black_box;
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering::Relaxed};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rcu_rust::{LeftRight, Rcu};
+
+const USAGE: &str = "usage: bench_left_right [--readers 1,2,4,...] [--len N] [--duration MS]";
+
+struct Config {
+    readers: Vec<usize>,
+    /// Elements of the vector read and written
+    len: usize,
+    duration: Duration,
+}
+
+impl Config {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut config = Config {
+            readers: vec![1, 2, 4, 8, 16],
+            len: 1024,
+            duration: Duration::from_millis(1000),
+        };
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                return Err(String::new());
+            }
+            let value = args.next().ok_or_else(|| format!("{arg} needs a value"))?;
+            let bad = || format!("bad value for {arg}: {value}");
+            match arg.as_str() {
+                "--readers" => {
+                    config.readers = value.split(',').map(str::parse).collect::<Result<_, _>>().map_err(|_| bad())?;
+                }
+                "--len" => config.len = value.parse().map_err(|_| bad())?,
+                "--duration" => config.duration = Duration::from_millis(value.parse().map_err(|_| bad())?),
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+        if config.len == 0 || config.readers.contains(&0) {
+            return Err("--len and --readers must be positive".into());
+        }
+        Ok(config)
+    }
+}
+
+/// Runs `readers` threads calling `read` and one calling `write` for `duration`, returning the
+/// reads and writes they made per second.
+fn run(
+    readers: usize,
+    duration: Duration,
+    read: impl Fn(usize) -> u64 + Sync,
+    mut write: impl FnMut(u64) + Send,
+) -> (f64, f64) {
+    let stop = AtomicBool::new(false);
+    let reads = AtomicU64::new(0);
+    let mut writes = 0;
+    let start = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..readers {
+            s.spawn(|| {
+                let mut n = 0;
+                while !stop.load(Relaxed) {
+                    black_box(read(n));
+                    n += 1;
+                }
+                reads.fetch_add(n as u64, Relaxed);
+            });
+        }
+        s.spawn(|| {
+            while !stop.load(Relaxed) {
+                write(writes);
+                writes += 1;
+            }
+        });
+        thread::sleep(duration);
+        stop.store(true, Relaxed);
+    });
+    let secs = start.elapsed().as_secs_f64();
+    (reads.into_inner() as f64 / secs, writes as f64 / secs)
+}
+
+fn main() {
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(err) if err.is_empty() => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            process::exit(2);
+        }
+    };
+    let len = config.len;
+    println!("structure,readers,len,reads_per_sec,writes_per_sec");
+    for &readers in &config.readers {
+        let lr = LeftRight::new(vec![0u64; len]);
+        let mut writer = lr.writer().expect("the only writer");
+        let (reads, writes) = run(
+            readers,
+            config.duration,
+            |i| lr.read_with(|values| values[i % len]),
+            |i| writer.apply(|values| values[i as usize % len] += 1),
+        );
+        println!("LeftRight,{readers},{len},{reads:.0},{writes:.0}");
+
+        let rcu = Rcu::new(vec![0u64; len]);
+        let (reads, writes) = run(
+            readers,
+            config.duration,
+            |i| rcu.read_with(|values| values[i % len]),
+            |i| {
+                rcu.update_with(|values| {
+                    let mut values = values.clone();
+                    values[i as usize % len] += 1;
+                    values
+                })
+                .expect("nothing refuses the value");
+            },
+        );
+        println!("Rcu,{readers},{len},{reads:.0},{writes:.0}");
+    }
+}
@@ -0,0 +1,329 @@
+//! Measures how read throughput and latency scale with the number of reader threads, optionally
+//! with writers publishing meanwhile, and prints one CSV or JSON row per reader count. The values
+//! read are the stress test's self-checking payloads, sampled reads are verified, so the numbers
+//! come from correct executions. Run with `cargo run --release --bin bench_scaling -- --help`.
+
+#[path = "../stress/checked.rs"]
+mod checked;
+
+use std::hint::black_box;
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering::Relaxed};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use checked::{Checker, Payload};
+use rcu_rust::Rcu;
+
+const USAGE: &str = "usage: bench_scaling [--readers 1,2,4,...] [--writers N] [--write-rate N] [--duration 2s] \
+                     [--payload-bytes N] [--read-mode clone|guard|arc] [--format csv|json]";
+
+/// One read in this many is timed
+const TIME_EVERY: u64 = 1024;
+/// One read in this many is checked, outside of the timed part
+const CHECK_EVERY: u64 = 64;
+/// Most latency samples kept per reader
+const MAX_SAMPLES: usize = 1 << 18;
+
+#[derive(Clone, Copy)]
+enum ReadMode {
+    /// `Rcu::read`, cloning the payload
+    Clone,
+    /// `Rcu::read_guard`, reading it in place
+    Guard,
+    /// `Rcu::read` of an `Rcu<Arc<Payload>>`, cloning the `Arc`
+    Arc,
+}
+
+impl ReadMode {
+    fn name(self) -> &'static str {
+        match self {
+            ReadMode::Clone => "clone",
+            ReadMode::Guard => "guard",
+            ReadMode::Arc => "arc",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Csv,
+    Json,
+}
+
+struct Config {
+    readers: Vec<usize>,
+    writers: usize,
+    /// Publishes per second per writer, 0 for as many as they can
+    write_rate: u64,
+    duration: Duration,
+    payload_bytes: usize,
+    read_mode: ReadMode,
+    format: Format,
+}
+
+impl Config {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut config = Config {
+            readers: vec![1, 2, 4, 8, 16, 32, 64],
+            writers: 0,
+            write_rate: 0,
+            duration: Duration::from_secs(2),
+            payload_bytes: 64,
+            read_mode: ReadMode::Clone,
+            format: Format::Csv,
+        };
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                return Err(String::new());
+            }
+            let value = args.next().ok_or_else(|| format!("missing value for {arg}"))?;
+            let invalid = || format!("invalid value for {arg}: {value}");
+            let number = || value.parse::<usize>().map_err(|_| invalid());
+            match arg.as_str() {
+                "--readers" => {
+                    config.readers = value.split(',').map(|n| n.parse()).collect::<Result<_, _>>().map_err(|_| invalid())?
+                }
+                "--writers" => config.writers = number()?,
+                "--write-rate" => config.write_rate = number()? as u64,
+                "--payload-bytes" => config.payload_bytes = number()?,
+                "--duration" => config.duration = parse_duration(&value)?,
+                "--read-mode" => {
+                    config.read_mode = match value.as_str() {
+                        "clone" => ReadMode::Clone,
+                        "guard" => ReadMode::Guard,
+                        "arc" => ReadMode::Arc,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "--format" => {
+                    config.format = match value.as_str() {
+                        "csv" => Format::Csv,
+                        "json" => Format::Json,
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(format!("unknown argument: {arg}")),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Parses durations like `2s` or `500ms`, plain numbers being seconds.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration: {value}");
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let number = value[..split].parse::<u64>().map_err(|_| invalid())?;
+    match &value[split..] {
+        "ms" => Ok(Duration::from_millis(number)),
+        "" | "s" => Ok(Duration::from_secs(number)),
+        _ => Err(invalid()),
+    }
+}
+
+/// The outcome of running one reader count.
+struct Row {
+    readers: usize,
+    reads: u64,
+    elapsed: Duration,
+    p50: Duration,
+    p99: Duration,
+    updates: u64,
+    /// Attempts of `Rcu::update_with` that lost against another writer and had to be redone
+    failed_updates: u64,
+}
+
+/// Reads the current payload of an `Rcu` and hands it to a callback while it is alive.
+type Read<T> = fn(&Rcu<T>, &mut dyn FnMut(&Payload));
+
+/// Runs `readers` readers and the configured writers against `rcu` for the configured duration.
+fn run<T: Clone + Send + Sync>(
+    config: &Config,
+    readers: usize,
+    rcu: Rcu<T>,
+    read: Read<T>,
+    next: fn(&T, u64, usize) -> T,
+) -> Result<Row, String> {
+    let stop = AtomicBool::new(false);
+    let reads = AtomicU64::new(0);
+    let updates = AtomicU64::new(0);
+    let attempts = AtomicU64::new(0);
+    let samples = Mutex::new(Vec::new());
+    let error = Mutex::new(None);
+    let fail = |err: String| {
+        error.lock().unwrap().get_or_insert(err);
+        stop.store(true, Relaxed);
+    };
+    let start = Instant::now();
+    thread::scope(|s| {
+        for reader in 0..readers {
+            let (rcu, stop, reads, samples, fail) = (&rcu, &stop, &reads, &samples, &fail);
+            s.spawn(move || {
+                let mut checker = Checker::default();
+                let mut latencies = Vec::new();
+                let mut n = 0u64;
+                while !stop.load(Relaxed) {
+                    let started = (n.is_multiple_of(TIME_EVERY) && latencies.len() < MAX_SAMPLES).then(Instant::now);
+                    let mut checked = Ok(());
+                    read(rcu, &mut |payload| {
+                        if let Some(started) = started {
+                            latencies.push(started.elapsed());
+                        }
+                        black_box(payload.seq);
+                        if n.is_multiple_of(CHECK_EVERY) {
+                            checked = checker.check(payload);
+                        }
+                    });
+                    if let Err(err) = checked {
+                        fail(format!("reader {reader}: {err}"));
+                    }
+                    n += 1;
+                }
+                reads.fetch_add(n, Relaxed);
+                samples.lock().unwrap().append(&mut latencies);
+            });
+        }
+        for writer in 0..config.writers {
+            let (rcu, stop, updates, attempts, fail) = (&rcu, &stop, &updates, &attempts, &fail);
+            s.spawn(move || {
+                let mut n = 0;
+                while !stop.load(Relaxed) {
+                    let published = rcu.update_with(|cur| {
+                        attempts.fetch_add(1, Relaxed);
+                        next(cur, writer as u64, config.payload_bytes)
+                    });
+                    if let Err(err) = published {
+                        fail(format!("writer {writer}: {err}"));
+                    }
+                    n += 1;
+                    if config.write_rate > 0 {
+                        let due = start + Duration::from_secs_f64(n as f64 / config.write_rate as f64);
+                        thread::sleep(due.saturating_duration_since(Instant::now()));
+                    }
+                }
+                updates.fetch_add(n, Relaxed);
+            });
+        }
+        thread::sleep(config.duration);
+        stop.store(true, Relaxed);
+    });
+    let elapsed = start.elapsed();
+    drop(rcu.into_box());
+    if let Some(err) = error.into_inner().unwrap() {
+        return Err(err);
+    }
+    if checked::alive() != 0 {
+        return Err(format!("{} payloads were never reclaimed", checked::alive()));
+    }
+    let mut samples = samples.into_inner().unwrap();
+    samples.sort_unstable();
+    let percentile = |p: usize| samples.get(samples.len().saturating_sub(1) * p / 100).copied().unwrap_or_default();
+    let updates = updates.into_inner();
+    Ok(Row {
+        readers,
+        reads: reads.into_inner(),
+        elapsed,
+        p50: percentile(50),
+        p99: percentile(99),
+        updates,
+        failed_updates: attempts.into_inner() - updates,
+    })
+}
+
+fn run_mode(config: &Config, readers: usize) -> Result<Row, String> {
+    let payload = || Payload::new(0, 0, config.payload_bytes);
+    match config.read_mode {
+        ReadMode::Clone => run(
+            config,
+            readers,
+            Rcu::new(payload()),
+            |rcu, f| f(&rcu.read()),
+            |cur, writer, len| Payload::new(cur.seq + 1, writer, len),
+        ),
+        ReadMode::Guard => run(
+            config,
+            readers,
+            Rcu::new(payload()),
+            |rcu, f| f(&rcu.read_guard()),
+            |cur, writer, len| Payload::new(cur.seq + 1, writer, len),
+        ),
+        ReadMode::Arc => run(
+            config,
+            readers,
+            Rcu::new(Arc::new(payload())),
+            |rcu, f| f(&rcu.read()),
+            |cur, writer, len| Arc::new(Payload::new(cur.seq + 1, writer, len)),
+        ),
+    }
+}
+
+fn main() {
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(err) if err.is_empty() => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            process::exit(2);
+        }
+    };
+    let columns = [
+        "readers",
+        "writers",
+        "read_mode",
+        "payload_bytes",
+        "reads",
+        "reads_per_sec",
+        "p50_ns",
+        "p99_ns",
+        "updates",
+        "failed_updates",
+    ];
+    match config.format {
+        Format::Csv => println!("{}", columns.join(",")),
+        Format::Json => println!("["),
+    }
+    for (i, &readers) in config.readers.iter().enumerate() {
+        let row = match run_mode(&config, readers) {
+            Ok(row) => row,
+            Err(err) => {
+                eprintln!("FAILED with {readers} readers: {err}");
+                process::exit(1);
+            }
+        };
+        let values = [
+            row.readers.to_string(),
+            config.writers.to_string(),
+            config.read_mode.name().to_string(),
+            config.payload_bytes.to_string(),
+            row.reads.to_string(),
+            format!("{:.0}", row.reads as f64 / row.elapsed.as_secs_f64()),
+            row.p50.as_nanos().to_string(),
+            row.p99.as_nanos().to_string(),
+            row.updates.to_string(),
+            row.failed_updates.to_string(),
+        ];
+        match config.format {
+            Format::Csv => println!("{}", values.join(",")),
+            Format::Json => {
+                let fields: Vec<_> = columns
+                    .iter()
+                    .zip(&values)
+                    .map(|(column, value)| match *column {
+                        "read_mode" => format!("\"{column}\": \"{value}\""),
+                        _ => format!("\"{column}\": {value}"),
+                    })
+                    .collect();
+                let comma = if i + 1 < config.readers.len() { "," } else { "" };
+                println!("  {{{}}}{comma}", fields.join(", "));
+            }
+        }
+    }
+    if config.format == Format::Json {
+        println!("]");
+    }
+}
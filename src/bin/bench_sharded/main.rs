@@ -0,0 +1,120 @@
+//! Compares what an insert into a large hash map behind an `Rcu` costs when every insert copies the
+//! whole map, as with `RcuHashMap`, and when it only copies the shard of its key, as with a
+//! `ShardedRcuMap` of various shard counts. Prints one CSV row per map. Run with
+//! `cargo run --release --bin bench_sharded -- --help`.
+
+use std::hint::black_box;
+use std::process;
+use std::time::{Duration, Instant};
+
+use rcu_rust::{RcuHashMap, ShardedRcuMap};
+
+const USAGE: &str = "usage: bench_sharded [--entries N] [--updates N] [--reads N] [--shards N,N,...]";
+
+struct Config {
+    entries: u64,
+    updates: u64,
+    reads: u64,
+    shards: Vec<usize>,
+}
+
+impl Config {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut config = Config {
+            entries: 1_000_000,
+            updates: 100,
+            reads: 1_000_000,
+            shards: vec![4, 16, 64, 256],
+        };
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                return Err(String::new());
+            }
+            let value = args.next().ok_or_else(|| format!("{arg} needs a value"))?;
+            let bad = || format!("bad value for {arg}: {value}");
+            match arg.as_str() {
+                "--entries" => config.entries = value.parse().map_err(|_| bad())?,
+                "--updates" => config.updates = value.parse().map_err(|_| bad())?,
+                "--reads" => config.reads = value.parse().map_err(|_| bad())?,
+                "--shards" => {
+                    config.shards = value.split(',').map(str::parse).collect::<Result<_, _>>().map_err(|_| bad())?;
+                }
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+        if config.entries == 0 || config.updates == 0 || config.reads == 0 || config.shards.contains(&0) {
+            return Err("--entries, --updates, --reads and --shards must be positive".into());
+        }
+        Ok(config)
+    }
+}
+
+/// Spreads the `i`th access over the keys, so neither updates nor reads hit the same entry.
+fn key(i: u64, entries: u64) -> u64 {
+    i.wrapping_mul(0x9e37_79b9_7f4a_7c15) % entries
+}
+
+/// Times `count` calls of `f`, returning the mean per call.
+fn time(count: u64, mut f: impl FnMut(u64)) -> Duration {
+    let start = Instant::now();
+    for i in 0..count {
+        f(i);
+    }
+    start.elapsed() / count as u32
+}
+
+/// Builds a map of `config.entries` entries with `build`, then times inserts and reads of it,
+/// printing the row.
+fn bench<M>(
+    name: &str,
+    shards: usize,
+    config: &Config,
+    build: impl FnOnce(u64) -> M,
+    mut insert: impl FnMut(&M, u64),
+    mut read: impl FnMut(&M, u64) -> Option<u64>,
+) {
+    let entries = config.entries;
+    let map = build(entries);
+    let insert = time(config.updates, |i| insert(&map, key(i, entries)));
+    let read = time(config.reads, |i| {
+        black_box(read(&map, key(i, entries)));
+    });
+    println!("{name},{shards},{entries},{},{}", insert.as_nanos(), read.as_nanos());
+}
+
+fn main() {
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(err) if err.is_empty() => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            process::exit(2);
+        }
+    };
+    println!("map,shards,entries,insert_ns,read_ns");
+    bench(
+        "RcuHashMap",
+        1,
+        &config,
+        |entries| RcuHashMap::<u64, u64>::from_map((0..entries).map(|i| (i, i)).collect()),
+        |map, key| drop(map.insert(key, key + 1)),
+        |map, key| map.get(&key),
+    );
+    for &shards in &config.shards {
+        bench(
+            "ShardedRcuMap",
+            shards,
+            &config,
+            |entries| {
+                let map = ShardedRcuMap::new(shards);
+                map.extend((0..entries).map(|i| (i, i))).expect("nothing refuses the entries");
+                map
+            },
+            |map, key| drop(map.insert(key, key + 1)),
+            |map, key| map.get(&key),
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! Compares the latency of a publish through `Rcu::set`, which takes the writer exclusion and
+//! compare-exchanges the pointer, with one through `SingleWriter::publish`, a plain exchange, while
+//! readers read the value in a loop, for various reader counts. Prints one CSV row per writer and
+//! reader count. Run with `cargo run --release --bin bench_single_writer -- --help`.
+
+use std::hint::black_box;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+use std::thread;
+use std::time::Instant;
+
+use rcu_rust::Rcu;
+
+const USAGE: &str = "usage: bench_single_writer [--readers 0,1,2,...] [--publishes N]";
+
+struct Config {
+    readers: Vec<usize>,
+    publishes: usize,
+}
+
+impl Config {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut config = Config {
+            readers: vec![0, 1, 2, 4, 8],
+            publishes: 100_000,
+        };
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                return Err(String::new());
+            }
+            let value = args.next().ok_or_else(|| format!("{arg} needs a value"))?;
+            let bad = || format!("bad value for {arg}: {value}");
+            match arg.as_str() {
+                "--readers" => {
+                    config.readers = value.split(',').map(str::parse).collect::<Result<_, _>>().map_err(|_| bad())?;
+                }
+                "--publishes" => config.publishes = value.parse().map_err(|_| bad())?,
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+        if config.publishes == 0 {
+            return Err("--publishes must be positive".into());
+        }
+        Ok(config)
+    }
+}
+
+/// Runs `readers` threads calling `read` while timing `publishes` calls of `publish`, returning the
+/// latencies in nanoseconds, sorted.
+fn run(readers: usize, publishes: usize, read: impl Fn() -> u64 + Sync, mut publish: impl FnMut(u64)) -> Vec<u64> {
+    let stop = AtomicBool::new(false);
+    let mut latencies = Vec::with_capacity(publishes);
+    thread::scope(|s| {
+        for _ in 0..readers {
+            s.spawn(|| {
+                while !stop.load(Relaxed) {
+                    black_box(read());
+                }
+            });
+        }
+        for i in 0..publishes as u64 {
+            let start = Instant::now();
+            publish(i);
+            latencies.push(start.elapsed().as_nanos() as u64);
+        }
+        stop.store(true, Relaxed);
+    });
+    latencies.sort_unstable();
+    latencies
+}
+
+fn main() {
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(err) if err.is_empty() => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            process::exit(2);
+        }
+    };
+    println!("writer,readers,mean_ns,p50_ns,p99_ns");
+    for &readers in &config.readers {
+        let row = |writer: &str, latencies: Vec<u64>| {
+            let mean = latencies.iter().sum::<u64>() as f64 / latencies.len() as f64;
+            let at = |quantile: f64| latencies[((latencies.len() - 1) as f64 * quantile) as usize];
+            println!("{writer},{readers},{mean:.0},{},{}", at(0.5), at(0.99));
+        };
+
+        let rcu = Rcu::new(0u64);
+        let latencies = run(readers, config.publishes, || rcu.read(), |i| {
+            rcu.set(i).expect("nothing refuses the value");
+        });
+        row("set", latencies);
+
+        let Ok((mut writer, reader)) = Rcu::new(0u64).into_single_writer() else {
+            unreachable!("a new `Rcu` has neither invariants nor limits, nor is it frozen")
+        };
+        row("single_writer", run(readers, config.publishes, || reader.read(), |i| writer.publish(i)));
+    }
+}
@@ -0,0 +1,120 @@
+//! A payload that can tell whether it was observed whole, and the checks stress runs apply to it.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering::Relaxed};
+
+/// Payloads created so far, clones included
+static CREATED: Counter = Counter::new();
+/// Payloads dropped so far
+static DROPPED: Counter = Counter::new();
+
+/// Payloads currently alive, zero once everything was reclaimed.
+pub fn alive() -> i64 {
+    CREATED.sum() as i64 - DROPPED.sum() as i64
+}
+
+const STRIPES: usize = 64;
+
+/// A counter spread over cache lines, each thread adding to its own, so that counting clones
+/// doesn't serialize the readers making them.
+struct Counter {
+    stripes: [Stripe; STRIPES],
+}
+
+#[repr(align(128))]
+struct Stripe(AtomicU64);
+
+impl Counter {
+    const fn new() -> Self {
+        Self {
+            stripes: [const { Stripe(AtomicU64::new(0)) }; STRIPES],
+        }
+    }
+
+    fn add(&self) {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        thread_local! {
+            static STRIPE: usize = NEXT.fetch_add(1, Relaxed) % STRIPES;
+        }
+        self.stripes[STRIPE.with(|stripe| *stripe)].0.fetch_add(1, Relaxed);
+    }
+
+    fn sum(&self) -> u64 {
+        self.stripes.iter().map(|stripe| stripe.0.load(Relaxed)).sum()
+    }
+}
+
+/// A value carrying its own seal: the sequence number it was published as, the writer that
+/// produced it, filler bytes derived from both and a checksum over all of it. Reading freed or
+/// half-written memory shows up as a checksum mismatch with overwhelming probability.
+pub struct Payload {
+    pub seq: u64,
+    pub writer: u64,
+    bytes: Vec<u8>,
+    checksum: u64,
+}
+
+impl Payload {
+    pub fn new(seq: u64, writer: u64, len: usize) -> Self {
+        let bytes = (0..len).map(|i| (seq as usize ^ writer as usize ^ i) as u8).collect::<Vec<_>>();
+        let checksum = checksum(seq, writer, &bytes);
+        CREATED.add();
+        Self {
+            seq,
+            writer,
+            bytes,
+            checksum,
+        }
+    }
+
+    /// Whether the payload is intact.
+    pub fn is_intact(&self) -> bool {
+        checksum(self.seq, self.writer, &self.bytes) == self.checksum
+    }
+}
+
+impl Clone for Payload {
+    fn clone(&self) -> Self {
+        CREATED.add();
+        Self {
+            seq: self.seq,
+            writer: self.writer,
+            bytes: self.bytes.clone(),
+            checksum: self.checksum,
+        }
+    }
+}
+
+impl Drop for Payload {
+    fn drop(&mut self) {
+        DROPPED.add();
+    }
+}
+
+/// FNV-1a over the whole payload.
+fn checksum(seq: u64, writer: u64, bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for byte in seq.to_le_bytes().iter().chain(&writer.to_le_bytes()).chain(bytes) {
+        hash = (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// Checks the payloads observed by one thread: each must be intact, and sequence numbers may
+/// never go backwards.
+#[derive(Default)]
+pub struct Checker {
+    last_seq: u64,
+}
+
+impl Checker {
+    pub fn check(&mut self, payload: &Payload) -> Result<(), String> {
+        if !payload.is_intact() {
+            return Err(format!("corrupted payload observed (seq {}, writer {})", payload.seq, payload.writer));
+        }
+        if payload.seq < self.last_seq {
+            return Err(format!("sequence went backwards: {} after {}", payload.seq, self.last_seq));
+        }
+        self.last_seq = payload.seq;
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+//! Hammers a single `Rcu` with concurrent readers and writers for a while, checking that every
+//! observed value is intact and recent enough, that nothing stalls and that every value is
+//! eventually reclaimed. Run with `cargo run --release --bin stress -- --help`.
+
+mod checked;
+
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering::{Relaxed, SeqCst}};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use checked::{Checker, Payload};
+use rcu_rust::Rcu;
+
+const USAGE: &str = "usage: stress [--readers N] [--writers N] [--duration 30s] [--payload-bytes N] \
+                     [--read-mode clone|guard] [--watchdog 1s] [--engine default|biased|membarrier]";
+
+#[derive(Clone, Copy, PartialEq)]
+enum ReadMode {
+    /// `Rcu::read`, checking the clone
+    Clone,
+    /// `Rcu::read_with`, checking the value in place
+    Guard,
+}
+
+/// How the `Rcu` under test is created.
+#[derive(Clone, Copy)]
+enum Engine {
+    /// `Rcu::new`
+    Default,
+    /// `Rcu::new_biased`
+    Biased,
+    /// `Rcu::new_membarrier`, only with the `membarrier` feature
+    #[cfg(feature = "membarrier")]
+    Membarrier,
+}
+
+struct Config {
+    readers: usize,
+    writers: usize,
+    duration: Duration,
+    payload_bytes: usize,
+    read_mode: ReadMode,
+    /// Longest a single operation may take before the run counts as stalled
+    watchdog: Duration,
+    engine: Engine,
+}
+
+impl Config {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut config = Config {
+            readers: 4,
+            writers: 2,
+            duration: Duration::from_secs(30),
+            payload_bytes: 4096,
+            read_mode: ReadMode::Clone,
+            watchdog: Duration::from_secs(1),
+            engine: Engine::Default,
+        };
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                return Err(String::new());
+            }
+            let value = args.next().ok_or_else(|| format!("missing value for {arg}"))?;
+            let number = || value.parse::<usize>().map_err(|_| format!("invalid value for {arg}: {value}"));
+            match arg.as_str() {
+                "--readers" => config.readers = number()?,
+                "--writers" => config.writers = number()?,
+                "--payload-bytes" => config.payload_bytes = number()?,
+                "--duration" => config.duration = parse_duration(&value)?,
+                "--watchdog" => config.watchdog = parse_duration(&value)?,
+                "--read-mode" => {
+                    config.read_mode = match value.as_str() {
+                        "clone" => ReadMode::Clone,
+                        "guard" => ReadMode::Guard,
+                        _ => return Err(format!("invalid read mode: {value}")),
+                    }
+                }
+                "--engine" => {
+                    config.engine = match value.as_str() {
+                        "default" => Engine::Default,
+                        "biased" => Engine::Biased,
+                        #[cfg(feature = "membarrier")]
+                        "membarrier" => Engine::Membarrier,
+                        _ => return Err(format!("invalid engine: {value}")),
+                    }
+                }
+                _ => return Err(format!("unknown argument: {arg}")),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Parses durations like `30s`, `500ms` or `2m`, plain numbers being seconds.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration: {value}");
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let number = value[..split].parse::<u64>().map_err(|_| invalid())?;
+    match &value[split..] {
+        "ms" => Ok(Duration::from_millis(number)),
+        "" | "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number * 60)),
+        _ => Err(invalid()),
+    }
+}
+
+/// When each thread started its current operation, in microseconds since the run started plus
+/// one, 0 while it is between operations.
+struct Watchdog {
+    start: Instant,
+    started: Vec<AtomicU64>,
+}
+
+impl Watchdog {
+    fn time<R>(&self, thread: usize, op: impl FnOnce() -> R) -> R {
+        self.started[thread].store(self.start.elapsed().as_micros() as u64 + 1, SeqCst);
+        let out = op();
+        self.started[thread].store(0, SeqCst);
+        out
+    }
+
+    /// The thread stuck in an operation for longer than `limit` and for how long, if any.
+    fn stalled(&self, limit: Duration) -> Option<(usize, Duration)> {
+        let now = self.start.elapsed().as_micros() as u64 + 1;
+        self.started.iter().enumerate().find_map(|(thread, started)| {
+            let started = started.load(SeqCst);
+            let stuck = Duration::from_micros(now.saturating_sub(started));
+            (started != 0 && stuck > limit).then_some((thread, stuck))
+        })
+    }
+}
+
+fn main() {
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(err) if err.is_empty() => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            process::exit(2);
+        }
+    };
+    let payload = Payload::new(0, 0, config.payload_bytes);
+    let rcu = match config.engine {
+        Engine::Default => Rcu::new(payload),
+        Engine::Biased => Rcu::new_biased(payload),
+        #[cfg(feature = "membarrier")]
+        Engine::Membarrier => Rcu::new_membarrier(payload),
+    };
+    let stop = AtomicBool::new(false);
+    let failed = AtomicBool::new(false);
+    let reads = AtomicU64::new(0);
+    let writes = AtomicU64::new(0);
+    let watchdog = Watchdog {
+        start: Instant::now(),
+        started: (0..config.readers + config.writers).map(|_| AtomicU64::new(0)).collect(),
+    };
+    let fail = |err: String| {
+        eprintln!("FAILED: {err}");
+        failed.store(true, SeqCst);
+        stop.store(true, SeqCst);
+    };
+
+    thread::scope(|s| {
+        for reader in 0..config.readers {
+            let (rcu, stop, reads, watchdog, fail) = (&rcu, &stop, &reads, &watchdog, &fail);
+            s.spawn(move || {
+                let mut checker = Checker::default();
+                let mut n = 0;
+                while !stop.load(Relaxed) {
+                    let checked = watchdog.time(reader, || match config.read_mode {
+                        ReadMode::Clone => checker.check(&rcu.read()),
+                        ReadMode::Guard => rcu.read_with(|payload| checker.check(payload)),
+                    });
+                    if let Err(err) = checked {
+                        fail(format!("reader {reader}: {err}"));
+                    }
+                    n += 1;
+                }
+                reads.fetch_add(n, Relaxed);
+            });
+        }
+        for writer in 0..config.writers {
+            let (rcu, stop, writes, watchdog, fail) = (&rcu, &stop, &writes, &watchdog, &fail);
+            let thread = config.readers + writer;
+            s.spawn(move || {
+                let mut checker = Checker::default();
+                let mut n = 0;
+                while !stop.load(Relaxed) {
+                    let published = watchdog.time(thread, || {
+                        rcu.update_with(|cur| Payload::new(cur.seq + 1, writer as u64, config.payload_bytes))
+                    });
+                    match published {
+                        Ok(payload) => {
+                            if let Err(err) = checker.check(&payload) {
+                                fail(format!("writer {writer}: {err}"));
+                            }
+                        }
+                        Err(err) => fail(format!("writer {writer}: {err}")),
+                    }
+                    n += 1;
+                }
+                writes.fetch_add(n, Relaxed);
+            });
+        }
+        let deadline = Instant::now() + config.duration;
+        while !stop.load(Relaxed) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            if let Some((thread, stuck)) = watchdog.stalled(config.watchdog) {
+                fail(format!("thread {thread} stuck in a single operation for {stuck:?}"));
+            }
+        }
+        stop.store(true, SeqCst);
+    });
+
+    let last = rcu.into_box();
+    let writes = writes.into_inner();
+    if last.seq != writes {
+        fail(format!("{writes} publishes but the last value has sequence number {}", last.seq));
+    }
+    drop(last);
+    if checked::alive() != 0 {
+        fail(format!("{} payloads were never reclaimed", checked::alive()));
+    }
+    println!(
+        "{} reads, {writes} writes in {:?}",
+        reads.into_inner(),
+        watchdog.start.elapsed()
+    );
+    if failed.load(SeqCst) {
+        process::exit(1);
+    }
+}
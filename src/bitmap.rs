@@ -0,0 +1,286 @@
+use std::collections::hash_map::{Entry, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering::{Relaxed, SeqCst}};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+use crate::raw::RawRcu;
+
+/// Which flags exist and the bits holding their values. A flag's bit stays put for as long as its
+/// layout is current, flipping it never publishes anything.
+struct Layout {
+    index: HashMap<String, usize>,
+    /// Flag `i` is bit `i % 64` of `words[i / 64]`
+    words: Box<[AtomicU64]>,
+    /// Set once a migration away from this layout began, flips then wait for the new one
+    moved: AtomicBool,
+}
+
+impl Layout {
+    fn new(flags: impl IntoIterator<Item = (String, bool)>) -> Self {
+        let mut index = HashMap::new();
+        let mut words = Vec::new();
+        for (name, enabled) in flags {
+            let i = index.len();
+            let Entry::Vacant(slot) = index.entry(name) else {
+                continue;
+            };
+            slot.insert(i);
+            if i % 64 == 0 {
+                words.push(0);
+            }
+            words[i / 64] |= u64::from(enabled) << (i % 64);
+        }
+        Self {
+            index,
+            words: words.into_iter().map(AtomicU64::new).collect(),
+            moved: AtomicBool::new(false),
+        }
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self.words[i / 64].load(Relaxed) & 1 << (i % 64) != 0
+    }
+
+    /// Every flag and its value, in index order.
+    fn flags(&self) -> Vec<(String, bool)> {
+        let mut flags: Vec<_> = self.index.iter().map(|(name, &i)| (i, name.clone())).collect();
+        flags.sort_unstable();
+        flags.into_iter().map(|(i, name)| (name, self.get(i))).collect()
+    }
+}
+
+/// A set of named boolean flags, e.g. feature flags. Which flags exist is an RCU-published
+/// layout, while their values are bits flipped in place by relaxed atomic operations: turning one
+/// flag on or off costs a single atomic instruction and never copies anything, adding or removing
+/// flags publishes a new layout carrying the current values over.
+///
+/// Flips are relaxed and independent of one another, so a reader may see two flags flipped one
+/// after the other in either order. A flip arriving while the layout is migrated waits for the
+/// new layout, so it is never lost; reads never wait and see the same values from either layout.
+pub struct RcuBitmap {
+    layout: RawRcu<Layout>,
+    /// Serializes migrations, the only publishes of `layout`
+    migrating: Mutex<()>,
+}
+
+impl Default for RcuBitmap {
+    fn default() -> Self {
+        Self::with_flags(Vec::<(String, bool)>::new())
+    }
+}
+
+impl RcuBitmap {
+    /// Creates a bitmap without any flags.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Creates a bitmap with the given flags and initial values. Only the first of several
+    /// flags named the same is kept.
+    pub fn with_flags(flags: impl IntoIterator<Item = (impl Into<String>, bool)>) -> Self {
+        let layout = Layout::new(flags.into_iter().map(|(name, enabled)| (name.into(), enabled)));
+        Self {
+            layout: RawRcu::new(Box::new(layout)),
+            migrating: Mutex::new(()),
+        }
+    }
+    /// Whether the flag `name` is on, false if there is no such flag.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.get(name).unwrap_or(false)
+    }
+    /// Whether the flag `name` is on, None if there is no such flag.
+    pub fn get(&self, name: &str) -> Option<bool> {
+        self.layout.read_with(|layout| layout.index.get(name).map(|&i| layout.get(i)))
+    }
+    /// Whether there is a flag named `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.layout.read_with(|layout| layout.index.contains_key(name))
+    }
+    /// The number of flags.
+    pub fn len(&self) -> usize {
+        self.layout.read_with(|layout| layout.index.len())
+    }
+    /// Whether there are no flags at all.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Every flag and its value, in the order they were added in. Taken from one layout, but the
+    /// values may be flipped while they are collected.
+    pub fn flags(&self) -> Vec<(String, bool)> {
+        self.layout.read_with(Layout::flags)
+    }
+    /// Turns the flag `name` on or off, returning whether it was on before. None, changing
+    /// nothing, if there is no such flag.
+    pub fn set(&self, name: &str, enabled: bool) -> Option<bool> {
+        self.flip(name, |word, bit| {
+            if enabled {
+                word.fetch_or(bit, Relaxed)
+            } else {
+                word.fetch_and(!bit, Relaxed)
+            }
+        })
+    }
+    /// Turns the flag `name` on if it was off and off if it was on, returning whether it was on
+    /// before. None, changing nothing, if there is no such flag.
+    pub fn toggle(&self, name: &str) -> Option<bool> {
+        self.flip(name, |word, bit| word.fetch_xor(bit, Relaxed))
+    }
+    /// Adds the flag `name` with the value `enabled`, publishing a new layout. Returns false,
+    /// changing nothing, if there already is such a flag.
+    pub fn add_flag(&self, name: impl Into<String>, enabled: bool) -> bool {
+        self.add_flags([(name, enabled)]) == 1
+    }
+    /// Adds every flag not there yet with its given value, in a single new layout. Returns how
+    /// many were added, nothing is published if none was.
+    pub fn add_flags(&self, flags: impl IntoIterator<Item = (impl Into<String>, bool)>) -> usize {
+        self.migrate(|layout| {
+            let mut added = 0;
+            for (name, enabled) in flags {
+                let name = name.into();
+                if !layout.iter().any(|(flag, _)| *flag == name) {
+                    layout.push((name, Some(enabled)));
+                    added += 1;
+                }
+            }
+            added
+        })
+    }
+    /// Removes the flag `name`, publishing a new layout. Returns false, changing nothing, if
+    /// there is no such flag.
+    pub fn remove_flag(&self, name: &str) -> bool {
+        self.remove_flags([name]) == 1
+    }
+    /// Removes every given flag that is there, in a single new layout. Returns how many were
+    /// removed, nothing is published if none was.
+    pub fn remove_flags<'a>(&self, names: impl IntoIterator<Item = &'a str>) -> usize {
+        self.migrate(|layout| {
+            let before = layout.len();
+            for name in names {
+                layout.retain(|(flag, _)| flag != name);
+            }
+            before - layout.len()
+        })
+    }
+
+    /// Applies `op` to the word holding the flag `name` and the flag's bit in it, returning whether
+    /// the bit was set in the word `op` returns.
+    fn flip(&self, name: &str, op: impl Fn(&AtomicU64, u64) -> u64) -> Option<bool> {
+        loop {
+            // None while the layout is being migrated away from
+            let flipped = self.layout.read_with(|layout| {
+                // Either the migration's grace period waits for this flip, or the flip sees `moved`
+                if layout.moved.load(SeqCst) {
+                    return None;
+                }
+                Some(layout.index.get(name).map(|&i| {
+                    let bit = 1 << (i % 64);
+                    op(&layout.words[i / 64], bit) & bit != 0
+                }))
+            });
+            match flipped {
+                Some(flipped) => return flipped,
+                // Never wait inside the read lock, the migration waits for readers to drain
+                None => thread::yield_now(),
+            }
+        }
+    }
+
+    /// Publishes a new layout with the flags `change` leaves in the list of current ones, handed
+    /// to it in index order. Flags it adds come with their value, those already there keep theirs
+    /// (None). Returns what `change` returns, nothing is published if that is 0.
+    fn migrate(&self, change: impl FnOnce(&mut Vec<(String, Option<bool>)>) -> usize) -> usize {
+        let _migrating = self.migrating.lock().unwrap_or_else(PoisonError::into_inner);
+        // Only migrations publish, so the layout can't change under us from here on
+        let mut flags: Vec<_> = self.flags().into_iter().map(|(name, _)| (name, None)).collect();
+        let changed = change(&mut flags);
+        if changed == 0 {
+            return 0;
+        }
+        // Holds new flips back, then waits for those that got in before, so the values stop moving
+        // before they are copied over
+        self.layout.read_with(|layout| layout.moved.store(true, SeqCst));
+        self.layout.synchronize();
+        let layout = self.layout.read_with(|current| {
+            Layout::new(flags.into_iter().map(|(name, enabled)| {
+                let enabled = enabled.unwrap_or_else(|| current.get(current.index[&name]));
+                (name, enabled)
+            }))
+        });
+        // Safety: migrations are the only publishes, and `self.migrating` serializes them
+        unsafe { self.layout.publish_exclusive(Box::new(layout), |_| {}) };
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use super::*;
+
+    const MIGRATIONS: usize = 200;
+
+    /// Flags `f0`, `f1`... with every other one on, from `f0`.
+    fn striped(len: usize) -> RcuBitmap {
+        RcuBitmap::with_flags((0..len).map(|i| (format!("f{i}"), i.is_multiple_of(2))))
+    }
+
+    /// Adds and removes flags of its own, publishing new layouts until it's done.
+    fn migrate(bitmap: &RcuBitmap, done: &AtomicBool) {
+        for migration in 0..MIGRATIONS {
+            let name = format!("extra{}", migration % 7);
+            if !bitmap.add_flag(name.clone(), migration.is_multiple_of(3)) {
+                assert!(bitmap.remove_flag(&name));
+            }
+            thread::yield_now();
+        }
+        done.store(true, SeqCst);
+    }
+
+    #[test]
+    fn reads_racing_migrations_see_every_flag_as_it_was() {
+        let bitmap = striped(150);
+        let done = AtomicBool::new(false);
+        thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    while !done.load(SeqCst) {
+                        for i in 0..150_usize {
+                            assert_eq!(bitmap.get(&format!("f{i}")), Some(i.is_multiple_of(2)), "f{i} moved");
+                        }
+                        thread::yield_now();
+                    }
+                });
+            }
+            migrate(&bitmap, &done);
+        });
+        let flags = bitmap.flags();
+        assert_eq!(&flags[..150], &striped(150).flags()[..]);
+    }
+
+    #[test]
+    fn flips_racing_migrations_are_never_lost() {
+        let bitmap = striped(70);
+        let done = AtomicBool::new(false);
+        thread::scope(|s| {
+            // Each flips a flag of its own, in a different word for the first and last
+            for i in [0_usize, 1, 69] {
+                let (bitmap, done) = (&bitmap, &done);
+                s.spawn(move || {
+                    let name = format!("f{i}");
+                    let enabled = i.is_multiple_of(2);
+                    while !done.load(SeqCst) {
+                        assert_eq!(bitmap.toggle(&name), Some(enabled), "a flip of {name} was lost");
+                        assert_eq!(bitmap.set(&name, enabled), Some(!enabled), "a set of {name} was lost");
+                        thread::yield_now();
+                    }
+                });
+            }
+            migrate(&bitmap, &done);
+        });
+        // Each flipper put its flag back as it found it
+        for i in 0..70_usize {
+            let name = format!("f{i}");
+            assert_eq!(bitmap.get(&name), Some(i.is_multiple_of(2)), "{name} ended up flipped");
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::raw::RawRcu;
+
+/// A cached value, shared by every copy of the index it is in.
+struct Slot<V> {
+    value: V,
+    /// Set by the first hit since the clock hand last passed, see `Index::evict`
+    referenced: AtomicBool,
+}
+
+/// One published version of a cache's contents.
+struct Index<K, V> {
+    map: HashMap<K, Arc<Slot<V>>>,
+    /// The keys of `map` in the order the clock hand visits them
+    order: Vec<K>,
+    /// Position in `order` of the next eviction candidate
+    hand: usize,
+}
+
+impl<K: Clone, V> Clone for Index<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+            order: self.order.clone(),
+            hand: self.hand,
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> Index<K, V> {
+    fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+            order: Vec::new(),
+            hand: 0,
+        }
+    }
+
+    /// Evicts one entry, CLOCK style: the hand clears the referenced bit of entries that were hit
+    /// since it last passed, sparing them once, and evicts the first one not hit. Readers may keep
+    /// setting bits behind it, so after two full turns the entry under the hand goes regardless.
+    fn evict(&mut self) {
+        for turn in 0.. {
+            if self.hand >= self.order.len() {
+                self.hand = 0;
+            }
+            let slot = &self.map[&self.order[self.hand]];
+            let spared = turn < 2 * self.order.len() && slot.referenced.swap(false, Relaxed);
+            if !spared {
+                let key = self.order.remove(self.hand);
+                self.map.remove(&key);
+                return;
+            }
+            self.hand += 1;
+        }
+    }
+}
+
+/// A bounded cache for memoizing values which are looked up far more often than they are
+/// inserted. Lookups are RCU reads of the current index, inserts copy the index, add the entry,
+/// evict whatever goes over the capacity and publish the copy, so they cost time proportional to
+/// the number of entries.
+///
+/// Eviction approximates least-recently-used with the CLOCK algorithm: a hit sets a per-entry
+/// referenced bit, which is the only write lookups ever do and only the first hit since the
+/// eviction hand last passed that entry does it, and eviction spares entries whose bit is set once.
+/// An entry hit once is thus as safe as one hit constantly, and one not hit since the hand passed
+/// it goes next even if it was very popular before.
+pub struct RcuCache<K: Hash + Eq + Clone, V: Clone> {
+    index: RawRcu<Index<K, V>>,
+    capacity: usize,
+    /// Serializes inserts and clears, the only publishes of `index`
+    writer: Mutex<()>,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> RcuCache<K, V> {
+    /// Creates an empty cache holding at most `capacity` entries.
+    ///
+    /// # Panics
+    /// If `capacity` is 0.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "cache capacity must be positive");
+        Self {
+            index: RawRcu::new(Box::new(Index::new())),
+            capacity,
+            writer: Mutex::new(()),
+        }
+    }
+    /// The most entries the cache holds.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    /// A clone of the value cached for `key`, if any.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.index.read_with(|index| {
+            let slot = index.map.get(key)?;
+            // Loading first keeps the cache line shared while the bit is already set
+            if !slot.referenced.load(Relaxed) {
+                slot.referenced.store(true, Relaxed);
+            }
+            Some(slot.value.clone())
+        })
+    }
+    /// Whether a value is cached for `key`, without counting as a hit.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.index.read_with(|index| index.map.contains_key(key))
+    }
+    /// Caches `value` for `key`, returning the value it replaces if there was one. Evicts an entry
+    /// if the cache is full and `key` wasn't in it.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let slot = Arc::new(Slot {
+            value,
+            referenced: AtomicBool::new(false),
+        });
+        self.write(|index| {
+            if let Some(replaced) = index.map.get_mut(&key) {
+                return Some(mem::replace(replaced, slot).value.clone());
+            }
+            if index.map.len() >= self.capacity {
+                index.evict();
+            }
+            // Right behind the hand, so a new entry is the last one it visits
+            index.order.insert(index.hand, key.clone());
+            index.hand += 1;
+            index.map.insert(key, slot);
+            None
+        })
+    }
+    /// The number of entries cached.
+    pub fn len(&self) -> usize {
+        self.index.read_with(|index| index.map.len())
+    }
+    /// Whether nothing is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Evicts every entry.
+    pub fn clear(&self) {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        // Safety: as in `write`
+        unsafe { self.index.publish_exclusive(Box::new(Index::new()), |_| {}) };
+    }
+
+    /// Publishes a copy of the index changed by `f`, returning what `f` returns.
+    fn write<R>(&self, f: impl FnOnce(&mut Index<K, V>) -> R) -> R {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        // Only writers publish, so the index can't change under us from here on
+        let mut index = self.index.read_with(Index::clone);
+        let out = f(&mut index);
+        // Safety: writers are the only publishers, and `self.writer` serializes them
+        unsafe { self.index.publish_exclusive(Box::new(index), |_| {}) };
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    use std::thread;
+
+    use super::*;
+
+    /// Checks the index's view of what is cached is consistent, and that every value is the one
+    /// inserted for its key.
+    fn check(cache: &RcuCache<usize, usize>) {
+        cache.index.read_with(|index| {
+            assert!(index.map.len() <= cache.capacity(), "over capacity");
+            assert_eq!(index.order.len(), index.map.len(), "the clock lost track of entries");
+            for key in &index.order {
+                assert_eq!(index.map[key].value, key * 2);
+            }
+        });
+    }
+
+    #[test]
+    fn insert_storms_stay_within_capacity_and_keep_values_whole() {
+        const WRITERS: usize = 4;
+        const KEYS: usize = 500;
+
+        let cache = RcuCache::new(64);
+        let done = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    let mut key = 0;
+                    while done.load(SeqCst) < WRITERS {
+                        key = (key + 7) % (WRITERS * KEYS);
+                        if let Some(value) = cache.get(&key) {
+                            assert_eq!(value, key * 2, "read a torn entry");
+                        }
+                        assert!(cache.len() <= 64);
+                        thread::yield_now();
+                    }
+                });
+            }
+            for writer in 0..WRITERS {
+                let (cache, done) = (&cache, &done);
+                s.spawn(move || {
+                    for key in (0..KEYS).map(|i| i * WRITERS + writer) {
+                        cache.insert(key, key * 2);
+                        // Writers overlap on every other key of the writer before them
+                        if writer > 0 && key % 2 == 0 {
+                            cache.insert(key - 1, (key - 1) * 2);
+                        }
+                    }
+                    done.fetch_add(1, SeqCst);
+                });
+            }
+        });
+        assert_eq!(cache.len(), 64);
+        check(&cache);
+    }
+
+    #[test]
+    fn hot_keys_survive_a_stream_of_cold_ones() {
+        const HOT: usize = 50;
+        const COLD: usize = 2000;
+
+        let cache = RcuCache::new(100);
+        let (mut hits, mut lookups) = (0, 0);
+        for cold in 0..COLD {
+            for key in 0..HOT {
+                lookups += 1;
+                match cache.get(&key) {
+                    Some(_) => hits += 1,
+                    None => assert!(cache.insert(key, key * 2).is_none()),
+                }
+            }
+            // Inserted once and never looked up again
+            cache.insert(HOT + cold, (HOT + cold) * 2);
+        }
+        check(&cache);
+        // Only the first round and the odd entry the hand reaches twice in a row should miss
+        assert!(hits * 100 >= lookups * 95, "{hits} hits out of {lookups} lookups");
+    }
+}
@@ -0,0 +1,58 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::rc::Rc;
+
+use crate::Rcu;
+
+/// Per `Rcu` id, the version and clone of its value last read by this thread
+type Cache = HashMap<u64, (u64, Rc<dyn Any>), BuildHasherDefault<IdHasher>>;
+
+thread_local! {
+    static CACHE: RefCell<Cache> = RefCell::new(Cache::default());
+}
+
+/// Ids are sequential and never chosen by anyone else, so they make fine hashes as they are
+#[derive(Default)]
+struct IdHasher(u64);
+
+impl Hasher for IdHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    fn write(&mut self, _: &[u8]) {
+        unreachable!("only ever hashes u64 ids")
+    }
+    fn write_u64(&mut self, id: u64) {
+        self.0 = id;
+    }
+}
+
+impl<T: Clone + 'static> Rcu<T> {
+    /// Runs `f` on a thread-local clone of the current value, returning what `f` returns. The clone
+    /// is only refreshed when the version changed since this thread last called `cached` on this
+    /// `Rcu`, otherwise the whole cost is a relaxed load of the version and a thread-local lookup,
+    /// with no read-side critical section at all.
+    ///
+    /// A change is noticed eventually rather than immediately: right after a publish, another
+    /// thread may still get the previous value once or twice. `f` runs outside of any critical
+    /// section, so it may do anything, including publishing to this `Rcu` or calling `cached` again.
+    ///
+    /// Clones stay in their thread until it exits, even after the `Rcu` itself is gone, so this is
+    /// meant for long-lived values read extremely often rather than for many short-lived ones.
+    pub fn cached<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        let version = self.raw.version_relaxed();
+        let hit = CACHE.with(|cache| match cache.borrow().get(&self.id) {
+            Some((cached, value)) if *cached == version => Some(Rc::clone(value)),
+            _ => None,
+        });
+        let value = hit.unwrap_or_else(|| {
+            let (value, version) = self.read_versioned();
+            let value: Rc<dyn Any> = Rc::new(value);
+            CACHE.with(|cache| cache.borrow_mut().insert(self.id, (version, Rc::clone(&value))));
+            value
+        });
+        f(value.downcast_ref().expect("ids are unique, so is the type cached under each"))
+    }
+}
@@ -0,0 +1,197 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::sync::Arc;
+
+use crate::raw::RawRcu;
+
+/// Identifies a listener registered with [`RcuCallbackList::register`], used to unregister it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ListenerId(u64);
+
+struct Listener<Args: ?Sized> {
+    id: ListenerId,
+    f: Arc<dyn Fn(&Args) + Send + Sync>,
+}
+
+impl<Args: ?Sized> Clone for Listener<Args> {
+    fn clone(&self) -> Self {
+        Self { id: self.id, f: Arc::clone(&self.f) }
+    }
+}
+
+/// A list of listeners called with every event emitted, published like the value of an `Rcu`:
+/// emitting only reads the list, in place, so events can be emitted from any number of threads at no more than the cost of
+/// a read, while registering and unregistering listeners, which is expected to be rare, publishes
+/// a copy of it.
+///
+/// A listener unregistered before an [`RcuCallbackList::emit`] started is never called by it, one
+/// registered while it runs may or may not be. An emit still calling a listener keeps it alive:
+/// it is dropped, along with what it captured, once no emit that may be calling it is left. Unlike
+/// an `Rcu`, the list doesn't keep the one it replaced around for `Rcu::read_prev`.
+///
+/// ```
+/// # use rcu_rust::RcuCallbackList;
+/// struct Config {
+///     routes: Vec<String>,
+/// }
+/// let config = Config { routes: vec!["/".into()] };
+/// let on_reload: RcuCallbackList<Config> = RcuCallbackList::new();
+/// let id = on_reload.register(|config| println!("reloaded, {} routes", config.routes.len()));
+/// assert_eq!(on_reload.emit(&config), 1);
+/// on_reload.unregister(id);
+/// ```
+pub struct RcuCallbackList<Args: ?Sized> {
+    listeners: RawRcu<Vec<Listener<Args>>>,
+    next_id: AtomicU64,
+}
+
+impl<Args: ?Sized> RcuCallbackList<Args> {
+    /// Creates a list without listeners.
+    pub fn new() -> Self {
+        Self {
+            listeners: RawRcu::new(Box::default()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Adds `f` to the listeners, to be called by every emit from now on, after the listeners
+    /// registered before it.
+    #[track_caller]
+    pub fn register(&self, f: impl Fn(&Args) + Send + Sync + 'static) -> ListenerId {
+        let id = ListenerId(self.next_id.fetch_add(1, Relaxed));
+        let f: Arc<dyn Fn(&Args) + Send + Sync> = Arc::new(f);
+        let listener = Listener { id, f };
+        self.listeners.modify(|listeners| {
+            let mut listeners = listeners.clone();
+            listeners.push(listener.clone());
+            listeners
+        });
+        id
+    }
+
+    /// Removes a listener, which no emit starting from now on calls. Emits in progress may still
+    /// be calling it, it is only dropped once they are done. Returns false if it was already
+    /// removed.
+    #[track_caller]
+    pub fn unregister(&self, id: ListenerId) -> bool {
+        let mut removed = false;
+        self.listeners.modify(|listeners| {
+            let mut remaining = listeners.clone();
+            remaining.retain(|listener| listener.id != id);
+            removed = remaining.len() != listeners.len();
+            remaining
+        });
+        removed
+    }
+
+    /// Calls every listener registered when the call started with `args`, in registration order,
+    /// returning how many were called. A panicking listener is contained: the remaining listeners
+    /// are still called.
+    ///
+    /// The listeners run inside a read-side critical section of the list, see
+    /// [`Rcu::read_with`](crate::Rcu::read_with): with the `reclaim-counted` engine, registering and
+    /// unregistering wait for emits in progress to return, so a listener must not register nor
+    /// unregister listeners of the same list.
+    #[track_caller]
+    pub fn emit(&self, args: &Args) -> usize {
+        self.listeners.read_with(|listeners| {
+            for listener in listeners {
+                // The panic is still reported by the panic hook, it just doesn't propagate
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| (listener.f)(args)));
+            }
+            listeners.len()
+        })
+    }
+
+    /// Number of listeners currently registered.
+    pub fn len(&self) -> usize {
+        self.listeners.read_with(Vec::len)
+    }
+
+    /// Whether no listener is currently registered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<Args: ?Sized> Default for RcuCallbackList<Args> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use std::sync::{mpsc, Arc, Barrier, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::RcuCallbackList;
+
+    /// Sets its flag when dropped.
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, SeqCst);
+        }
+    }
+
+    #[test]
+    fn unregistering_keeps_a_listener_alive_until_emits_calling_it_return() {
+        let list = RcuCallbackList::<u32>::new();
+        let dropped = Arc::new(AtomicBool::new(false));
+        let entered = Arc::new(Barrier::new(2));
+        let (release, wait_release) = mpsc::channel::<()>();
+        let id = list.register({
+            let captured = DropFlag(Arc::clone(&dropped));
+            let entered = Arc::clone(&entered);
+            let wait_release = Mutex::new(wait_release);
+            move |_| {
+                let _captured = &captured;
+                entered.wait();
+                let _ = wait_release.lock().unwrap().recv();
+            }
+        });
+        let emitted = AtomicBool::new(false);
+        thread::scope(|s| {
+            s.spawn(|| {
+                assert_eq!(list.emit(&1), 1);
+                emitted.store(true, SeqCst);
+            });
+            entered.wait();
+            let (unregistered, wait_unregistered) = mpsc::channel();
+            let list = &list;
+            s.spawn(move || unregistered.send(list.unregister(id)).unwrap());
+            // Engines waiting for readers in-line keep `unregister` waiting for the emit, the
+            // others return right away
+            let early = wait_unregistered.recv_timeout(Duration::from_millis(50));
+            assert!(!emitted.load(SeqCst));
+            assert!(!dropped.load(SeqCst), "dropped while an emit was still calling it");
+            drop(release);
+            let unregistered = early.or_else(|_| wait_unregistered.recv()).unwrap();
+            assert!(unregistered);
+        });
+        list.listeners.barrier();
+        assert!(dropped.load(SeqCst), "not dropped once the emit returned");
+        assert_eq!(list.emit(&2), 0);
+        assert!(!list.unregister(id));
+    }
+
+    #[test]
+    fn listeners_run_in_registration_order_and_panics_are_contained() {
+        let list = RcuCallbackList::<u32>::new();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        for listener in 0..3 {
+            let seen = Arc::clone(&seen);
+            list.register(move |&event| {
+                seen.lock().unwrap().push((listener, event));
+                assert_ne!(listener, 1, "listener 1 panics");
+            });
+        }
+        assert_eq!(list.emit(&7), 3);
+        assert_eq!(*seen.lock().unwrap(), [(0, 7), (1, 7), (2, 7)]);
+        assert_eq!(list.len(), 3);
+    }
+}
@@ -0,0 +1,172 @@
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering::{Relaxed, SeqCst}};
+
+use crate::Rcu;
+
+impl<T: Clone> Rcu<T> {
+    /// Creates a [`Coalescer`] publishing to this `Rcu`.
+    pub fn coalescer(&self) -> Coalescer<'_, T> {
+        Coalescer {
+            rcu: self,
+            pending: AtomicPtr::new(ptr::null_mut()),
+            publishing: AtomicBool::new(false),
+            dropped: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Latest-wins publishing for bursty streams of values, created with [`Rcu::coalescer`].
+///
+/// Offered values go to a single pending slot, replacing (and dropping) any value still waiting
+/// there, and the newest pending value is published whenever no publish from this coalescer is in
+/// progress. So at most one publish is ever in flight per coalescer, and values offered faster than
+/// they can be published are skipped, each costing a single allocation and no grace period.
+///
+/// An offer that finds no publish in progress performs it itself, along with any values offered
+/// meanwhile, an offer arriving during a publish merely leaves its value in the slot and returns.
+/// The last value offered is always published, unless an invariant refuses it.
+///
+/// Offers never wait for readers: the values their publishes displace are retired without waiting,
+/// freed by whichever later grace period finds their readers gone, see [`Rcu::pending_retired`].
+/// The only wait is for a publish to the `Rcu` from outside of the coalescer, if one is in flight.
+///
+/// ```
+/// # use rcu_rust::Rcu;
+/// let telemetry = Rcu::new(0);
+/// let coalescer = telemetry.coalescer();
+/// let reading = telemetry.read_guard();
+/// // Publishes right away, though a reader still holds the value it replaces
+/// coalescer.offer(1);
+/// assert_eq!(*reading, 0);
+/// assert_eq!(telemetry.read(), 1);
+/// ```
+pub struct Coalescer<'a, T: Clone> {
+    rcu: &'a Rcu<T>,
+    /// The newest value not yet published, from `Box::into_raw`, or null
+    pending: AtomicPtr<T>,
+    /// Whether some thread is publishing pending values
+    publishing: AtomicBool,
+    /// Offered values replaced before they could be published
+    dropped: AtomicU64,
+}
+
+impl<T: Clone> Coalescer<'_, T> {
+    /// Offers `value` for publishing, dropping the previously offered value if it wasn't published yet.
+    pub fn offer(&self, value: T) {
+        let old = self.pending.swap(Box::into_raw(Box::new(value)), SeqCst);
+        if !old.is_null() {
+            // Safety: whoever swaps a pointer out of `pending` owns it
+            drop(unsafe { Box::from_raw(old) });
+            self.dropped.fetch_add(1, Relaxed);
+        }
+        // If the publisher checked the slot before our swap, it also gave up before our load
+        while !self.pending.load(SeqCst).is_null()
+            && self.publishing.compare_exchange(false, true, SeqCst, SeqCst).is_ok()
+        {
+            let _publishing = Publishing(&self.publishing);
+            loop {
+                let next = self.pending.swap(ptr::null_mut(), SeqCst);
+                if next.is_null() {
+                    break;
+                }
+                // Safety: whoever swaps a pointer out of `pending` owns it. A refused value is dropped
+                let _ = self.rcu.set_box_deferred(unsafe { Box::from_raw(next) });
+            }
+        }
+    }
+    /// The number of offered values that were replaced by newer ones before being published.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Relaxed)
+    }
+}
+
+/// Clears `publishing` once done, even if a hook panicked.
+struct Publishing<'a>(&'a AtomicBool);
+
+impl Drop for Publishing<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, SeqCst);
+    }
+}
+
+impl<T: Clone> Drop for Coalescer<'_, T> {
+    fn drop(&mut self) {
+        let pending = *self.pending.get_mut();
+        if !pending.is_null() {
+            // Safety: we own `self`, and with it the pending value; only left over by a panicking hook
+            drop(unsafe { Box::from_raw(pending) });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+
+    use super::*;
+    use crate::SpinYield;
+
+    #[test]
+    fn the_last_value_of_a_burst_is_published() {
+        const OFFERS: u64 = 10_000;
+        let rcu = Rcu::new(0).with_wait_strategy(SpinYield::default());
+        let done = AtomicBool::new(false);
+        let coalescer = rcu.coalescer();
+        thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    let mut last = 0;
+                    while !done.load(SeqCst) {
+                        let guard = rcu.read_guard();
+                        assert!(*guard >= last, "went back from {last} to {}", *guard);
+                        last = *guard;
+                    }
+                });
+            }
+            for i in 1..=OFFERS {
+                coalescer.offer(i);
+            }
+            done.store(true, SeqCst);
+        });
+        assert_eq!(rcu.read(), OFFERS);
+        // Every offer was either published or replaced
+        assert_eq!(rcu.version() + coalescer.dropped(), OFFERS);
+        rcu.barrier();
+        assert_eq!(rcu.pending_retired(), 0);
+    }
+
+    #[test]
+    fn offers_never_wait_for_readers() {
+        let rcu = Rcu::new(0);
+        let coalescer = rcu.coalescer();
+        let guard = rcu.read_guard();
+        // Would wait for the guard forever with the `reclaim-counted` engine if it did
+        for i in 1..=3 {
+            coalescer.offer(i);
+        }
+        assert_eq!((*guard, rcu.read()), (0, 3));
+        drop(guard);
+        rcu.barrier();
+        assert_eq!(rcu.pending_retired(), 0);
+    }
+
+    #[test]
+    fn offers_from_many_threads_converge() {
+        let rcu = Rcu::new((0, 0)).with_wait_strategy(SpinYield::default());
+        let coalescer = rcu.coalescer();
+        thread::scope(|s| {
+            for thread in 0..4 {
+                let coalescer = &coalescer;
+                s.spawn(move || {
+                    for i in 1..=1000 {
+                        coalescer.offer((thread, i));
+                    }
+                });
+            }
+        });
+        // Whichever thread offered last, its last value is the one published
+        assert_eq!(rcu.read().1, 1000);
+        assert_eq!(rcu.version() + coalescer.dropped(), 4000);
+    }
+}
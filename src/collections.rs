@@ -0,0 +1,261 @@
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::any::Any;
+use std::hash::{BuildHasher, Hash};
+use std::sync::{Arc, OnceLock};
+
+use crate::{PublishError, Rcu};
+
+/// A map behind an `Rcu`: lookups are RCU reads of the current map, every change publishes a
+/// changed copy of it, retrying from the newer map when another writer got there first.
+///
+/// What a change costs depends on the map: copying a std [`RcuHashMap`] or [`RcuBTreeMap`] takes
+/// time proportional to its length, so every change does, while the persistent maps of the `im`
+/// feature, `RcuImHashMap` and `RcuImOrdMap`, share all but the changed path with the map
+/// they were copied from, which makes changes cost time proportional to the change, logarithmic per
+/// entry. The API is the same for all of them, switching is a change of type. Reads of the
+/// persistent maps are somewhat slower. A [`ShardedRcuMap`](crate::ShardedRcuMap) instead splits a
+/// std `HashMap` into a fixed number of shards, changes copying one of them.
+///
+/// Changes fail, handing back the map they would have published, when an invariant of the `Rcu`
+/// refuses it or the `Rcu` is frozen, see [`RcuMap::as_rcu`]. The values of single keys of a std
+/// `RcuHashMap` can be watched for changes, see [`RcuMap::watch_key`].
+pub struct RcuMap<M: Clone> {
+    pub(crate) rcu: Rcu<M>,
+    /// The keys watched with `watch_key`, indexed on the first call
+    pub(crate) watched: OnceLock<Arc<dyn Any + Send + Sync>>,
+}
+
+/// An [`RcuMap`] over a std `HashMap`, changes copy the whole map.
+pub type RcuHashMap<K, V, S = std::collections::hash_map::RandomState> = RcuMap<HashMap<K, V, S>>;
+/// An [`RcuMap`] over a std `BTreeMap`, changes copy the whole map.
+pub type RcuBTreeMap<K, V> = RcuMap<BTreeMap<K, V>>;
+/// An [`RcuMap`] over a persistent `im::HashMap`, changes only copy what they change.
+#[cfg(feature = "im")]
+pub type RcuImHashMap<K, V, S = std::collections::hash_map::RandomState> = RcuMap<im::HashMap<K, V, S>>;
+/// An [`RcuMap`] over a persistent `im::OrdMap`, changes only copy what they change.
+#[cfg(feature = "im")]
+pub type RcuImOrdMap<K, V> = RcuMap<im::OrdMap<K, V>>;
+
+impl<M: Clone> RcuMap<M> {
+    /// Creates an empty map.
+    pub fn new() -> Self
+    where
+        M: Default,
+    {
+        Self::from_map(M::default())
+    }
+    /// Publishes `map` as the initial contents.
+    pub fn from_map(map: M) -> Self {
+        Self {
+            rcu: Rcu::new(map),
+            watched: OnceLock::new(),
+        }
+    }
+    /// The `Rcu` holding the map, for everything else an `Rcu` does: subscribing, waiting for
+    /// changes, invariants, freezing...
+    pub fn as_rcu(&self) -> &Rcu<M> {
+        &self.rcu
+    }
+    /// Consumes the wrapper, returning the current map.
+    pub fn into_inner(self) -> M {
+        *self.rcu.into_box()
+    }
+    /// A copy of the current map, which for persistent maps costs next to nothing.
+    pub fn snapshot(&self) -> M {
+        self.rcu.read()
+    }
+    /// Runs `f` on the current map without copying it, see [`Rcu::read_with`].
+    #[track_caller]
+    pub fn read_with<R>(&self, f: impl FnOnce(&M) -> R) -> R {
+        self.rcu.read_with(f)
+    }
+    /// Publishes a copy of the current map changed by `f`, returning what `f` returned. Like every
+    /// change, `f` runs again on a copy of the newer map whenever another writer got there first.
+    #[track_caller]
+    pub fn update<R>(&self, f: impl FnMut(&mut M) -> R) -> Result<R, PublishError<M>> {
+        self.rcu.update_mut(f)
+    }
+}
+
+impl<M: Clone + Default> Default for RcuMap<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The methods every map backend `$map` has, bounded as `$bound` requires of keys looked up by.
+macro_rules! map_methods {
+    ($map:ty; $($bound:tt)+) => {
+        /// A clone of the value of `key`, if any.
+        #[track_caller]
+        pub fn get<Q>(&self, key: &Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: $($bound)+ + ?Sized,
+        {
+            self.rcu.read_with(|map| map.get(key).cloned())
+        }
+        /// Whether `key` has a value.
+        #[track_caller]
+        pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: $($bound)+ + ?Sized,
+        {
+            self.rcu.read_with(|map| map.contains_key(key))
+        }
+        /// The number of entries.
+        pub fn len(&self) -> usize {
+            self.rcu.read_with(|map| map.len())
+        }
+        /// Whether the map has no entries.
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+        /// Sets the value of `key`, returning the one it replaces.
+        pub fn insert(&self, key: K, value: V) -> Result<Option<V>, PublishError<$map>> {
+            self.rcu.update_mut(|map| map.insert(key.clone(), value.clone()))
+        }
+        /// Removes the value of `key`, returning it. Publishes nothing if there was none.
+        pub fn remove<Q>(&self, key: &Q) -> Result<Option<V>, PublishError<$map>>
+        where
+            K: Borrow<Q>,
+            Q: $($bound)+ + ?Sized,
+        {
+            if !self.contains_key(key) {
+                return Ok(None);
+            }
+            self.rcu.update_mut(|map| map.remove(key))
+        }
+        /// Sets the values of every key of `entries` with a single publish, later ones winning.
+        pub fn extend(&self, entries: impl IntoIterator<Item = (K, V)>) -> Result<(), PublishError<$map>> {
+            let entries: Vec<(K, V)> = entries.into_iter().collect();
+            self.rcu.update_mut(|map| map.extend(entries.iter().cloned()))
+        }
+        /// Removes every entry.
+        pub fn clear(&self) -> Result<(), PublishError<$map>> {
+            self.rcu.update_mut(|map| map.clear())
+        }
+    };
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, S: BuildHasher + Clone> RcuMap<HashMap<K, V, S>> {
+    map_methods!(HashMap<K, V, S>; Hash + Eq);
+}
+
+impl<K: Ord + Clone, V: Clone> RcuMap<BTreeMap<K, V>> {
+    map_methods!(BTreeMap<K, V>; Ord);
+}
+
+#[cfg(feature = "im")]
+impl<K: Hash + Eq + Clone, V: Clone, S: BuildHasher + Clone> RcuMap<im::HashMap<K, V, S>> {
+    map_methods!(im::HashMap<K, V, S>; Hash + Eq);
+}
+
+#[cfg(feature = "im")]
+impl<K: Ord + Clone, V: Clone> RcuMap<im::OrdMap<K, V>> {
+    map_methods!(im::OrdMap<K, V>; Ord);
+}
+
+/// A list behind an `Rcu`, like [`RcuMap`] is for maps: changes of a std [`RcuVec`] copy the
+/// whole list, those of an `RcuImVector` of the `im` feature only what they change.
+pub struct RcuVector<L: Clone> {
+    rcu: Rcu<L>,
+}
+
+/// An [`RcuVector`] over a std `Vec`, changes copy the whole list.
+pub type RcuVec<A> = RcuVector<Vec<A>>;
+/// An [`RcuVector`] over a persistent `im::Vector`, changes only copy what they change.
+#[cfg(feature = "im")]
+pub type RcuImVector<A> = RcuVector<im::Vector<A>>;
+
+impl<L: Clone> RcuVector<L> {
+    /// Creates an empty list.
+    pub fn new() -> Self
+    where
+        L: Default,
+    {
+        Self::from_list(L::default())
+    }
+    /// Publishes `list` as the initial contents.
+    pub fn from_list(list: L) -> Self {
+        Self { rcu: Rcu::new(list) }
+    }
+    /// See [`RcuMap::as_rcu`].
+    pub fn as_rcu(&self) -> &Rcu<L> {
+        &self.rcu
+    }
+    /// Consumes the wrapper, returning the current list.
+    pub fn into_inner(self) -> L {
+        *self.rcu.into_box()
+    }
+    /// See [`RcuMap::snapshot`].
+    pub fn snapshot(&self) -> L {
+        self.rcu.read()
+    }
+    /// See [`RcuMap::read_with`].
+    #[track_caller]
+    pub fn read_with<R>(&self, f: impl FnOnce(&L) -> R) -> R {
+        self.rcu.read_with(f)
+    }
+    /// See [`RcuMap::update`].
+    #[track_caller]
+    pub fn update<R>(&self, f: impl FnMut(&mut L) -> R) -> Result<R, PublishError<L>> {
+        self.rcu.update_mut(f)
+    }
+}
+
+impl<L: Clone + Default> Default for RcuVector<L> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The methods every list backend `$list` has, `$push` and `$pop` naming its methods at the back.
+macro_rules! list_methods {
+    ($list:ty; $push:ident, $pop:ident) => {
+        /// A clone of the element at `index`, if any.
+        #[track_caller]
+        pub fn get(&self, index: usize) -> Option<A> {
+            self.rcu.read_with(|list| list.get(index).cloned())
+        }
+        /// The number of elements.
+        pub fn len(&self) -> usize {
+            self.rcu.read_with(|list| list.len())
+        }
+        /// Whether the list has no elements.
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+        /// Appends `value`.
+        pub fn push(&self, value: A) -> Result<(), PublishError<$list>> {
+            self.rcu.update_mut(|list| list.$push(value.clone()))
+        }
+        /// Removes the last element, returning it. Publishes nothing if the list is empty.
+        pub fn pop(&self) -> Result<Option<A>, PublishError<$list>> {
+            if self.is_empty() {
+                return Ok(None);
+            }
+            self.rcu.update_mut(|list| list.$pop())
+        }
+        /// Replaces the element at `index`, returning it. Publishes nothing, returning None, if
+        /// `index` is out of bounds.
+        pub fn set(&self, index: usize, value: A) -> Result<Option<A>, PublishError<$list>> {
+            if index >= self.len() {
+                return Ok(None);
+            }
+            self.rcu
+                .update_mut(|list| Some(std::mem::replace(list.get_mut(index)?, value.clone())))
+        }
+    };
+}
+
+impl<A: Clone> RcuVector<Vec<A>> {
+    list_methods!(Vec<A>; push, pop);
+}
+
+#[cfg(feature = "im")]
+impl<A: Clone> RcuVector<im::Vector<A>> {
+    list_methods!(im::Vector<A>; push_back, pop_back);
+}
@@ -0,0 +1,151 @@
+use std::sync::{PoisonError, TryLockError};
+
+use crate::Rcu;
+
+impl<T: Clone> Rcu<T> {
+    /// A clone of the current value if it passes `is_valid`, otherwise of the value `compute`
+    /// returns, which is published. Single-flight: when the value is missing or stale only one
+    /// caller at a time runs `compute`, concurrent callers block until it published and then get
+    /// its value, so a stampede of callers computes it once.
+    ///
+    /// If `compute` panics, the panic propagates to its caller and the next caller in line
+    /// computes instead. If the computed value can't be published, refused by an invariant, the
+    /// `Rcu` frozen or its backlog past a limit, it is still returned, and the next caller computes
+    /// again. `compute` must not publish to the same `Rcu`, nor call this on it.
+    ///
+    /// ```
+    /// # use std::time::{Duration, Instant};
+    /// # use rcu_rust::Rcu;
+    /// #[derive(Clone)]
+    /// struct Token {
+    ///     expires_at: Instant,
+    /// }
+    /// let fetch_token = || Token { expires_at: Instant::now() + Duration::from_secs(60) };
+    /// let tokens = Rcu::new(Token { expires_at: Instant::now() });
+    /// let token = tokens.get_or_compute(|token| token.expires_at > Instant::now(), fetch_token);
+    /// assert!(token.expires_at > Instant::now());
+    /// ```
+    #[track_caller]
+    pub fn get_or_compute(&self, is_valid: impl Fn(&T) -> bool, compute: impl FnOnce() -> T) -> T {
+        if let Some(value) = self.read_valid(&is_valid) {
+            return value;
+        }
+        let _computing = self.computing.lock().unwrap_or_else(PoisonError::into_inner);
+        // Whoever held the lock before may have published a valid value meanwhile
+        if let Some(value) = self.read_valid(&is_valid) {
+            return value;
+        }
+        self.publish_computed(compute())
+    }
+    /// Like [`Rcu::get_or_compute`], except that callers finding another one computing don't wait
+    /// for it, but get a clone of the current value even though it doesn't pass `is_valid`.
+    #[track_caller]
+    pub fn get_or_compute_stale_ok(&self, is_valid: impl Fn(&T) -> bool, compute: impl FnOnce() -> T) -> T {
+        if let Some(value) = self.read_valid(&is_valid) {
+            return value;
+        }
+        let _computing = match self.computing.try_lock() {
+            Ok(computing) => computing,
+            Err(TryLockError::Poisoned(computing)) => computing.into_inner(),
+            Err(TryLockError::WouldBlock) => return self.read(),
+        };
+        if let Some(value) = self.read_valid(&is_valid) {
+            return value;
+        }
+        self.publish_computed(compute())
+    }
+    /// A clone of the current value if it passes `is_valid`.
+    #[track_caller]
+    fn read_valid(&self, is_valid: impl Fn(&T) -> bool) -> Option<T> {
+        self.read_with(|value| is_valid(value).then(|| value.clone()))
+    }
+    /// Publishes a clone of `value`, which is handed back published or not.
+    fn publish_computed(&self, value: T) -> T {
+        let _ = self.set(value.clone());
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    use std::sync::Barrier;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::Rcu;
+
+    /// Long enough for every other caller to pile up behind the one computing.
+    const SLOW: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn a_stampede_computes_once() {
+        // 0 is missing, anything else valid
+        let rcu = Rcu::new(0);
+        let computes = AtomicUsize::new(0);
+        let start = Barrier::new(8);
+        thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    start.wait();
+                    let value = rcu.get_or_compute(
+                        |&n| n != 0,
+                        || {
+                            computes.fetch_add(1, SeqCst);
+                            thread::sleep(SLOW);
+                            42
+                        },
+                    );
+                    assert_eq!(value, 42);
+                });
+            }
+        });
+        assert_eq!(computes.load(SeqCst), 1, "computed more than once");
+        assert_eq!(rcu.read(), 42);
+        assert_eq!(rcu.version(), 1);
+    }
+
+    #[test]
+    fn stale_ok_callers_get_the_stale_value_instead_of_waiting() {
+        let rcu = Rcu::new(0);
+        let computes = AtomicUsize::new(0);
+        let computing = Barrier::new(2);
+        thread::scope(|s| {
+            s.spawn(|| {
+                rcu.get_or_compute(
+                    |&n| n != 0,
+                    || {
+                        computes.fetch_add(1, SeqCst);
+                        computing.wait();
+                        thread::sleep(SLOW);
+                        42
+                    },
+                )
+            });
+            computing.wait();
+            let stale = rcu.get_or_compute_stale_ok(|&n| n != 0, || {
+                computes.fetch_add(1, SeqCst);
+                7
+            });
+            assert_eq!(stale, 0, "didn't get the stale value");
+        });
+        assert_eq!(computes.load(SeqCst), 1);
+        assert_eq!(rcu.get_or_compute_stale_ok(|&n| n != 0, || unreachable!()), 42);
+    }
+
+    #[test]
+    fn a_panicking_compute_lets_the_next_caller_compute() {
+        let rcu = Rcu::new(0);
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            rcu.get_or_compute(|&n| n != 0, || panic!("compute failed"))
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(rcu.version(), 0, "published after panicking");
+        // Neither blocks on the panicked compute's slot
+        assert_eq!(rcu.get_or_compute(|&n| n != 0, || 1), 1);
+        let rcu = Rcu::new(0);
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| rcu.get_or_compute(|&n| n != 0, || panic!("compute failed"))));
+        assert_eq!(rcu.get_or_compute_stale_ok(|&n| n != 0, || 2), 2, "the stale-ok caller didn't compute");
+    }
+}
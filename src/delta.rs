@@ -0,0 +1,190 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::staleness::{Staleness, StalenessHandle, View};
+use crate::Rcu;
+
+type DiffFn<T, D> = Box<dyn Fn(&T, &T) -> D + Send + Sync>;
+
+impl<T: Clone> Rcu<T> {
+    /// Creates a subscriber delivering `diff(previous, current)` instead of whole values, starting
+    /// from the value current at subscription time.
+    pub fn subscribe_delta<D>(&self, diff: impl Fn(&T, &T) -> D + Send + Sync + 'static) -> DeltaSubscriber<'_, T, D> {
+        let last = self.read_versioned();
+        let view = View::new(&self.head);
+        view.record(last.1);
+        DeltaSubscriber {
+            rcu: self,
+            diff: Box::new(diff),
+            last,
+            view,
+        }
+    }
+}
+
+/// Delivers the changes between successive values of a `Rcu`, as computed by a user supplied
+/// diff function, created with [`Rcu::subscribe_delta`].
+///
+/// The subscriber keeps its own clone of the last value it delivered a delta up to, so deltas
+/// can always be computed no matter when the `Rcu` reclaimed that value. Deltas are coalesced:
+/// if several publishes happened since the last delta, the next one spans from the last
+/// delivered value straight to the latest one, intermediate values are never seen.
+pub struct DeltaSubscriber<'a, T: Clone, D> {
+    rcu: &'a Rcu<T>,
+    diff: DiffFn<T, D>,
+    /// The last value a delta was delivered up to, with its version
+    last: (T, u64),
+    /// The version of `last`, for staleness reports
+    view: Arc<View>,
+}
+
+impl<T: Clone, D> DeltaSubscriber<'_, T, D> {
+    /// The delta from the last delivered value to the current one along with the current version,
+    /// None if nothing was published since. Never blocks.
+    pub fn try_next_delta(&mut self) -> Option<(D, u64)> {
+        if self.rcu.version() == self.last.1 {
+            return None;
+        }
+        let (current, version) = self.rcu.read_versioned();
+        let delta = (self.diff)(&self.last.0, &current);
+        self.last = (current, version);
+        self.view.record(version);
+        Some((delta, version))
+    }
+    /// Like [`DeltaSubscriber::try_next_delta`], blocking until something was published.
+    pub fn next_delta(&mut self) -> (D, u64) {
+        loop {
+            if let Some(delta) = self.try_next_delta() {
+                return delta;
+            }
+            self.rcu.wait_for_change(self.last.1);
+        }
+    }
+    /// Like [`DeltaSubscriber::next_delta`], giving up after `timeout` and returning None.
+    pub fn next_delta_timeout(&mut self, timeout: Duration) -> Option<(D, u64)> {
+        self.rcu.wait_for_change_timeout(self.last.1, timeout)?;
+        self.try_next_delta()
+    }
+    /// The version of the last value a delta was delivered up to.
+    pub fn version(&self) -> u64 {
+        self.last.1
+    }
+    /// How far the last value a delta was delivered up to is behind the latest publish.
+    pub fn staleness(&self) -> Staleness {
+        self.view.staleness()
+    }
+    /// Tracks [`DeltaSubscriber::staleness`] without borrowing the subscriber, see
+    /// [`StalenessRegistry`](crate::StalenessRegistry).
+    pub fn staleness_handle(&self) -> StalenessHandle {
+        StalenessHandle::new(&self.view)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use crate::Rcu;
+
+    /// What was added to and removed from a set, in order.
+    #[derive(Debug, PartialEq)]
+    struct Change {
+        added: Vec<u32>,
+        removed: Vec<u32>,
+    }
+
+    // Takes what `Rcu<Vec<u32>>` hands it
+    #[allow(clippy::ptr_arg)]
+    fn diff(old: &Vec<u32>, new: &Vec<u32>) -> Change {
+        let old: BTreeSet<_> = old.iter().copied().collect();
+        let new: BTreeSet<_> = new.iter().copied().collect();
+        Change {
+            added: new.difference(&old).copied().collect(),
+            removed: old.difference(&new).copied().collect(),
+        }
+    }
+
+    fn with(set: &[u32], n: u32) -> Vec<u32> {
+        set.iter().copied().chain([n]).collect()
+    }
+
+    fn without(set: &[u32], n: u32) -> Vec<u32> {
+        set.iter().copied().filter(|&m| m != n).collect()
+    }
+
+    #[test]
+    fn deltas_span_from_the_last_delivered_value() {
+        let rcu = Rcu::new(vec![1, 2]);
+        let mut deltas = rcu.subscribe_delta(diff);
+        assert!(deltas.try_next_delta().is_none(), "a delta before any publish");
+        rcu.update_with(|set| with(set, 3)).unwrap();
+        assert_eq!(deltas.try_next_delta(), Some((Change { added: vec![3], removed: vec![] }, 1)));
+        assert!(deltas.try_next_delta().is_none(), "the same delta twice");
+        rcu.update_with(|set| without(set, 1)).unwrap();
+        assert_eq!(deltas.try_next_delta(), Some((Change { added: vec![], removed: vec![1] }, 2)));
+        assert_eq!(deltas.version(), 2);
+    }
+
+    #[test]
+    fn deltas_coalesce_the_publishes_in_between() {
+        let rcu = Rcu::new(vec![1, 2]);
+        let mut deltas = rcu.subscribe_delta(diff);
+        rcu.update_with(|set| with(set, 3)).unwrap();
+        // Added and removed again before the subscriber looked
+        rcu.update_with(|set| with(set, 4)).unwrap();
+        rcu.update_with(|set| without(set, 4)).unwrap();
+        rcu.update_with(|set| without(set, 2)).unwrap();
+        assert_eq!(deltas.try_next_delta(), Some((Change { added: vec![3], removed: vec![2] }, 4)));
+        assert!(deltas.try_next_delta().is_none());
+    }
+
+    #[test]
+    fn next_delta_timeout_gives_up_or_delivers_what_is_published_meanwhile() {
+        let rcu = Rcu::new(vec![1]);
+        let mut deltas = rcu.subscribe_delta(diff);
+        let start = Instant::now();
+        assert!(deltas.next_delta_timeout(Duration::from_millis(20)).is_none(), "a delta without a publish");
+        assert!(start.elapsed() >= Duration::from_millis(20), "gave up after {:?}", start.elapsed());
+        thread::scope(|s| {
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(20));
+                rcu.update_with(|set| with(set, 2)).unwrap();
+            });
+            let delta = deltas.next_delta_timeout(Duration::from_secs(10));
+            assert_eq!(delta, Some((Change { added: vec![2], removed: vec![] }, 1)));
+        });
+    }
+
+    #[test]
+    fn applying_every_delta_rebuilds_the_value() {
+        let rcu = Rcu::new(Vec::new());
+        let mut deltas = rcu.subscribe_delta(diff);
+        let mut rebuilt = BTreeSet::new();
+        thread::scope(|s| {
+            s.spawn(|| {
+                for n in 0..500 {
+                    rcu.update_with(|set| with(set, n)).unwrap();
+                    if n % 3 == 0 {
+                        rcu.update_with(|set| without(set, n / 2)).unwrap();
+                    }
+                    thread::yield_now();
+                }
+            });
+            // A publish per number, and one more per multiple of 3
+            let last = 500 + 500_u64.div_ceil(3);
+            let mut version = 0;
+            while version < last {
+                let (change, next) = deltas.next_delta();
+                assert!(next > version, "went back from {version} to {next}");
+                version = next;
+                rebuilt.extend(change.added);
+                for n in change.removed {
+                    assert!(rebuilt.remove(&n), "removed {n}, which was never added");
+                }
+            }
+        });
+        assert_eq!(rebuilt, rcu.read().into_iter().collect());
+    }
+}
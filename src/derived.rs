@@ -0,0 +1,315 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering::{Relaxed, SeqCst}};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::notify::Notify;
+use crate::Rcu;
+
+/// Source values waiting to be recomputed from, shared by the hook and the recomputing thread.
+struct Inbox<T> {
+    queued: Mutex<Queued<T>>,
+    /// Wakes the recomputing thread when `queued` changes
+    wake: Condvar,
+}
+
+struct Queued<T> {
+    /// The newest source value not recomputed from yet, with its version
+    latest: Option<(u64, T)>,
+    /// Set by the handle, the thread exits without recomputing what is left
+    stopped: bool,
+}
+
+/// What the recomputing thread and its handle share.
+struct Progress<U: Clone> {
+    derived: Arc<Rcu<U>>,
+    /// The version of the source value the derived value was last computed from
+    source_version: AtomicU64,
+    /// Wakes `wait_for_source_version` when `source_version` moves
+    caught_up: Notify,
+    /// Derived values computed after the initial one
+    recomputes: AtomicU64,
+    /// Recomputations that panicked
+    panics: AtomicU64,
+}
+
+impl<T: Clone + Send + Sync + 'static> Rcu<T> {
+    /// Derives a value from this one with `f`, published to an `Rcu` of its own, which is kept in
+    /// sync: `f` computes the initial derived value right away, then recomputes it on a thread of
+    /// its own after every publish of this `Rcu`, and publishes the result.
+    ///
+    /// The derived value lags behind: a reader sees it once it was recomputed, so until then it
+    /// still reads the one derived from an older value. [`DerivedRcu::source_version`] tells which
+    /// version of this `Rcu` the derived value was computed from, and
+    /// [`DerivedRcu::wait_for_source_version`] waits for it to catch up. Publishes arriving faster
+    /// than `f` recomputes are coalesced: only the newest value published meanwhile is recomputed
+    /// from, the others are skipped.
+    ///
+    /// Every publish clones its value for the recomputing thread, from a hook, see
+    /// [`Rcu::on_update`]. A panic in `f` is caught and counted, see [`DerivedRcu::panics`], and
+    /// leaves the derived value as it was, except for the initial computation, whose panic
+    /// propagates. Dropping the handle removes the hook and stops the thread.
+    ///
+    /// ```
+    /// # use std::collections::HashSet;
+    /// # use rcu_rust::Rcu;
+    /// let patterns = Rcu::new(vec!["/health".to_string()]);
+    /// let matcher = patterns.map_value(|patterns: &Vec<String>| patterns.iter().cloned().collect::<HashSet<_>>());
+    /// let hit = matcher.as_rcu().read_with(|matcher| matcher.contains("/health"));
+    /// assert!(hit);
+    /// ```
+    pub fn map_value<U: Clone + Send + Sync + 'static>(
+        &self,
+        f: impl Fn(&T) -> U + Send + Sync + 'static,
+    ) -> DerivedRcu<'_, U> {
+        let inbox = Arc::new(Inbox {
+            queued: Mutex::new(Queued { latest: None, stopped: false }),
+            wake: Condvar::new(),
+        });
+        let coalesced = Arc::new(AtomicU64::new(0));
+        // Hooked before reading the initial value, so no publish after it is missed
+        let hook = self.on_update_versioned({
+            let inbox = Arc::clone(&inbox);
+            let coalesced = Arc::clone(&coalesced);
+            move |value, version| {
+                let mut queued = inbox.queued.lock().unwrap_or_else(PoisonError::into_inner);
+                if queued.latest.replace((version, value.clone())).is_some() {
+                    coalesced.fetch_add(1, Relaxed);
+                }
+                drop(queued);
+                inbox.wake.notify_one();
+            }
+        });
+        let (value, version) = self.read_versioned();
+        let initial = match panic::catch_unwind(AssertUnwindSafe(|| f(&value))) {
+            Ok(initial) => initial,
+            Err(panic) => {
+                self.remove_hook(hook);
+                panic::resume_unwind(panic);
+            }
+        };
+        let progress = Arc::new(Progress {
+            derived: Arc::new(Rcu::new(initial)),
+            source_version: AtomicU64::new(version),
+            caught_up: Notify::default(),
+            recomputes: AtomicU64::new(0),
+            panics: AtomicU64::new(0),
+        });
+        let thread = thread::Builder::new()
+            .name("rcu-derived".into())
+            .spawn({
+                let inbox = Arc::clone(&inbox);
+                let progress = Arc::clone(&progress);
+                move || recompute_published(&inbox, &progress, f)
+            })
+            .expect("failed to spawn the thread recomputing a derived value");
+        DerivedRcu {
+            progress,
+            coalesced,
+            stop: Box::new(move || {
+                self.remove_hook(hook);
+                inbox.queued.lock().unwrap_or_else(PoisonError::into_inner).stopped = true;
+                inbox.wake.notify_one();
+            }),
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Recomputes the derived value from the newest source value in `inbox`, until stopped.
+fn recompute_published<T, U: Clone>(inbox: &Inbox<T>, progress: &Progress<U>, f: impl Fn(&T) -> U) {
+    loop {
+        let (version, value) = {
+            let mut queued = inbox.queued.lock().unwrap_or_else(PoisonError::into_inner);
+            loop {
+                if queued.stopped {
+                    return;
+                }
+                if let Some(latest) = queued.latest.take() {
+                    break latest;
+                }
+                queued = inbox.wake.wait(queued).unwrap_or_else(PoisonError::into_inner);
+            }
+        };
+        // Published between hooking and reading the initial value, which already has it
+        if version <= progress.source_version.load(SeqCst) {
+            continue;
+        }
+        match panic::catch_unwind(AssertUnwindSafe(|| f(&value))) {
+            Ok(derived) => {
+                // Refused by an invariant of the derived `Rcu` or frozen, the derived value stays,
+                // as it would for any other publish to it
+                let _ = progress.derived.set(derived);
+                progress.recomputes.fetch_add(1, Relaxed);
+                progress.source_version.store(version, SeqCst);
+                progress.caught_up.notify();
+            }
+            Err(_) => {
+                progress.panics.fetch_add(1, Relaxed);
+            }
+        }
+    }
+}
+
+/// A value derived from an `Rcu` and kept in sync with it, created with [`Rcu::map_value`].
+/// Dropping it stops recomputing, like [`DerivedRcu::stop`] does; the derived `Rcu` itself lives
+/// on for as long as clones of it do.
+pub struct DerivedRcu<'a, U: Clone> {
+    progress: Arc<Progress<U>>,
+    /// Source values replaced by newer ones before they were recomputed from, counted by the hook
+    coalesced: Arc<AtomicU64>,
+    /// Removes the hook and stops the thread, without knowing the source value type
+    stop: Box<dyn Fn() + Send + Sync + 'a>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<U: Clone> DerivedRcu<'_, U> {
+    /// The `Rcu` the derived value is published to, read it as any other `Rcu`. Clone it to read
+    /// from elsewhere. Values published to it other than by recomputing are overwritten by the
+    /// next recomputation.
+    pub fn as_rcu(&self) -> &Arc<Rcu<U>> {
+        &self.progress.derived
+    }
+
+    /// The version of the source `Rcu` the derived value was last computed from.
+    pub fn source_version(&self) -> u64 {
+        self.progress.source_version.load(SeqCst)
+    }
+
+    /// Blocks until the derived value was computed from version `version` of the source `Rcu` or
+    /// a later one, or `timeout` elapsed. Returns whether it was. A source value whose
+    /// recomputation panicked, or which was coalesced into a later one, is never caught up with by
+    /// itself, only through a later one.
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use rcu_rust::Rcu;
+    /// let patterns = Rcu::new(vec!["/health".to_string()]);
+    /// let matcher = patterns.map_value(|patterns: &Vec<String>| patterns.len());
+    /// patterns.set(vec!["/health".to_string(), "/ready".to_string()])?;
+    /// assert!(matcher.wait_for_source_version(patterns.version(), Duration::from_secs(1)));
+    /// assert_eq!(matcher.as_rcu().read(), 2);
+    /// # Ok::<(), rcu_rust::PublishError<Vec<String>>>(())
+    /// ```
+    pub fn wait_for_source_version(&self, version: u64, timeout: Duration) -> bool {
+        self.progress.caught_up.wait_until(|| self.source_version() >= version, Some(timeout))
+    }
+
+    /// Number of derived values computed so far, not counting the initial one.
+    pub fn recomputes(&self) -> u64 {
+        self.progress.recomputes.load(Relaxed)
+    }
+
+    /// Number of source values skipped so far because a newer one was published before they could
+    /// be recomputed from.
+    pub fn coalesced(&self) -> u64 {
+        self.coalesced.load(Relaxed)
+    }
+
+    /// Number of recomputations that panicked so far.
+    pub fn panics(&self) -> u64 {
+        self.progress.panics.load(Relaxed)
+    }
+
+    /// Stops recomputing, waiting for a recomputation in progress to finish.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        (self.stop)();
+        if let Some(thread) = self.thread.take() {
+            // Panics in `f` are caught, the thread can't fail
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<U: Clone> Drop for DerivedRcu<'_, U> {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::mpsc;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Counts its clones in the counter it shares with the test.
+    #[derive(Debug)]
+    struct Cloned(u32, Arc<AtomicUsize>);
+
+    impl Clone for Cloned {
+        fn clone(&self) -> Self {
+            self.1.fetch_add(1, SeqCst);
+            Self(self.0, Arc::clone(&self.1))
+        }
+    }
+
+    #[test]
+    fn derived_values_catch_up_with_every_publish() {
+        let source = Rcu::new(0);
+        let doubled = source.map_value(|value: &u32| value * 2);
+        assert_eq!((doubled.as_rcu().read(), doubled.source_version()), (0, 0));
+        for value in 1..=5 {
+            source.set(value).unwrap();
+            assert!(doubled.wait_for_source_version(source.version(), TIMEOUT));
+            assert_eq!(doubled.as_rcu().read(), value * 2);
+            assert_eq!(doubled.source_version(), source.version());
+        }
+        assert_eq!((doubled.recomputes(), doubled.as_rcu().version()), (5, 5));
+    }
+
+    #[test]
+    fn slow_recomputations_lag_behind_and_coalesce_what_was_published_meanwhile() {
+        let (started, wait_started) = mpsc::channel();
+        let (release, wait_release) = mpsc::channel::<()>();
+        let gate = Mutex::new((started, wait_release));
+        let source = Rcu::new(0);
+        let doubled = source.map_value(move |value: &u32| {
+            if *value == 1 {
+                let gate = gate.lock().unwrap();
+                gate.0.send(()).unwrap();
+                gate.1.recv().unwrap();
+            }
+            value * 2
+        });
+        source.set(1).unwrap();
+        wait_started.recv().unwrap();
+        for value in 2..=4 {
+            source.set(value).unwrap();
+        }
+        // Readers of the derived value still see the one computed from the initial value
+        assert_eq!(doubled.source_version(), 0);
+        assert_eq!(doubled.as_rcu().read(), 0);
+        assert!(!doubled.wait_for_source_version(1, Duration::from_millis(10)));
+        release.send(()).unwrap();
+        assert!(doubled.wait_for_source_version(source.version(), TIMEOUT));
+        assert_eq!(doubled.as_rcu().read(), 8);
+        // 1 and then 4 were recomputed from, 2 and 3 were replaced before the thread got to them
+        assert_eq!((doubled.recomputes(), doubled.coalesced()), (2, 2));
+        assert_eq!(doubled.as_rcu().version(), 2);
+    }
+
+    #[test]
+    fn dropping_the_handle_removes_the_hook() {
+        let clones = Arc::new(AtomicUsize::new(0));
+        let source = Rcu::new(Cloned(0, Arc::clone(&clones)));
+        let derived = source.map_value(|value: &Cloned| value.0);
+        clones.store(0, SeqCst);
+        source.set(Cloned(1, Arc::clone(&clones))).unwrap();
+        assert!(derived.wait_for_source_version(source.version(), TIMEOUT));
+        assert_eq!(clones.load(SeqCst), 1, "the hook didn't clone the value for the thread");
+        let rcu = Arc::clone(derived.as_rcu());
+        drop(derived);
+        source.set(Cloned(2, Arc::clone(&clones))).unwrap();
+        assert_eq!(clones.load(SeqCst), 1, "the hook outlived its handle");
+        assert_eq!(rcu.read(), 1);
+    }
+}
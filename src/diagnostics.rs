@@ -0,0 +1,249 @@
+use std::panic::Location;
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+#[cfg(debug_assertions)]
+use std::sync::{Mutex, PoisonError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::reclaim::{Retired, Watchdog};
+use crate::{Rcu, StallReport};
+
+/// Upper bounds of the buckets of a [`GraceHistogram`], the last one catching everything longer.
+const BOUNDS: [Duration; 8] = [
+    Duration::from_micros(1),
+    Duration::from_micros(10),
+    Duration::from_micros(100),
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(100),
+    Duration::from_secs(1),
+    Duration::MAX,
+];
+
+/// How many of the longest grace periods a [`GraceHistogram`] keeps.
+#[cfg(debug_assertions)]
+const SLOWEST: usize = 8;
+
+/// The grace periods of the values displaced from an `Rcu`, see [`Rcu::grace_period_histogram`].
+#[derive(Clone, Debug, Default)]
+pub struct GraceHistogram {
+    /// The upper bound of each bucket with the number of grace periods up to it and longer than the
+    /// bound before, in increasing order; the last bound is `Duration::MAX`
+    pub buckets: Vec<(Duration, u64)>,
+    /// How many grace periods were recorded
+    pub count: u64,
+    /// Their total duration
+    pub total: Duration,
+    /// The longest one
+    pub max: Duration,
+    /// The longest ones with the readers that were active when they started, longest first. Only
+    /// tracked in debug builds; empty otherwise
+    pub slowest: Vec<SlowGracePeriod>,
+}
+
+impl GraceHistogram {
+    /// The average grace period, zero if none was recorded.
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_nanos((self.total.as_nanos() / self.count as u128) as u64)
+    }
+}
+
+/// One of the longest grace periods in a [`GraceHistogram`].
+#[derive(Clone, Debug)]
+pub struct SlowGracePeriod {
+    /// How long it took from the publish until the displaced value was freed
+    pub waited: Duration,
+    /// Where the read-side critical sections active at the publish were entered, the readers that
+    /// may have held it up
+    pub reader_locations: Vec<&'static Location<'static>>,
+}
+
+/// Records the grace periods of one `RawRcu`.
+#[derive(Default)]
+pub(crate) struct GraceStats {
+    buckets: [AtomicU64; BOUNDS.len()],
+    count: AtomicU64,
+    /// In nanoseconds
+    total: AtomicU64,
+    /// In nanoseconds
+    max: AtomicU64,
+    #[cfg(debug_assertions)]
+    slowest: Mutex<Vec<SlowGracePeriod>>,
+}
+
+/// A displaced value whose grace period is recorded once it is freed.
+struct Timed {
+    retired: Retired,
+    at: Instant,
+    stats: Arc<GraceStats>,
+    /// Where the readers active at the publish entered
+    #[cfg(debug_assertions)]
+    readers: Vec<&'static Location<'static>>,
+}
+
+/// Frees the value, then records how long it waited.
+unsafe fn record(timed: *mut Timed) {
+    let timed = Box::from_raw(timed);
+    timed.retired.reclaim();
+    let waited = timed.at.elapsed();
+    #[cfg(debug_assertions)]
+    timed.stats.record(waited, timed.readers);
+    #[cfg(not(debug_assertions))]
+    timed.stats.record(waited);
+}
+
+impl GraceStats {
+    /// Wraps `retired`, just displaced from an `Rcu` whose engine `watchdog` observes, to record its
+    /// grace period once it is freed.
+    pub(crate) fn time(self: &Arc<Self>, retired: Retired, watchdog: &Watchdog) -> Retired {
+        #[cfg(not(debug_assertions))]
+        let _ = watchdog;
+        let timed = Timed {
+            retired,
+            at: Instant::now(),
+            stats: Arc::clone(self),
+            #[cfg(debug_assertions)]
+            readers: watchdog.reader_locations(),
+        };
+        Retired::with_deleter(Box::into_raw(Box::new(timed)), record)
+    }
+
+    fn record(&self, waited: Duration, #[cfg(debug_assertions)] readers: Vec<&'static Location<'static>>) {
+        let bucket = BOUNDS.iter().position(|bound| waited <= *bound).unwrap_or(BOUNDS.len() - 1);
+        self.buckets[bucket].fetch_add(1, Relaxed);
+        self.count.fetch_add(1, Relaxed);
+        let nanos = u64::try_from(waited.as_nanos()).unwrap_or(u64::MAX);
+        self.total.fetch_add(nanos, Relaxed);
+        self.max.fetch_max(nanos, Relaxed);
+        #[cfg(debug_assertions)]
+        {
+            let mut slowest = self.slowest.lock().unwrap_or_else(PoisonError::into_inner);
+            if slowest.len() < SLOWEST || slowest.last().is_some_and(|last| last.waited < waited) {
+                let at = slowest.partition_point(|slow| slow.waited >= waited);
+                slowest.insert(at, SlowGracePeriod { waited, reader_locations: readers });
+                slowest.truncate(SLOWEST);
+            }
+        }
+    }
+
+    pub(crate) fn histogram(&self) -> GraceHistogram {
+        GraceHistogram {
+            buckets: BOUNDS.iter().zip(&self.buckets).map(|(bound, count)| (*bound, count.load(Relaxed))).collect(),
+            count: self.count.load(Relaxed),
+            total: Duration::from_nanos(self.total.load(Relaxed)),
+            max: Duration::from_nanos(self.max.load(Relaxed)),
+            #[cfg(debug_assertions)]
+            slowest: self.slowest.lock().unwrap_or_else(PoisonError::into_inner).clone(),
+            #[cfg(not(debug_assertions))]
+            slowest: Vec::new(),
+        }
+    }
+}
+
+impl<T: Clone> Rcu<T> {
+    /// Arms the stall watchdog: whenever a publish waits longer than `threshold` for readers to
+    /// finish, a [`StallReport`] is recorded (see [`Rcu::stall_report`]) and passed to the callback
+    /// set with [`Rcu::on_stall`]. None disarms it, which is the initial state.
+    ///
+    /// The watchdog only observes, waits take as long as they would without it. Only the
+    /// `reclaim-counted` engine makes publishes wait for readers, with the other engines the only
+    /// waits are in `RcuDomain::synchronize`. Members of an `RcuDomain` share the domain's watchdog.
+    pub fn set_stall_threshold(&self, threshold: Option<Duration>) {
+        self.raw.watchdog().set_threshold(threshold)
+    }
+    /// The most recent report of a wait that exceeded the threshold, None if there was none yet.
+    pub fn stall_report(&self) -> Option<StallReport> {
+        self.raw.watchdog().last_report()
+    }
+    /// Sets a callback to be called with every new stall report, replacing the previous one. It
+    /// runs on the waiting thread, while it still waits, so it must not read or publish to this
+    /// `Rcu` itself; a panic in it is ignored.
+    pub fn on_stall(&self, f: impl Fn(&StallReport) + Send + Sync + 'static) {
+        self.raw.watchdog().set_callback(Arc::new(f))
+    }
+    /// How long the values displaced from this `Rcu` took to be freed once displaced, in fixed
+    /// buckets from 1µs to 1s. Recorded from the publish until the value is freed, so it includes
+    /// the time retire batching or a lazy engine holds it back beyond when its readers finished.
+    ///
+    /// In debug builds the longest ones are kept with where the readers active at their publish
+    /// entered, the readers (see [`Rcu::read`]) that may have held them up; a reader only counts
+    /// if it was still active at the publish.
+    ///
+    /// ```
+    /// # use rcu_rust::Rcu;
+    /// let config = Rcu::new(1);
+    /// config.set(2)?;
+    /// let histogram = config.grace_period_histogram();
+    /// for slow in &histogram.slowest {
+    ///     eprintln!("{:?} waiting for {:?}", slow.waited, slow.reader_locations);
+    /// }
+    /// # Ok::<(), rcu_rust::PublishError<i32>>(())
+    /// ```
+    pub fn grace_period_histogram(&self) -> GraceHistogram {
+        self.raw.grace_period_histogram()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::thread;
+
+    use super::*;
+    use crate::SpinYield;
+
+    /// The shortest grace period the injected reader holds up, it holds the value it read twice as long.
+    const SLOW: Duration = Duration::from_millis(20);
+
+    #[test]
+    fn a_slow_reader_lands_in_its_bucket_and_is_blamed() {
+        let rcu = Rcu::new(0).with_wait_strategy(SpinYield::default());
+        let (entered, wait_entered) = mpsc::channel();
+        let (exit, wait_exit) = mpsc::channel::<()>();
+        let slow_reader = thread::scope(|s| {
+            let rcu = &rcu;
+            let reader = s.spawn(move || {
+                // The line right above the read
+                let location = Location::caller();
+                let guard = rcu.read_guard();
+                entered.send(()).unwrap();
+                let _ = wait_exit.recv();
+                drop(guard);
+                location
+            });
+            wait_entered.recv().unwrap();
+            // The second publish displaces the value read, the counted engine waits for it in-line
+            let displaced = rcu.version() + 2;
+            let writer = s.spawn(move || {
+                rcu.set(1).unwrap();
+                rcu.set(2).unwrap();
+            });
+            // Held from the displacing publish on, however late the writer got to run
+            while rcu.version() < displaced {
+                thread::yield_now();
+            }
+            thread::sleep(2 * SLOW);
+            drop(exit);
+            writer.join().unwrap();
+            reader.join().unwrap()
+        });
+        rcu.barrier();
+        let histogram = rcu.grace_period_histogram();
+        assert_eq!(histogram.count, 1);
+        assert!(histogram.max >= SLOW, "recorded {:?}", histogram.max);
+        let (bound, count) = histogram.buckets.iter().find(|(_, count)| *count > 0).unwrap();
+        assert!(*bound >= histogram.max && *bound > SLOW, "{SLOW:?} counted up to {bound:?}");
+        assert_eq!(*count, 1);
+        #[cfg(debug_assertions)]
+        {
+            let slowest = &histogram.slowest[0];
+            assert_eq!(slowest.waited, histogram.max);
+            assert!(slowest.reader_locations.iter().any(|location| location.line() == slow_reader.line() + 1));
+        }
+        #[cfg(not(debug_assertions))]
+        let _ = slow_reader;
+    }
+}
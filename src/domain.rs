@@ -0,0 +1,266 @@
+use std::sync::atomic::{fence, AtomicPtr, Ordering::{Acquire, Release, SeqCst}};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::raw::RawRcu;
+use crate::reclaim::{Reclaim, Reclaimer, ReadLock, Retired};
+use crate::wait::{Preference, WaitStrategy, Waiter};
+use crate::Rcu;
+
+/// Reader tracking and reclamation shared by any number of `Rcu`s, instead of each carrying its
+/// own. Readers can enter the whole domain once with [`RcuDomain::read_lock`] and read as many
+/// member values as they like, and [`RcuDomain::synchronize`] waits out the readers of every member
+/// at once. Values displaced by publishes to any member go through the domain's engine.
+///
+/// Members keep the domain's state alive, so the `RcuDomain` itself may be dropped before them.
+///
+/// The domain's grace periods can also protect structures of your own, outside of any `Rcu`: read
+/// their pointers with [`DomainReadGuard::dereference`], publish with [`RcuDomain::assign`] and free
+/// what was unlinked with [`RcuDomain::retire`]. See `examples/rcu_list.rs` for a linked list
+/// built on just these.
+///
+/// With the `reclaim-counted` engine, publishing to any member waits for the readers of all
+/// members, so never publish to a member or call `synchronize` while holding the domain's read
+/// lock, and read members through the guard rather than through their own read methods while
+/// holding it: both would wait for the guard itself.
+#[derive(Clone, Default)]
+pub struct RcuDomain {
+    reclaimer: Arc<Reclaimer>,
+}
+
+impl RcuDomain {
+    /// Creates an empty domain.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Makes the domain wait for its readers through `strategy`, see [`Rcu::with_wait_strategy`].
+    ///
+    /// # Panics
+    /// If the domain has members or clones already.
+    pub fn with_wait_strategy(mut self, strategy: impl WaitStrategy + 'static) -> Self {
+        Arc::get_mut(&mut self.reclaimer)
+            .expect("domain already shared")
+            .set_waiter(Waiter::new(Arc::new(strategy)));
+        self
+    }
+    /// Makes the domain's grace periods hold readers back, or not, see [`Rcu::with_preference`].
+    ///
+    /// # Panics
+    /// If the domain has members or clones already.
+    pub fn with_preference(mut self, preference: Preference) -> Self {
+        Arc::get_mut(&mut self.reclaimer).expect("domain already shared").set_preference(preference);
+        self
+    }
+    /// Enters a read-side critical section covering every member, exited when the guard is dropped.
+    #[track_caller]
+    pub fn read_lock(&self) -> DomainReadGuard<'_> {
+        DomainReadGuard {
+            lock: ReadLock::new(&self.reclaimer),
+        }
+    }
+    /// Waits until every read-side critical section on any member that was active when this was
+    /// called has exited, whether entered through the domain or through a member.
+    pub fn synchronize(&self) {
+        self.reclaimer.synchronize();
+    }
+    /// Blocks until everything retired on the domain before the call, by its members or
+    /// [`RcuDomain::retire`], was freed, its deleter having returned, where
+    /// [`RcuDomain::synchronize`] only waits for readers. Displaced values a member holds back
+    /// with [`Rcu::with_retire_batching`] aren't retired yet: see [`Rcu::barrier`] for those.
+    ///
+    /// As with `synchronize`, never call it while holding the domain's read lock. Safe to call from
+    /// any number of threads at once.
+    pub fn barrier(&self) {
+        self.reclaimer.barrier();
+    }
+    /// Frees everything retired on the domain, by its members or [`RcuDomain::retire`], that no
+    /// reader can observe anymore right now, without waiting for readers, see [`Rcu::reclaim_now`].
+    pub fn reclaim_now(&self) {
+        // Safety: an empty batch retires nothing, only what was retired before is freed
+        unsafe { self.reclaimer.retire_batch(Vec::new(), Some(Instant::now())) };
+    }
+    /// Number of allocations retired on the domain that aren't freed yet, not counting those its
+    /// members' retire batching holds back.
+    pub fn pending_retired(&self) -> usize {
+        self.reclaimer.pending()
+    }
+    /// Publishes `node` through `slot`: a reader loading it with [`DomainReadGuard::dereference`]
+    /// sees everything written to it before this call. Use it for every store to a slot readers
+    /// dereference, unlinking included.
+    pub fn assign<N>(&self, slot: &AtomicPtr<N>, node: *mut N) {
+        slot.store(node, Release);
+    }
+    /// Frees `node` by calling `deleter` on it once every read-side critical section on the domain
+    /// that could still observe it has exited. Depending on the engine that happens in-line, waiting
+    /// for readers like a publish to a member does, or later on whichever thread ends the grace period.
+    ///
+    /// With the `reclaim-counted` engine this waits for active readers, so never call it while
+    /// holding the domain's read lock.
+    ///
+    /// # Safety
+    /// - `node` must already be unreachable for readers entering after this call: every slot that
+    ///   pointed to it has been re-assigned by this thread, or by one that synchronized with it.
+    /// - `node` must not be retired twice, nor used by anybody but readers afterwards.
+    /// - Calling `deleter(node)` once must be sound, from any thread.
+    pub unsafe fn retire<N>(&self, node: *mut N, deleter: unsafe fn(*mut N)) {
+        // Orders the stores that unlinked `node` before the engine checks for readers
+        fence(SeqCst);
+        self.reclaimer.retire(Retired::with_deleter(node, deleter));
+    }
+}
+
+/// A read-side critical section on a whole [`RcuDomain`], created with [`RcuDomain::read_lock`].
+pub struct DomainReadGuard<'a> {
+    lock: ReadLock<'a>,
+}
+
+impl DomainReadGuard<'_> {
+    /// The current value of `rcu`, which stays alive while the guard does, however many publishes
+    /// happen meanwhile; every call returns the value current at that point.
+    ///
+    /// # Panics
+    /// If `rcu` isn't a member of the domain this guard was taken on.
+    pub fn get<'g, T: Clone>(&'g self, rcu: &'g Rcu<T>) -> &'g T {
+        rcu.raw.read_in(&self.lock)
+    }
+    /// Loads the node `slot` points to, None if it is null. The node stays alive while the guard
+    /// does, even if it is unlinked and retired meanwhile.
+    ///
+    /// # Safety
+    /// Every node `slot` ever points to must have been published with [`RcuDomain::assign`] (or
+    /// before any reader could load it), and may only be freed through [`RcuDomain::retire`] on the
+    /// domain this guard was taken on.
+    pub unsafe fn dereference<'g, N>(&'g self, slot: &AtomicPtr<N>) -> Option<&'g N> {
+        // Acquire matches the Release in `RcuDomain::assign`
+        slot.load(Acquire).as_ref()
+    }
+}
+
+impl<T: Clone + 'static> Rcu<T> {
+    /// Creates a new `Rcu` holding `value`, as a member of `domain`.
+    ///
+    /// Values the member displaces are freed by the domain's grace periods, which may only end
+    /// after the member itself is gone, hence `T: 'static`: a value borrowing anything could
+    /// otherwise be dropped after what it borrows.
+    ///
+    /// ```compile_fail
+    /// # use rcu_rust::{Rcu, RcuDomain};
+    /// let domain = RcuDomain::new();
+    /// let name = String::from("session");
+    /// let rcu = Rcu::new_in_domain(name.as_str(), &domain);
+    /// ```
+    pub fn new_in_domain(value: T, domain: &RcuDomain) -> Self {
+        Self::from_raw(RawRcu::new_shared(Box::new(value), Arc::clone(&domain.reclaimer)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst};
+    use std::sync::Mutex;
+    use std::thread;
+
+    use super::*;
+
+    /// Counts its drops in the counter it shares with the test.
+    #[derive(Clone, Debug)]
+    struct Tracked(Arc<AtomicUsize>);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, SeqCst);
+        }
+    }
+
+    #[test]
+    fn values_displaced_by_dropped_members_are_freed_by_the_domain() {
+        let domain = RcuDomain::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        for _ in 0..4 {
+            let rcu = Rcu::new_in_domain(Tracked(Arc::clone(&drops)), &domain);
+            for _ in 0..10 {
+                rcu.set(Tracked(Arc::clone(&drops))).unwrap();
+            }
+        }
+        // Members are gone, what they displaced may still wait for the domain
+        domain.barrier();
+        assert_eq!(drops.load(SeqCst), 44);
+        assert_eq!(domain.pending_retired(), 0);
+    }
+
+    #[test]
+    fn guards_read_every_member() {
+        let domain = RcuDomain::new();
+        let first = Rcu::new_in_domain(1, &domain);
+        let second = Rcu::new_in_domain(2, &domain);
+        let guard = domain.read_lock();
+        assert_eq!(*guard.get(&first) + *guard.get(&second), 3);
+    }
+
+    /// Logs its id once its deleter ran.
+    struct Node {
+        id: usize,
+        freed: Arc<Mutex<Vec<usize>>>,
+    }
+
+    unsafe fn free(node: *mut Node) {
+        let node = Box::from_raw(node);
+        node.freed.lock().unwrap().push(node.id);
+    }
+
+    #[test]
+    fn barriers_see_every_deleter_queued_before_them_run() {
+        let domain = RcuDomain::new();
+        let freed = Arc::new(Mutex::new(Vec::new()));
+        let stop = AtomicBool::new(false);
+        thread::scope(|s| {
+            s.spawn(|| {
+                while !stop.load(SeqCst) {
+                    drop(domain.read_lock());
+                    thread::yield_now();
+                }
+            });
+            let retirers: Vec<_> = (0..2)
+                .map(|retirer| {
+                    let (domain, freed) = (&domain, &freed);
+                    s.spawn(move || {
+                        for id in (retirer * 100..).take(100) {
+                            let node = Box::into_raw(Box::new(Node { id, freed: Arc::clone(freed) }));
+                            // Safety: never published
+                            unsafe { domain.retire(node, free) };
+                            if id % 10 == 9 {
+                                domain.barrier();
+                                let freed = freed.lock().unwrap();
+                                assert!(freed.contains(&id), "deleter of {id} hadn't run");
+                            }
+                        }
+                    })
+                })
+                .collect();
+            for retirer in retirers {
+                retirer.join().unwrap();
+            }
+            stop.store(true, SeqCst);
+        });
+        domain.barrier();
+        let mut freed = freed.lock().unwrap().clone();
+        freed.sort_unstable();
+        assert_eq!(freed, (0..200).collect::<Vec<_>>());
+        assert_eq!(domain.pending_retired(), 0);
+    }
+
+    #[test]
+    fn barriers_return_with_nothing_queued() {
+        let domain = RcuDomain::new();
+        domain.barrier();
+        let rcu = Rcu::new_in_domain(0, &domain);
+        rcu.barrier();
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| domain.barrier());
+                s.spawn(|| rcu.barrier());
+            }
+        });
+        assert_eq!(domain.pending_retired(), 0);
+    }
+}
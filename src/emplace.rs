@@ -0,0 +1,46 @@
+use std::mem::MaybeUninit;
+
+use crate::Rcu;
+
+impl<T: Clone> Rcu<T> {
+    /// Like [`Rcu::new`], for values too large to pass around by value: allocates the value's box
+    /// first and has `init` initialize it in place, so the value never exists on the stack.
+    ///
+    /// `init` proves it initialized the slot by returning the reference `MaybeUninit::write` or
+    /// `assume_init_mut` hand back. It must return a reference to the slot it was given; returning
+    /// any other reference panics, leaking whatever the slot holds.
+    ///
+    /// ```
+    /// # use std::mem::MaybeUninit;
+    /// # use rcu_rust::Rcu;
+    /// let frames = Rcu::emplace(|slot: &mut MaybeUninit<[u8; 1 << 26]>| {
+    ///     // Safety: all zeroes is a valid array of bytes
+    ///     unsafe {
+    ///         slot.as_mut_ptr().write_bytes(0, 1);
+    ///         slot.assume_init_mut()
+    ///     }
+    /// });
+    /// assert!(frames.read_with(|frames| frames.iter().all(|&byte| byte == 0)));
+    /// ```
+    pub fn emplace(init: impl FnOnce(&mut MaybeUninit<T>) -> &mut T) -> Self {
+        Self::from_box(Self::emplace_box(init))
+    }
+    /// Like [`Rcu::update_box`], initializing the new value in place like [`Rcu::emplace`]. On
+    /// failure the initialized box is handed back.
+    pub fn update_emplace(&self, init: impl FnOnce(&mut MaybeUninit<T>) -> &mut T) -> Result<(), Box<T>> {
+        self.update_box(Self::emplace_box(init))
+    }
+    /// The box [`Rcu::emplace`] publishes, initialized in place by `init`, for code producing the
+    /// value elsewhere, e.g. a parser, to hand to [`Rcu::update_box`] or [`Rcu::from_box`] later.
+    pub fn emplace_box(init: impl FnOnce(&mut MaybeUninit<T>) -> &mut T) -> Box<T> {
+        let mut slot = Box::<T>::new_uninit();
+        let expected = slot.as_ptr();
+        let initialized: *const T = init(&mut slot);
+        assert!(
+            initialized == expected,
+            "emplace: init returned a reference to something other than the slot it was given"
+        );
+        // Safety: `init` handed out a `&mut T` to the slot, so the slot holds a valid value
+        unsafe { slot.assume_init() }
+    }
+}
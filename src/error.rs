@@ -0,0 +1,310 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use crate::{Backlog, Staleness};
+
+/// Why a value could not be published. The value is always handed back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublishError<T> {
+    /// An invariant installed with [`Rcu::set_invariant`](crate::Rcu::set_invariant) refused the value.
+    Rejected { reason: String, value: T },
+    /// The `Rcu` was frozen with [`Rcu::freeze`](crate::Rcu::freeze), it never publishes again.
+    Frozen { value: T },
+    /// Too many displaced values were waiting to be freed, see
+    /// [`Rcu::with_max_pending_retired`](crate::Rcu::with_max_pending_retired).
+    Backpressure { backlog: Backlog, value: T },
+}
+
+impl<T> PublishError<T> {
+    /// Returns the value that could not be published.
+    pub fn into_value(self) -> T {
+        match self {
+            PublishError::Rejected { value, .. }
+            | PublishError::Frozen { value }
+            | PublishError::Backpressure { value, .. } => value,
+        }
+    }
+}
+
+impl<T> fmt::Display for PublishError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublishError::Rejected { reason, .. } => write!(f, "value rejected by invariant: {reason}"),
+            PublishError::Frozen { .. } => write!(f, "value not published, the rcu is frozen"),
+            PublishError::Backpressure { backlog, .. } => write!(
+                f,
+                "value not published, {} displaced values ({} bytes) are waiting to be freed",
+                backlog.count, backlog.bytes
+            ),
+        }
+    }
+}
+
+impl<T: fmt::Debug> Error for PublishError<T> {}
+
+/// What became of the value a successful [`Rcu::update_with_deadline`](crate::Rcu::update_with_deadline)
+/// replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReclaimStatus {
+    /// Its readers were done in time, and it was dealt with before the call returned.
+    Done,
+    /// The deadline passed while waiting for its readers; it will be freed by a later grace period
+    /// instead.
+    Deferred,
+}
+
+/// Why [`Rcu::update_with_deadline`](crate::Rcu::update_with_deadline) published nothing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeadlineError<T> {
+    /// The deadline passed while waiting for another publish to complete, and the value is handed
+    /// back.
+    Expired { value: T },
+    /// An invariant refused the value, the `Rcu` is frozen or its backlog is past a limit.
+    Rejected(PublishError<T>),
+}
+
+impl<T> DeadlineError<T> {
+    /// Returns the value that could not be published.
+    pub fn into_value(self) -> T {
+        match self {
+            DeadlineError::Expired { value } => value,
+            DeadlineError::Rejected(err) => err.into_value(),
+        }
+    }
+}
+
+impl<T> fmt::Display for DeadlineError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeadlineError::Expired { .. } => write!(f, "deadline expired before the value could be published"),
+            DeadlineError::Rejected(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<T: fmt::Debug> Error for DeadlineError<T> {}
+
+/// Why [`RcuWriteGuard::try_commit`](crate::RcuWriteGuard::try_commit) published nothing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommitError<T> {
+    /// Another writer published since the guard was created.
+    Conflict(CommitConflict<T>),
+    /// An invariant refused the staged value, the `Rcu` is frozen or its backlog is past a limit.
+    Rejected(PublishError<T>),
+}
+
+impl<T> CommitError<T> {
+    /// Returns the staged value, which was not published.
+    pub fn into_value(self) -> T {
+        match self {
+            CommitError::Conflict(conflict) => conflict.staged,
+            CommitError::Rejected(err) => err.into_value(),
+        }
+    }
+}
+
+impl<T> fmt::Display for CommitError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommitError::Conflict(conflict) => conflict.fmt(f),
+            CommitError::Rejected(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<T: fmt::Debug> Error for CommitError<T> {}
+
+/// A write guard's staged value, which lost to a concurrent publish, along with what won, to rebase
+/// the change on if it still applies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitConflict<T> {
+    /// The staged value, not published
+    pub staged: T,
+    /// The current value when the conflict was found
+    pub current: T,
+    /// The version `current` was published as
+    pub version: u64,
+}
+
+impl<T> fmt::Display for CommitConflict<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "value not published, version {} was published since it was staged", self.version)
+    }
+}
+
+impl<T: fmt::Debug> Error for CommitConflict<T> {}
+
+/// Why [`Rcu::promote`](crate::Rcu::promote) published nothing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromoteError<T> {
+    /// The handle is stale, the value it was for is no longer staged.
+    Stale(StaleStage),
+    /// An invariant refused the staged value, the `Rcu` is frozen or its backlog is past a limit.
+    /// The value is no longer staged either.
+    Rejected(PublishError<T>),
+}
+
+impl<T> fmt::Display for PromoteError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PromoteError::Stale(stale) => stale.fmt(f),
+            PromoteError::Rejected(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<T: fmt::Debug> Error for PromoteError<T> {}
+
+impl<T> From<StaleStage> for PromoteError<T> {
+    fn from(stale: StaleStage) -> Self {
+        PromoteError::Stale(stale)
+    }
+}
+
+/// Why [`Rcu::promote`](crate::Rcu::promote) or [`Rcu::rollback`](crate::Rcu::rollback) did
+/// nothing: another value was staged since, superseding the one the handle was for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaleStage {
+    /// The stage the handle was for
+    pub stage: u64,
+    /// The latest stage of the `Rcu`
+    pub latest: u64,
+}
+
+impl fmt::Display for StaleStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stage {} is no longer staged, the latest is stage {}", self.stage, self.latest)
+    }
+}
+
+impl Error for StaleStage {}
+
+/// Why [`Rcu::read_fresh`](crate::Rcu::read_fresh) failed: the value was published too long ago.
+/// It is handed out anyway.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stale<T> {
+    /// The current value when it was read
+    pub value: T,
+    /// How long before the read it was published
+    pub age: Duration,
+}
+
+impl<T> Stale<T> {
+    /// Returns the stale value.
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+impl<T> fmt::Display for Stale<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "value published {:?} ago, too long to be fresh", self.age)
+    }
+}
+
+impl<T: fmt::Debug> Error for Stale<T> {}
+
+/// Why [`Rcu::wait_until_observed`](crate::Rcu::wait_until_observed) timed out: some acked
+/// subscribers had not read the version yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaggingSubscribers {
+    /// The version waited for
+    pub version: u64,
+    /// The id (see [`RcuSubscriber::ack_id`](crate::RcuSubscriber::ack_id)) and staleness of each
+    /// subscriber still behind it when time ran out, in creation order
+    pub behind: Vec<(u64, Staleness)>,
+}
+
+impl fmt::Display for LaggingSubscribers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} subscribers have not observed version {} yet", self.behind.len(), self.version)
+    }
+}
+
+impl Error for LaggingSubscribers {}
+
+/// Why [`RcuRegistry::try_get`](crate::RcuRegistry::try_get) found no `Rcu` of the type asked for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LookupError {
+    /// Nothing is registered under the name.
+    Missing { name: String },
+    /// The `Rcu` registered under the name holds values of another type.
+    WrongType {
+        name: String,
+        /// The value type asked for
+        expected: &'static str,
+        /// The value type of the registered `Rcu`
+        found: &'static str,
+    },
+}
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LookupError::Missing { name } => write!(f, "no rcu registered as {name:?}"),
+            LookupError::WrongType { name, expected, found } => {
+                write!(f, "the rcu registered as {name:?} holds {found}, not {expected}")
+            }
+        }
+    }
+}
+
+impl Error for LookupError {}
+
+/// Why [`Rcu::try_new`](crate::Rcu::try_new) or [`Rcu::try_update`](crate::Rcu::try_update)
+/// failed: the allocator had no memory to box the value in. The value is handed back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllocError<T> {
+    /// The value that could not be boxed
+    pub value: T,
+}
+
+impl<T> AllocError<T> {
+    /// Returns the value that could not be boxed.
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+impl<T> fmt::Display for AllocError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "out of memory allocating a {}", std::any::type_name::<T>())
+    }
+}
+
+impl<T: fmt::Debug> Error for AllocError<T> {}
+
+/// Why [`watch_file`](crate::watch_file) couldn't load or reload the file it watches.
+#[cfg(feature = "watch")]
+#[derive(Debug)]
+pub enum WatchError<E> {
+    /// The file couldn't be read.
+    Io(std::io::Error),
+    /// Its contents didn't parse.
+    Parse(E),
+    /// It couldn't be watched for changes.
+    Watch(::notify::Error),
+}
+
+#[cfg(feature = "watch")]
+impl<E: fmt::Display> fmt::Display for WatchError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchError::Io(err) => write!(f, "failed to read the watched file: {err}"),
+            WatchError::Parse(err) => write!(f, "failed to parse the watched file: {err}"),
+            WatchError::Watch(err) => write!(f, "failed to watch the file: {err}"),
+        }
+    }
+}
+
+#[cfg(feature = "watch")]
+impl<E: Error + 'static> Error for WatchError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WatchError::Io(err) => Some(err),
+            WatchError::Parse(err) => Some(err),
+            WatchError::Watch(err) => Some(err),
+        }
+    }
+}
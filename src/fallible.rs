@@ -0,0 +1,39 @@
+use std::alloc::{self, Layout};
+
+use crate::error::AllocError;
+use crate::Rcu;
+
+/// Boxes `value`, handing it back if the allocator has no memory for it instead of aborting.
+fn try_box<T>(value: T) -> Result<Box<T>, AllocError<T>> {
+    let layout = Layout::new::<T>();
+    if layout.size() == 0 {
+        // Allocates nothing
+        return Ok(Box::new(value));
+    }
+    // Safety: the layout has a non-zero size
+    let ptr = unsafe { alloc::alloc(layout) }.cast::<T>();
+    if ptr.is_null() {
+        return Err(AllocError { value });
+    }
+    // Safety: `ptr` was just allocated by the global allocator with the layout of `T`, which is
+    // what `Box` expects of it
+    unsafe {
+        ptr.write(value);
+        Ok(Box::from_raw(ptr))
+    }
+}
+
+impl<T: Clone> Rcu<T> {
+    /// Like [`Rcu::new`], handing `value` back instead of aborting the process if there is no
+    /// memory to box it in. Only the allocation of the value is fallible, the small bookkeeping
+    /// of the `Rcu` itself is allocated as usual.
+    pub fn try_new(value: T) -> Result<Self, AllocError<T>> {
+        try_box(value).map(Self::from_box)
+    }
+    /// Like [`Rcu::update`], handing `value` back instead of aborting the process if there is no
+    /// memory to box it in, the `Rcu` left as it was. The box is the only allocation a publish
+    /// needs, except with engines keeping displaced values in a list which may have to grow.
+    pub fn try_update(&self, value: T) -> Result<bool, AllocError<T>> {
+        Ok(self.update_box(try_box(value)?).is_ok())
+    }
+}
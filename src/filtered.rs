@@ -0,0 +1,178 @@
+use std::sync::atomic::{AtomicU32, Ordering::{Relaxed, SeqCst}};
+use std::sync::Arc;
+
+use crate::hooks::HookId;
+use crate::notify::Notify;
+use crate::raw::RawRcu;
+use crate::Rcu;
+
+/// Shared between a `FilteredSubscriber` and the hook feeding it.
+struct FilterState<T> {
+    /// The most recent matching value, tagged with the number of matches at the time
+    latest: RawRcu<Option<(u32, T)>>,
+    /// Number of matching publishes observed so far
+    matches: AtomicU32,
+    /// Wakes `wait_for_match` when `matches` moves
+    matched: Notify,
+}
+
+impl<T: Clone + Send + Sync + 'static> Rcu<T> {
+    /// Creates a subscriber that only ever observes values satisfying `pred`.
+    ///
+    /// `pred` is evaluated by the publishing thread for every value at the moment it is published,
+    /// not when the subscriber gets around to reading, so a matching value that is immediately
+    /// replaced by a non-matching one is still retrievable through [`FilteredSubscriber::read`].
+    /// The value current at subscription time counts as the latest match if it satisfies `pred`.
+    pub fn subscribe_filtered(&self, pred: impl Fn(&T) -> bool + Send + Sync + 'static) -> FilteredSubscriber<'_, T> {
+        let state = Arc::new(FilterState {
+            latest: RawRcu::new(Box::new(None)),
+            matches: AtomicU32::new(0),
+            matched: Notify::default(),
+        });
+        let pred = Arc::new(pred);
+        let hook = {
+            let state = Arc::clone(&state);
+            let pred = Arc::clone(&pred);
+            self.on_update(move |value| {
+                if !pred(value) {
+                    return;
+                }
+                // Hooks of different publishes never overlap, so this is the only writer apart from
+                // the initial seeding below
+                let n = state.matches.load(Relaxed) + 1;
+                state.latest.modify(|_| Some((n, value.clone())));
+                state.matches.store(n, SeqCst);
+                state.matched.notify();
+            })
+        };
+        // Seed with the current value, unless the hook already stored something newer
+        self.read_with(|value| {
+            if pred(value) {
+                let _ = state.latest.try_modify(
+                    |latest| match latest {
+                        None => Ok(Box::new(Some((0, value.clone())))),
+                        Some(_) => Err(()),
+                    },
+                    |_| {},
+                );
+            }
+        });
+        FilteredSubscriber {
+            rcu: self,
+            hook,
+            state,
+            seen: AtomicU32::new(0),
+        }
+    }
+}
+
+/// A subscriber to a `Rcu` that only observes values satisfying a predicate, created with
+/// [`Rcu::subscribe_filtered`]. Dropping it stops evaluating the predicate.
+pub struct FilteredSubscriber<'a, T: Clone + Send + Sync + 'static> {
+    rcu: &'a Rcu<T>,
+    hook: HookId,
+    state: Arc<FilterState<T>>,
+    /// The match number of the last value returned by this subscriber
+    seen: AtomicU32,
+}
+
+impl<T: Clone + Send + Sync + 'static> FilteredSubscriber<'_, T> {
+    /// The most recently published value that satisfied the predicate, None if there was none yet.
+    pub fn read(&self) -> Option<T> {
+        self.state.latest.read_with(|latest| {
+            latest.as_ref().map(|(n, value)| {
+                self.seen.store(*n, Relaxed);
+                value.clone()
+            })
+        })
+    }
+    /// Whether a matching value was published since the last one this subscriber returned.
+    pub fn has_changed(&self) -> bool {
+        self.state.matches.load(SeqCst) != self.seen.load(Relaxed)
+    }
+    /// Blocks until a matching value newer than the last one this subscriber returned is
+    /// published, and returns it. Publishes of non-matching values never wake it up.
+    pub fn wait_for_match(&self) -> T {
+        loop {
+            self.state.matched.wait_until(|| self.has_changed(), None);
+            // A match was stored before `matches` was bumped, so there is a value
+            if let Some(value) = self.read() {
+                return value;
+            }
+        }
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> Drop for FilteredSubscriber<'_, T> {
+    fn drop(&mut self) {
+        self.rcu.remove_hook(self.hook);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst};
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::Rcu;
+
+    #[test]
+    fn a_match_replaced_right_away_is_still_read() {
+        let rcu = Rcu::new(1u64);
+        let evens = rcu.subscribe_filtered(|n| n % 2 == 0);
+        assert_eq!(evens.read(), None, "the odd initial value matched");
+        rcu.set(2).unwrap();
+        rcu.set(3).unwrap();
+        assert!(evens.has_changed());
+        assert_eq!(evens.read(), Some(2));
+        assert!(!evens.has_changed(), "changed without a newer match");
+        rcu.set(5).unwrap();
+        assert!(!evens.has_changed(), "an odd value counted as a match");
+        assert_eq!(evens.read(), Some(2));
+    }
+
+    #[test]
+    fn the_value_current_at_subscription_counts_until_the_subscriber_drops() {
+        let rcu = Rcu::new(4u64);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&calls);
+        let evens = rcu.subscribe_filtered(move |n| {
+            counted.fetch_add(1, SeqCst);
+            n % 2 == 0
+        });
+        assert_eq!(evens.read(), Some(4));
+        rcu.set(6).unwrap();
+        assert_eq!(calls.load(SeqCst), 2);
+        drop(evens);
+        rcu.set(8).unwrap();
+        assert_eq!(calls.load(SeqCst), 2, "the predicate outlived its subscriber");
+    }
+
+    #[test]
+    fn waiting_subscribers_only_ever_wake_up_to_matches() {
+        let rcu = Rcu::new(1u64);
+        let tens = rcu.subscribe_filtered(|n| n % 10 == 0);
+        let done = AtomicBool::new(false);
+        thread::scope(|s| {
+            s.spawn(|| {
+                // Each match is displaced by a non-matching value right away
+                for n in 2..=1001 {
+                    rcu.set(n).unwrap();
+                    thread::yield_now();
+                }
+                done.store(true, SeqCst);
+            });
+            let mut last = 0;
+            while last < 1000 {
+                let n = tens.wait_for_match();
+                assert_eq!(n % 10, 0, "woke up to {n}");
+                assert!(n > last, "went back from {last} to {n}");
+                last = n;
+            }
+        });
+        assert!(done.load(SeqCst));
+        assert_eq!(rcu.read(), 1001);
+        assert_eq!(tens.read(), Some(1000));
+    }
+}
@@ -0,0 +1,199 @@
+//! The gate every publish of a `RawRcu` goes through, closed for good by `RawRcu::freeze`.
+//!
+//! A publish first counts itself in, then checks the gate is still open; `freeze` first closes the
+//! gate, then waits for the count to drop to zero. Both sides write before they read, with a full
+//! fence in between: either the publish sees the gate closed and backs out, or `freeze` sees it
+//! counted and waits for it. Once the count is zero `freeze` marks the value frozen, with a release
+//! store that readers acquire before reading the value without a read-side critical section, past
+//! the writes of every publish that got in.
+//!
+//! The handshake is model-checked with [loom](https://docs.rs/loom):
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test --release --lib freeze
+//! ```
+
+#[cfg(not(loom))]
+use std::sync::atomic::{fence, AtomicU8, AtomicUsize};
+use std::sync::atomic::Ordering::{Acquire, Release, SeqCst};
+
+#[cfg(loom)]
+use loom::sync::atomic::{fence, AtomicU8, AtomicUsize};
+
+use crate::wait::Waiter;
+
+/// Publishes are allowed
+const OPEN: u8 = 0;
+/// No new publish is allowed, those already past the check may still be in progress
+const FREEZING: u8 = 1;
+/// No publish will ever happen again, the current value is never retired
+const FROZEN: u8 = 2;
+
+pub(crate) struct FreezeGate {
+    /// `OPEN`, `FREEZING` or `FROZEN`
+    state: AtomicU8,
+    /// Publishes that got past the check of `state` and haven't completed yet
+    publishers: AtomicUsize,
+}
+
+impl FreezeGate {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: AtomicU8::new(OPEN),
+            publishers: AtomicUsize::new(0),
+        }
+    }
+
+    /// Lets a publish in unless the gate is closing, None otherwise. The publish counts as in
+    /// progress until the returned guard is dropped.
+    pub(crate) fn enter<'a>(&'a self, waiter: &'a Waiter) -> Option<Publishing<'a>> {
+        self.publishers.fetch_add(1, SeqCst);
+        // Either `freeze` sees us in `publishers`, or we see it changed `state`. Sequentially
+        // consistent accesses alone would do, the fence is what loom can check
+        fence(SeqCst);
+        if self.state.load(SeqCst) != OPEN {
+            self.publishers.fetch_sub(1, SeqCst);
+            waiter.notify();
+            return None;
+        }
+        Some(Publishing {
+            publishers: &self.publishers,
+            waiter,
+        })
+    }
+
+    /// Closes the gate for good, waiting for the publishes in progress to complete, or for the
+    /// thread that closed it first to see them complete.
+    pub(crate) fn freeze(&self, waiter: &Waiter) {
+        if self.state.compare_exchange(OPEN, FREEZING, SeqCst, SeqCst).is_ok() {
+            // Pairs with the fence in `enter`
+            fence(SeqCst);
+            wait_while(waiter, || self.publishers.load(SeqCst) > 0);
+            self.state.store(FROZEN, Release);
+            waiter.notify();
+        } else {
+            // Someone else is freezing it
+            wait_while(waiter, || !self.is_frozen());
+        }
+    }
+
+    /// Whether `freeze` completed.
+    pub(crate) fn is_frozen(&self) -> bool {
+        // Acquire matches the Release in `freeze`, which came after every publish completed
+        self.state.load(Acquire) == FROZEN
+    }
+}
+
+/// Waits through `waiter` while `waiting` returns true.
+#[cfg(not(loom))]
+fn wait_while(waiter: &Waiter, waiting: impl FnMut() -> bool) {
+    waiter.wait_while(waiting, None);
+}
+
+/// Yields to the other threads of the model while `waiting` returns true.
+#[cfg(loom)]
+fn wait_while(_waiter: &Waiter, mut waiting: impl FnMut() -> bool) {
+    while waiting() {
+        loom::thread::yield_now();
+    }
+}
+
+/// Counts a publish as in progress until dropped, see `FreezeGate::enter`.
+pub(crate) struct Publishing<'a> {
+    publishers: &'a AtomicUsize,
+    /// Notified for a `freeze` waiting for publishes in progress
+    waiter: &'a Waiter,
+}
+
+impl Drop for Publishing<'_> {
+    fn drop(&mut self) {
+        self.publishers.fetch_sub(1, Release);
+        self.waiter.notify();
+    }
+}
+
+#[cfg(all(loom, test))]
+mod loom_model {
+    use loom::cell::UnsafeCell;
+    use loom::sync::Arc;
+    use loom::thread;
+
+    use super::*;
+
+    /// Stands for the value and the pointer to it, written by every publish and read without a
+    /// read-side critical section once frozen: loom reports such a read racing with a publish.
+    struct Value(UnsafeCell<u64>);
+
+    impl Value {
+        fn read(&self) -> u64 {
+            // Safety: loom checks the accesses, which is the point
+            self.0.with(|value| unsafe { *value })
+        }
+    }
+
+    /// Publishes `value` through `gate`, returning whether it got in.
+    fn publish(gate: &FreezeGate, waiter: &Waiter, shared: &Value, value: u64) -> bool {
+        let Some(_publishing) = gate.enter(waiter) else {
+            return false;
+        };
+        // Safety: as for `Value::read`
+        shared.0.with_mut(|shared| unsafe { *shared = value });
+        true
+    }
+
+    #[test]
+    fn frozen_reads_never_race_with_a_publish() {
+        loom::model(|| {
+            let gate = Arc::new(FreezeGate::new());
+            let value = Arc::new(Value(UnsafeCell::new(0)));
+            let publisher = {
+                let (gate, value) = (Arc::clone(&gate), Arc::clone(&value));
+                thread::spawn(move || publish(&gate, &Waiter::default(), &value, 1))
+            };
+            gate.freeze(&Waiter::default());
+            let seen = value.read();
+            // Nothing gets in once frozen
+            assert!(!publish(&gate, &Waiter::default(), &value, 2));
+            let published = publisher.join().unwrap();
+            assert_eq!(seen, u64::from(published));
+        });
+    }
+
+    #[test]
+    fn readers_seeing_it_frozen_see_the_last_publish() {
+        loom::model(|| {
+            let gate = Arc::new(FreezeGate::new());
+            let value = Arc::new(Value(UnsafeCell::new(0)));
+            let reader = {
+                let (gate, value) = (Arc::clone(&gate), Arc::clone(&value));
+                thread::spawn(move || gate.is_frozen().then(|| value.read()))
+            };
+            let published = publish(&gate, &Waiter::default(), &value, 1);
+            assert!(published, "nobody froze it yet");
+            gate.freeze(&Waiter::default());
+            if let Some(seen) = reader.join().unwrap() {
+                assert_eq!(seen, 1);
+            }
+        });
+    }
+
+    #[test]
+    fn every_freeze_returns_once_frozen() {
+        loom::model(|| {
+            let gate = Arc::new(FreezeGate::new());
+            let value = Arc::new(Value(UnsafeCell::new(0)));
+            let freezer = {
+                let gate = Arc::clone(&gate);
+                thread::spawn(move || {
+                    gate.freeze(&Waiter::default());
+                    assert!(gate.is_frozen());
+                })
+            };
+            let published = publish(&gate, &Waiter::default(), &value, 1);
+            gate.freeze(&Waiter::default());
+            assert!(gate.is_frozen());
+            assert_eq!(value.read(), u64::from(published));
+            freezer.join().unwrap();
+        });
+    }
+}
@@ -0,0 +1,288 @@
+use std::any::{type_name, Any, TypeId};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::Rcu;
+
+type Member = (TypeId, Arc<dyn Any + Send + Sync>);
+
+/// Looks up the member of type `M`, which the type of its group proves is there.
+fn find<M: Any>(members: &[Member]) -> &M {
+    members
+        .iter()
+        .find(|(id, _)| *id == TypeId::of::<M>())
+        .and_then(|(_, value)| value.downcast_ref())
+        .expect("the members match the type of the group")
+}
+
+/// Implemented by the member list `L` of an [`RcuGroup<L>`] for every type `M` it holds, `I` being
+/// where in the list `M` is, which the compiler infers: using a type that isn't a member is a
+/// compile error. Lists are built by [`RcuGroup::with`], `(M, L)` adding `M` to `L`.
+pub trait HasMember<M, I> {}
+
+/// `M` is first in the list, see [`HasMember`].
+pub struct Here;
+
+/// `M` is somewhere after the first member of the list, see [`HasMember`].
+pub struct There<I>(PhantomData<I>);
+
+impl<M, L> HasMember<M, Here> for (M, L) {}
+
+impl<M, N, L: HasMember<M, I>, I> HasMember<M, There<I>> for (N, L) {}
+
+/// Several values of distinct types published together: a [`RcuGroup::transaction`] changing
+/// any number of them becomes visible in a single publication, so a reader of the group never
+/// sees some members from before a transaction and others from after it.
+///
+/// Members are identified by their type, each type can be a member at most once. The types of the
+/// members are part of the type of the group, `L`, so reading or setting a type that isn't a member
+/// doesn't compile:
+///
+/// ```compile_fail
+/// # use rcu_rust::RcuGroup;
+/// #[derive(Clone)]
+/// struct Routes(Vec<String>);
+/// #[derive(Clone)]
+/// struct Weights(Vec<u32>);
+///
+/// let group = RcuGroup::new().with(Routes(Vec::new()));
+/// group.transaction(|tx| tx.set(Weights(vec![1])));
+/// ```
+///
+/// Where the member is in `L` is inferred, only its type has to be named, e.g.
+/// `group.handle::<Routes, _>()`.
+pub struct RcuGroup<L = ()> {
+    members: Rcu<Vec<Member>>,
+    _members: PhantomData<fn() -> L>,
+}
+
+impl RcuGroup {
+    /// Creates a group without any members, add them with [`RcuGroup::with`].
+    pub fn new() -> Self {
+        Self {
+            members: Rcu::new(Vec::new()),
+            _members: PhantomData,
+        }
+    }
+}
+
+impl<L> RcuGroup<L> {
+    /// Adds `value` as a member of the group.
+    ///
+    /// # Panics
+    /// If a value of type `M` is already a member.
+    pub fn with<M: Any + Send + Sync>(self, value: M) -> RcuGroup<(M, L)> {
+        let mut members = self.members.read();
+        assert!(
+            members.iter().all(|(id, _)| *id != TypeId::of::<M>()),
+            "`{}` is already a member of this group",
+            type_name::<M>()
+        );
+        members.push((TypeId::of::<M>(), Arc::new(value)));
+        let _ = self.members.set(members);
+        RcuGroup {
+            members: self.members,
+            _members: PhantomData,
+        }
+    }
+    /// A handle for reading the member of type `M` on its own.
+    pub fn handle<M: Any + Send + Sync + Clone, I>(&self) -> GroupHandle<'_, M>
+    where
+        L: HasMember<M, I>,
+    {
+        GroupHandle {
+            members: &self.members,
+            _member: PhantomData,
+        }
+    }
+    /// All members as of a single publication, for readers that need several of them to be consistent.
+    pub fn snapshot(&self) -> GroupSnapshot<L> {
+        GroupSnapshot {
+            members: self.members.read(),
+            _members: PhantomData,
+        }
+    }
+    /// Runs `f` against a copy of the current members and publishes every change it makes at once.
+    /// Returns what `f` returned for the copy that was published.
+    ///
+    /// If another transaction publishes first, `f` is re-run from scratch against the members that
+    /// transaction published, so `f` can be called several times and should only derive its changes
+    /// from what it reads through the [`Transaction`]. Transactions are therefore serialized: none of
+    /// them is lost, and none is merged with another one.
+    pub fn transaction<R>(&self, mut f: impl FnMut(&mut Transaction<L>) -> R) -> R {
+        let mut result = None;
+        let _ = self.members.update_with(|members| {
+            let mut tx = Transaction {
+                members: members.clone(),
+                _members: PhantomData,
+            };
+            result = Some(f(&mut tx));
+            tx.members
+        });
+        // No invariants are ever installed on `self.members`, so the update always succeeds
+        result.unwrap()
+    }
+}
+
+impl Default for RcuGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads one member of a [`RcuGroup`], created with [`RcuGroup::handle`].
+pub struct GroupHandle<'a, M> {
+    members: &'a Rcu<Vec<Member>>,
+    _member: PhantomData<fn() -> M>,
+}
+
+impl<M: Any + Send + Sync + Clone> GroupHandle<'_, M> {
+    /// Returns a clone of the current value of the member, without cloning any other member.
+    pub fn read(&self) -> M {
+        self.members.read_with(|members| find::<M>(members).clone())
+    }
+}
+
+/// The members of a [`RcuGroup`] as of a single publication.
+pub struct GroupSnapshot<L = ()> {
+    members: Vec<Member>,
+    _members: PhantomData<fn() -> L>,
+}
+
+impl<L> GroupSnapshot<L> {
+    /// The member of type `M`.
+    pub fn get<M: Any, I>(&self) -> &M
+    where
+        L: HasMember<M, I>,
+    {
+        find(&self.members)
+    }
+}
+
+/// The staged members of a [`RcuGroup::transaction`].
+pub struct Transaction<L = ()> {
+    members: Vec<Member>,
+    _members: PhantomData<fn() -> L>,
+}
+
+impl<L> Transaction<L> {
+    /// The member of type `M` including any change made so far by this transaction.
+    pub fn get<M: Any, I>(&self) -> &M
+    where
+        L: HasMember<M, I>,
+    {
+        find(&self.members)
+    }
+    /// Replaces the member of type `M`.
+    pub fn set<M: Any + Send + Sync, I>(&mut self, value: M)
+    where
+        L: HasMember<M, I>,
+    {
+        let slot = self
+            .members
+            .iter_mut()
+            .find(|(id, _)| *id == TypeId::of::<M>())
+            .expect("the members match the type of the group");
+        slot.1 = Arc::new(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use std::thread;
+
+    use super::RcuGroup;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Routes(Vec<String>);
+    #[derive(Clone, Debug, PartialEq)]
+    struct Weights(Vec<u32>);
+    #[derive(Clone, Debug, PartialEq)]
+    struct Generation(u64);
+
+    type Members = (Generation, (Weights, (Routes, ())));
+
+    fn group() -> RcuGroup<Members> {
+        RcuGroup::new()
+            .with(Routes(Vec::new()))
+            .with(Weights(Vec::new()))
+            .with(Generation(0))
+    }
+
+    #[test]
+    fn handles_read_their_member() {
+        let group = group();
+        let routes = group.handle::<Routes, _>();
+        let generation = group.handle::<Generation, _>();
+        group.transaction(|tx| {
+            tx.set(Routes(vec!["/".into()]));
+            let next = tx.get::<Generation, _>().0 + 1;
+            tx.set(Generation(next));
+        });
+        assert_eq!(routes.read(), Routes(vec!["/".into()]));
+        assert_eq!(generation.read(), Generation(1));
+        assert_eq!(group.handle::<Weights, _>().read(), Weights(Vec::new()));
+    }
+
+    #[test]
+    fn transactions_stage_their_changes() {
+        let group = group();
+        let staged = group.transaction(|tx| {
+            tx.set(Weights(vec![3]));
+            tx.get::<Weights, _>().clone()
+        });
+        assert_eq!(staged, Weights(vec![3]));
+        assert_eq!(*group.snapshot().get::<Weights, _>(), staged);
+    }
+
+    #[test]
+    #[should_panic(expected = "already a member")]
+    fn members_are_unique() {
+        let _ = RcuGroup::new().with(Generation(0)).with(Routes(Vec::new())).with(Generation(1));
+    }
+
+    #[test]
+    fn snapshots_are_never_torn() {
+        let group = group();
+        let stop = AtomicBool::new(false);
+        thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| {
+                    while !stop.load(SeqCst) {
+                        let snapshot = group.snapshot();
+                        let Generation(generation) = *snapshot.get::<Generation, _>();
+                        let routes = &snapshot.get::<Routes, _>().0;
+                        let weights = &snapshot.get::<Weights, _>().0;
+                        assert_eq!(routes.len() as u64, generation, "routes from another transaction");
+                        assert_eq!(weights.len() as u64, generation, "weights from another transaction");
+                    }
+                });
+            }
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..200 {
+                        group.transaction(|tx| {
+                            let Generation(generation) = *tx.get::<Generation, _>();
+                            let mut routes = tx.get::<Routes, _>().clone();
+                            routes.0.push(format!("/{generation}"));
+                            let mut weights = tx.get::<Weights, _>().clone();
+                            weights.0.push(1);
+                            tx.set(routes);
+                            tx.set(weights);
+                            tx.set(Generation(generation + 1));
+                        });
+                    }
+                });
+            }
+            s.spawn(|| {
+                while group.handle::<Generation, _>().read().0 < 400 {
+                    thread::yield_now();
+                }
+                stop.store(true, SeqCst);
+            });
+        });
+        // Transactions are serialized, none of them is lost
+        assert_eq!(group.handle::<Generation, _>().read(), Generation(400));
+    }
+}
@@ -0,0 +1,341 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::thread;
+
+use crate::raw::RawReadGuard;
+use crate::{CommitError, PublishError, Rcu};
+
+/// A clone-free read of an `Rcu`, created with [`Rcu::read_guard`]. Derefs to the value that was
+/// current when it was created, which stays alive until the guard is dropped however many
+/// publishes happen meanwhile.
+///
+/// The guard holds a read-side critical section: with the `reclaim-counted` engine publishers
+/// wait for it to be dropped, and with the others the values they displace aren't freed before it
+/// is. Keep it short-lived, and never publish to the same `Rcu` while holding one. It must be
+/// dropped on the thread that created it, so it is neither `Send` nor `Sync`.
+pub struct RcuReadGuard<'a, T> {
+    raw: RawReadGuard<'a, T>,
+}
+
+impl<T> Deref for RcuReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.raw
+    }
+}
+
+impl<'a, T> RcuReadGuard<'a, T> {
+    /// Narrows the guard to the part of the value `f` returns, e.g. a field, for handing out a
+    /// borrow of it without exposing the whole value. The mapped guard holds the same read-side
+    /// critical section, with the same consequences, until it is dropped in turn; should `f`
+    /// panic, the critical section is exited as the guard is dropped.
+    ///
+    /// ```
+    /// # use std::ops::Deref;
+    /// # use rcu_rust::Rcu;
+    /// # type RouteTable = Vec<String>;
+    /// #[derive(Clone)]
+    /// struct Config {
+    ///     routes: RouteTable,
+    /// }
+    ///
+    /// struct Server {
+    ///     config: Rcu<Config>,
+    /// }
+    ///
+    /// impl Server {
+    ///     fn routes(&self) -> impl Deref<Target = RouteTable> + '_ {
+    ///         self.config.read_guard().map(|config| &config.routes)
+    ///     }
+    /// }
+    ///
+    /// let server = Server { config: Rcu::new(Config { routes: vec!["/".into()] }) };
+    /// assert_eq!(server.routes().len(), 1);
+    /// ```
+    pub fn map<U: ?Sized>(self, f: impl FnOnce(&T) -> &U) -> MappedRcuReadGuard<'a, U> {
+        match self.try_map(|value| Some(f(value))) {
+            Ok(mapped) => mapped,
+            Err(_) => unreachable!("the projection always returns a value"),
+        }
+    }
+    /// Like [`RcuReadGuard::map`] for a part of the value which may not be there, handing the
+    /// guard back if `f` returns None.
+    pub fn try_map<U: ?Sized>(self, f: impl FnOnce(&T) -> Option<&U>) -> Result<MappedRcuReadGuard<'a, U>, Self> {
+        match self.raw.try_map(f) {
+            Ok(raw) => Ok(MappedRcuReadGuard { raw }),
+            Err(raw) => Err(Self { raw }),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RcuReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A part of a value read with [`Rcu::read_guard`], created with [`RcuReadGuard::map`]. Holds
+/// the read-side critical section of the guard it was mapped from, see [`RcuReadGuard`].
+pub struct MappedRcuReadGuard<'a, U: ?Sized> {
+    raw: RawReadGuard<'a, U>,
+}
+
+impl<U: ?Sized> Deref for MappedRcuReadGuard<'_, U> {
+    type Target = U;
+
+    fn deref(&self) -> &U {
+        &self.raw
+    }
+}
+
+impl<U: ?Sized + fmt::Debug> fmt::Debug for MappedRcuReadGuard<'_, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<'a, U: ?Sized> MappedRcuReadGuard<'a, U> {
+    /// Narrows the guard further, see [`RcuReadGuard::map`].
+    pub fn map<V: ?Sized>(self, f: impl FnOnce(&U) -> &V) -> MappedRcuReadGuard<'a, V> {
+        match self.try_map(|value| Some(f(value))) {
+            Ok(mapped) => mapped,
+            Err(_) => unreachable!("the projection always returns a value"),
+        }
+    }
+    /// Narrows the guard further, see [`RcuReadGuard::try_map`].
+    pub fn try_map<V: ?Sized>(self, f: impl FnOnce(&U) -> Option<&V>) -> Result<MappedRcuReadGuard<'a, V>, Self> {
+        match self.raw.try_map(f) {
+            Ok(raw) => Ok(MappedRcuReadGuard { raw }),
+            Err(raw) => Err(Self { raw }),
+        }
+    }
+}
+
+impl<T: Clone> Rcu<T> {
+    /// Reads the current value without cloning it, see [`RcuReadGuard`]. Like [`Rcu::read_with`],
+    /// for reads that don't fit in a closure.
+    #[track_caller]
+    pub fn read_guard(&self) -> RcuReadGuard<'_, T> {
+        RcuReadGuard {
+            raw: self.raw.read_guard(),
+        }
+    }
+}
+
+/// A staged copy of the value of an `Rcu`, created with [`Rcu::write_guard`]: derefs mutably to a
+/// clone of the value current when it was created, published when the guard is dropped.
+///
+/// Dropping it publishes like [`Rcu::set`], over whatever was published meanwhile, refusals
+/// included silently. [`RcuWriteGuard::commit`] does the same, reporting refusals, and
+/// [`RcuWriteGuard::try_commit`] only publishes if nothing was published since the guard was
+/// created. [`RcuWriteGuard::abort`] discards the staged copy, and so does dropping the guard while
+/// panicking: a change interrupted halfway never ships.
+///
+/// The guard holds no read-side critical section, nor does it keep other writers out, so it may be
+/// held for as long as the change takes.
+pub struct RcuWriteGuard<'a, T: Clone> {
+    rcu: &'a Rcu<T>,
+    /// None once committed or aborted
+    staged: Option<T>,
+    /// The address of the value the staged copy was cloned from
+    base: usize,
+    /// The version that value was published as
+    version: u64,
+}
+
+impl<T: Clone> Deref for RcuWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.staged.as_ref().expect("only taken by consuming the guard")
+    }
+}
+
+impl<T: Clone> DerefMut for RcuWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.staged.as_mut().expect("only taken by consuming the guard")
+    }
+}
+
+impl<T: Clone + fmt::Debug> fmt::Debug for RcuWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: Clone> RcuWriteGuard<'_, T> {
+    /// The version of the value the staged copy was cloned from.
+    pub fn base_version(&self) -> u64 {
+        self.version
+    }
+    /// Discards the staged copy without publishing it.
+    pub fn abort(mut self) {
+        self.staged = None;
+    }
+    /// Publishes the staged copy like [`Rcu::set`], over whatever was published since it was
+    /// staged.
+    #[track_caller]
+    pub fn commit(mut self) -> Result<(), PublishError<T>> {
+        let staged = self.staged.take().expect("only taken by consuming the guard");
+        self.rcu.set(staged)
+    }
+    /// Publishes the staged copy in a single attempt, only if nothing was published since it was
+    /// staged, returning the version it was published as. Otherwise hands it back along with the
+    /// current value and its version, see [`CommitConflict`](crate::CommitConflict), for the
+    /// caller to decide whether to apply the change to that one instead.
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use rcu_rust::{CommitError, Rcu};
+    /// # fn retry_on(_: HashMap<&str, &str>) {}
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let rcu = Rcu::new(HashMap::new());
+    /// let (path, backend) = ("/", "10.0.0.1");
+    /// let mut routes = rcu.write_guard();
+    /// routes.insert(path, backend);
+    /// match routes.try_commit() {
+    ///     Ok(version) => println!("published version {version}"),
+    ///     Err(CommitError::Conflict(conflict)) => retry_on(conflict.current),
+    ///     Err(CommitError::Rejected(err)) => return Err(err.to_string().into()),
+    /// }
+    /// assert_eq!(rcu.read()[path], backend);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn try_commit(mut self) -> Result<u64, CommitError<T>> {
+        let staged = self.staged.take().expect("only taken by consuming the guard");
+        self.rcu.publish_if_current(self.base, self.version, staged)
+    }
+}
+
+impl<T: Clone> Drop for RcuWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        // A panic may have left the staged copy halfway through its change
+        if thread::panicking() {
+            return;
+        }
+        if let Some(staged) = self.staged.take() {
+            let _ = self.rcu.set(staged);
+        }
+    }
+}
+
+impl<T: Clone> Rcu<T> {
+    /// Stages a clone of the current value to be changed in place and published, see
+    /// [`RcuWriteGuard`].
+    #[track_caller]
+    pub fn write_guard(&self) -> RcuWriteGuard<'_, T> {
+        let (staged, base, version) =
+            self.raw.read_versioned(|value, version| (value.clone(), value as *const T as usize, version));
+        RcuWriteGuard {
+            rcu: self,
+            staged: Some(staged),
+            base,
+            version,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::{CommitConflict, CommitError, Rcu};
+
+    /// Logs its name when dropped.
+    #[derive(Clone)]
+    struct Config {
+        name: String,
+        dropped: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Drop for Config {
+        fn drop(&mut self) {
+            self.dropped.lock().unwrap().push(self.name.clone());
+        }
+    }
+
+    #[test]
+    fn a_mapped_guard_outlives_publishes_displacing_its_value() {
+        let dropped = Arc::new(Mutex::new(Vec::new()));
+        let config = |name: &str| Config {
+            name: name.into(),
+            dropped: Arc::clone(&dropped),
+        };
+        let rcu = Rcu::new(config("a"));
+        let name = rcu.read_guard().map(|config| config.name.as_str());
+        let (published, wait_published) = mpsc::channel();
+        thread::scope(|s| {
+            s.spawn(|| {
+                // "a" is kept as the previous value by the first publish, and retired by the second
+                for next in ["b", "c"] {
+                    rcu.set(config(next)).ok().unwrap();
+                    published.send(next).unwrap();
+                }
+            });
+            assert_eq!(wait_published.recv().unwrap(), "b");
+            // Engines waiting for readers in-line keep the second publish waiting for the guard
+            let _ = wait_published.recv_timeout(Duration::from_millis(50));
+            assert_eq!(&*name, "a");
+            assert!(dropped.lock().unwrap().is_empty(), "freed under the mapped guard");
+            drop(name);
+        });
+        rcu.barrier();
+        assert_eq!(*dropped.lock().unwrap(), ["a"]);
+        assert_eq!(&*rcu.read_guard().map(|config| &config.name), "c");
+    }
+
+    #[test]
+    fn a_write_guard_dropped_while_panicking_publishes_nothing() {
+        let rcu = Rcu::new(vec![1, 2, 3]);
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut staged = rcu.write_guard();
+            staged.clear();
+            panic!("interrupted halfway through the change");
+        }));
+        assert!(panicked.is_err());
+        assert_eq!((rcu.read(), rcu.version()), (vec![1, 2, 3], 0));
+
+        let mut staged = rcu.write_guard();
+        staged.push(4);
+        staged.abort();
+        assert_eq!((rcu.read(), rcu.version()), (vec![1, 2, 3], 0));
+        let mut staged = rcu.write_guard();
+        staged.push(4);
+        drop(staged);
+        assert_eq!((rcu.read(), rcu.version()), (vec![1, 2, 3, 4], 1));
+    }
+
+    #[test]
+    fn try_commit_reports_the_publish_it_lost_to() {
+        let rcu = Rcu::new(vec![1]);
+        let mut staged = rcu.write_guard();
+        staged.push(2);
+        assert_eq!(staged.base_version(), 0);
+        thread::scope(|s| {
+            s.spawn(|| rcu.set(vec![10]).unwrap());
+        });
+        let Err(CommitError::Conflict(conflict)) = staged.try_commit() else {
+            panic!("committed over another writer's publish");
+        };
+        assert_eq!(
+            conflict,
+            CommitConflict {
+                staged: vec![1, 2],
+                current: vec![10],
+                version: 1,
+            }
+        );
+        assert_eq!((rcu.read(), rcu.version()), (vec![10], 1));
+
+        let mut rebased = rcu.write_guard();
+        rebased.extend(&conflict.staged[1..]);
+        assert_eq!(rebased.try_commit().ok(), Some(2));
+        assert_eq!(rcu.read(), [10, 2]);
+    }
+}
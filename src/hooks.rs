@@ -0,0 +1,182 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::sync::Arc;
+
+use crate::raw::RawRcu;
+
+/// Identifies a hook registered with [`Rcu::on_update`](crate::Rcu::on_update), used to remove it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HookId(u64);
+
+/// Called with a published value and the version it was published as.
+type HookFn<T> = dyn Fn(&T, u64) + Send + Sync;
+
+struct Hook<T> {
+    id: HookId,
+    f: Arc<HookFn<T>>,
+}
+
+impl<T> Clone for Hook<T> {
+    fn clone(&self) -> Self {
+        Self { id: self.id, f: Arc::clone(&self.f) }
+    }
+}
+
+/// The hooks registered on an `Rcu`, itself published through RCU so that running the hooks
+/// never blocks threads registering or removing them.
+pub(crate) struct HookList<T> {
+    hooks: RawRcu<Vec<Hook<T>>>,
+    next_id: AtomicU64,
+}
+
+impl<T> HookList<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            hooks: RawRcu::new(Box::default()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn add(&self, f: impl Fn(&T) + Send + Sync + 'static) -> HookId {
+        self.add_versioned(move |value, _| f(value))
+    }
+
+    /// Like `add`, `f` also getting the version the value was published as.
+    pub(crate) fn add_versioned(&self, f: impl Fn(&T, u64) + Send + Sync + 'static) -> HookId {
+        let id = HookId(self.next_id.fetch_add(1, Relaxed));
+        let f: Arc<HookFn<T>> = Arc::new(f);
+        self.hooks.modify(|hooks| {
+            let mut hooks = hooks.clone();
+            hooks.push(Hook { id, f: Arc::clone(&f) });
+            hooks
+        });
+        id
+    }
+
+    pub(crate) fn remove(&self, id: HookId) -> bool {
+        let mut removed = false;
+        self.hooks.modify(|hooks| {
+            let mut hooks = hooks.clone();
+            let len = hooks.len();
+            hooks.retain(|hook| hook.id != id);
+            removed = hooks.len() != len;
+            hooks
+        });
+        removed
+    }
+
+    /// Calls every registered hook with `value`, published as `version`, containing any panics so
+    /// that one failing hook neither skips the others nor unwinds into the publish.
+    pub(crate) fn run(&self, value: &T, version: u64) {
+        // Work on our own copy of the list, a hook is allowed to add or remove hooks, which
+        // must not wait on us reading the list
+        let hooks = self.hooks.read_with(|hooks| hooks.clone());
+        for hook in hooks {
+            // The panic is still reported by the panic hook, it just doesn't propagate
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| (hook.f)(value, version)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    use std::sync::{Arc, Mutex};
+
+    use crate::Rcu;
+
+    #[test]
+    fn hooks_see_every_publish_in_registration_order() {
+        let rcu = Rcu::new(0);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        for hook in 0..3 {
+            let seen = Arc::clone(&seen);
+            rcu.on_update(move |&value| seen.lock().unwrap().push((hook, value)));
+        }
+        rcu.set(1).unwrap();
+        assert!(rcu.update(2));
+        assert_eq!(*seen.lock().unwrap(), [(0, 1), (1, 1), (2, 1), (0, 2), (1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn removed_hooks_are_not_called() {
+        let rcu = Rcu::new(0);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let id = rcu.on_update({
+            let calls = Arc::clone(&calls);
+            move |_| {
+                calls.fetch_add(1, SeqCst);
+            }
+        });
+        rcu.set(1).unwrap();
+        assert!(rcu.remove_hook(id));
+        assert!(!rcu.remove_hook(id));
+        rcu.set(2).unwrap();
+        assert_eq!(calls.load(SeqCst), 1);
+    }
+
+    #[test]
+    fn a_hook_updating_its_own_rcu_gets_false() {
+        let rcu = Arc::new(Rcu::new(0));
+        let reentrant = Arc::new(Mutex::new(Vec::new()));
+        rcu.on_update({
+            // A weak reference, the hook would keep its own `Rcu` alive otherwise
+            let rcu = Arc::downgrade(&rcu);
+            let reentrant = Arc::clone(&reentrant);
+            move |&value| {
+                let rcu = rcu.upgrade().unwrap();
+                reentrant.lock().unwrap().push(rcu.update(value + 100));
+            }
+        });
+        rcu.set(1).unwrap();
+        assert_eq!(*reentrant.lock().unwrap(), [false]);
+        assert_eq!(rcu.read(), 1);
+        assert_eq!(rcu.version(), 1);
+    }
+
+    #[test]
+    fn hooks_may_add_and_remove_hooks() {
+        let rcu = Arc::new(Rcu::new(0));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let ids = Arc::new(Mutex::new(Vec::new()));
+        let id = rcu.on_update({
+            let rcu = Arc::downgrade(&rcu);
+            let calls = Arc::clone(&calls);
+            let ids = Arc::clone(&ids);
+            move |_| {
+                let rcu = rcu.upgrade().unwrap();
+                let calls = Arc::clone(&calls);
+                ids.lock().unwrap().push(rcu.on_update(move |_| {
+                    calls.fetch_add(1, SeqCst);
+                }));
+            }
+        });
+        rcu.set(1).unwrap();
+        // Registered by the publish, only called from the next one on
+        assert_eq!(calls.load(SeqCst), 0);
+        assert!(rcu.remove_hook(id));
+        rcu.set(2).unwrap();
+        assert_eq!(calls.load(SeqCst), 1);
+        assert_eq!(ids.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn a_panicking_hook_is_contained() {
+        let rcu = Rcu::new(0);
+        let after = Arc::new(AtomicUsize::new(0));
+        rcu.on_update(|_| panic!("hook failed"));
+        rcu.on_update({
+            let after = Arc::clone(&after);
+            move |_| {
+                after.fetch_add(1, SeqCst);
+            }
+        });
+        assert!(rcu.set(1).is_ok());
+        assert!(rcu.update(2));
+        assert_eq!(after.load(SeqCst), 2, "the hook after the panicking one was skipped");
+        assert_eq!(rcu.read(), 2);
+        assert_eq!(rcu.version(), 2);
+        rcu.update_with(|n| n + 1).unwrap();
+        assert_eq!(rcu.read(), 3);
+    }
+}
@@ -0,0 +1,214 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, PoisonError};
+
+use crate::raw::RawRcu;
+
+/// A string interned by an [`RcuInterner`], resolved with [`RcuInterner::resolve`]. Symbols of one
+/// interner are numbered from 0 in the order their strings were first interned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// The number of strings interned before this one.
+    pub fn index(self) -> u32 {
+        self.0
+    }
+}
+
+/// A string of the arena, which never moves nor is freed before the interner is dropped.
+#[derive(Clone, Copy)]
+struct Interned(*const str);
+
+impl Interned {
+    fn as_str(&self) -> &str {
+        // Safety: the arena keeps every string alive, unchanged, for as long as the interner lives,
+        // and indexes are only ever read through it
+        unsafe { &*self.0 }
+    }
+}
+
+impl PartialEq for Interned {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Interned {}
+
+impl Hash for Interned {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl Borrow<str> for Interned {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+// Safety: the strings pointed to are never written to once in the arena
+unsafe impl Send for Interned {}
+unsafe impl Sync for Interned {}
+
+/// One published version of the interned strings, both ways.
+#[derive(Clone, Default)]
+struct Index {
+    symbols: HashMap<Interned, u32>,
+    /// By symbol
+    strings: Vec<Interned>,
+}
+
+/// A string interner for strings which are looked up far more often than new ones are interned.
+/// Lookups both ways are wait-free RCU reads of the current index, which maps strings to their
+/// [`Symbol`] and back.
+///
+/// Strings are copied once into an append-only arena which only frees them when the interner is
+/// dropped: a resolved `&str` stays valid for as long as the interner does, whatever is interned
+/// meanwhile. Interning a new string copies the index and publishes the copy, so building an
+/// interner string by string costs quadratic time, [`intern_all`](Self::intern_all) publishes once
+/// for many strings instead.
+pub struct RcuInterner {
+    index: RawRcu<Index>,
+    /// Owns the strings of the index, serializes publishes of it
+    arena: Mutex<Vec<Box<str>>>,
+}
+
+impl Default for RcuInterner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RcuInterner {
+    /// Creates an interner without any strings.
+    pub fn new() -> Self {
+        Self {
+            index: RawRcu::new(Box::default()),
+            arena: Mutex::default(),
+        }
+    }
+    /// The symbol of `string`, if it was interned.
+    pub fn get(&self, string: &str) -> Option<Symbol> {
+        self.index.read_with(|index| index.symbols.get(string).copied().map(Symbol))
+    }
+    /// The string `symbol` was handed out for.
+    ///
+    /// # Panics
+    /// If `symbol` wasn't handed out by this interner. A symbol of another interner may also
+    /// resolve to an unrelated string of this one.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        let interned = self.index.read_with(|index| index.strings.get(symbol.0 as usize).copied());
+        let interned = interned.expect("symbol of another interner");
+        // Safety: the arena keeps the string alive for as long as `self` is borrowed
+        unsafe { &*interned.0 }
+    }
+    /// The symbol of `string`, interning it first if it wasn't already. Threads interning the same
+    /// string concurrently all get the same symbol.
+    pub fn intern(&self, string: &str) -> Symbol {
+        self.get(string).unwrap_or_else(|| self.intern_all([string])[0])
+    }
+    /// The symbols of `strings`, interning those that weren't already with a single publish.
+    pub fn intern_all<'s>(&self, strings: impl IntoIterator<Item = &'s str>) -> Vec<Symbol> {
+        let mut arena = self.arena.lock().unwrap_or_else(PoisonError::into_inner);
+        // Only interns publish, and they hold `arena`, so the published index is the latest one;
+        // the copy to publish is only made once a string is new
+        let mut grown: Option<Index> = None;
+        let mut symbols = Vec::new();
+        for string in strings {
+            let found = match &grown {
+                Some(index) => index.symbols.get(string).copied(),
+                None => self.index.read_with(|index| index.symbols.get(string).copied()),
+            };
+            if let Some(symbol) = found {
+                symbols.push(Symbol(symbol));
+                continue;
+            }
+            let index = grown.get_or_insert_with(|| self.index.read_with(Index::clone));
+            let symbol = u32::try_from(index.strings.len()).expect("more than u32::MAX interned strings");
+            let owned: Box<str> = string.into();
+            let interned = Interned(&*owned);
+            arena.push(owned);
+            index.symbols.insert(interned, symbol);
+            index.strings.push(interned);
+            symbols.push(Symbol(symbol));
+        }
+        if let Some(index) = grown {
+            // Safety: only interns publish, and they are serialized by `self.arena`
+            unsafe { self.index.publish_exclusive(Box::new(index), |_| {}) };
+        }
+        symbols
+    }
+    /// The number of interned strings.
+    pub fn len(&self) -> usize {
+        self.index.read_with(|index| index.strings.len())
+    }
+    /// Whether no string was interned yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Barrier;
+    use std::thread;
+
+    use super::*;
+
+    const THREADS: usize = 8;
+    const STRINGS: usize = 100;
+
+    #[test]
+    fn racing_interns_of_a_string_agree_on_its_symbol() {
+        let interner = RcuInterner::new();
+        let strings: Vec<_> = (0..STRINGS).map(|i| format!("string {i}")).collect();
+        let start = Barrier::new(THREADS);
+        let seen: Vec<Vec<Symbol>> = thread::scope(|s| {
+            let threads: Vec<_> = (0..THREADS)
+                .map(|id| {
+                    let (interner, strings, start) = (&interner, &strings, &start);
+                    s.spawn(move || {
+                        start.wait();
+                        // Each starts somewhere else, half of them interning several at once
+                        let mut symbols: Vec<_> = (0..STRINGS)
+                            .map(|i| (i + id * 13) % STRINGS)
+                            .map(|i| {
+                                let symbol = if id.is_multiple_of(2) {
+                                    interner.intern(&strings[i])
+                                } else {
+                                    interner.intern_all([&*strings[i], &*strings[(i + 1) % STRINGS]])[0]
+                                };
+                                thread::yield_now();
+                                (i, symbol)
+                            })
+                            .collect();
+                        symbols.sort_unstable();
+                        symbols.into_iter().map(|(_, symbol)| symbol).collect()
+                    })
+                })
+                .collect();
+            threads.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        assert_eq!(interner.len(), STRINGS, "a string was interned twice");
+        for symbols in &seen[1..] {
+            assert_eq!(symbols, &seen[0]);
+        }
+        for (string, &symbol) in strings.iter().zip(&seen[0]) {
+            assert_eq!(interner.resolve(symbol), string);
+            assert_eq!(interner.get(string), Some(symbol));
+        }
+    }
+
+    #[test]
+    fn resolved_strings_outlive_later_interns() {
+        let interner = RcuInterner::new();
+        let first = interner.resolve(interner.intern("first"));
+        let numbers: Vec<_> = (0..1000).map(|i| i.to_string()).collect();
+        interner.intern_all(numbers.iter().map(String::as_str));
+        assert_eq!(first, "first");
+        assert_eq!(interner.intern("first"), Symbol(0));
+    }
+}
@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use crate::error::PublishError;
+use crate::raw::RawRcu;
+
+type Check<T> = Arc<dyn Fn(&T) -> Result<(), String> + Send + Sync>;
+
+/// The invariants installed on an `Rcu`. Published through RCU like the hook list, so checking
+/// them never blocks a thread installing a new one.
+pub(crate) struct Invariants<T> {
+    checks: RawRcu<Vec<Check<T>>>,
+}
+
+impl<T> Invariants<T> {
+    pub(crate) fn new() -> Self {
+        Self { checks: RawRcu::new(Box::default()) }
+    }
+
+    pub(crate) fn add(&self, f: impl Fn(&T) -> Result<(), String> + Send + Sync + 'static) {
+        let f: Check<T> = Arc::new(f);
+        self.checks.modify(|checks| {
+            let mut checks = checks.clone();
+            checks.push(Arc::clone(&f));
+            checks
+        });
+    }
+
+    /// Whether no invariant was ever installed.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.checks.read_with(Vec::is_empty)
+    }
+
+    /// Runs every invariant against `value` in installation order, stopping at the first one
+    /// that refuses it.
+    pub(crate) fn check_ref(&self, value: &T) -> Result<(), String> {
+        // Like hooks, an invariant must not be able to wedge threads installing new ones
+        let checks = self.checks.read_with(|checks| checks.clone());
+        checks.iter().try_for_each(|check| check(value))
+    }
+
+    /// Like `check_ref`, but takes ownership of `value` to hand it back inside the rejection.
+    pub(crate) fn check(&self, value: T) -> Result<T, PublishError<T>> {
+        match self.check_ref(&value) {
+            Ok(()) => Ok(value),
+            Err(reason) => Err(PublishError::Rejected { reason, value }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::{PublishError, Rcu, SpinYield};
+
+    fn even(n: &i64) -> Result<(), String> {
+        if n % 2 == 0 {
+            Ok(())
+        } else {
+            Err(format!("{n} is odd"))
+        }
+    }
+
+    #[test]
+    fn invariants_compose_in_installation_order() {
+        let rcu = Rcu::new(0i64);
+        rcu.set_invariant(even);
+        rcu.set_invariant(|n| if *n >= 0 { Ok(()) } else { Err("negative".into()) });
+        assert!(matches!(rcu.set(3), Err(PublishError::Rejected { reason, value: 3 }) if reason == "3 is odd"));
+        assert!(matches!(rcu.set(-2), Err(PublishError::Rejected { reason, value: -2 }) if reason == "negative"));
+        assert!(rcu.set(2).is_ok());
+        assert_eq!(rcu.read(), 2);
+        assert_eq!(rcu.version(), 1);
+    }
+
+    #[test]
+    fn every_publish_path_checks_invariants() {
+        let rcu = Rcu::new(0i64);
+        rcu.set_invariant(even);
+        assert!(!rcu.update(1));
+        assert!(rcu.update_box(Box::new(1)).is_err());
+        assert!(rcu.set(1).is_err());
+        assert!(rcu.update_with(|n| n + 1).is_err());
+        assert!(rcu.update_batch([|n: &mut i64| *n += 1]).is_err());
+        assert_eq!(rcu.update_or_merge(1, |_, mine| mine), 0);
+        assert!(rcu.update_with_deadline(1, Duration::from_secs(1)).is_err());
+        let mut guard = rcu.write_guard();
+        *guard = 1;
+        assert!(guard.commit().is_err());
+        let mut guard = rcu.write_guard();
+        *guard = 1;
+        assert!(guard.try_commit().is_err());
+        assert_eq!(rcu.read(), 0);
+        assert_eq!(rcu.version(), 0);
+    }
+
+    #[test]
+    fn rejected_values_are_never_visible() {
+        let rcu = Rcu::new(0i64).with_wait_strategy(SpinYield::default());
+        rcu.set_invariant(even);
+        let published = Arc::new(AtomicUsize::new(0));
+        rcu.on_update({
+            let published = Arc::clone(&published);
+            move |n| {
+                assert_eq!(n % 2, 0, "a hook saw a rejected value");
+                published.fetch_add(1, SeqCst);
+            }
+        });
+        let stop = AtomicBool::new(false);
+        let accepted = thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| {
+                    let subscriber = rcu.subscribe();
+                    while !stop.load(SeqCst) {
+                        let (n, version) = rcu.read_versioned();
+                        assert_eq!(n % 2, 0, "a reader saw a rejected value");
+                        assert_eq!(version, n as u64 / 2, "a rejected publish bumped the version");
+                        assert_eq!(subscriber.read() % 2, 0);
+                    }
+                });
+            }
+            let writers: Vec<_> = (0..2)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut accepted = 0;
+                        for _ in 0..500 {
+                            // Every accepted publish adds 2, every rejected one would add 1
+                            accepted += rcu.update_with(|n| n + 2).is_ok() as u64;
+                            assert!(rcu.update_with(|n| n + 1).is_err());
+                            assert!(!rcu.update(rcu.read() + 1));
+                        }
+                        accepted
+                    })
+                })
+                .collect();
+            let accepted = writers.into_iter().map(|writer| writer.join().unwrap()).sum::<u64>();
+            stop.store(true, SeqCst);
+            accepted
+        });
+        assert_eq!(accepted, 1000);
+        assert_eq!(rcu.read(), 2000);
+        assert_eq!(rcu.version(), 1000);
+        assert_eq!(published.load(SeqCst), 1000);
+    }
+}
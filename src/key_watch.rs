@@ -0,0 +1,305 @@
+use std::any::Any;
+use std::collections::HashMap;
+#[cfg(feature = "async")]
+use std::future;
+use std::hash::{BuildHasher, Hash};
+use std::sync::atomic::{AtomicU64, Ordering::SeqCst};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+#[cfg(feature = "async")]
+use std::task::Poll;
+use std::time::Duration;
+
+use crate::notify::Notify;
+#[cfg(feature = "async")]
+use crate::reclaim::Wakers;
+use crate::RcuMap;
+
+/// How the value of a watched key changed, see [`KeyWatcher::changed`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyChange<V> {
+    /// The key got a value, having none before
+    Inserted(V),
+    /// The value of the key was replaced by a different one
+    Updated(V),
+    /// The value of the key was removed
+    Removed,
+}
+
+/// The watched keys of a map, each with the slot its watchers share, checked by a hook of the
+/// map after every publish.
+struct KeyIndex<K, V> {
+    slots: Mutex<HashMap<K, Weak<KeySlot<V>>>>,
+}
+
+/// What the watchers of one key share.
+struct KeySlot<V> {
+    state: Mutex<SlotState<V>>,
+    /// Number of changes so far, bumped with `state` locked
+    changes: AtomicU64,
+    /// Wakes `KeyWatcher::changed` when `changes` moves
+    changed: Notify,
+    /// Wakes `KeyWatcher::changed_async` when `changes` moves
+    #[cfg(feature = "async")]
+    wakers: Wakers,
+    /// Removes the slot from the index, once its last watcher is gone
+    unwatch: Box<dyn Fn() + Send + Sync>,
+}
+
+struct SlotState<V> {
+    /// The value of the key as of the last publish checked
+    value: Option<V>,
+    /// The latest change, None before the first one
+    last: Option<KeyChange<V>>,
+}
+
+impl<V: Clone + PartialEq> KeySlot<V> {
+    /// Records `value` as the value of the key, waking the watchers if it changed.
+    fn published(&self, value: Option<&V>) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let change = match (&state.value, value) {
+            (None, None) => return,
+            (Some(old), Some(new)) if old == new => return,
+            (None, Some(new)) => KeyChange::Inserted(new.clone()),
+            (Some(_), Some(new)) => KeyChange::Updated(new.clone()),
+            (Some(_), None) => KeyChange::Removed,
+        };
+        state.value = value.cloned();
+        state.last = Some(change);
+        self.changes.fetch_add(1, SeqCst);
+        drop(state);
+        self.changed.notify();
+        #[cfg(feature = "async")]
+        self.wakers.wake();
+    }
+}
+
+impl<V> Drop for KeySlot<V> {
+    fn drop(&mut self) {
+        (self.unwatch)();
+    }
+}
+
+impl<K, V, S> RcuMap<HashMap<K, V, S>>
+where
+    K: Hash + Eq + Clone + Send + Sync + 'static,
+    V: Clone + PartialEq + Send + Sync + 'static,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    /// Watches the value of `key`, the returned watcher waking up only when a publish changes
+    /// it: inserts it, replaces it with a different value, or removes it. Publishes leaving it
+    /// as it was, those changing other keys or setting it to an equal value, never wake it up.
+    ///
+    /// Every publish checks each watched key once however many watchers it has, so its cost
+    /// grows with the number of distinct keys watched, never with the number of watchers: it is
+    /// one lookup per key, from a hook of the map, see [`Rcu::on_update`](crate::Rcu::on_update),
+    /// where the publish copied the whole map already. Dropping the last watcher of a key stops
+    /// checking it.
+    ///
+    /// ```
+    /// # use rcu_rust::{KeyChange, RcuHashMap};
+    /// # fn reload(_: u16) {}
+    /// # fn unroute(_: &str) {}
+    /// let routes: RcuHashMap<String, u16> = RcuHashMap::new();
+    /// let mut watcher = routes.watch_key("/health".to_string());
+    /// routes.insert("/health".to_string(), 8080).ok().unwrap();
+    /// match watcher.changed() {
+    ///     KeyChange::Inserted(route) | KeyChange::Updated(route) => reload(route),
+    ///     KeyChange::Removed => unroute("/health"),
+    /// }
+    /// ```
+    pub fn watch_key(&self, key: K) -> KeyWatcher<V> {
+        let index = self.key_index();
+        let mut slots = index.slots.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(slot) = slots.get(&key).and_then(Weak::upgrade) {
+            drop(slots);
+            return KeyWatcher::new(slot);
+        }
+        // Read with the index locked, so the hook of a publish either runs before and this sees
+        // the value it published, or runs after and compares against this one
+        let value = self.rcu.read_with(|map| map.get(&key).cloned());
+        let slot = Arc::new(KeySlot {
+            state: Mutex::new(SlotState { value, last: None }),
+            changes: AtomicU64::new(0),
+            changed: Notify::default(),
+            #[cfg(feature = "async")]
+            wakers: Wakers::default(),
+            unwatch: Box::new({
+                let index = Arc::downgrade(&index);
+                let key = key.clone();
+                move || {
+                    let Some(index) = index.upgrade() else {
+                        return;
+                    };
+                    let mut slots = index.slots.lock().unwrap_or_else(PoisonError::into_inner);
+                    // Unless a new watcher took the key over meanwhile
+                    if slots.get(&key).is_some_and(|slot| slot.strong_count() == 0) {
+                        slots.remove(&key);
+                    }
+                }
+            }),
+        });
+        slots.insert(key, Arc::downgrade(&slot));
+        drop(slots);
+        KeyWatcher::new(slot)
+    }
+
+    /// The index of the watched keys, hooking it on the first call.
+    fn key_index(&self) -> Arc<KeyIndex<K, V>> {
+        let index = self.watched.get_or_init(|| {
+            let index = Arc::new(KeyIndex {
+                slots: Mutex::new(HashMap::new()),
+            });
+            self.rcu.on_update({
+                let index = Arc::clone(&index);
+                move |map: &HashMap<K, V, S>| {
+                    let watched: Vec<_> = {
+                        let slots = index.slots.lock().unwrap_or_else(PoisonError::into_inner);
+                        slots.iter().filter_map(|(key, slot)| Some((slot.upgrade()?, map.get(key)))).collect()
+                    };
+                    // With the index unlocked, the last watcher of a slot may be gone by now,
+                    // dropping it here removes it from the index
+                    for (slot, value) in watched {
+                        slot.published(value);
+                    }
+                }
+            });
+            let index: Arc<dyn Any + Send + Sync> = index;
+            index
+        });
+        Arc::clone(index).downcast().expect("only ever indexes keys of this map")
+    }
+}
+
+/// Watches the value of a single key of an [`RcuHashMap`](crate::RcuHashMap), created with
+/// [`RcuMap::watch_key`]. Changes are coalesced: if several happened since the last one this
+/// watcher returned, the next call returns the latest one only.
+pub struct KeyWatcher<V> {
+    slot: Arc<KeySlot<V>>,
+    /// The number of changes of the slot as of the last one this watcher returned
+    seen: u64,
+}
+
+impl<V: Clone> KeyWatcher<V> {
+    fn new(slot: Arc<KeySlot<V>>) -> Self {
+        let seen = slot.changes.load(SeqCst);
+        Self { slot, seen }
+    }
+
+    /// The value of the key as of the last publish, None if it has none.
+    pub fn current(&self) -> Option<V> {
+        self.slot.state.lock().unwrap_or_else(PoisonError::into_inner).value.clone()
+    }
+
+    /// Whether the value of the key changed since the last change this watcher returned, or since
+    /// it was created.
+    pub fn has_changed(&self) -> bool {
+        self.slot.changes.load(SeqCst) != self.seen
+    }
+
+    /// Blocks until the value of the key changed since the last change this watcher returned, or
+    /// since it was created, and returns the latest change.
+    pub fn changed(&mut self) -> KeyChange<V> {
+        self.slot.changed.wait_until(|| self.has_changed(), None);
+        self.take_change()
+    }
+
+    /// Like [`KeyWatcher::changed`], giving up after `timeout`.
+    pub fn changed_timeout(&mut self, timeout: Duration) -> Option<KeyChange<V>> {
+        let changed = self.slot.changed.wait_until(|| self.has_changed(), Some(timeout));
+        changed.then(|| self.take_change())
+    }
+
+    /// Like [`KeyWatcher::changed`], awaiting the change instead of blocking for it.
+    #[cfg(feature = "async")]
+    pub async fn changed_async(&mut self) -> KeyChange<V> {
+        future::poll_fn(|cx| {
+            if self.has_changed() {
+                return Poll::Ready(());
+            }
+            self.slot.wakers.register(cx.waker());
+            // A change landing before registering didn't wake us
+            if self.has_changed() { Poll::Ready(()) } else { Poll::Pending }
+        })
+        .await;
+        self.take_change()
+    }
+
+    /// The latest change, marking every change so far as seen. Only called once there was one.
+    fn take_change(&mut self) -> KeyChange<V> {
+        let state = self.slot.state.lock().unwrap_or_else(PoisonError::into_inner);
+        self.seen = self.slot.changes.load(SeqCst);
+        state.last.clone().expect("a change was recorded")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::RcuHashMap;
+
+    /// Long enough for a woken watcher to have returned
+    const SETTLE: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn publishes_leaving_the_key_alone_never_wake_its_watcher() {
+        let map: RcuHashMap<&str, u32> = RcuHashMap::new();
+        map.insert("watched", 1).unwrap();
+        let mut watcher = map.watch_key("watched");
+        thread::scope(|s| {
+            let waiter = s.spawn(|| watcher.changed());
+            for value in 0..100 {
+                map.insert("other", value).unwrap();
+            }
+            map.remove("other").unwrap();
+            map.remove("absent").unwrap();
+            map.insert("watched", 1).unwrap();
+            map.update(|map| map.insert("yet another", 0)).unwrap();
+            thread::sleep(SETTLE);
+            assert!(!waiter.is_finished(), "woken by a publish leaving the key alone");
+            map.insert("watched", 2).unwrap();
+            assert_eq!(waiter.join().unwrap(), KeyChange::Updated(2));
+        });
+        map.insert("other", 0).unwrap();
+        assert!(!watcher.has_changed());
+        assert_eq!(watcher.changed_timeout(SETTLE), None);
+    }
+
+    #[test]
+    fn removals_tell_apart_from_updates() {
+        let map: RcuHashMap<&str, u32> = RcuHashMap::new();
+        let mut watcher = map.watch_key("watched");
+        assert_eq!(watcher.current(), None);
+        map.insert("watched", 1).unwrap();
+        assert_eq!(watcher.changed(), KeyChange::Inserted(1));
+        map.insert("watched", 2).unwrap();
+        assert_eq!(watcher.changed(), KeyChange::Updated(2));
+        map.remove("watched").unwrap();
+        assert_eq!((watcher.changed(), watcher.current()), (KeyChange::Removed, None));
+        map.insert("watched", 2).unwrap();
+        assert_eq!(watcher.changed(), KeyChange::Inserted(2));
+        // Coalesced into the latest change, a removal still reads as one
+        map.insert("watched", 3).unwrap();
+        map.remove("watched").unwrap();
+        assert_eq!(watcher.changed(), KeyChange::Removed);
+        map.clear().unwrap();
+        assert!(!watcher.has_changed());
+    }
+
+    #[test]
+    fn watchers_of_a_key_all_see_its_change() {
+        let map: RcuHashMap<&str, u32> = RcuHashMap::new();
+        let mut watchers = [map.watch_key("watched"), map.watch_key("watched")];
+        let mut other = map.watch_key("other");
+        map.insert("watched", 1).unwrap();
+        for watcher in &mut watchers {
+            assert_eq!(watcher.changed(), KeyChange::Inserted(1));
+        }
+        assert!(!other.has_changed());
+        map.remove("watched").unwrap();
+        map.insert("other", 1).unwrap();
+        assert_eq!(other.changed(), KeyChange::Inserted(1));
+        assert_eq!(watchers[1].changed(), KeyChange::Removed);
+    }
+}
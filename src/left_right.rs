@@ -0,0 +1,174 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::{Acquire, Release, SeqCst}};
+
+/// The left-right technique: two copies of `T`, readers reading whichever one is currently
+/// published and the single writer applying every change twice, first to the standby copy, then,
+/// after flipping the copies and waiting for readers to drain from the previously published
+/// one, again to that copy.
+///
+/// Reads are wait-free, a couple of counter updates around the read itself, and updates never
+/// allocate. The costs are twice the memory, every change being applied twice, and the writer
+/// waiting for readers like `Rcu` does with the `reclaim-counted` engine.
+pub struct LeftRight<T> {
+    sides: [UnsafeCell<T>; 2],
+    /// The side readers read from
+    left_right: AtomicUsize,
+    /// Which of `read_indicators` new readers register in
+    version_index: AtomicUsize,
+    /// Readers currently registered in each version
+    read_indicators: [AtomicUsize; 2],
+    /// Whether a writer handle is alive
+    writer_taken: AtomicBool,
+}
+
+impl<T: Clone> LeftRight<T> {
+    /// Creates a new `LeftRight`, cloning `value` once for the standby copy.
+    pub fn new(value: T) -> Self {
+        Self {
+            sides: [UnsafeCell::new(value.clone()), UnsafeCell::new(value)],
+            left_right: AtomicUsize::new(0),
+            version_index: AtomicUsize::new(0),
+            read_indicators: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            writer_taken: AtomicBool::new(false),
+        }
+    }
+    /// Returns a clone of the current value.
+    pub fn read(&self) -> T {
+        self.read_with(T::clone)
+    }
+}
+
+impl<T> LeftRight<T> {
+    /// Runs `f` on the currently published copy, returning what `f` returns. Never waits, however
+    /// the writer is doing; the writer waits for `f` to return before touching this copy again.
+    pub fn read_with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        let vi = self.version_index.load(SeqCst);
+        self.read_indicators[vi].fetch_add(1, SeqCst);
+        let side = self.left_right.load(SeqCst);
+        // Decrements the indicator even if `f` panics
+        let _departure = Departure(&self.read_indicators[vi]);
+        // Safety: the writer won't touch `side` before our read indicator drained
+        f(unsafe { &*self.sides[side].get() })
+    }
+    /// The handle for changing the value, None while another one is alive.
+    pub fn writer(&self) -> Option<LeftRightWriter<'_, T>> {
+        self.writer_taken
+            .compare_exchange(false, true, Acquire, Acquire)
+            .is_ok()
+            .then_some(LeftRightWriter { lr: self })
+    }
+}
+
+struct Departure<'a>(&'a AtomicUsize);
+
+impl Drop for Departure<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, SeqCst);
+    }
+}
+
+unsafe impl<T> Send for LeftRight<T> where T: Send {}
+unsafe impl<T> Sync for LeftRight<T> where T: Send + Sync {}
+
+/// The single writer of a [`LeftRight`], created with [`LeftRight::writer`].
+pub struct LeftRightWriter<'a, T> {
+    lr: &'a LeftRight<T>,
+}
+
+impl<T> LeftRightWriter<'_, T> {
+    /// Applies `op` to the value. `op` is run twice, once on each copy, so it must be
+    /// deterministic: both runs have to leave the copies equal.
+    pub fn apply(&mut self, mut op: impl FnMut(&mut T)) {
+        let lr = self.lr;
+        let published = lr.left_right.load(SeqCst);
+        let standby = 1 - published;
+        // Safety: readers drained from `standby` at the end of the previous `apply`, and new
+        // ones only ever read `published`
+        op(unsafe { &mut *lr.sides[standby].get() });
+        lr.left_right.store(standby, SeqCst);
+        // Readers registered in either version may still have read `left_right` before the store
+        // above: wait for the idle version to drain, move new readers over to it, then wait for
+        // the previous one to drain
+        let prev_vi = lr.version_index.load(SeqCst);
+        let next_vi = 1 - prev_vi;
+        while lr.read_indicators[next_vi].load(SeqCst) != 0 {
+            std::hint::spin_loop();
+        }
+        lr.version_index.store(next_vi, SeqCst);
+        while lr.read_indicators[prev_vi].load(SeqCst) != 0 {
+            std::hint::spin_loop();
+        }
+        // Safety: every reader that could have been reading `published` has departed
+        op(unsafe { &mut *lr.sides[published].get() });
+    }
+}
+
+impl<T> Drop for LeftRightWriter<'_, T> {
+    fn drop(&mut self) {
+        self.lr.writer_taken.store(false, Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use std::thread;
+
+    use super::LeftRight;
+
+    #[test]
+    fn readers_never_see_a_change_half_applied() {
+        const APPLIES: u64 = 2000;
+        let lr = LeftRight::new((0u64, 0u64));
+        let done = AtomicBool::new(false);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let mut last = 0;
+                    while !done.load(SeqCst) {
+                        let (a, b) = lr.read_with(|&pair| pair);
+                        assert_eq!(a, b, "read a copy halfway through a change");
+                        assert!(a >= last, "went back from {last} to {a}");
+                        last = a;
+                        thread::yield_now();
+                    }
+                });
+            }
+            let mut writer = lr.writer().unwrap();
+            for _ in 0..APPLIES {
+                writer.apply(|(a, b)| {
+                    *a += 1;
+                    *b += 1;
+                });
+            }
+            done.store(true, SeqCst);
+        });
+        assert_eq!(lr.read(), (APPLIES, APPLIES));
+    }
+
+    #[test]
+    fn every_op_is_replayed_onto_the_other_copy() {
+        let lr = LeftRight::new(Vec::new());
+        let mut writer = lr.writer().unwrap();
+        for op in 1..=5 {
+            writer.apply(|ops| ops.push(op));
+            assert_eq!(lr.read(), (1..=op).collect::<Vec<_>>());
+        }
+        // Safety: no reader nor the writer is touching either copy
+        let sides = lr.sides.each_ref().map(|side| unsafe { &*side.get() });
+        assert_eq!(sides[0], sides[1], "the copies diverged");
+        assert_eq!(*sides[0], [1, 2, 3, 4, 5], "an op was applied twice to one copy");
+    }
+
+    #[test]
+    fn only_one_writer_at_a_time() {
+        let lr = LeftRight::new(0);
+        let mut writer = lr.writer().unwrap();
+        assert!(lr.writer().is_none());
+        writer.apply(|n| *n += 1);
+        drop(writer);
+        let mut writer = lr.writer().expect("no writer after the first one was dropped");
+        writer.apply(|n| *n += 1);
+        assert_eq!(lr.read(), 2);
+    }
+}
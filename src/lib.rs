@@ -0,0 +1,197 @@
+//! A "read, copy, update" cell: readers get a snapshot of the current value without ever
+//! blocking writers out, and writers publish a whole new value which replaces the old one
+//! once no reader can be observing it anymore.
+//!
+//! How the old value is reclaimed is chosen at compile time with one of these features:
+//!
+//! - `reclaim-counted` (default): reader counters; `update` waits in-line for current readers to
+//!   finish before freeing the old value, holding new readers back meanwhile unless configured
+//!   otherwise (see [`Preference`]). Smallest footprint, writers may block.
+//! - `reclaim-retire-list`: old values are queued and freed in bulk whenever a writer observes
+//!   no active readers. Writers never block, but a constant stream of readers delays reclamation.
+//! - `reclaim-epoch`: two-epoch reader tracking; old values are freed once the readers from the
+//!   epoch they were retired in have drained. Writers never block and reclamation keeps making
+//!   progress under constant reads.
+//!
+//! With the `fallback-lock` feature, a baseline without any of the lock-free code, to compare
+//! against when it is suspected:
+//!
+//! - an `Rcu` keeps its value in a `RwLock<Arc<T>>` instead of behind an atomic pointer, readers
+//!   holding a clone of the `Arc` rather than a raw reference;
+//! - the selected engine is replaced by one tracking readers under a plain `Mutex`, which writers
+//!   wait on in-line, and `Rcu::new_biased` and `Rcu::new_membarrier` behave like `Rcu::new`.
+//!
+//! It is slower, but the API and its semantics are the same: wherever these docs say the
+//! `reclaim-counted` engine waits for readers or holds them back, so does this one. Only
+//! allocations differ: every value is moved into an `Arc` of its own, so `Rcu::from_box`,
+//! `Rcu::update_box`, `Rcu::into_box` and the `emplace` family don't reuse the allocations they are
+//! handed, and reading signal-safely takes a lock.
+//!
+//! On `wasm32` targets without the `atomics` target feature, which have no threads, the selected
+//! engine is replaced by one that never waits: publishes never block, and values displaced while a
+//! read is in progress on the stack are freed when it completes.
+//!
+//! Other features each enable one more part of the API:
+//!
+//! - `derive`: `#[derive(RcuFields)]` turns a struct into a struct of `Rcu`s, one per field, see
+//!   [`rcu_rust_derive`](RcuFields).
+//! - `diagnostics`: publishes waiting too long for readers can be reported, see
+//!   `Rcu::set_stall_threshold`.
+//! - `metrics`: every `Rcu` reports publishes, failed publishes, grace-period waits, the number of
+//!   values waiting to be freed and reads through the [`metrics`](https://docs.rs/metrics) facade,
+//!   see `Rcu::metrics_label`.
+//! - `audit`: an `Rcu` can keep a bounded log of who published when, see `Rcu::enable_audit_log`.
+//! - `rkyv`: an `ArchivedRcu` publishes [rkyv](https://docs.rs/rkyv) archives which readers access
+//!   in place, without deserializing.
+//! - `async`: `Rcu::update_async` publishes and awaits readers of the replaced value instead of
+//!   blocking for them, and `Rcu::sink` is a `futures::Sink` publishing what a stream forwards to it.
+//! - `membarrier`: `Rcu::new_membarrier` creates values whose reads use plain stores only,
+//!   publishes ordering themselves with `membarrier(2)` on Linux.
+//! - `im`: `RcuImHashMap` and friends are maps and lists behind an `Rcu` whose changes only copy
+//!   what they change, where those over std collections (see [`RcuMap`]) copy everything.
+//! - `serde`: `DynRcu::serialize_snapshot` writes the current value of an `Rcu` registered in an
+//!   [`RcuRegistry`] as JSON, which then only takes serializable values.
+//! - `watch`: `watch_file` keeps an `Rcu` holding the parsed contents of a file as it changes.
+
+mod acks;
+mod aggregate;
+mod arc;
+#[cfg(feature = "rkyv")]
+mod archived;
+#[cfg(feature = "audit")]
+mod audit;
+mod backpressure;
+mod batch;
+mod bitmap;
+mod cache;
+mod cached;
+mod callbacks;
+mod coalescer;
+mod collections;
+mod compute;
+mod delta;
+mod derived;
+#[cfg(feature = "diagnostics")]
+mod diagnostics;
+mod domain;
+mod emplace;
+mod error;
+mod fallible;
+mod filtered;
+// The freeze handshake of the atomic backend, see `raw`
+#[cfg(any(not(feature = "fallback-lock"), test))]
+mod freeze;
+mod group;
+mod guard;
+mod hooks;
+mod interner;
+mod invariant;
+mod key_watch;
+mod left_right;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod notify;
+mod owned;
+#[cfg(feature = "async")]
+mod publish_sink;
+mod raw;
+mod rcu;
+mod reclaim;
+mod refresh;
+mod registry;
+mod round_robin;
+mod seq;
+mod sharded;
+mod single_writer;
+mod sink;
+mod slab;
+mod staging;
+mod staleness;
+mod triple;
+mod updates;
+mod wait;
+#[cfg(feature = "watch")]
+mod watch;
+mod window;
+mod writer_pool;
+
+pub use aggregate::{Aggregator, RcuAggregate, Summary};
+#[cfg(feature = "rkyv")]
+pub use archived::{ArchivedGuard, ArchivedRcu};
+#[cfg(feature = "audit")]
+pub use audit::AuditEntry;
+pub use backpressure::{Backlog, OnBacklog};
+pub use bitmap::RcuBitmap;
+pub use cache::RcuCache;
+pub use callbacks::{ListenerId, RcuCallbackList};
+pub use coalescer::Coalescer;
+pub use collections::{RcuBTreeMap, RcuHashMap, RcuMap, RcuVec, RcuVector};
+#[cfg(feature = "im")]
+pub use collections::{RcuImHashMap, RcuImOrdMap, RcuImVector};
+pub use delta::DeltaSubscriber;
+pub use derived::DerivedRcu;
+#[cfg(feature = "diagnostics")]
+pub use diagnostics::{GraceHistogram, SlowGracePeriod};
+pub use domain::{DomainReadGuard, RcuDomain};
+pub use error::{
+    AllocError, CommitConflict, CommitError, DeadlineError, LaggingSubscribers, LookupError, PromoteError, PublishError,
+    ReclaimStatus, Stale, StaleStage,
+};
+#[cfg(feature = "watch")]
+pub use error::WatchError;
+pub use filtered::FilteredSubscriber;
+pub use group::{GroupHandle, GroupSnapshot, HasMember, Here, RcuGroup, There, Transaction};
+pub use guard::{MappedRcuReadGuard, RcuReadGuard, RcuWriteGuard};
+pub use hooks::HookId;
+pub use interner::{RcuInterner, Symbol};
+pub use key_watch::{KeyChange, KeyWatcher};
+pub use left_right::{LeftRight, LeftRightWriter};
+pub use owned::OwnedSnapshot;
+#[cfg(feature = "async")]
+pub use publish_sink::RcuSink;
+pub use rcu::{Rcu, RcuSubscriber};
+#[cfg(feature = "diagnostics")]
+pub use reclaim::StallReport;
+pub use refresh::RefresherHandle;
+pub use registry::{DynRcu, RcuRegistry, TypedHandle};
+pub use round_robin::RcuRoundRobin;
+pub use seq::{NoPadding, RcuSeq};
+pub use sharded::ShardedRcuMap;
+pub use single_writer::{RcuReader, SingleWriter};
+pub use slab::{RcuSlab, SlabGuard, SlabKey};
+pub use staging::StagedVersion;
+pub use staleness::{Staleness, StalenessHandle, StalenessRegistry};
+pub use triple::{TripleBuffer, TripleConsumer, TripleProducer};
+pub use updates::{OwnedUpdateIter, UpdateIter};
+pub use wait::{Park, Preference, Spin, SpinYield, WaitStrategy};
+#[cfg(feature = "watch")]
+pub use watch::{watch_file, WatcherGuard};
+pub use window::{RcuWindow, WindowProducer, WindowReader};
+pub use writer_pool::{PoolHandle, UpdateSender};
+
+/// Generates a struct holding every field in its own `Rcu`, see the `rcu_rust_derive` crate for the
+/// accessors it gets and the attributes it takes:
+///
+/// ```
+/// # use std::time::Duration;
+/// # use rcu_rust::RcuFields;
+/// # type Url = &'static str;
+/// #[derive(Clone, RcuFields)]
+/// struct Config {
+///     timeout: Duration,
+///     #[rcu(rename = "urls")]
+///     endpoints: Vec<Url>,
+///     #[rcu(skip)]
+///     name: String,
+/// }
+///
+/// # let config = Config { timeout: Duration::ZERO, endpoints: vec!["a"], name: "svc".into() };
+/// let config = ConfigRcu::from(config);
+/// config.set_timeout(Duration::from_secs(5)).unwrap();
+/// let urls: Vec<Url> = config.urls();
+/// let config: Config = config.snapshot();
+/// # assert_eq!(config.timeout, Duration::from_secs(5));
+/// # assert_eq!(urls, ["a"]);
+/// ```
+#[cfg(feature = "derive")]
+pub use rcu_rust_derive::RcuFields;
@@ -0,0 +1,229 @@
+//! Reporting through the `metrics` facade, with the `metrics` feature. Every `Rcu` reports:
+//!
+//! - `rcu_publishes_total`: values published;
+//! - `rcu_publish_failures_total`: calls that published nothing, labeled with a `reason` of
+//!   `rejected` (an invariant refused the value), `conflict` (another publish was in flight),
+//!   `expired` (a deadline passed first), `frozen` or `backpressure` (too many displaced values
+//!   were waiting to be freed); retries inside a single call aren't counted;
+//! - `rcu_grace_period_seconds`: how long each publish spent handing the replaced value to the
+//!   engine, waiting for its readers included;
+//! - `rcu_retire_queue_depth`: values retired but not freed yet, sampled after every publish that
+//!   displaced one;
+//! - `rcu_reads_total`: reads, counted per thread and reported in batches of 1024, each
+//!   batch going to the `Rcu` whose read completed it.
+//!
+//! Series carry an `rcu` label with the name given to `Rcu::metrics_label`, if any. Instruments
+//! are registered with the installed recorder on first use.
+
+use std::cell::Cell;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use metrics::{Counter, Histogram, Label};
+
+/// Reads per thread between two reports to `rcu_reads_total`
+pub(crate) const READ_BATCH: u32 = 1024;
+
+/// Why a call published nothing.
+#[derive(Clone, Copy)]
+pub(crate) enum Failure {
+    Rejected,
+    Conflict,
+    Expired,
+    Frozen,
+    Backpressure,
+}
+
+struct Instruments {
+    publishes: Counter,
+    rejected: Counter,
+    conflicts: Counter,
+    expired: Counter,
+    frozen: Counter,
+    backpressure: Counter,
+    grace_period: Histogram,
+    retire_queue: Histogram,
+    reads: Counter,
+}
+
+/// The instruments of one `Rcu`.
+#[derive(Default)]
+pub(crate) struct Metrics {
+    label: Option<String>,
+    instruments: OnceLock<Instruments>,
+}
+
+impl Metrics {
+    pub(crate) fn labeled(label: String) -> Self {
+        Self {
+            label: Some(label),
+            instruments: OnceLock::new(),
+        }
+    }
+
+    fn instruments(&self) -> &Instruments {
+        self.instruments.get_or_init(|| {
+            let labels: Vec<Label> = self.label.iter().map(|label| Label::new("rcu", label.clone())).collect();
+            let failures = |reason: &'static str| {
+                let mut labels = labels.clone();
+                labels.push(Label::new("reason", reason));
+                metrics::counter!("rcu_publish_failures_total", labels)
+            };
+            Instruments {
+                publishes: metrics::counter!("rcu_publishes_total", labels.clone()),
+                rejected: failures("rejected"),
+                conflicts: failures("conflict"),
+                expired: failures("expired"),
+                frozen: failures("frozen"),
+                backpressure: failures("backpressure"),
+                grace_period: metrics::histogram!("rcu_grace_period_seconds", labels.clone()),
+                retire_queue: metrics::histogram!("rcu_retire_queue_depth", labels.clone()),
+                reads: metrics::counter!("rcu_reads_total", labels),
+            }
+        })
+    }
+
+    pub(crate) fn published(&self) {
+        self.instruments().publishes.increment(1);
+    }
+
+    pub(crate) fn failed(&self, failure: Failure) {
+        let instruments = self.instruments();
+        match failure {
+            Failure::Rejected => instruments.rejected.increment(1),
+            Failure::Conflict => instruments.conflicts.increment(1),
+            Failure::Expired => instruments.expired.increment(1),
+            Failure::Frozen => instruments.frozen.increment(1),
+            Failure::Backpressure => instruments.backpressure.increment(1),
+        }
+    }
+
+    /// Records a value handed to the engine after `waited`, with `pending` values still waiting to be freed.
+    pub(crate) fn retired(&self, waited: Duration, pending: usize) {
+        let instruments = self.instruments();
+        instruments.grace_period.record(waited);
+        instruments.retire_queue.record(pending as f64);
+    }
+
+    pub(crate) fn read(&self) {
+        thread_local! {
+            static READS: Cell<u32> = const { Cell::new(0) };
+        }
+        let batch_done = READS.with(|reads| {
+            let n = reads.get() + 1;
+            reads.set(n % READ_BATCH);
+            n == READ_BATCH
+        });
+        if batch_done {
+            self.instruments().reads.increment(READ_BATCH as u64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{mpsc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use metrics_util::MetricKind;
+
+    use super::READ_BATCH;
+    use crate::Rcu;
+
+    /// What each series recorded, keyed by its name and labels: counters by their value, histograms
+    /// by their number of samples.
+    type Series = HashMap<(String, Vec<(String, String)>), u64>;
+
+    /// Runs `f` with a recorder of its own on this thread, returning what it recorded.
+    fn recorded(f: impl FnOnce()) -> Series {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, f);
+        snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let labels = key.key().labels().map(|label| (label.key().to_owned(), label.value().to_owned()));
+                let value = match (key.kind(), value) {
+                    (MetricKind::Counter, DebugValue::Counter(count)) => count,
+                    (MetricKind::Histogram, DebugValue::Histogram(samples)) => samples.len() as u64,
+                    (kind, value) => panic!("unexpected {kind:?} value {value:?}"),
+                };
+                ((key.key().name().to_owned(), labels.collect()), value)
+            })
+            .collect()
+    }
+
+    fn labels(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn a_scripted_workload_reports_every_series() {
+        let series = recorded(|| {
+            let rcu = Rcu::new(0).metrics_label("routing_table");
+            rcu.set_invariant(|value| if *value == 13 { Err("unlucky".into()) } else { Ok(()) });
+            for i in 1..=3 {
+                rcu.set(i).unwrap();
+            }
+            assert!(rcu.set(13).is_err());
+            for _ in 0..2 * READ_BATCH {
+                rcu.read();
+            }
+            // Keeps the publish of 4 in flight, for the next two calls to give up on
+            let (entered, wait_entered) = mpsc::channel();
+            let (release, wait_release) = mpsc::channel::<()>();
+            let wait_release = Mutex::new(wait_release);
+            rcu.on_update(move |value| {
+                if *value == 4 {
+                    entered.send(()).unwrap();
+                    let _ = wait_release.lock().unwrap().recv();
+                }
+            });
+            thread::scope(|s| {
+                s.spawn(|| rcu.set(4).unwrap());
+                wait_entered.recv().unwrap();
+                assert!(!rcu.update(5));
+                assert!(rcu.update_with_deadline(6, Duration::from_millis(10)).is_err());
+                drop(release);
+            });
+            rcu.freeze();
+            assert!(rcu.set(7).is_err());
+        });
+        let label = labels(&[("rcu", "routing_table")]);
+        let failures = |reason| labels(&[("rcu", "routing_table"), ("reason", reason)]);
+        let expected = [
+            (("rcu_publishes_total", label.clone()), 4),
+            (("rcu_publish_failures_total", failures("rejected")), 1),
+            (("rcu_publish_failures_total", failures("conflict")), 1),
+            (("rcu_publish_failures_total", failures("expired")), 1),
+            (("rcu_publish_failures_total", failures("frozen")), 1),
+            (("rcu_publish_failures_total", failures("backpressure")), 0),
+            // The first publish displaced nothing, the initial value staying readable as the previous one
+            (("rcu_grace_period_seconds", label.clone()), 3),
+            (("rcu_retire_queue_depth", label.clone()), 3),
+            (("rcu_reads_total", label), u64::from(2 * READ_BATCH)),
+        ];
+        let expected: Series =
+            expected.into_iter().map(|((name, labels), value)| ((name.to_owned(), labels), value)).collect();
+        assert_eq!(series, expected);
+    }
+
+    #[test]
+    fn unlabeled_values_report_unlabeled_series() {
+        let series = recorded(|| {
+            Rcu::new(0).set(1).unwrap();
+        });
+        assert_eq!(series[&("rcu_publishes_total".to_owned(), Vec::new())], 1);
+    }
+
+    #[test]
+    fn nothing_is_registered_before_first_use() {
+        let series = recorded(|| drop(Rcu::new(0)));
+        assert!(series.is_empty(), "registered {series:?}");
+    }
+}
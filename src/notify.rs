@@ -0,0 +1,52 @@
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Lets threads block until some condition, changed by other threads, becomes true. Changing
+/// the condition costs a single load unless someone is actually waiting.
+#[derive(Default)]
+pub(crate) struct Notify {
+    waiters: AtomicUsize,
+    lock: Mutex<()>,
+    cond: Condvar,
+}
+
+impl Notify {
+    /// Wakes every waiter, to be called after changing the condition they wait on.
+    pub(crate) fn notify(&self) {
+        if self.waiters.load(SeqCst) > 0 {
+            // Taking the lock orders us after any waiter that checked the condition but isn't
+            // waiting on `self.cond` yet
+            drop(self.lock.lock().unwrap_or_else(PoisonError::into_inner));
+            self.cond.notify_all();
+        }
+    }
+
+    /// Blocks until `done` returns true, or until `timeout` elapsed. Returns whether `done` was
+    /// satisfied. `done` should only depend on state published with `SeqCst` before `notify` is called.
+    pub(crate) fn wait_until(&self, mut done: impl FnMut() -> bool, timeout: Option<Duration>) -> bool {
+        if done() {
+            return true;
+        }
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        self.waiters.fetch_add(1, SeqCst);
+        let mut guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        let satisfied = loop {
+            if done() {
+                break true;
+            }
+            guard = match deadline {
+                None => self.cond.wait(guard).unwrap_or_else(PoisonError::into_inner),
+                Some(deadline) => {
+                    let Some(left) = deadline.checked_duration_since(Instant::now()) else {
+                        break false;
+                    };
+                    self.cond.wait_timeout(guard, left).unwrap_or_else(PoisonError::into_inner).0
+                }
+            };
+        };
+        drop(guard);
+        self.waiters.fetch_sub(1, SeqCst);
+        satisfied
+    }
+}
@@ -0,0 +1,57 @@
+use std::fmt;
+use std::ops::Deref;
+
+use crate::raw::Snapshot;
+use crate::Rcu;
+
+/// A snapshot of the value of an `Rcu`, created with [`Rcu::read_owned`]: keeps the value it read
+/// alive, dereferencing to it, for as long as it lives, without holding a read-side critical
+/// section. It owns what it needs, so it can be sent to other threads and held across `.await`s.
+///
+/// Only the value it read is kept alive: publishes carry on, and the values they displace after it
+/// are freed as usual. Holding on to many snapshots of many values keeps all of them alive.
+pub struct OwnedSnapshot<T> {
+    snapshot: Snapshot<T>,
+}
+
+impl<T> Deref for OwnedSnapshot<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.snapshot
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OwnedSnapshot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OwnedSnapshot").field(&**self).finish()
+    }
+}
+
+impl<T: Clone> Rcu<T> {
+    /// Reads the current value without cloning it into a snapshot owning what keeps it alive:
+    /// unlike [`Rcu::read_guard`] it can be held across `.await`s and sent to other threads, and
+    /// the `Rcu` can be dropped before it.
+    ///
+    /// A snapshot never holds up publishes: it doesn't hold a read-side critical section, only the
+    /// value it read stays alive until it is dropped. Taking one costs a lock of a table of
+    /// snapshots on top of a read; once the first one was taken, every publish wraps the value it
+    /// displaces to look it up there once it is freed.
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use std::thread;
+    /// # use rcu_rust::Rcu;
+    /// let table = Rcu::new(HashMap::from([("/", "index.html")]));
+    /// let routes = table.read_owned();
+    /// table.set(HashMap::new()).ok().unwrap();
+    /// let page = thread::spawn(move || routes["/"]).join().unwrap();
+    /// assert_eq!(page, "index.html");
+    /// ```
+    #[track_caller]
+    pub fn read_owned(&self) -> OwnedSnapshot<T> {
+        OwnedSnapshot {
+            snapshot: self.raw.pin(),
+        }
+    }
+}
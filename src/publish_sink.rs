@@ -0,0 +1,118 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_sink::Sink;
+
+use crate::raw::Reclamation;
+use crate::{PublishError, Rcu};
+
+impl<T: Clone> Rcu<T> {
+    /// A [`Sink`] publishing every value sent to it, for wiring a stream of values into the `Rcu`,
+    /// e.g. `configs.map(Ok).forward(rcu.sink())`. See [`RcuSink`].
+    pub fn sink(&self) -> RcuSink<'_, T> {
+        RcuSink {
+            rcu: self,
+            latest_wins: false,
+            staged: None,
+            reclaiming: None,
+        }
+    }
+}
+
+/// Publishes the values sent to it like [`Rcu::update_async`], created with [`Rcu::sink`].
+///
+/// `start_send` checks the value against the invariants and stages it, `poll_flush` publishes it
+/// and then stays pending until the readers of the value it replaced are done, and `poll_close`
+/// flushes, then freezes the `Rcu`, see [`Rcu::freeze`]. A value refused by an invariant, by the
+/// backlog limits or because the `Rcu` is frozen is reported as the [`PublishError`] handing it
+/// back, from `start_send` or from the call that tried to publish it.
+///
+/// Only one publish is in flight at a time: `poll_ready` stays pending until the value staged
+/// before was published and the grace period after it is over, so every value sent gets published,
+/// in order, each one waiting for the readers of the last. With [`RcuSink::latest_wins`], values
+/// sent while a grace period is still going replace the one staged instead, the sink publishing
+/// only the latest once it is over: a fast stream is never held back by slow readers, at the cost
+/// of intermediate values never being published.
+///
+/// A publish in flight for another writer, or a backlog past [`Rcu::with_max_pending_retired`]
+/// with [`OnBacklog::Block`](crate::OnBacklog::Block), makes the sink yield and try again, as
+/// [`Rcu::update_with_async`] does.
+///
+/// ```
+/// # use futures::{executor::block_on, stream, StreamExt};
+/// # use rcu_rust::Rcu;
+/// let config = Rcu::new(0);
+/// let configs = stream::iter(1..=3);
+/// block_on(configs.map(Ok).forward(config.sink()))?;
+/// assert_eq!(config.read(), 3);
+/// assert!(config.is_frozen());
+/// # Ok::<(), rcu_rust::PublishError<i32>>(())
+/// ```
+pub struct RcuSink<'a, T: Clone> {
+    rcu: &'a Rcu<T>,
+    latest_wins: bool,
+    /// Checked, waiting to be published
+    staged: Option<T>,
+    /// The grace period after the last publish, if not over yet
+    reclaiming: Option<Reclamation<'a, T>>,
+}
+
+// Neither the staged value nor the reclamation are ever pinned
+impl<T: Clone> Unpin for RcuSink<'_, T> {}
+
+impl<T: Clone> RcuSink<'_, T> {
+    /// Makes values sent while a grace period is going replace the one staged, rather than wait
+    /// for their turn, see [`RcuSink`].
+    pub fn latest_wins(mut self) -> Self {
+        self.latest_wins = true;
+        self
+    }
+}
+
+impl<T: Clone> Sink<T> for RcuSink<'_, T> {
+    type Error = PublishError<T>;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let flushed = self.as_mut().poll_flush(cx)?;
+        if self.latest_wins || flushed.is_ready() {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        let item = self.rcu.check(item)?;
+        // Only ever replaces a staged value with `latest_wins`, `poll_ready` waits for it otherwise
+        self.staged = Some(item);
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = &mut *self;
+        loop {
+            if let Some(reclaiming) = &mut this.reclaiming {
+                ready!(Pin::new(reclaiming).poll(cx));
+                this.reclaiming = None;
+            }
+            if this.staged.is_none() {
+                return Poll::Ready(Ok(()));
+            }
+            match this.rcu.publish_staged(&mut this.staged)? {
+                Some(reclamation) => this.reclaiming = Some(reclamation),
+                None => {
+                    // Yields to the publish in flight or the backlog, like `YieldNow`
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        self.rcu.freeze();
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,860 @@
+// Only built for the differential tests with `fallback-lock`, which don't use all of it
+#![cfg_attr(feature = "fallback-lock", allow(dead_code))]
+
+use std::panic::{self, AssertUnwindSafe};
+use std::convert::Infallible;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicU64, Ordering::{Acquire, Relaxed, SeqCst}};
+
+use std::sync::Arc;
+#[cfg(feature = "async")]
+use std::{future::Future, pin::Pin, task::{Context, Poll}};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+#[cfg(feature = "diagnostics")]
+use crate::diagnostics::{GraceHistogram, GraceStats};
+#[cfg(feature = "diagnostics")]
+use crate::reclaim::Watchdog;
+use crate::reclaim::{Engine, Reclaim, Reclaimer, ReadLock, Retired, SignalSafeLock};
+use crate::backpressure::RetiredBytes;
+use crate::batch::RetireBatch;
+use crate::freeze::{FreezeGate, Publishing};
+use crate::sink::RetireSink;
+use crate::staleness;
+use crate::wait::{Preference, Waiter};
+use super::pins::Pins;
+use super::{Modify, Refused};
+
+/// The publication protocol shared by every RCU-managed value in the crate: an atomic pointer
+/// to the current allocation and the reclamation engine that frees displaced ones. Carries no
+/// bounds on `T`, so the crate can use it for its own bookkeeping (e.g. hook lists) without
+/// recursing into `Rcu`.
+pub(crate) struct RawRcu<T> {
+    /// Holds the data `T`
+    data_ptr: AtomicPtr<T>,
+    /// Holds a pointer to the previous data, used for updating
+    prev_ptr: AtomicPtr<T>,
+    /// Tracks readers and decides when unpublished data can be deallocated
+    reclaimer: Engine,
+    /// Number of successful publishes so far
+    version: AtomicU64,
+    /// When the current value was published, or created if never, see `staleness::now`
+    published_at: AtomicU64,
+    /// The value published before the current one, null before the first publish or unless
+    /// `keep_previous`
+    previous: AtomicPtr<T>,
+    /// Whether displaced values are kept in `previous` until the next publish before being retired
+    keep_previous: bool,
+    /// Where displaced values go once they are reclaimed, instead of being dropped
+    sink: RetireSink<T>,
+    /// The size of displaced values not freed yet, while sized
+    bytes: RetiredBytes<T>,
+    /// Values held by owned snapshots, shared with them
+    pins: Arc<Pins>,
+    /// How long displaced values took to be freed, shared with them until they are
+    #[cfg(feature = "diagnostics")]
+    grace: Arc<GraceStats>,
+    /// Displaced values not handed to the engine yet, while batching
+    batch: RetireBatch,
+    /// Closed for good by [`RawRcu::freeze`]
+    gate: FreezeGate,
+    /// How waits for this value's publishes wait, the engine waiting its own way
+    waiter: Waiter,
+    /// Where reads and grace periods are reported, None for the crate's own bookkeeping
+    #[cfg(feature = "metrics")]
+    metrics: Option<Metrics>,
+}
+
+impl<T> RawRcu<T> {
+    pub(crate) fn new(value: Box<T>) -> Self {
+        Self::with_engine(value, Engine::default())
+    }
+
+    /// Like [`RawRcu::new`], biased towards the current thread until another one touches it.
+    pub(crate) fn new_biased(value: Box<T>) -> Self {
+        Self::with_engine(value, Engine::biased())
+    }
+
+    /// Like [`RawRcu::new`], with readers that only use plain stores where supported, see `Engine::membarrier`.
+    #[cfg(feature = "membarrier")]
+    pub(crate) fn new_membarrier(value: Box<T>) -> Self {
+        Self::with_engine(value, Engine::membarrier())
+    }
+
+    /// Like [`RawRcu::new`], tracking readers with an engine shared with other values.
+    pub(crate) fn new_shared(value: Box<T>, reclaimer: Arc<Reclaimer>) -> Self
+    where
+        // What is retired to a shared engine may only be freed after the `RawRcu` is gone
+        T: 'static,
+    {
+        Self::with_engine(value, Engine::Shared(reclaimer))
+    }
+
+    fn with_engine(value: Box<T>, reclaimer: Engine) -> Self {
+        let data_ptr = Box::into_raw(value);
+        Self {
+            data_ptr: AtomicPtr::new(data_ptr),
+            prev_ptr: AtomicPtr::new(data_ptr),
+            reclaimer,
+            version: AtomicU64::new(0),
+            published_at: AtomicU64::new(staleness::now()),
+            previous: AtomicPtr::new(ptr::null_mut()),
+            keep_previous: false,
+            sink: RetireSink::new(),
+            bytes: RetiredBytes::new(),
+            pins: Arc::default(),
+            #[cfg(feature = "diagnostics")]
+            grace: Arc::default(),
+            batch: RetireBatch::new(),
+            gate: FreezeGate::new(),
+            waiter: Waiter::default(),
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+
+    /// Makes waits for this value's publishes and its engine's grace periods go through `waiter`,
+    /// unless the engine is shared.
+    pub(crate) fn set_waiter(&mut self, waiter: Waiter) {
+        self.reclaimer.set_waiter(waiter.clone());
+        self.waiter = waiter;
+    }
+
+    /// Holds displaced values back from the engine until `max_len` of them accumulated, or the
+    /// oldest of them waited for `max_age`, see `Rcu::with_retire_batching`.
+    pub(crate) fn set_retire_batching(&mut self, max_len: usize, max_age: Duration) {
+        self.batch.set(max_len, max_age);
+    }
+
+    /// Hands every displaced value held back by batching to the engine, as a single batch.
+    pub(crate) fn flush_retired(&self) {
+        self.flush_retired_before(None);
+    }
+
+    /// Like `flush_retired`, giving up on waiting for readers at `deadline`, see `Reclaim::retire_batch`.
+    /// With nothing batched, frees what the engine can of what it was handed before.
+    pub(crate) fn flush_retired_before(&self, deadline: Option<Instant>) -> bool {
+        let batch = self.batch.take();
+        if batch.is_empty() {
+            return self.reclaimer.reclaim_before(deadline);
+        }
+        // Safety: every batched value was unpublished, and is taken out of the batch only once
+        unsafe { self.reclaimer.retire_batch(batch, deadline) }
+    }
+
+    /// Number of displaced values held back by batching.
+    pub(crate) fn batched(&self) -> usize {
+        self.batch.len()
+    }
+
+    /// Number of displaced values not freed yet, batched or retired.
+    pub(crate) fn pending_retired(&self) -> usize {
+        self.reclaimer.pending() + self.batch.len()
+    }
+
+    /// Counts the size of displaced values as `size_of` estimates it until they are freed.
+    pub(crate) fn set_retired_size(&mut self, size_of: fn(&T) -> usize) {
+        self.bytes.set(size_of);
+    }
+
+    /// The estimated size of displaced values not freed yet, 0 unless sized.
+    pub(crate) fn retired_bytes(&self) -> usize {
+        self.bytes.total()
+    }
+
+    /// Makes the engine hold readers back as `preference` says, unless it is shared.
+    pub(crate) fn set_preference(&mut self, preference: Preference) {
+        self.reclaimer.set_preference(preference);
+    }
+
+    pub(crate) fn waiter(&self) -> &Waiter {
+        &self.waiter
+    }
+
+    /// Makes the value keep its previously published value around, see [`RawRcu::read_pair`].
+    pub(crate) fn keeping_previous(mut self) -> Self {
+        self.keep_previous = true;
+        self
+    }
+
+    /// Makes the value report through `metrics`.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn set_metrics(&mut self, metrics: Metrics) {
+        self.metrics = Some(metrics);
+    }
+
+    #[cfg(feature = "metrics")]
+    pub(crate) fn metrics(&self) -> Option<&Metrics> {
+        self.metrics.as_ref()
+    }
+
+    /// Moves displaced values into `sink` instead of dropping them, see `Rcu::set_retire_sink`.
+    pub(crate) fn set_retire_sink(&self, sink: Option<Arc<dyn Fn(T) + Send + Sync>>) {
+        self.sink.set(sink);
+    }
+
+    /// Wraps the displaced `ptr` for the engine, going to the sink, counted while sized and
+    /// kept alive for its owned snapshots.
+    ///
+    /// # Safety
+    /// `ptr` must still be alive.
+    unsafe fn wrap(&self, ptr: *mut T) -> Retired {
+        self.bytes.track(self.bytes.size(&*ptr), self.pins.track(ptr as usize, self.sink.retired(ptr)))
+    }
+
+    /// Like `wrap`, for a value just displaced by a publish, whose grace period starts now.
+    ///
+    /// # Safety
+    /// As for `wrap`.
+    unsafe fn retired(&self, ptr: *mut T) -> Retired {
+        #[cfg(feature = "diagnostics")]
+        return self.grace.time(self.wrap(ptr), self.watchdog());
+        #[cfg(not(feature = "diagnostics"))]
+        self.wrap(ptr)
+    }
+
+    /// Takes the current value out, without copying it unless owned snapshots hold on to it.
+    pub(crate) fn into_box(mut self) -> Box<T>
+    where
+        T: Clone,
+    {
+        self.unpin_current();
+        // Left null for `drop`, which frees everything else
+        let current = mem::replace(self.data_ptr.get_mut(), ptr::null_mut());
+        // Safety: we own `self`, so there are no readers, and the current value is never retired
+        unsafe { Box::from_raw(current) }
+    }
+
+    /// Number of allocations holding values of this `RawRcu` that aren't freed yet: the current
+    /// value, the previous one, those displaced but not freed, see `pending_retired`, and those
+    /// only owned snapshots hold on to.
+    pub(crate) fn outstanding_allocations(&self) -> usize {
+        1 + usize::from(!self.previous.load(Relaxed).is_null()) + self.pending_retired() + self.pins.held()
+    }
+
+    /// The current value, kept alive until the returned snapshot is dropped, without holding a
+    /// read-side critical section meanwhile.
+    #[track_caller]
+    pub(crate) fn pin(&self) -> Snapshot<T> {
+        self.pins.start();
+        let guard = self.read_guard();
+        let value: *const T = &*guard;
+        self.pins.pin(value as usize);
+        Snapshot {
+            value,
+            pins: Arc::clone(&self.pins),
+            _owns: PhantomData,
+        }
+    }
+
+    /// Replaces the current value by a copy of it if owned snapshots hold on to it, before it is
+    /// handed out mutably, leaving the original to them.
+    fn unpin_current(&mut self)
+    where
+        T: Clone,
+    {
+        let current = *self.data_ptr.get_mut();
+        if !self.pins.is_pinned(current as usize) {
+            return;
+        }
+        // Safety: the current value is never retired
+        let copy = Box::into_raw(Box::new(unsafe { (*current).clone() }));
+        *self.data_ptr.get_mut() = copy;
+        *self.prev_ptr.get_mut() = copy;
+        // Safety: borrowing `self` mutably rules out any reader but the snapshots
+        unsafe { self.pins.free(current as usize, Retired::new(current)) };
+    }
+
+    /// The number of successful publishes so far.
+    pub(crate) fn version(&self) -> u64 {
+        self.version.load(SeqCst)
+    }
+
+    /// Like [`RawRcu::version`], for callers that only need to notice a change eventually.
+    pub(crate) fn version_relaxed(&self) -> u64 {
+        self.version.load(Relaxed)
+    }
+
+    /// When the latest publish happened, see `staleness::now`. Set before the publish's
+    /// `published` runs.
+    pub(crate) fn published_at(&self) -> u64 {
+        self.published_at.load(SeqCst)
+    }
+
+    /// Observes the grace-period waits of this value's engine.
+    #[cfg(feature = "diagnostics")]
+    pub(crate) fn watchdog(&self) -> &Watchdog {
+        self.reclaimer.watchdog()
+    }
+
+    /// See `Rcu::grace_period_histogram`.
+    #[cfg(feature = "diagnostics")]
+    pub(crate) fn grace_period_histogram(&self) -> GraceHistogram {
+        self.grace.histogram()
+    }
+
+    /// Forbids any further publish, waiting for those in progress to complete. Readers of a frozen
+    /// value skip the read-side critical section. Must not be called from a publish's `published`.
+    pub(crate) fn freeze(&self) {
+        self.gate.freeze(&self.waiter);
+    }
+
+    /// Whether [`RawRcu::freeze`] completed.
+    pub(crate) fn is_frozen(&self) -> bool {
+        self.gate.is_frozen()
+    }
+
+    /// The current value, if frozen: it then stays alive for as long as `self`.
+    pub(crate) fn frozen_ref(&self) -> Option<&T> {
+        // Safety: frozen values are never retired, only dropped with `self`
+        self.is_frozen().then(|| unsafe { &*self.data_ptr.load(Relaxed) })
+    }
+
+    /// The current value, borrowed exclusively: borrowing `self` mutably rules out any reader,
+    /// owned snapshots being left the original.
+    pub(crate) fn get_mut(&mut self) -> &mut T
+    where
+        T: Clone,
+    {
+        self.unpin_current();
+        // Safety: the current value is never retired, and nobody else can reach it meanwhile
+        unsafe { &mut *self.data_ptr.load(Relaxed) }
+    }
+
+    /// Lets a publish in unless the value is being frozen, None otherwise. The publish counts as
+    /// in progress until the returned guard is dropped.
+    fn enter_publish(&self) -> Option<Publishing<'_>> {
+        self.gate.enter(&self.waiter)
+    }
+
+    /// Runs `f` on the current value inside a read-side critical section.
+    #[track_caller]
+    pub(crate) fn read_with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.read_guard())
+    }
+
+    /// The current value, kept alive by a read-side critical section until the guard is dropped.
+    #[track_caller]
+    pub(crate) fn read_guard(&self) -> RawReadGuard<'_, T> {
+        #[cfg(feature = "metrics")]
+        self.count_read();
+        if let Some(frozen) = self.frozen_ref() {
+            return RawReadGuard {
+                value: frozen,
+                _lock: None,
+                _not_send: PhantomData,
+            };
+        }
+        let lock = ReadLock::new(&self.reclaimer);
+        RawReadGuard {
+            // Safety: `self.data_ptr` will never be null, and the read lock keeps it from being reclaimed
+            value: unsafe { &*self.data_ptr.load(SeqCst) },
+            _lock: Some(lock),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Runs `f` on the current value like `read_with`, only using async-signal-safe operations,
+    /// see `Rcu::read_signal_safe`. Not counted in the metrics, whose recorder may lock or allocate.
+    pub(crate) fn read_signal_safe<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        if let Some(frozen) = self.frozen_ref() {
+            return f(frozen);
+        }
+        let _lock = SignalSafeLock::new(&self.reclaimer);
+        // Safety: `self.data_ptr` will never be null, and the lock keeps it from being reclaimed
+        f(unsafe { &*self.data_ptr.load(SeqCst) })
+    }
+
+    /// The current value, kept alive by a read-side critical section entered beforehand.
+    ///
+    /// # Panics
+    /// If `lock` wasn't entered on this value's engine.
+    pub(crate) fn read_in<'a>(&'a self, lock: &'a ReadLock<'_>) -> &'a T {
+        assert!(lock.is_on(&self.reclaimer), "read lock taken on another engine");
+        #[cfg(feature = "metrics")]
+        self.count_read();
+        // Safety: `self.data_ptr` will never be null, and the read lock keeps it from being reclaimed
+        unsafe { &*self.data_ptr.load(SeqCst) }
+    }
+
+    /// Runs `f` on the current value and the version it was published as, inside a read-side
+    /// critical section. Waits out a publish in flight, whose value and version don't match yet.
+    #[track_caller]
+    pub(crate) fn read_versioned<R>(&self, f: impl FnOnce(&T, u64) -> R) -> R {
+        self.read_stamped(|value, version, _| f(value, version))
+    }
+
+    /// Like [`RawRcu::read_versioned`], also passing when the value was published, see
+    /// `staleness::now`, which is just as coherent with it.
+    #[track_caller]
+    pub(crate) fn read_stamped<R>(&self, f: impl FnOnce(&T, u64, u64) -> R) -> R {
+        #[cfg(feature = "metrics")]
+        self.count_read();
+        if let Some(frozen) = self.frozen_ref() {
+            return f(frozen, self.version.load(Relaxed), self.published_at.load(Relaxed));
+        }
+        let mut attempt = 0;
+        loop {
+            let lock = ReadLock::new(&self.reclaimer);
+            let cur = self.data_ptr.load(SeqCst);
+            // `cur`'s publish is complete once `prev_ptr` caught up, so its version bump and timestamp
+            // are visible, and if `data_ptr` is still `cur` afterwards no later publish has bumped
+            // or overwritten them yet
+            let settled = self.prev_ptr.load(SeqCst) == cur;
+            let version = self.version.load(SeqCst);
+            let published_at = self.published_at.load(SeqCst);
+            if settled && self.data_ptr.load(SeqCst) == cur {
+                // Safety: the read lock keeps `cur` alive
+                return f(unsafe { &*cur }, version, published_at);
+            }
+            // Never wait inside the read lock, the publish in flight may be waiting for readers to drain
+            drop(lock);
+            self.waiter.wait(attempt);
+            attempt += 1;
+        }
+    }
+
+    /// Runs `f` on the current value and the one published before it, None before the first
+    /// publish or unless `keep_previous`, inside a read-side critical section. Both always come from
+    /// the same publish: waits out a publish in flight, which hasn't moved the previous value on yet.
+    #[track_caller]
+    pub(crate) fn read_pair<R>(&self, f: impl FnOnce(&T, Option<&T>) -> R) -> R {
+        #[cfg(feature = "metrics")]
+        self.count_read();
+        if let Some(frozen) = self.frozen_ref() {
+            // Safety: `previous` is only retired once replaced, which can't happen anymore
+            return f(frozen, unsafe { self.previous.load(Relaxed).as_ref() });
+        }
+        let mut attempt = 0;
+        loop {
+            let lock = ReadLock::new(&self.reclaimer);
+            let cur = self.data_ptr.load(SeqCst);
+            // `previous` is moved on before `prev_ptr` catches up, and before any later publish
+            // could have moved `data_ptr` on again
+            let settled = self.prev_ptr.load(SeqCst) == cur;
+            let previous = self.previous.load(SeqCst);
+            if settled && self.data_ptr.load(SeqCst) == cur {
+                // Safety: the read lock keeps both alive, `previous` is only retired once replaced
+                return f(unsafe { &*cur }, unsafe { previous.as_ref() });
+            }
+            // Never wait inside the read lock, the publish in flight may be waiting for readers to drain
+            drop(lock);
+            self.waiter.wait(attempt);
+            attempt += 1;
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn count_read(&self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.read();
+        }
+    }
+
+    /// Publishes `neo` unless another publish is in flight or the value is frozen, in which case it is handed back.
+    /// `published` runs with the new value once it is visible, while no other writer can replace it
+    /// yet. If it panics the publish is still completed before the panic is resumed.
+    ///
+    /// Waiting for readers of the displaced value gives up at `deadline`, if any. Returns whether the
+    /// displaced value was dealt with in time, if not it is left for a later grace period to free.
+    pub(crate) fn publish_before(
+        &self,
+        neo: Box<T>,
+        deadline: Option<Instant>,
+        published: impl FnOnce(&T),
+    ) -> Result<bool, Refused<T>> {
+        let Some(_publishing) = self.enter_publish() else {
+            return Err(Refused::Frozen(neo));
+        };
+        let prev = self.prev_ptr.load(Acquire);
+        // Safety: `prev` was swapped out if this succeeds, and only this thread can retire it
+        self.swap_from(prev, neo)
+            .map(|neo| unsafe { self.finish(neo, prev, deadline, published) })
+            .map_err(Refused::InFlight)
+    }
+
+    /// Publishes `neo` unconditionally, by a plain exchange of the pointer.
+    ///
+    /// # Safety
+    /// No other publish may ever run concurrently with this one, through any method.
+    pub(crate) unsafe fn publish_exclusive(&self, neo: Box<T>, published: impl FnOnce(&T)) {
+        let neo = Box::into_raw(neo);
+        let old = self.data_ptr.swap(neo, SeqCst);
+        self.finish(neo, old, None, published);
+    }
+
+    /// Publishes `f(current)`, re-running `f` on the newer value whenever another writer got
+    /// there first, until it sticks, or gives up if the value is frozen. Unlike `publish_before` no
+    /// concurrent update is ever lost.
+    pub(crate) fn modify(&self, mut f: impl FnMut(&T) -> T) {
+        let mut attempt = 0;
+        while !matches!(
+            self.try_modify(|cur| Ok::<_, Infallible>(Box::new(f(cur))), |_| {}),
+            Modify::Published | Modify::Frozen(_)
+        ) {
+            self.waiter.wait(attempt);
+            attempt += 1;
+        }
+    }
+
+    /// A single attempt at publishing the value produced by `f(current)`, failing if another
+    /// writer replaced `current` before the new value could be swapped in. `published` is as for `publish_before`.
+    ///
+    /// Waits for any publish in flight to complete first, so `f` is only ever given fully
+    /// published values and isn't called repeatedly against the same one.
+    #[track_caller]
+    pub(crate) fn try_modify<E>(
+        &self,
+        f: impl FnOnce(&T) -> Result<Box<T>, E>,
+        published: impl FnOnce(&T),
+    ) -> Modify<T, E> {
+        self.wait_settled();
+        let lock = ReadLock::new(&self.reclaimer);
+        let cur = self.data_ptr.load(SeqCst);
+        // Safety: the read lock keeps `cur` alive
+        let neo = match f(unsafe { &*cur }) {
+            Ok(neo) => neo,
+            Err(err) => return Modify::Aborted(err),
+        };
+        let Some(_publishing) = self.enter_publish() else {
+            return Modify::Frozen(neo);
+        };
+        // Hold the read lock over the exchange so `cur` can't be freed and its address
+        // reused, which would let the exchange succeed against a value `f` never saw
+        let swapped = self.swap_from(cur, neo);
+        drop(lock);
+        match swapped {
+            Ok(neo) => {
+                // Safety: `cur` was swapped out above, and only this thread can retire it
+                unsafe { self.finish(neo, cur, None, published) };
+                Modify::Published
+            }
+            Err(neo) => Modify::Conflict(neo),
+        }
+    }
+
+    /// Waits until no publish is in flight.
+    pub(crate) fn wait_settled(&self) {
+        // Never call this inside a read lock, the in-flight publish may be waiting for readers to drain
+        self.waiter.wait_while(|| self.prev_ptr.load(Acquire) != self.data_ptr.load(SeqCst), None);
+    }
+
+    /// Like [`RawRcu::wait_settled`], giving up at `deadline`. Returns whether no publish is in flight.
+    pub(crate) fn wait_settled_before(&self, deadline: Instant) -> bool {
+        self.waiter
+            .wait_while(|| self.prev_ptr.load(Acquire) != self.data_ptr.load(SeqCst), Some(deadline))
+    }
+
+    /// Waits until every read-side critical section active when this is called has exited. Never
+    /// call this inside a read lock on this value, it would wait for itself.
+    pub(crate) fn synchronize(&self) {
+        self.reclaimer.synchronize();
+    }
+
+    /// Hands every displaced value held back by batching to the engine, then waits until the
+    /// engine freed everything handed to it before, see `Reclaim::barrier`.
+    pub(crate) fn barrier(&self) {
+        self.flush_retired();
+        self.reclaimer.barrier();
+    }
+
+    /// Hands an allocation reachable by this value's readers over to its engine, see `Reclaim::retire`.
+    ///
+    /// # Safety
+    /// As for `Reclaim::retire`.
+    pub(crate) unsafe fn retire(&self, retired: Retired) {
+        self.reclaimer.retire(retired);
+    }
+
+    /// Swaps `neo` in if `expected` is the current, fully published value, returning the now published pointer.
+    fn swap_from(&self, expected: *mut T, neo: Box<T>) -> Result<*mut T, Box<T>> {
+        let neo = Box::into_raw(neo);
+        // A publish is only complete once `prev_ptr` caught up with `data_ptr`, racing one that
+        // is still in flight would leave `prev_ptr` pointing at a displaced value
+        if self.prev_ptr.load(Acquire) == expected
+            && self.data_ptr.compare_exchange(expected, neo, SeqCst, Relaxed).is_ok()
+        {
+            return Ok(neo);
+        }
+        // Safety: We know nothing will read from neo ever again.
+        // Unsuccessful, hand neo back
+        Err(unsafe { Box::from_raw(neo) })
+    }
+
+    /// Completes a publish started by `swap_from`, returning whether `old` was dealt with before `deadline`.
+    ///
+    /// # Safety
+    /// `old` must be the value just swapped out for `neo` by this thread.
+    unsafe fn finish(&self, neo: *mut T, old: *mut T, deadline: Option<Instant>, published: impl FnOnce(&T)) -> bool {
+        let (displaced, published) = self.install(neo, old, published);
+        // Nothing to report if nothing was displaced
+        #[cfg(feature = "metrics")]
+        let retiring = (self.metrics.is_some() && !displaced.is_null()).then(Instant::now);
+        let in_time = match deadline {
+            _ if displaced.is_null() => true,
+            _ if self.batch.is_on() => match self.batch.push(self.retired(displaced)) {
+                Some(due) => self.reclaimer.retire_batch(due, deadline),
+                // Left for the grace period of a later batch
+                None => true,
+            },
+            None => {
+                self.reclaimer.retire(self.retired(displaced));
+                true
+            }
+            Some(deadline) => self.reclaimer.retire_before(self.retired(displaced), deadline),
+        };
+        #[cfg(feature = "metrics")]
+        if let Some(retiring) = retiring {
+            self.report_retired(retiring);
+        }
+        // Reset `self.prev_ptr` to newly allocated data, for future updates
+        self.prev_ptr.store(neo, SeqCst);
+        self.waiter.notify();
+        if let Err(payload) = published {
+            panic::resume_unwind(payload);
+        }
+        in_time
+    }
+
+    /// The first half of `finish`: counts the publish and runs `published`, whose panic is handed
+    /// back, returning the value it displaced, null if none.
+    ///
+    /// # Safety
+    /// As for `finish`.
+    unsafe fn install(&self, neo: *mut T, old: *mut T, published: impl FnOnce(&T)) -> (*mut T, std::thread::Result<()>) {
+        self.published_at.store(staleness::now(), SeqCst);
+        self.version.fetch_add(1, SeqCst);
+        // Nobody else can publish until `self.prev_ptr` is moved on, so `neo` is still alive here
+        let published = panic::catch_unwind(AssertUnwindSafe(|| published(&*neo)));
+        // With `keep_previous`, `old` stays readable as the previous value and the one it replaces
+        // there is displaced instead. From this point on we know no new threads will read the displaced
+        // data, hand it to the reclaimer to be dropped once current readers are done with it.
+        let displaced = if self.keep_previous {
+            self.previous.swap(old, SeqCst)
+        } else {
+            old
+        };
+        (displaced, published)
+    }
+
+    #[cfg(feature = "metrics")]
+    fn report_retired(&self, retiring: Instant) {
+        if let Some(metrics) = &self.metrics {
+            metrics.retired(retiring.elapsed(), self.pending_retired());
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> RawRcu<T> {
+    /// Like `publish_before`, without waiting for readers of the displaced value: the publish is
+    /// complete on return, and the displaced value is freed by awaiting the returned future.
+    pub(crate) fn publish_detached(
+        &self,
+        neo: Box<T>,
+        published: impl FnOnce(&T),
+    ) -> Result<Reclamation<'_, T>, Refused<T>> {
+        let Some(_publishing) = self.enter_publish() else {
+            return Err(Refused::Frozen(neo));
+        };
+        let prev = self.prev_ptr.load(Acquire);
+        // Safety: `prev` was swapped out if this succeeds, and only this thread can retire it
+        self.swap_from(prev, neo)
+            .map(|neo| unsafe { self.finish_detached(neo, prev, published) })
+            .map_err(Refused::InFlight)
+    }
+
+    /// Like `try_modify`, publishing like `publish_detached`. Doesn't wait for a publish in flight
+    /// either, but fails with a conflict, so check `is_settled` first.
+    pub(crate) fn try_modify_detached<E>(
+        &self,
+        f: impl FnOnce(&T) -> Result<Box<T>, E>,
+        published: impl FnOnce(&T),
+    ) -> Result<Reclamation<'_, T>, Modify<T, E>> {
+        let lock = ReadLock::new(&self.reclaimer);
+        let cur = self.data_ptr.load(SeqCst);
+        // Safety: the read lock keeps `cur` alive
+        let neo = f(unsafe { &*cur }).map_err(Modify::Aborted)?;
+        let Some(_publishing) = self.enter_publish() else {
+            return Err(Modify::Frozen(neo));
+        };
+        // As in `try_modify`
+        let swapped = self.swap_from(cur, neo);
+        drop(lock);
+        // Safety: `cur` was swapped out if this succeeds, and only this thread can retire it
+        swapped
+            .map(|neo| unsafe { self.finish_detached(neo, cur, published) })
+            .map_err(Modify::Conflict)
+    }
+
+    /// Whether no publish is in flight, see `wait_settled`.
+    pub(crate) fn is_settled(&self) -> bool {
+        self.prev_ptr.load(Acquire) == self.data_ptr.load(SeqCst)
+    }
+
+    /// Like `finish`, handing the displaced value to the returned future instead of waiting for its
+    /// readers, so the next publish can start right away. If `published` panics the displaced value
+    /// is retired as `finish` would before the panic is resumed.
+    ///
+    /// # Safety
+    /// As for `finish`.
+    unsafe fn finish_detached(&self, neo: *mut T, old: *mut T, published: impl FnOnce(&T)) -> Reclamation<'_, T> {
+        let (displaced, published) = self.install(neo, old, published);
+        if let Err(payload) = published {
+            if !displaced.is_null() {
+                self.reclaimer.retire(self.retired(displaced));
+            }
+            self.prev_ptr.store(neo, SeqCst);
+            self.waiter.notify();
+            panic::resume_unwind(payload);
+        }
+        self.prev_ptr.store(neo, SeqCst);
+        self.waiter.notify();
+        let retired = (!displaced.is_null())
+            .then(|| self.retired(displaced))
+            .and_then(|retired| self.reclaimer.retire_biased(retired));
+        Reclamation {
+            raw: self,
+            ticket: if retired.is_some() { self.reclaimer.start_grace_period() } else { 0 },
+            retired,
+            #[cfg(feature = "metrics")]
+            retiring: Instant::now(),
+        }
+    }
+}
+
+/// Frees a value displaced by a detached publish once the readers that may still observe it are
+/// done, without blocking: the task is woken by their exit. Dropped before that, it leaves the
+/// value for a later grace period to free.
+#[cfg(feature = "async")]
+#[must_use = "the displaced value is only freed by awaiting this, or later once dropped"]
+pub(crate) struct Reclamation<'a, T> {
+    raw: &'a RawRcu<T>,
+    /// None once freed, or if there was nothing to wait for
+    retired: Option<Retired>,
+    /// See `Reclaim::start_grace_period`
+    ticket: usize,
+    #[cfg(feature = "metrics")]
+    retiring: Instant,
+}
+
+#[cfg(feature = "async")]
+impl<T> Future for Reclamation<'_, T> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.retired.is_some() && !self.raw.reclaimer.poll_grace_period(self.ticket, cx.waker()) {
+            return Poll::Pending;
+        }
+        if let Some(retired) = self.retired.take() {
+            // Safety: the grace period started after `retired` was unpublished is over
+            unsafe { retired.reclaim() };
+            #[cfg(feature = "metrics")]
+            self.raw.report_retired(self.retiring);
+        }
+        Poll::Ready(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> Drop for Reclamation<'_, T> {
+    fn drop(&mut self) {
+        if let Some(retired) = self.retired.take() {
+            // Waiting would block the task being cancelled, defer it to the next grace period instead.
+            // Safety: `retired` was unpublished and is only ever handed over once
+            unsafe { self.raw.reclaimer.retire_before(retired, Instant::now()) };
+        }
+    }
+}
+
+/// A value read from a `RawRcu`, together with the read-side critical section keeping it alive.
+pub(crate) struct RawReadGuard<'a, T: ?Sized> {
+    value: &'a T,
+    /// None for a frozen value, which is never retired
+    _lock: Option<ReadLock<'a, Engine>>,
+    /// A biased engine tracks its owner's critical sections with plain stores, so one must be
+    /// exited on the thread that entered it
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<T: ?Sized> Deref for RawReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<'a, T: ?Sized> RawReadGuard<'a, T> {
+    /// The guard of the part of the value `f` returns, holding the same critical section. Should `f`
+    /// return None, or panic, the critical section is handed back, or exited, with `self`.
+    pub(crate) fn try_map<U: ?Sized>(self, f: impl FnOnce(&T) -> Option<&U>) -> Result<RawReadGuard<'a, U>, Self> {
+        match f(self.value) {
+            Some(value) => Ok(RawReadGuard {
+                value,
+                _lock: self._lock,
+                _not_send: PhantomData,
+            }),
+            None => Err(self),
+        }
+    }
+}
+
+/// A value pinned by `RawRcu::pin`, kept alive until this is dropped on any thread.
+pub(crate) struct Snapshot<T> {
+    value: *const T,
+    pins: Arc<Pins>,
+    _owns: PhantomData<T>,
+}
+
+// Safety: a snapshot only hands out shared references to the value, which stays alive until it
+// is dropped on any thread
+unsafe impl<T: Send + Sync> Send for Snapshot<T> {}
+unsafe impl<T: Send + Sync> Sync for Snapshot<T> {}
+
+impl<T> Deref for Snapshot<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Safety: the value isn't freed while pinned
+        unsafe { &*self.value }
+    }
+}
+
+impl<T> Drop for Snapshot<T> {
+    fn drop(&mut self) {
+        self.pins.unpin(self.value as usize);
+    }
+}
+
+// Safety: values are shared between readers and dropped by whichever thread reclaims them
+impl<T> Drop for RawRcu<T> {
+    fn drop(&mut self) {
+        for retired in self.batch.take() {
+            // Safety: we own `self`, so there are no readers
+            unsafe { retired.reclaim() };
+        }
+        let previous = *self.previous.get_mut();
+        if !previous.is_null() {
+            // Safety: we own `self`, so there are no readers, and the previous value is only
+            // retired once replaced
+            unsafe { self.wrap(previous).reclaim() };
+        }
+        let current = *self.data_ptr.get_mut();
+        if !current.is_null() {
+            // Safety: we own `self`, so there are no readers but owned snapshots, and the current
+            // value is never retired
+            unsafe { self.pins.free(current as usize, Retired::new(current)) };
+        }
+        // What the engine still holds is freed once it is dropped in turn, or by a later grace
+        // period of a domain sharing it
+    }
+}
+
+unsafe impl<T: Send + Sync> Send for RawRcu<T> {}
+unsafe impl<T: Send + Sync> Sync for RawRcu<T> {}
@@ -0,0 +1,360 @@
+//! The atomic and the locked backend checked against each other: the same seeded scripts run on
+//! both, and whatever one observes, down to the order values are dropped and delivered to a sink in,
+//! the other must observe too. Both are built whatever the features, so every backend's tests
+//! compare them over the engine the features select.
+
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::{Modify, Refused};
+use crate::reclaim::Reclaimer;
+use crate::wait::Waiter;
+use crate::SpinYield;
+
+const SCRIPTS: u64 = 64;
+const OPS: usize = 200;
+
+/// What values log, shared by every value of one run.
+#[derive(Clone, Default)]
+struct Logs {
+    dropped: Arc<Mutex<Vec<u64>>>,
+    delivered: Arc<Mutex<Vec<u64>>>,
+}
+
+impl Logs {
+    fn value(&self, n: u64) -> Value {
+        Value {
+            n,
+            dropped: Arc::clone(&self.dropped),
+        }
+    }
+
+    /// What was dropped and delivered since the last call.
+    fn take(&self) -> (Vec<u64>, Vec<u64>) {
+        let take = |log: &Mutex<Vec<u64>>| std::mem::take(&mut *log.lock().unwrap_or_else(PoisonError::into_inner));
+        (take(&self.dropped), take(&self.delivered))
+    }
+}
+
+/// Logs its drop.
+struct Value {
+    n: u64,
+    dropped: Arc<Mutex<Vec<u64>>>,
+}
+
+impl Clone for Value {
+    fn clone(&self) -> Self {
+        Value {
+            n: self.n,
+            dropped: Arc::clone(&self.dropped),
+        }
+    }
+}
+
+impl Drop for Value {
+    fn drop(&mut self) {
+        self.dropped.lock().unwrap_or_else(PoisonError::into_inner).push(self.n);
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Flavour {
+    Own,
+    Biased,
+    Shared,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    /// `publish_before`, with a deadline or not, its hook attempting another publish or not
+    Publish { n: u64, deadline: bool, nested: Option<u64> },
+    /// `try_modify` adding `n`, refusing to on multiples of 5
+    TryModify(u64),
+    /// `modify` adding `n`
+    Modify(u64),
+    ReadStamped,
+    ReadPair,
+    /// Takes an owned snapshot, held until `Unpin` picks it
+    Pin,
+    /// Drops the `n`th snapshot held, modulo their count
+    Unpin(usize),
+    /// `get_mut` adding `n`
+    GetMut(u64),
+    Freeze,
+    /// Installs a sink, or removes it
+    Sink(bool),
+    Flush,
+    Barrier,
+    /// `publish_detached`, its reclamation awaited or dropped
+    #[cfg(feature = "async")]
+    PublishDetached { n: u64, awaited: bool },
+}
+
+/// The options a value is created with, and what is done with it.
+#[derive(Debug)]
+struct Script {
+    flavour: Flavour,
+    keep_previous: bool,
+    sized: bool,
+    batch: Option<usize>,
+    ops: Vec<Op>,
+}
+
+fn script(seed: u64) -> Script {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let flavour = [Flavour::Own, Flavour::Biased, Flavour::Shared][rng.gen_range(0..3)];
+    let (keep_previous, sized) = (rng.gen_bool(0.75), rng.gen_bool(0.5));
+    let batch = rng.gen_bool(0.25).then(|| rng.gen_range(1..5));
+    let ops = (0..OPS)
+        .map(|_| match rng.gen_range(0..100) {
+            0..=24 => Op::Publish {
+                n: rng.gen_range(0..1000),
+                deadline: rng.gen_bool(0.3),
+                nested: rng.gen_bool(0.2).then(|| rng.gen_range(0..1000)),
+            },
+            25..=34 => Op::TryModify(rng.gen_range(1..10)),
+            35..=44 => Op::Modify(rng.gen_range(1..10)),
+            45..=54 => Op::ReadStamped,
+            55..=64 => Op::ReadPair,
+            65..=71 => Op::Pin,
+            72..=78 => Op::Unpin(rng.gen_range(0..8)),
+            79..=81 => Op::GetMut(rng.gen_range(1..10)),
+            82 if rng.gen_bool(0.2) => Op::Freeze,
+            83..=86 => Op::Sink(rng.gen_bool(0.5)),
+            87..=89 => Op::Flush,
+            90..=93 => Op::Barrier,
+            #[cfg(feature = "async")]
+            94..=99 => Op::PublishDetached {
+                n: rng.gen_range(0..1000),
+                awaited: rng.gen_bool(0.5),
+            },
+            _ => Op::ReadStamped,
+        })
+        .collect();
+    Script {
+        flavour,
+        keep_previous,
+        sized,
+        batch,
+        ops,
+    }
+}
+
+/// Why a publish was refused, with the value handed back.
+fn refusal(refused: Refused<Value>) -> (&'static str, u64) {
+    match refused {
+        Refused::InFlight(value) => ("in flight", value.n),
+        Refused::Frozen(value) => ("frozen", value.n),
+    }
+}
+
+fn pair(value: &Value, previous: Option<&Value>) -> Seen {
+    Seen::Pair(value.n, previous.map(|previous| previous.n))
+}
+
+fn outcome(modified: Modify<Value, ()>) -> (&'static str, Option<u64>) {
+    match modified {
+        Modify::Published => ("published", None),
+        Modify::Frozen(value) => ("frozen", Some(value.n)),
+        Modify::Conflict(value) => ("conflict", Some(value.n)),
+        Modify::Aborted(()) => ("aborted", None),
+    }
+}
+
+/// Everything a run observes, in order.
+#[derive(Debug, PartialEq)]
+enum Seen {
+    Published(Result<bool, (&'static str, u64)>),
+    /// What a publish's hook saw published, and the outcome of the publish it attempted, if any
+    Hook(u64, Option<Result<bool, (&'static str, u64)>>),
+    Modified((&'static str, Option<u64>)),
+    Stamped(u64, u64),
+    Pair(u64, Option<u64>),
+    Pinned(u64),
+    Mutated(u64),
+    /// The state after every op
+    State {
+        version: u64,
+        frozen: Option<u64>,
+        batched: usize,
+        pending: usize,
+        bytes: usize,
+        outstanding: usize,
+        dropped: Vec<u64>,
+        delivered: Vec<u64>,
+    },
+    /// What dropping the remaining snapshots, and then the value, dropped
+    Dropped(Vec<u64>, Vec<u64>),
+}
+
+/// A function running a script on one backend, returning what it observes.
+macro_rules! runner {
+    ($run:ident, $backend:ident) => {
+        fn $run(script: &Script) -> Vec<Seen> {
+            use super::$backend::RawRcu;
+
+            let logs = Logs::default();
+            let domain = Arc::<Reclaimer>::default();
+            let initial = Box::new(logs.value(0));
+            let mut raw = match script.flavour {
+                Flavour::Own => RawRcu::new(initial),
+                Flavour::Biased => RawRcu::new_biased(initial),
+                Flavour::Shared => RawRcu::new_shared(initial, Arc::clone(&domain)),
+            };
+            if script.keep_previous {
+                raw = raw.keeping_previous();
+            }
+            if script.sized {
+                raw.set_retired_size(|value| value.n as usize);
+            }
+            if let Some(max_len) = script.batch {
+                raw.set_retire_batching(max_len, Duration::from_secs(3600));
+            }
+            let mut seen = Vec::new();
+            let mut snapshots = Vec::new();
+            for op in &script.ops {
+                match *op {
+                    Op::Publish { n, deadline, nested } => {
+                        let mut hook = None;
+                        let deadline = deadline.then(|| Instant::now() + Duration::from_secs(5));
+                        let published = raw.publish_before(Box::new(logs.value(n)), deadline, |value| {
+                            let nested = nested.map(|m| {
+                                raw.publish_before(Box::new(logs.value(m)), None, |_| {}).map_err(refusal)
+                            });
+                            hook = Some(Seen::Hook(value.n, nested));
+                        });
+                        seen.push(Seen::Published(published.map_err(refusal)));
+                        seen.extend(hook);
+                    }
+                    Op::TryModify(n) => {
+                        let modified = raw.try_modify(
+                            |cur| if cur.n % 5 == 0 { Err(()) } else { Ok(Box::new(logs.value(cur.n + n))) },
+                            |_| {},
+                        );
+                        seen.push(Seen::Modified(outcome(modified)));
+                    }
+                    Op::Modify(n) => raw.modify(|cur| logs.value(cur.n + n)),
+                    Op::ReadStamped => seen.push(raw.read_stamped(|value, version, _| Seen::Stamped(value.n, version))),
+                    Op::ReadPair => seen.push(raw.read_pair(pair)),
+                    Op::Pin => {
+                        let snapshot = raw.pin();
+                        seen.push(Seen::Pinned(snapshot.n));
+                        snapshots.push(snapshot);
+                    }
+                    Op::Unpin(i) if !snapshots.is_empty() => drop(snapshots.remove(i % snapshots.len())),
+                    Op::Unpin(_) => {}
+                    Op::GetMut(n) => {
+                        let value = raw.get_mut();
+                        value.n += n;
+                        seen.push(Seen::Mutated(value.n));
+                    }
+                    Op::Freeze => raw.freeze(),
+                    Op::Sink(true) => {
+                        let delivered = Arc::clone(&logs.delivered);
+                        raw.set_retire_sink(Some(Arc::new(move |value: Value| {
+                            delivered.lock().unwrap_or_else(PoisonError::into_inner).push(value.n);
+                        })));
+                    }
+                    Op::Sink(false) => raw.set_retire_sink(None),
+                    Op::Flush => raw.flush_retired(),
+                    Op::Barrier => raw.barrier(),
+                    #[cfg(feature = "async")]
+                    Op::PublishDetached { n, awaited } => {
+                        match raw.publish_detached(Box::new(logs.value(n)), |_| {}) {
+                            Ok(reclamation) if awaited => futures::executor::block_on(reclamation),
+                            Ok(reclamation) => drop(reclamation),
+                            Err(refused) => seen.push(Seen::Published(Err(refusal(refused)))),
+                        }
+                    }
+                }
+                let (dropped, delivered) = logs.take();
+                seen.push(Seen::State {
+                    version: raw.version(),
+                    frozen: raw.frozen_ref().map(|value| value.n),
+                    batched: raw.batched(),
+                    pending: raw.pending_retired(),
+                    bytes: raw.retired_bytes(),
+                    outstanding: raw.outstanding_allocations(),
+                    dropped,
+                    delivered,
+                });
+            }
+            drop(snapshots);
+            drop(raw);
+            drop(domain);
+            let (dropped, delivered) = logs.take();
+            seen.push(Seen::Dropped(dropped, delivered));
+            seen
+        }
+    };
+}
+
+runner!(run_atomic, atomic);
+runner!(run_locked, locked);
+
+#[test]
+fn scripts_observe_the_same_on_both_backends() {
+    for seed in 0..SCRIPTS {
+        let script = script(seed);
+        let (atomic, locked) = (run_atomic(&script), run_locked(&script));
+        // The first difference, rather than both traces whole
+        let at = atomic.iter().zip(&locked).position(|(atomic, locked)| atomic != locked);
+        if let Some(at) = at {
+            panic!(
+                "seed {seed}: the backends differ at observation {at}: {:?} on the atomic one, {:?} on the locked one\n\
+                 {script:?}",
+                atomic[at], locked[at],
+            );
+        }
+        assert_eq!(atomic.len(), locked.len(), "seed {seed}: one backend observed more");
+    }
+}
+
+/// Runs concurrent `modify`s of +1, checking that readers only ever see a previous value one less
+/// than the current one, returning the final value and version.
+macro_rules! concurrent {
+    ($run:ident, $backend:ident) => {
+        fn $run() -> (u64, u64) {
+            use super::$backend::RawRcu;
+
+            const THREADS: u64 = 4;
+            const MODIFIES: u64 = 200;
+            let logs = Logs::default();
+            let mut raw = RawRcu::new(Box::new(logs.value(0))).keeping_previous();
+            raw.set_waiter(Waiter::new(Arc::new(SpinYield::default())));
+            thread::scope(|s| {
+                for _ in 0..THREADS {
+                    s.spawn(|| {
+                        for _ in 0..MODIFIES {
+                            raw.modify(|cur| logs.value(cur.n + 1));
+                        }
+                    });
+                }
+                s.spawn(|| {
+                    while raw.read_pair(|cur, previous| {
+                        if let Some(previous) = previous {
+                            assert_eq!(previous.n + 1, cur.n, "read a previous value from another publish");
+                        }
+                        cur.n < THREADS * MODIFIES
+                    }) {
+                        thread::yield_now();
+                    }
+                });
+            });
+            raw.read_stamped(|value, version, _| (value.n, version))
+        }
+    };
+}
+
+concurrent!(concurrent_atomic, atomic);
+concurrent!(concurrent_locked, locked);
+
+#[test]
+fn concurrent_modifies_end_the_same_on_both_backends() {
+    assert_eq!(concurrent_atomic(), (800, 800));
+    assert_eq!(concurrent_locked(), (800, 800));
+}
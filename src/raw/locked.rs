@@ -0,0 +1,954 @@
+// Only built for the differential tests without `fallback-lock`, which don't use all of it
+#![cfg_attr(not(feature = "fallback-lock"), allow(dead_code))]
+
+use std::convert::Infallible;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(feature = "async")]
+use std::{future::Future, pin::Pin, task::{Context, Poll}};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+#[cfg(feature = "diagnostics")]
+use crate::diagnostics::{GraceHistogram, GraceStats};
+#[cfg(feature = "diagnostics")]
+use crate::reclaim::Watchdog;
+use crate::reclaim::{Engine, Reclaim, Reclaimer, ReadLock, Retired, SignalSafeLock};
+use crate::backpressure::RetiredBytes;
+use crate::batch::RetireBatch;
+use crate::sink::{self, RetireSink, Sink};
+use crate::staleness;
+use crate::wait::{Preference, Waiter};
+use super::{Modify, Refused};
+
+/// The publication protocol with `fallback-lock`: the current value in a `RwLock<Arc<T>>`, swapped
+/// under the write lock instead of by compare-exchange, with the same interface and semantics as
+/// the atomic backend.
+///
+/// Guards, snapshots and closures all read through a clone of the `Arc`, taken under the read
+/// lock, so what they read never depends on the engine: displaced values are freed by dropping their
+/// last `Arc`. They are still retired to the engine, whose grace period decides when the `RawRcu` lets
+/// go of them, for the sink, the backlog and barriers to behave as they do otherwise. The only
+/// reads relying on it are those of `read_in`, whose borrow nothing else keeps alive.
+///
+/// User code never runs under the lock, which is only ever held to read or swap the fields of `Published`.
+pub(crate) struct RawRcu<T> {
+    published: RwLock<Published<T>>,
+    /// The current value once frozen, set by the first read after `freeze`, or after `get_mut`
+    frozen: OnceLock<Arc<T>>,
+    /// Tracks readers, and decides when displaced values are let go of
+    reclaimer: Engine,
+    /// Whether displaced values are kept in `previous` until the next publish before being retired
+    keep_previous: bool,
+    /// Where displaced values go once they are let go of, instead of being dropped
+    sink: RetireSink<T>,
+    /// The size of displaced values not let go of yet, while sized
+    bytes: RetiredBytes<T>,
+    /// Displaced values let go of that owned snapshots still hold, shared with them
+    held: Arc<Held<T>>,
+    /// How long displaced values took to be let go of, shared with them until they are
+    #[cfg(feature = "diagnostics")]
+    grace: Arc<GraceStats>,
+    /// Displaced values not handed to the engine yet, while batching
+    batch: RetireBatch,
+    /// How waits for this value's publishes wait, the engine waiting its own way
+    waiter: Waiter,
+    /// Where reads and grace periods are reported, None for the crate's own bookkeeping
+    #[cfg(feature = "metrics")]
+    metrics: Option<Metrics>,
+}
+
+/// What the `RwLock` of a `RawRcu` guards.
+struct Published<T> {
+    current: Arc<T>,
+    /// The value published before the current one, None before the first publish or unless `keep_previous`
+    previous: Option<Arc<T>>,
+    /// Number of successful publishes so far
+    version: u64,
+    /// When the current value was published, or created if never, see `staleness::now`
+    published_at: u64,
+    /// Whether a publish swapped its value in and hasn't retired the one it displaced yet
+    in_flight: bool,
+    /// Set by `freeze`, no publish is let in anymore
+    closed: bool,
+    /// Set once `freeze` saw the publish in flight complete, if any
+    frozen: bool,
+}
+
+impl<T> Published<T> {
+    /// Makes `neo` the current value, returning the value it displaced, None if none: with
+    /// `keep_previous` the value it replaced stays readable as the previous one, and the one it
+    /// replaces there is displaced instead.
+    fn install(&mut self, neo: Arc<T>, keep_previous: bool) -> Option<Arc<T>> {
+        let old = mem::replace(&mut self.current, neo);
+        self.published_at = staleness::now();
+        self.version += 1;
+        self.in_flight = true;
+        if keep_previous {
+            self.previous.replace(old)
+        } else {
+            Some(old)
+        }
+    }
+}
+
+/// A displaced value retired to the engine, delivered by being dropped once the engine reclaims it.
+struct Retiring<T> {
+    /// Only None once dropped
+    value: Option<Arc<T>>,
+    /// The sink installed when it was displaced
+    sink: Option<Sink<T>>,
+    held: Arc<Held<T>>,
+}
+
+impl<T> Drop for Retiring<T> {
+    fn drop(&mut self) {
+        if let Some(value) = self.value.take() {
+            self.held.release(value, self.sink.take());
+        }
+    }
+}
+
+/// A displaced value with the sink it goes to.
+type HeldValue<T> = (Arc<T>, Option<Sink<T>>);
+
+/// A value just published, with the one it displaced.
+type Swapped<T> = (Arc<T>, Option<Arc<T>>);
+
+/// The displaced values of one `RawRcu` whose grace period is over but owned snapshots still hold,
+/// each with the sink it goes to once the last of them is dropped.
+struct Held<T> {
+    values: Mutex<Vec<HeldValue<T>>>,
+}
+
+impl<T> Default for Held<T> {
+    fn default() -> Self {
+        Self {
+            values: Mutex::default(),
+        }
+    }
+}
+
+impl<T> Held<T> {
+    fn values(&self) -> MutexGuard<'_, Vec<HeldValue<T>>> {
+        self.values.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Delivers `value` to `sink`, unless snapshots still hold it: then the last of them does.
+    fn release(&self, mut value: Arc<T>, sink: Option<Sink<T>>) {
+        // Checked under the lock, so a snapshot dropped meanwhile finds it there when sweeping
+        let mut values = self.values();
+        if Arc::get_mut(&mut value).is_none() {
+            values.push((value, sink));
+            return;
+        }
+        drop(values);
+        deliver(value, sink);
+    }
+
+    /// Delivers the values no snapshot holds anymore.
+    fn sweep(&self) {
+        let mut values = self.values();
+        let mut released = Vec::new();
+        for (mut value, sink) in mem::take(&mut *values) {
+            if Arc::get_mut(&mut value).is_some() {
+                released.push((value, sink));
+            } else {
+                values.push((value, sink));
+            }
+        }
+        drop(values);
+        for (value, sink) in released {
+            deliver(value, sink);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.values().len()
+    }
+}
+
+/// Delivers the last `Arc` of a displaced value to `sink`, if any. Without one it is dropped in
+/// place: `deliver_to` is the only frame the value is ever moved into.
+fn deliver<T>(value: Arc<T>, sink: Option<Sink<T>>) {
+    if let Some(sink) = sink {
+        deliver_to(value, &sink);
+    }
+}
+
+fn deliver_to<T>(value: Arc<T>, sink: &Sink<T>) {
+    if let Some(value) = Arc::into_inner(value) {
+        sink::deliver(sink, value);
+    }
+}
+
+/// Borrows the value of `value` for `'a`.
+///
+/// # Safety
+/// The value must stay alive for `'a`: the caller holds on to a clone of `value` meanwhile, or to a
+/// read lock on the engine it is retired to once displaced.
+unsafe fn extend<'a, T>(value: &Arc<T>) -> &'a T {
+    &*Arc::as_ptr(value)
+}
+
+impl<T> RawRcu<T> {
+    pub(crate) fn new(value: Box<T>) -> Self {
+        Self::with_engine(value, Engine::default())
+    }
+
+    /// Like [`RawRcu::new`], biased towards the current thread until another one touches it.
+    pub(crate) fn new_biased(value: Box<T>) -> Self {
+        Self::with_engine(value, Engine::biased())
+    }
+
+    /// Like [`RawRcu::new`], with readers that only use plain stores where supported, see `Engine::membarrier`.
+    #[cfg(feature = "membarrier")]
+    pub(crate) fn new_membarrier(value: Box<T>) -> Self {
+        Self::with_engine(value, Engine::membarrier())
+    }
+
+    /// Like [`RawRcu::new`], tracking readers with an engine shared with other values.
+    pub(crate) fn new_shared(value: Box<T>, reclaimer: Arc<Reclaimer>) -> Self
+    where
+        // What is retired to a shared engine may only be freed after the `RawRcu` is gone
+        T: 'static,
+    {
+        Self::with_engine(value, Engine::Shared(reclaimer))
+    }
+
+    fn with_engine(value: Box<T>, reclaimer: Engine) -> Self {
+        Self {
+            published: RwLock::new(Published {
+                current: Arc::from(value),
+                previous: None,
+                version: 0,
+                published_at: staleness::now(),
+                in_flight: false,
+                closed: false,
+                frozen: false,
+            }),
+            frozen: OnceLock::new(),
+            reclaimer,
+            keep_previous: false,
+            sink: RetireSink::new(),
+            bytes: RetiredBytes::new(),
+            held: Arc::default(),
+            #[cfg(feature = "diagnostics")]
+            grace: Arc::default(),
+            batch: RetireBatch::new(),
+            waiter: Waiter::default(),
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Published<T>> {
+        self.published.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Published<T>> {
+        self.published.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Makes waits for this value's publishes and its engine's grace periods go through `waiter`,
+    /// unless the engine is shared.
+    pub(crate) fn set_waiter(&mut self, waiter: Waiter) {
+        self.reclaimer.set_waiter(waiter.clone());
+        self.waiter = waiter;
+    }
+
+    /// Holds displaced values back from the engine until `max_len` of them accumulated, or the
+    /// oldest of them waited for `max_age`, see `Rcu::with_retire_batching`.
+    pub(crate) fn set_retire_batching(&mut self, max_len: usize, max_age: Duration) {
+        self.batch.set(max_len, max_age);
+    }
+
+    /// Hands every displaced value held back by batching to the engine, as a single batch.
+    pub(crate) fn flush_retired(&self) {
+        self.flush_retired_before(None);
+    }
+
+    /// Like `flush_retired`, giving up on waiting for readers at `deadline`, see `Reclaim::retire_batch`.
+    /// With nothing batched, lets go of what the engine can of what it was handed before.
+    pub(crate) fn flush_retired_before(&self, deadline: Option<Instant>) -> bool {
+        let batch = self.batch.take();
+        if batch.is_empty() {
+            return self.reclaimer.reclaim_before(deadline);
+        }
+        // Safety: every batched value was displaced, and is taken out of the batch only once
+        unsafe { self.reclaimer.retire_batch(batch, deadline) }
+    }
+
+    /// Number of displaced values held back by batching.
+    pub(crate) fn batched(&self) -> usize {
+        self.batch.len()
+    }
+
+    /// Number of displaced values not let go of yet, batched or retired.
+    pub(crate) fn pending_retired(&self) -> usize {
+        self.reclaimer.pending() + self.batch.len()
+    }
+
+    /// Counts the size of displaced values as `size_of` estimates it until they are let go of.
+    pub(crate) fn set_retired_size(&mut self, size_of: fn(&T) -> usize) {
+        self.bytes.set(size_of);
+    }
+
+    /// The estimated size of displaced values not let go of yet, 0 unless sized.
+    pub(crate) fn retired_bytes(&self) -> usize {
+        self.bytes.total()
+    }
+
+    /// Makes the engine hold readers back as `preference` says, unless it is shared.
+    pub(crate) fn set_preference(&mut self, preference: Preference) {
+        self.reclaimer.set_preference(preference);
+    }
+
+    pub(crate) fn waiter(&self) -> &Waiter {
+        &self.waiter
+    }
+
+    /// Makes the value keep its previously published value around, see [`RawRcu::read_pair`].
+    pub(crate) fn keeping_previous(mut self) -> Self {
+        self.keep_previous = true;
+        self
+    }
+
+    /// Makes the value report through `metrics`.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn set_metrics(&mut self, metrics: Metrics) {
+        self.metrics = Some(metrics);
+    }
+
+    #[cfg(feature = "metrics")]
+    pub(crate) fn metrics(&self) -> Option<&Metrics> {
+        self.metrics.as_ref()
+    }
+
+    /// Moves displaced values into `sink` instead of dropping them, see `Rcu::set_retire_sink`.
+    pub(crate) fn set_retire_sink(&self, sink: Option<Sink<T>>) {
+        self.sink.set(sink);
+    }
+
+    /// The displaced `value` on its way to the sink installed now, or to the snapshots holding it.
+    fn retiring(&self, value: Arc<T>) -> Retiring<T> {
+        Retiring {
+            value: Some(value),
+            sink: self.sink.get(),
+            held: Arc::clone(&self.held),
+        }
+    }
+
+    /// Wraps the displaced `value` for the engine, like `retiring`, counted while sized.
+    fn wrap(&self, value: Arc<T>) -> Retired {
+        let size = self.bytes.size(&value);
+        self.bytes.track(size, Retired::owned(self.retiring(value)))
+    }
+
+    /// Like `wrap`, for a value just displaced by a publish, whose grace period starts now.
+    fn retired(&self, value: Arc<T>) -> Retired {
+        #[cfg(feature = "diagnostics")]
+        return self.grace.time(self.wrap(value), self.watchdog());
+        #[cfg(not(feature = "diagnostics"))]
+        self.wrap(value)
+    }
+
+    /// Takes the current value out, without copying it unless owned snapshots hold on to it.
+    pub(crate) fn into_box(self) -> Box<T>
+    where
+        T: Clone,
+    {
+        let current = Arc::clone(&self.read().current);
+        drop(self);
+        Box::new(Arc::unwrap_or_clone(current))
+    }
+
+    /// Number of allocations holding values of this `RawRcu` that aren't freed yet: the current
+    /// value, the previous one, those displaced but not let go of, see `pending_retired`, and those
+    /// only owned snapshots hold on to.
+    pub(crate) fn outstanding_allocations(&self) -> usize {
+        1 + usize::from(self.read().previous.is_some()) + self.pending_retired() + self.held.len()
+    }
+
+    /// The current value, kept alive until the returned snapshot is dropped, without holding a
+    /// read-side critical section meanwhile.
+    pub(crate) fn pin(&self) -> Snapshot<T> {
+        #[cfg(feature = "metrics")]
+        self.count_read();
+        Snapshot {
+            value: Some(Arc::clone(&self.read().current)),
+            held: Arc::clone(&self.held),
+        }
+    }
+
+    /// The number of successful publishes so far.
+    pub(crate) fn version(&self) -> u64 {
+        self.read().version
+    }
+
+    /// Like [`RawRcu::version`], for callers that only need to notice a change eventually.
+    pub(crate) fn version_relaxed(&self) -> u64 {
+        self.version()
+    }
+
+    /// When the latest publish happened, see `staleness::now`. Set before the publish's
+    /// `published` runs.
+    pub(crate) fn published_at(&self) -> u64 {
+        self.read().published_at
+    }
+
+    /// Observes the grace-period waits of this value's engine.
+    #[cfg(feature = "diagnostics")]
+    pub(crate) fn watchdog(&self) -> &Watchdog {
+        self.reclaimer.watchdog()
+    }
+
+    /// See `Rcu::grace_period_histogram`.
+    #[cfg(feature = "diagnostics")]
+    pub(crate) fn grace_period_histogram(&self) -> GraceHistogram {
+        self.grace.histogram()
+    }
+
+    /// Forbids any further publish, waiting for the one in progress to complete, if any. Readers of a
+    /// frozen value skip the read-side critical section. Must not be called from a publish's `published`.
+    pub(crate) fn freeze(&self) {
+        self.write().closed = true;
+        self.waiter.wait_while(|| self.read().in_flight, None);
+        self.write().frozen = true;
+        self.waiter.notify();
+    }
+
+    /// Whether [`RawRcu::freeze`] completed.
+    pub(crate) fn is_frozen(&self) -> bool {
+        self.frozen.get().is_some() || self.read().frozen
+    }
+
+    /// The current value, if frozen: it then stays alive for as long as `self`.
+    pub(crate) fn frozen_ref(&self) -> Option<&T> {
+        if let Some(frozen) = self.frozen.get() {
+            return Some(frozen);
+        }
+        let published = self.read();
+        published.frozen.then(|| &**self.frozen.get_or_init(|| Arc::clone(&published.current)))
+    }
+
+    /// The current value, borrowed exclusively: borrowing `self` mutably rules out any reader,
+    /// owned snapshots being left the original.
+    pub(crate) fn get_mut(&mut self) -> &mut T
+    where
+        T: Clone,
+    {
+        // Set again from the current value by the next read, if frozen
+        self.frozen.take();
+        let current = &mut self.published.get_mut().unwrap_or_else(PoisonError::into_inner).current;
+        if Arc::get_mut(current).is_none() {
+            // Held until the snapshots are dropped, like a displaced value, but never delivered
+            let copy = Arc::new(T::clone(current));
+            self.held.release(mem::replace(current, copy), None);
+        }
+        Arc::get_mut(current).expect("just copied")
+    }
+
+    /// Runs `f` on the current value inside a read-side critical section.
+    #[track_caller]
+    pub(crate) fn read_with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.read_guard())
+    }
+
+    /// The current value, kept alive by the guard's own `Arc` until it is dropped, inside a
+    /// read-side critical section.
+    #[track_caller]
+    pub(crate) fn read_guard(&self) -> RawReadGuard<'_, T> {
+        #[cfg(feature = "metrics")]
+        self.count_read();
+        if let Some(frozen) = self.frozen_ref() {
+            return RawReadGuard {
+                value: frozen,
+                _current: None,
+                _lock: None,
+                _not_send: PhantomData,
+            };
+        }
+        let lock = ReadLock::new(&self.reclaimer);
+        let current = Arc::clone(&self.read().current);
+        RawReadGuard {
+            // Safety: the guard holds on to `current`
+            value: unsafe { extend(&current) },
+            _current: Some(current),
+            _lock: Some(lock),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Runs `f` on the current value like `read_with`. Takes the lock, so unlike the atomic
+    /// backend's this isn't async-signal-safe, see `Rcu::read_signal_safe`.
+    pub(crate) fn read_signal_safe<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        if let Some(frozen) = self.frozen_ref() {
+            return f(frozen);
+        }
+        let _lock = SignalSafeLock::new(&self.reclaimer);
+        let current = Arc::clone(&self.read().current);
+        f(&current)
+    }
+
+    /// The current value, kept alive by a read-side critical section entered beforehand.
+    ///
+    /// # Panics
+    /// If `lock` wasn't entered on this value's engine.
+    pub(crate) fn read_in<'a>(&'a self, lock: &'a ReadLock<'_>) -> &'a T {
+        assert!(lock.is_on(&self.reclaimer), "read lock taken on another engine");
+        #[cfg(feature = "metrics")]
+        self.count_read();
+        // Safety: once displaced the value is retired to the engine, which only lets go of it once the
+        // read lock exited
+        unsafe { extend(&self.read().current) }
+    }
+
+    /// Runs `f` on the current value and the version it was published as, inside a read-side
+    /// critical section. Waits out a publish in flight, whose value and version don't match yet.
+    #[track_caller]
+    pub(crate) fn read_versioned<R>(&self, f: impl FnOnce(&T, u64) -> R) -> R {
+        self.read_stamped(|value, version, _| f(value, version))
+    }
+
+    /// Like [`RawRcu::read_versioned`], also passing when the value was published, see
+    /// `staleness::now`, which is just as coherent with it.
+    #[track_caller]
+    pub(crate) fn read_stamped<R>(&self, f: impl FnOnce(&T, u64, u64) -> R) -> R {
+        #[cfg(feature = "metrics")]
+        self.count_read();
+        if let Some(frozen) = self.frozen_ref() {
+            let published = self.read();
+            let (version, published_at) = (published.version, published.published_at);
+            drop(published);
+            return f(frozen, version, published_at);
+        }
+        let mut attempt = 0;
+        loop {
+            let lock = ReadLock::new(&self.reclaimer);
+            let published = self.read();
+            // Matches the atomic backend, where a publish in flight bumps the version after the swap
+            if !published.in_flight {
+                let current = Arc::clone(&published.current);
+                let (version, published_at) = (published.version, published.published_at);
+                drop(published);
+                return f(&current, version, published_at);
+            }
+            // Never wait inside the read lock, the publish in flight may be waiting for readers to drain
+            drop(published);
+            drop(lock);
+            self.waiter.wait(attempt);
+            attempt += 1;
+        }
+    }
+
+    /// Runs `f` on the current value and the one published before it, None before the first
+    /// publish or unless `keep_previous`, inside a read-side critical section. Both always come from
+    /// the same publish: waits out a publish in flight, as the atomic backend does.
+    #[track_caller]
+    pub(crate) fn read_pair<R>(&self, f: impl FnOnce(&T, Option<&T>) -> R) -> R {
+        #[cfg(feature = "metrics")]
+        self.count_read();
+        if let Some(frozen) = self.frozen_ref() {
+            let previous = self.read().previous.clone();
+            return f(frozen, previous.as_deref());
+        }
+        let mut attempt = 0;
+        loop {
+            let lock = ReadLock::new(&self.reclaimer);
+            let published = self.read();
+            if !published.in_flight {
+                let (current, previous) = (Arc::clone(&published.current), published.previous.clone());
+                drop(published);
+                return f(&current, previous.as_deref());
+            }
+            // As in `read_stamped`
+            drop(published);
+            drop(lock);
+            self.waiter.wait(attempt);
+            attempt += 1;
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn count_read(&self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.read();
+        }
+    }
+
+    /// Publishes `neo` unless another publish is in flight or the value is frozen, in which case it is handed back.
+    /// `published` runs with the new value once it is visible, while no other writer can replace it
+    /// yet. If it panics the publish is still completed before the panic is resumed.
+    ///
+    /// Waiting for readers of the displaced value gives up at `deadline`, if any. Returns whether the
+    /// displaced value was dealt with in time, if not it is left for a later grace period to let go of.
+    pub(crate) fn publish_before(
+        &self,
+        neo: Box<T>,
+        deadline: Option<Instant>,
+        published: impl FnOnce(&T),
+    ) -> Result<bool, Refused<T>> {
+        let (neo, displaced) = self.swap(neo, None)?;
+        Ok(self.finish(neo, displaced, deadline, published))
+    }
+
+    /// Publishes `neo` unconditionally.
+    ///
+    /// # Safety
+    /// As for the atomic backend, whose callers this shares: no other publish may ever run
+    /// concurrently with this one, through any method.
+    pub(crate) unsafe fn publish_exclusive(&self, neo: Box<T>, published: impl FnOnce(&T)) {
+        let neo = Arc::from(neo);
+        let displaced = self.write().install(Arc::clone(&neo), self.keep_previous);
+        self.finish(neo, displaced, None, published);
+    }
+
+    /// Publishes `f(current)`, re-running `f` on the newer value whenever another writer got
+    /// there first, until it sticks, or gives up if the value is frozen. Unlike `publish_before` no
+    /// concurrent update is ever lost.
+    pub(crate) fn modify(&self, mut f: impl FnMut(&T) -> T) {
+        let mut attempt = 0;
+        while !matches!(
+            self.try_modify(|cur| Ok::<_, Infallible>(Box::new(f(cur))), |_| {}),
+            Modify::Published | Modify::Frozen(_)
+        ) {
+            self.waiter.wait(attempt);
+            attempt += 1;
+        }
+    }
+
+    /// A single attempt at publishing the value produced by `f(current)`, failing if another
+    /// writer replaced `current` before the new value could be swapped in. `published` is as for `publish_before`.
+    ///
+    /// Waits for any publish in flight to complete first, so `f` is only ever given fully
+    /// published values and isn't called repeatedly against the same one.
+    #[track_caller]
+    pub(crate) fn try_modify<E>(
+        &self,
+        f: impl FnOnce(&T) -> Result<Box<T>, E>,
+        published: impl FnOnce(&T),
+    ) -> Modify<T, E> {
+        self.wait_settled();
+        let lock = ReadLock::new(&self.reclaimer);
+        let cur = Arc::clone(&self.read().current);
+        let neo = match f(&cur) {
+            Ok(neo) => neo,
+            Err(err) => return Modify::Aborted(err),
+        };
+        let swapped = self.swap(neo, Some(&cur));
+        // Let go of before waiting for readers, for the displaced value to be the engine's alone
+        drop(cur);
+        drop(lock);
+        match swapped {
+            Ok((neo, displaced)) => {
+                self.finish(neo, displaced, None, published);
+                Modify::Published
+            }
+            Err(Refused::Frozen(neo)) => Modify::Frozen(neo),
+            Err(Refused::InFlight(neo)) => Modify::Conflict(neo),
+        }
+    }
+
+    /// Waits until no publish is in flight.
+    pub(crate) fn wait_settled(&self) {
+        // Never call this inside a read lock, the in-flight publish may be waiting for readers to drain
+        self.waiter.wait_while(|| self.read().in_flight, None);
+    }
+
+    /// Like [`RawRcu::wait_settled`], giving up at `deadline`. Returns whether no publish is in flight.
+    pub(crate) fn wait_settled_before(&self, deadline: Instant) -> bool {
+        self.waiter.wait_while(|| self.read().in_flight, Some(deadline))
+    }
+
+    /// Waits until every read-side critical section active when this is called has exited. Never
+    /// call this inside a read lock on this value, it would wait for itself.
+    pub(crate) fn synchronize(&self) {
+        self.reclaimer.synchronize();
+    }
+
+    /// Hands every displaced value held back by batching to the engine, then waits until the
+    /// engine let go of everything handed to it before, see `Reclaim::barrier`.
+    pub(crate) fn barrier(&self) {
+        self.flush_retired();
+        self.reclaimer.barrier();
+    }
+
+    /// Hands an allocation reachable by this value's readers over to its engine, see `Reclaim::retire`.
+    ///
+    /// # Safety
+    /// As for `Reclaim::retire`.
+    pub(crate) unsafe fn retire(&self, retired: Retired) {
+        self.reclaimer.retire(retired);
+    }
+
+    /// Swaps `neo` in, unless the value is being frozen, a publish is in flight or, with
+    /// `expected`, the current value isn't that one anymore. Returns the now published value with
+    /// the one it displaced, see `Published::install`.
+    fn swap(&self, neo: Box<T>, expected: Option<&Arc<T>>) -> Result<Swapped<T>, Refused<T>> {
+        let mut published = self.write();
+        if published.closed {
+            return Err(Refused::Frozen(neo));
+        }
+        if published.in_flight || expected.is_some_and(|expected| !Arc::ptr_eq(expected, &published.current)) {
+            return Err(Refused::InFlight(neo));
+        }
+        let neo = Arc::from(neo);
+        let displaced = published.install(Arc::clone(&neo), self.keep_previous);
+        Ok((neo, displaced))
+    }
+
+    /// Completes a publish started by `swap`, returning whether `displaced` was dealt with before `deadline`.
+    fn finish(
+        &self,
+        neo: Arc<T>,
+        displaced: Option<Arc<T>>,
+        deadline: Option<Instant>,
+        published: impl FnOnce(&T),
+    ) -> bool {
+        let published = panic::catch_unwind(AssertUnwindSafe(|| published(&neo)));
+        drop(neo);
+        // Nothing to report if nothing was displaced
+        #[cfg(feature = "metrics")]
+        let retiring = (self.metrics.is_some() && displaced.is_some()).then(Instant::now);
+        // Safety: `displaced` was just unpublished by this thread, and is only retired here
+        let in_time = unsafe {
+            match (displaced, deadline) {
+                (None, _) => true,
+                (Some(displaced), _) if self.batch.is_on() => match self.batch.push(self.retired(displaced)) {
+                    Some(due) => self.reclaimer.retire_batch(due, deadline),
+                    // Left for the grace period of a later batch
+                    None => true,
+                },
+                (Some(displaced), None) => {
+                    self.reclaimer.retire(self.retired(displaced));
+                    true
+                }
+                (Some(displaced), Some(deadline)) => self.reclaimer.retire_before(self.retired(displaced), deadline),
+            }
+        };
+        #[cfg(feature = "metrics")]
+        if let Some(retiring) = retiring {
+            self.report_retired(retiring);
+        }
+        self.settle();
+        if let Err(payload) = published {
+            panic::resume_unwind(payload);
+        }
+        in_time
+    }
+
+    /// Lets the next publish in, and readers waiting out this one.
+    fn settle(&self) {
+        self.write().in_flight = false;
+        self.waiter.notify();
+    }
+
+    #[cfg(feature = "metrics")]
+    fn report_retired(&self, retiring: Instant) {
+        if let Some(metrics) = &self.metrics {
+            metrics.retired(retiring.elapsed(), self.pending_retired());
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> RawRcu<T> {
+    /// Like `publish_before`, without waiting for readers of the displaced value: the publish is
+    /// complete on return, and the displaced value is let go of by awaiting the returned future.
+    pub(crate) fn publish_detached(
+        &self,
+        neo: Box<T>,
+        published: impl FnOnce(&T),
+    ) -> Result<Reclamation<'_, T>, Refused<T>> {
+        let (neo, displaced) = self.swap(neo, None)?;
+        Ok(self.finish_detached(neo, displaced, published))
+    }
+
+    /// Like `try_modify`, publishing like `publish_detached`. Doesn't wait for a publish in flight
+    /// either, but fails with a conflict, so check `is_settled` first.
+    pub(crate) fn try_modify_detached<E>(
+        &self,
+        f: impl FnOnce(&T) -> Result<Box<T>, E>,
+        published: impl FnOnce(&T),
+    ) -> Result<Reclamation<'_, T>, Modify<T, E>> {
+        let lock = ReadLock::new(&self.reclaimer);
+        let cur = Arc::clone(&self.read().current);
+        let neo = f(&cur).map_err(Modify::Aborted)?;
+        // As in `try_modify`
+        let swapped = self.swap(neo, Some(&cur));
+        drop(cur);
+        drop(lock);
+        match swapped {
+            Ok((neo, displaced)) => Ok(self.finish_detached(neo, displaced, published)),
+            Err(Refused::Frozen(neo)) => Err(Modify::Frozen(neo)),
+            Err(Refused::InFlight(neo)) => Err(Modify::Conflict(neo)),
+        }
+    }
+
+    /// Whether no publish is in flight, see `wait_settled`.
+    pub(crate) fn is_settled(&self) -> bool {
+        !self.read().in_flight
+    }
+
+    /// Like `finish`, handing the displaced value to the returned future instead of waiting for its
+    /// readers, so the next publish can start right away. If `published` panics the displaced value
+    /// is retired as `finish` would before the panic is resumed.
+    fn finish_detached(
+        &self,
+        neo: Arc<T>,
+        displaced: Option<Arc<T>>,
+        published: impl FnOnce(&T),
+    ) -> Reclamation<'_, T> {
+        let published = panic::catch_unwind(AssertUnwindSafe(|| published(&neo)));
+        drop(neo);
+        if let Err(payload) = published {
+            if let Some(displaced) = displaced {
+                // Safety: `displaced` was just unpublished by this thread, and is only retired here
+                unsafe { self.reclaimer.retire(self.retired(displaced)) };
+            }
+            self.settle();
+            panic::resume_unwind(payload);
+        }
+        self.settle();
+        // Safety: as above
+        let retired = displaced.and_then(|displaced| unsafe { self.reclaimer.retire_biased(self.retired(displaced)) });
+        Reclamation {
+            raw: self,
+            ticket: if retired.is_some() { self.reclaimer.start_grace_period() } else { 0 },
+            retired,
+            #[cfg(feature = "metrics")]
+            retiring: Instant::now(),
+        }
+    }
+}
+
+/// Lets go of a value displaced by a detached publish once the readers that may still observe it
+/// are done, without blocking: the task is woken by their exit. Dropped before that, it leaves the
+/// value for a later grace period.
+#[cfg(feature = "async")]
+#[must_use = "the displaced value is only let go of by awaiting this, or later once dropped"]
+pub(crate) struct Reclamation<'a, T> {
+    raw: &'a RawRcu<T>,
+    /// None once let go of, or if there was nothing to wait for
+    retired: Option<Retired>,
+    /// See `Reclaim::start_grace_period`
+    ticket: usize,
+    #[cfg(feature = "metrics")]
+    retiring: Instant,
+}
+
+#[cfg(feature = "async")]
+impl<T> Future for Reclamation<'_, T> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.retired.is_some() && !self.raw.reclaimer.poll_grace_period(self.ticket, cx.waker()) {
+            return Poll::Pending;
+        }
+        if let Some(retired) = self.retired.take() {
+            // Safety: the grace period started after `retired` was unpublished is over
+            unsafe { retired.reclaim() };
+            #[cfg(feature = "metrics")]
+            self.raw.report_retired(self.retiring);
+        }
+        Poll::Ready(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> Drop for Reclamation<'_, T> {
+    fn drop(&mut self) {
+        if let Some(retired) = self.retired.take() {
+            // Waiting would block the task being cancelled, defer it to the next grace period instead.
+            // Safety: `retired` was unpublished and is only ever handed over once
+            unsafe { self.raw.reclaimer.retire_before(retired, Instant::now()) };
+        }
+    }
+}
+
+/// Anything a guard may keep alive, whatever part of it the guard was mapped to.
+trait Owner {}
+
+impl<T: ?Sized> Owner for T {}
+
+/// A value read from a `RawRcu`, together with the `Arc` and the read-side critical section
+/// keeping it alive.
+pub(crate) struct RawReadGuard<'a, T: ?Sized> {
+    value: &'a T,
+    /// What `value` is part of, None for a frozen value, which lives as long as the `RawRcu`.
+    /// Dropped before the lock, for the displaced value to be the engine's alone once it exited
+    _current: Option<Arc<dyn Owner + 'a>>,
+    /// None for a frozen value, which is never retired
+    _lock: Option<ReadLock<'a, Engine>>,
+    /// A biased engine tracks its owner's critical sections with plain stores, so one must be
+    /// exited on the thread that entered it
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<T: ?Sized> Deref for RawReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<'a, T: ?Sized> RawReadGuard<'a, T> {
+    /// The guard of the part of the value `f` returns, holding the same `Arc` and critical section.
+    /// Should `f` return None, or panic, both are handed back, or let go of, with `self`.
+    pub(crate) fn try_map<U: ?Sized>(self, f: impl FnOnce(&T) -> Option<&U>) -> Result<RawReadGuard<'a, U>, Self> {
+        match f(self.value) {
+            Some(value) => Ok(RawReadGuard {
+                value,
+                _current: self._current,
+                _lock: self._lock,
+                _not_send: PhantomData,
+            }),
+            None => Err(self),
+        }
+    }
+}
+
+/// A value read by `RawRcu::pin`, kept alive by its own `Arc` until this is dropped on any thread.
+pub(crate) struct Snapshot<T> {
+    /// Only None once dropped
+    value: Option<Arc<T>>,
+    held: Arc<Held<T>>,
+}
+
+impl<T> Deref for Snapshot<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value.as_deref().expect("only taken when dropped")
+    }
+}
+
+impl<T> Drop for Snapshot<T> {
+    fn drop(&mut self) {
+        // Whoever drops the last snapshot of a displaced value delivers it
+        drop(self.value.take());
+        self.held.sweep();
+    }
+}
+
+impl<T> Drop for RawRcu<T> {
+    fn drop(&mut self) {
+        for retired in self.batch.take() {
+            // Safety: we own `self`, so there are no readers
+            unsafe { retired.reclaim() };
+        }
+        let previous = self.published.get_mut().unwrap_or_else(PoisonError::into_inner).previous.take();
+        if let Some(previous) = previous {
+            drop(self.retiring(previous));
+        }
+        // The current value is dropped with the last `Arc` of it, ours or an owned snapshot's. What the
+        // engine still holds is let go of once it is dropped in turn, or by a later grace period of a
+        // domain sharing it
+    }
+}
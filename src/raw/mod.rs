@@ -0,0 +1,73 @@
+//! The publication protocol shared by every RCU-managed value in the crate, behind an interface
+//! carrying no bounds on `T`, so the crate can use it for its own bookkeeping (e.g. hook lists)
+//! without recursing into `Rcu`.
+//!
+//! Exactly one backend implements it for the rest of the crate:
+//!
+//! - `atomic` (unless `fallback-lock`): an atomic pointer to the current allocation, swapped by
+//!   compare-exchange, displaced allocations being handed to the reclamation engine.
+//! - `locked` (with `fallback-lock`): the current value in a `RwLock<Arc<T>>`, swapped under the
+//!   write lock, displaced values being dropped with their last `Arc` once the engine's readers
+//!   exited. No atomic pointer, no manual freeing: the baseline to bisect memory bugs out of the
+//!   other one with.
+//!
+//! Tests build both, and the differential tests in `differential` check that they agree.
+
+#[cfg(feature = "async")]
+use std::{future::Future, pin::Pin, task::{Context, Poll}};
+
+#[cfg(any(not(feature = "fallback-lock"), test))]
+mod atomic;
+#[cfg(test)]
+mod differential;
+#[cfg(any(feature = "fallback-lock", test))]
+mod locked;
+#[cfg(any(not(feature = "fallback-lock"), test))]
+mod pins;
+
+#[cfg(all(feature = "async", not(feature = "fallback-lock")))]
+pub(crate) use atomic::Reclamation;
+#[cfg(not(feature = "fallback-lock"))]
+pub(crate) use atomic::{RawReadGuard, RawRcu, Snapshot};
+#[cfg(all(feature = "async", feature = "fallback-lock"))]
+pub(crate) use locked::Reclamation;
+#[cfg(feature = "fallback-lock")]
+pub(crate) use locked::{RawReadGuard, RawRcu, Snapshot};
+
+/// Why `RawRcu::publish_before` didn't publish, the value is handed back.
+pub(crate) enum Refused<T> {
+    /// Another publish is in flight.
+    InFlight(Box<T>),
+    /// The value is frozen.
+    Frozen(Box<T>),
+}
+
+/// The outcome of a single `RawRcu::try_modify` attempt.
+pub(crate) enum Modify<T, E> {
+    Published,
+    /// The value is frozen, the new one is handed back.
+    Frozen(Box<T>),
+    /// Another writer replaced the value the new one was computed from, which is handed back.
+    Conflict(Box<T>),
+    /// The closure refused to produce a new value.
+    Aborted(E),
+}
+
+/// Returns pending once, waking the task right away, so others can run before it goes on.
+#[cfg(feature = "async")]
+#[derive(Default)]
+pub(crate) struct YieldNow(bool);
+
+#[cfg(feature = "async")]
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
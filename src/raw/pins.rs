@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::reclaim::Retired;
+
+/// The values of one `RawRcu` held by owned snapshots, by address, keeping them from being freed
+/// past their grace period until the last snapshot of each is dropped.
+#[derive(Default)]
+pub(crate) struct Pins {
+    /// Set by the first snapshot, values are only tracked from then on
+    used: AtomicBool,
+    table: Mutex<HashMap<usize, Pin>>,
+}
+
+/// The snapshots of one value.
+struct Pin {
+    count: usize,
+    /// Frees the value once the last snapshot is dropped, set once its grace period is over
+    retired: Option<Retired>,
+}
+
+/// A displaced value whose freeing waits for its snapshots.
+struct Pinned {
+    addr: usize,
+    retired: Retired,
+    pins: Arc<Pins>,
+}
+
+/// Frees the value unless snapshots hold on to it, leaving it to the last of them otherwise.
+unsafe fn release(pinned: *mut Pinned) {
+    let pinned = Box::from_raw(pinned);
+    pinned.pins.free(pinned.addr, pinned.retired);
+}
+
+impl Pins {
+    /// To be called before reading a value to pin: from then on displaced values are tracked.
+    pub(crate) fn start(&self) {
+        // Pairs with the load in `track`: a value displaced before that load saw this store can't
+        // be the one read after it
+        self.used.store(true, SeqCst);
+    }
+
+    /// Adds a snapshot of the value at `addr`, which must be kept from being freed meanwhile.
+    pub(crate) fn pin(&self, addr: usize) {
+        let mut table = self.table.lock().unwrap_or_else(PoisonError::into_inner);
+        table.entry(addr).or_insert(Pin { count: 0, retired: None }).count += 1;
+    }
+
+    /// Drops a snapshot of the value at `addr`, freeing it if it was the last one and the value
+    /// was freed meanwhile.
+    pub(crate) fn unpin(&self, addr: usize) {
+        let mut table = self.table.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(pin) = table.get_mut(&addr) else {
+            return;
+        };
+        pin.count -= 1;
+        if pin.count > 0 {
+            return;
+        }
+        let retired = table.remove(&addr).and_then(|pin| pin.retired);
+        drop(table);
+        if let Some(retired) = retired {
+            // Safety: its grace period was over, and the last snapshot of it is gone
+            unsafe { retired.reclaim() };
+        }
+    }
+
+    /// Whether snapshots hold on to the value at `addr`.
+    pub(crate) fn is_pinned(&self, addr: usize) -> bool {
+        self.used.load(SeqCst) && self.table.lock().unwrap_or_else(PoisonError::into_inner).contains_key(&addr)
+    }
+
+    /// Number of values whose grace period is over, waiting for their snapshots to be dropped.
+    pub(crate) fn held(&self) -> usize {
+        let table = self.table.lock().unwrap_or_else(PoisonError::into_inner);
+        table.values().filter(|pin| pin.retired.is_some()).count()
+    }
+
+    /// Frees the value at `addr` with `retired`, or once its last snapshot is dropped.
+    ///
+    /// # Safety
+    /// No reader but its snapshots may observe the value anymore.
+    pub(crate) unsafe fn free(&self, addr: usize, retired: Retired) {
+        let mut table = self.table.lock().unwrap_or_else(PoisonError::into_inner);
+        match table.get_mut(&addr) {
+            Some(pin) => pin.retired = Some(retired),
+            None => {
+                drop(table);
+                retired.reclaim();
+            }
+        }
+    }
+
+    /// Wraps `retired`, which frees the displaced value at `addr`, to wait for its snapshots too.
+    pub(crate) fn track(self: &Arc<Self>, addr: usize, retired: Retired) -> Retired {
+        if !self.used.load(SeqCst) {
+            return retired;
+        }
+        let pinned = Pinned {
+            addr,
+            retired,
+            pins: Arc::clone(self),
+        };
+        Retired::with_deleter(Box::into_raw(Box::new(pinned)), release)
+    }
+}
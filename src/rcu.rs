@@ -0,0 +1,1498 @@
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::time::{Duration, Instant};
+
+use crate::acks::Acks;
+#[cfg(feature = "audit")]
+use crate::audit::AuditLog;
+use crate::backpressure::Limits;
+use crate::error::{CommitConflict, CommitError, DeadlineError, LaggingSubscribers, PublishError, ReclaimStatus, Stale};
+use crate::hooks::{HookId, HookList};
+use crate::invariant::Invariants;
+#[cfg(feature = "metrics")]
+use crate::metrics::{Failure, Metrics};
+use crate::notify::Notify;
+#[cfg(feature = "async")]
+use crate::raw::{Reclamation, YieldNow};
+use crate::raw::{Modify, RawRcu, Refused};
+use crate::staging::Staging;
+use crate::staleness::{self, Head, Staleness, StalenessHandle, View};
+use crate::wait::{Preference, WaitStrategy, Waiter};
+
+/// An implementation of a "read, copy, update" data structure. When the previous value is
+/// freed is decided by the reclamation engine selected at compile time, see the crate docs.
+pub struct Rcu<T: Clone> {
+    /// Holds the data `T` and takes care of publishing and reclaiming it
+    pub(crate) raw: RawRcu<T>,
+    /// Callbacks to run with every newly published value
+    hooks: HookList<T>,
+    /// Checks every value has to pass before it is published
+    pub(crate) invariants: Invariants<T>,
+    /// Limits on the displaced values waiting to be freed, and what publishes do past them
+    pub(crate) limits: Limits,
+    /// Wakes threads waiting for a new version
+    changed: Notify,
+    /// Unique among all `Rcu`s ever created, keys thread-local caches
+    pub(crate) id: u64,
+    /// The latest publish, for subscribers to measure their staleness against
+    pub(crate) head: Arc<Head>,
+    /// Subscribers publishers can wait for, see `subscribe_acked`
+    acks: Acks,
+    /// Held while `get_or_compute` computes a new value
+    pub(crate) computing: Mutex<()>,
+    /// The candidate value, see `stage`
+    pub(crate) staging: Staging<T>,
+    /// Recent publishes, while enabled
+    #[cfg(feature = "audit")]
+    pub(crate) audit: AuditLog<T>,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+impl<T: Clone> Rcu<T> {
+    /// Associated method for creating a new `Rcu`.
+    pub fn new(value: T) -> Self {
+        Self::from_box(Box::new(value))
+    }
+    /// Creates a new `Rcu` holding the boxed value, reusing the allocation as is.
+    pub fn from_box(value: Box<T>) -> Self {
+        Self::from_raw(RawRcu::new(value))
+    }
+    /// Like [`Rcu::new`], biased towards the current thread: for as long as no other thread reads or
+    /// updates it, reads from this thread skip the reader counter and updates free the previous value
+    /// right away instead of waiting for a grace period. The first access from another thread
+    /// revokes the bias for good, which costs a process-wide memory barrier once; from then on the
+    /// value behaves exactly like one created with `new`.
+    ///
+    /// Meant for values that are mostly or entirely used by the thread that creates them but must
+    /// stay shareable. Only Linux provides the barrier the revocation relies on, elsewhere this is
+    /// the same as `new`.
+    pub fn new_biased(value: T) -> Self {
+        Self::from_raw(RawRcu::new_biased(Box::new(value)))
+    }
+    /// Like [`Rcu::new`], with reads that never execute a read-modify-write nor a hardware memory
+    /// fence: each thread marks its reads with plain stores into a slot of its own, and publishes
+    /// force a memory barrier on every thread of the process (`membarrier(2)`) to order themselves
+    /// against them instead, twice per publish, waiting for current readers to finish like the
+    /// `reclaim-counted` engine does whichever engine is selected. Meant for values read far more
+    /// often than they are published.
+    ///
+    /// Only Linux provides the barrier, and only kernels supporting its private expedited command,
+    /// checked once per process; otherwise this is the same as `new`.
+    #[cfg(feature = "membarrier")]
+    pub fn new_membarrier(value: T) -> Self {
+        Self::from_raw(RawRcu::new_membarrier(Box::new(value)))
+    }
+    pub(crate) fn from_raw(raw: RawRcu<T>) -> Self {
+        #[cfg_attr(not(feature = "metrics"), allow(unused_mut))]
+        let mut raw = raw.keeping_previous();
+        #[cfg(feature = "metrics")]
+        raw.set_metrics(Metrics::default());
+        let head = Head::new(raw.published_at());
+        Self {
+            raw,
+            hooks: HookList::new(),
+            invariants: Invariants::new(),
+            limits: Limits::default(),
+            changed: Notify::default(),
+            id: NEXT_ID.fetch_add(1, Relaxed),
+            head,
+            acks: Acks::default(),
+            computing: Mutex::new(()),
+            staging: Staging::new(),
+            #[cfg(feature = "audit")]
+            audit: AuditLog::new(),
+        }
+    }
+    /// Names this `Rcu` in the series it reports through the `metrics` facade, as their `rcu` label.
+    /// Unlabeled `Rcu`s report series without it.
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use rcu_rust::Rcu;
+    /// let routes = Rcu::new(HashMap::<String, String>::new()).metrics_label("routing_table");
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn metrics_label(mut self, label: impl Into<String>) -> Self {
+        self.raw.set_metrics(Metrics::labeled(label.into()));
+        self
+    }
+    /// Makes every wait on this `Rcu` go through `strategy` instead of spinning: for readers of
+    /// grace periods and paused by publishes, for publishes in flight and for new versions.
+    /// Values of an [`RcuDomain`](crate::RcuDomain) wait for grace periods the way the domain does.
+    ///
+    /// ```
+    /// # use rcu_rust::{Park, Rcu};
+    /// let config = Rcu::new(String::new()).with_wait_strategy(Park::default());
+    /// ```
+    pub fn with_wait_strategy(mut self, strategy: impl WaitStrategy + 'static) -> Self {
+        self.raw.set_waiter(Waiter::new(Arc::new(strategy)));
+        self
+    }
+    /// Makes publishes hold new readers back while waiting for a grace period, or not, as
+    /// `preference` says; by default they do, see [`Preference::WriterPreferred`]. Values of an
+    /// [`RcuDomain`](crate::RcuDomain) take the preference of the domain.
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use rcu_rust::{Preference, Rcu};
+    /// let routes = Rcu::new(HashMap::<String, String>::new()).with_preference(Preference::ReaderPreferred);
+    /// ```
+    pub fn with_preference(mut self, preference: Preference) -> Self {
+        self.raw.set_preference(preference);
+        self
+    }
+    /// Batches the values displaced by publishes: instead of handing each of them to the engine,
+    /// which waits for or checks on readers once per value, publishes hold them back until
+    /// `max_len` of them accumulated, or the oldest of them waited for `max_age`, and then hand the
+    /// whole batch over for a single grace period to reclaim. Bursts of publishes then pay for one
+    /// grace period per batch, at the cost of keeping up to `max_len` displaced values alive.
+    ///
+    /// Both thresholds are only checked by publishes: call [`Rcu::flush`] once a burst is over to
+    /// reclaim what is left in the batch.
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use rcu_rust::Rcu;
+    /// let prices = Rcu::new(0.0f64).with_retire_batching(64, Duration::from_millis(10));
+    /// for tick in 0..100 {
+    ///     prices.set(f64::from(tick) / 100.0)?;
+    /// }
+    /// prices.flush();
+    /// assert_eq!(prices.retire_batch_len(), 0);
+    /// # Ok::<(), rcu_rust::PublishError<f64>>(())
+    /// ```
+    pub fn with_retire_batching(mut self, max_len: usize, max_age: Duration) -> Self {
+        self.raw.set_retire_batching(max_len, max_age);
+        self
+    }
+    /// Hands every displaced value held back by [`Rcu::with_retire_batching`] to the engine at once,
+    /// as a publish handing over a full batch would: with the `reclaim-counted` engine this waits for
+    /// readers once. Leaves the batch empty, though engines that defer reclamation may still hold on
+    /// to what was in it for a while.
+    pub fn flush(&self) {
+        self.raw.flush_retired();
+    }
+    /// Frees every displaced value no reader can observe anymore right now, whether held back by
+    /// [`Rcu::with_retire_batching`] or by an engine that defers reclamation, returning once that is
+    /// done. Doesn't wait for readers: values an active reader may still observe stay pending, see
+    /// [`Rcu::pending_retired`], so with readers quiescent this leaves nothing pending. Unlike
+    /// [`Rcu::flush`] it may be called while holding a read guard, and concurrently with readers,
+    /// publishes and other calls to it.
+    ///
+    /// Values of an [`RcuDomain`](crate::RcuDomain) are freed along with the domain's other
+    /// eligible values.
+    ///
+    /// ```
+    /// # use rcu_rust::Rcu;
+    /// let cache = Rcu::new(vec![1, 2, 3]);
+    /// cache.set(vec![4, 5, 6])?;
+    /// cache.reclaim_now();
+    /// assert_eq!(cache.pending_retired(), 0);
+    /// # Ok::<(), rcu_rust::PublishError<Vec<i32>>>(())
+    /// ```
+    pub fn reclaim_now(&self) {
+        self.raw.flush_retired_before(Some(Instant::now()));
+    }
+    /// Blocks until every value displaced before the call was freed and dropped: those held back
+    /// by [`Rcu::with_retire_batching`], those an engine that defers reclamation still holds, and
+    /// those other threads are in the middle of freeing. Where [`Rcu::reclaim_now`] never waits for
+    /// readers, this waits for those that may still observe them as long as it takes, so never call
+    /// it while holding a read guard. Meant for shutdown, making sure the side effects of dropping
+    /// displaced values happened.
+    ///
+    /// Safe to call from any number of threads at once, and with nothing to free. For a member of
+    /// an [`RcuDomain`](crate::RcuDomain), what the other members retired before is freed too.
+    /// Values whose `Rcu::update_async` future is still pending are freed by that future instead.
+    ///
+    /// ```
+    /// # use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    /// # use rcu_rust::Rcu;
+    /// static OPEN: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// #[derive(Debug)]
+    /// struct Connection;
+    ///
+    /// impl Connection {
+    ///     fn open() -> Self {
+    ///         OPEN.fetch_add(1, SeqCst);
+    ///         Connection
+    ///     }
+    /// }
+    ///
+    /// impl Drop for Connection {
+    ///     fn drop(&mut self) {
+    ///         OPEN.fetch_sub(1, SeqCst);
+    ///     }
+    /// }
+    ///
+    /// let connections = Rcu::new(std::sync::Arc::new(vec![Connection::open(), Connection::open()]));
+    /// connections.set(Default::default())?;
+    /// connections.set(Default::default())?;
+    /// connections.barrier();
+    /// assert_eq!(OPEN.load(SeqCst), 0);
+    /// # Ok::<(), rcu_rust::PublishError<std::sync::Arc<Vec<Connection>>>>(())
+    /// ```
+    pub fn barrier(&self) {
+        self.raw.barrier();
+    }
+    /// Number of displaced values held back by [`Rcu::with_retire_batching`], not handed to the
+    /// engine yet.
+    pub fn retire_batch_len(&self) -> usize {
+        self.raw.batched()
+    }
+    /// Number of allocations holding values of this `Rcu` that aren't freed yet: the current value,
+    /// the previous one kept for [`Rcu::read_prev`], and the displaced values counted by
+    /// [`Rcu::pending_retired`], so across the whole domain for values of an
+    /// [`RcuDomain`](crate::RcuDomain). Meant for leak checks: once readers are done and the backlog
+    /// was flushed, it is back to 2 after the first publish, 1 before.
+    pub fn outstanding_allocations(&self) -> usize {
+        self.raw.outstanding_allocations()
+    }
+    /// Consumes the `Rcu`, returning the current value in the allocation it was published in.
+    pub fn into_box(self) -> Box<T> {
+        self.raw.into_box()
+    }
+    /// Create a subscriber to the `Rcu`
+    pub fn subscribe(&self) -> RcuSubscriber<'_, T> {
+        RcuSubscriber {
+            rcu: self,
+            view: View::new(&self.head),
+            ack_id: None,
+        }
+    }
+    /// Like [`Rcu::subscribe`], creating a subscriber [`Rcu::wait_until_observed`] waits for: every
+    /// version one of its reads returns counts as observed by it, until it is dropped.
+    pub fn subscribe_acked(&self) -> RcuSubscriber<'_, T> {
+        let view = View::new(&self.head);
+        RcuSubscriber {
+            rcu: self,
+            ack_id: Some(self.acks.register(&view)),
+            view,
+        }
+    }
+    /// Blocks until every live subscriber created with [`Rcu::subscribe_acked`] returned `version`
+    /// or a newer one from its last read, e.g. the [`Rcu::version`] of a publish that every consumer
+    /// has to act on before a rollout goes on. Subscribers dropped meanwhile stop being waited for,
+    /// and a new subscriber counts as having observed the version current when it was created.
+    ///
+    /// Gives up after `timeout`, listing the subscribers still behind. Returns right away if there
+    /// are no acked subscribers.
+    pub fn wait_until_observed(&self, version: u64, timeout: Duration) -> Result<(), LaggingSubscribers> {
+        self.acks.wait(version, timeout)
+    }
+    /// Reads the data currently held by the `Rcu`. Returns a cloned version of the current T held by the `Rcu`.
+    #[track_caller]
+    pub fn read(&self) -> T {
+        self.raw.read_with(T::clone)
+    }
+    /// Runs `f` on the current value without cloning it, returning what `f` returns.
+    ///
+    /// `f` runs inside the read-side critical section, which keeps the value it was given alive
+    /// however many publishes happen meanwhile. With the `reclaim-counted` engine publishers wait for
+    /// `f` to return, so keep it short, and never publish to the same `Rcu` from inside it: that
+    /// would wait for itself.
+    #[track_caller]
+    pub fn read_with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        self.raw.read_with(f)
+    }
+    /// Like [`Rcu::read_with`], but safe to call from a signal handler, e.g. a profiler's
+    /// `SIGPROF` handler looking up symbolization tables, even one that interrupted a read or a
+    /// publish of the same `Rcu` on its own thread. It never allocates, takes no lock, never waits
+    /// for the thread it interrupted and only uses async-signal-safe atomic operations; `f` has to
+    /// stick to the same for the whole call to be signal-safe.
+    ///
+    /// These reads aren't counted in the metrics, nor tracked by the diagnostics. With
+    /// [`Rcu::new_biased`], a read from a thread other than the creator revokes the bias, as any
+    /// other access does, waiting for the creator's current read. With the `async` feature, their exit
+    /// never wakes a task awaiting `Rcu::update_async`, the next regular read's exit does.
+    ///
+    /// With the `fallback-lock` feature this takes the lock guarding the value, so it is only a
+    /// regular read under another name, and isn't signal-safe.
+    pub fn read_signal_safe<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        self.raw.read_signal_safe(f)
+    }
+    /// Iterates by value over a clone of the current collection. The clone is taken once up
+    /// front, so the iteration sees exactly one published version regardless of concurrent publishes.
+    pub fn iter_snapshot(&self) -> impl Iterator<Item = <T as IntoIterator>::Item>
+    where
+        T: IntoIterator,
+    {
+        self.read().into_iter()
+    }
+    /// Calls `f` with every element of the current slice-like value, without cloning anything.
+    /// Like [`Rcu::iter_snapshot`] every element comes from the same published version, and the
+    /// same caveats as for [`Rcu::read_with`] apply to `f`.
+    #[track_caller]
+    pub fn for_each<U>(&self, f: impl FnMut(&U))
+    where
+        T: Deref<Target = [U]>,
+    {
+        self.read_with(|value| value.iter().for_each(f))
+    }
+    /// The number of successful publishes since the `Rcu` was created. Rejected or failed
+    /// publishes never bump it.
+    pub fn version(&self) -> u64 {
+        self.raw.version()
+    }
+    /// Like [`Rcu::read`], also returning the version the value was published as. The pair is
+    /// always coherent: the version is never that of an earlier or later publish.
+    #[track_caller]
+    pub fn read_versioned(&self) -> (T, u64) {
+        self.raw.read_versioned(|value, version| (value.clone(), version))
+    }
+    /// When the current value was published, or the `Rcu` created if nothing was published yet.
+    pub fn last_updated(&self) -> Instant {
+        staleness::instant(self.raw.published_at())
+    }
+    /// How long ago the current value was published, see [`Rcu::last_updated`].
+    pub fn age(&self) -> Duration {
+        self.last_updated().elapsed()
+    }
+    /// Like [`Rcu::read`], failing with the value anyway if it was published more than `max_age`
+    /// ago, so callers can still serve it while refreshing it, or refuse to. The age is always that
+    /// of the value handed out, never of an earlier or later publish, like in [`Rcu::read_versioned`].
+    #[track_caller]
+    pub fn read_fresh(&self, max_age: Duration) -> Result<T, Stale<T>> {
+        self.raw.read_stamped(|value, _, published_at| {
+            let age = staleness::instant(published_at).elapsed();
+            if age <= max_age {
+                Ok(value.clone())
+            } else {
+                Err(Stale {
+                    value: value.clone(),
+                    age,
+                })
+            }
+        })
+    }
+    /// Blocks until a version newer than `version` is published, returning the current version.
+    pub fn wait_for_change(&self, version: u64) -> u64 {
+        self.wait_until(|| self.version() > version, None);
+        self.version()
+    }
+    /// Like [`Rcu::wait_for_change`], giving up after `timeout` and returning None.
+    pub fn wait_for_change_timeout(&self, version: u64, timeout: Duration) -> Option<u64> {
+        self.wait_until(|| self.version() > version, Some(timeout)).then(|| self.version())
+    }
+    /// Blocks until `done`, which may only depend on publishes and freezing, returns true, or
+    /// `timeout` elapsed, through the wait strategy if one was set. Returns whether it did.
+    pub(crate) fn wait_until(&self, mut done: impl FnMut() -> bool, timeout: Option<Duration>) -> bool {
+        let waiter = self.raw.waiter();
+        if waiter.is_default() {
+            return self.changed.wait_until(done, timeout);
+        }
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        waiter.wait_while(|| !done(), deadline)
+    }
+    /// Returns a clone of the value published before the current one, None if nothing was published yet.
+    ///
+    /// To make this possible every `Rcu` keeps the value it last replaced alive until the next
+    /// publish replaces it in turn, so it holds up to two values at any time besides whatever the
+    /// reclamation engine has yet to free.
+    #[track_caller]
+    pub fn read_prev(&self) -> Option<T> {
+        self.raw.read_pair(|_, previous| previous.cloned())
+    }
+    /// Returns clones of the current value and the one published right before it, always from the
+    /// same publish however many publishes race with the read. See [`Rcu::read_prev`].
+    #[track_caller]
+    pub fn read_pair(&self) -> (T, Option<T>) {
+        self.raw.read_pair(|current, previous| (current.clone(), previous.cloned()))
+    }
+    /// Seals the current value for good: waits for publishes in progress to complete, after which
+    /// every publishing method fails with [`PublishError::Frozen`] (`update` returns false). Reads
+    /// of a frozen `Rcu` skip the read-side critical section, and [`Rcu::frozen_ref`] hands out the
+    /// value itself. Freezing a frozen `Rcu` does nothing.
+    ///
+    /// Publishes racing with `freeze` either complete before it returns or fail. Must not be called
+    /// from a hook on the same `Rcu`, it would wait for the publish the hook is part of.
+    pub fn freeze(&self) {
+        self.raw.freeze();
+        // Wakes update iterators, nothing newer will ever come
+        self.changed.notify();
+    }
+    /// Whether [`Rcu::freeze`] was called and completed.
+    pub fn is_frozen(&self) -> bool {
+        self.raw.is_frozen()
+    }
+    /// The value itself if the `Rcu` is frozen: as it can never be replaced again it lives as
+    /// long as the `Rcu` does, and reading it costs nothing more than a load of the frozen state.
+    pub fn frozen_ref(&self) -> Option<&T> {
+        self.raw.frozen_ref()
+    }
+    /// Method that will attempt to update the data held by the `Rcu`. Returns a boolean,
+    /// true if the update was successful, false otherwise (including when an invariant refused
+    /// `new_val`, the `Rcu` is frozen or its backlog is past a limit failing publishes, see
+    /// [`Rcu::with_max_pending_retired`]).
+    pub fn update(&self, new_val: T) -> bool {
+        let Ok(new_val) = self.check(new_val) else {
+            return false;
+        };
+        if self.admit().is_err() {
+            return false;
+        }
+        self.publish_box(Box::new(new_val), |_| {}).map_err(|refused| self.refused(refused)).is_ok()
+    }
+    /// Like [`Rcu::update`], but publishes the boxed value by handing its allocation over as is,
+    /// saving the allocation and copy `update` does. On failure the box is handed back untouched.
+    /// Once the engine's queues of displaced values have grown, publishing allocates nothing, but
+    /// for the records of grace periods the `diagnostics` feature keeps.
+    pub fn update_box(&self, new_val: Box<T>) -> Result<(), Box<T>> {
+        if self.check_ref(&new_val).is_err() || self.admit().is_err() {
+            return Err(new_val);
+        }
+        self.publish_box(new_val, |_| {}).map_err(|refused| self.refused(refused))
+    }
+    /// Publishes `value`, waiting for a publish in flight to complete instead of failing like
+    /// [`Rcu::update`]. The only failures are an invariant refusing the value, the `Rcu` being frozen
+    /// and its backlog being past a limit failing publishes.
+    pub fn set(&self, value: T) -> Result<(), PublishError<T>> {
+        let value = self.check(value)?;
+        if let Err(backlog) = self.admit() {
+            return Err(self.backpressure(backlog, value));
+        }
+        self.publish_settled(Box::new(value)).map_err(|value| self.frozen(*value))
+    }
+    /// Like [`Rcu::set`], handing the allocation over as is, and never waiting for readers of the
+    /// replaced value: it is retired with a deadline that has already passed, freeing it left to a
+    /// later grace period if readers are still active. A publish in flight is still waited for. A
+    /// refused box is handed back.
+    pub(crate) fn set_box_deferred(&self, mut value: Box<T>) -> Result<(), Box<T>> {
+        if self.check_ref(&value).is_err() || self.admit().is_err() {
+            return Err(value);
+        }
+        loop {
+            match self.publish_box_before(value, Some(Instant::now()), |_| {}) {
+                Ok(_) => return Ok(()),
+                Err(Refused::InFlight(back)) => value = back,
+                Err(Refused::Frozen(back)) => return Err(self.refused(Refused::Frozen(back))),
+            }
+            self.raw.wait_settled();
+        }
+    }
+    /// Publishes `new` like [`Rcu::set`], but never waits past `dur`, whether for a publish in flight
+    /// to complete or for readers of the replaced value to finish. The outcome is always consistent:
+    ///
+    /// - if time runs out before `new` could be published, nothing changes and `new` is handed back
+    ///   in [`DeadlineError::Expired`];
+    /// - if `new` was published, `Ok` says whether the replaced value was dealt with in time,
+    ///   [`ReclaimStatus::Done`], or whether its readers were still active when time ran out
+    ///   (possible with engines that wait for readers in-line, such as `reclaim-counted`), freeing it
+    ///   being then left to a later grace period, [`ReclaimStatus::Deferred`].
+    ///
+    /// The deadline is checked while spinning, so it may be overshot by a few microseconds.
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use rcu_rust::{Rcu, ReclaimStatus};
+    /// let limits = Rcu::new(10);
+    /// let reading = limits.read_guard();
+    /// match limits.update_with_deadline(20, Duration::from_millis(10))? {
+    ///     ReclaimStatus::Done => {}
+    ///     // `reading` is still active: 10 is freed once it's dropped, by a later grace period
+    ///     ReclaimStatus::Deferred => assert_eq!(*reading, 10),
+    /// }
+    /// assert_eq!(limits.read(), 20);
+    /// # Ok::<(), rcu_rust::DeadlineError<i32>>(())
+    /// ```
+    pub fn update_with_deadline(&self, new: T, dur: Duration) -> Result<ReclaimStatus, DeadlineError<T>> {
+        let deadline = Instant::now() + dur;
+        let mut neo = Box::new(self.check(new).map_err(DeadlineError::Rejected)?);
+        match self.admit_before(Some(deadline)) {
+            Ok(true) => {}
+            Ok(false) => return Err(self.expired(*neo)),
+            Err(backlog) => return Err(DeadlineError::Rejected(self.backpressure(backlog, *neo))),
+        }
+        loop {
+            match self.publish_box_before(neo, Some(deadline), |_| {}) {
+                Ok(true) => return Ok(ReclaimStatus::Done),
+                Ok(false) => return Ok(ReclaimStatus::Deferred),
+                Err(Refused::InFlight(back)) => neo = back,
+                Err(Refused::Frozen(back)) => return Err(DeadlineError::Rejected(self.frozen(*back))),
+            }
+            if !self.raw.wait_settled_before(deadline) {
+                return Err(self.expired(*neo));
+            }
+        }
+    }
+    /// Publishes `neo` in a single attempt, only if the current value is still the one at `base`,
+    /// published as `version`, returning the version `neo` was published as. See
+    /// `RcuWriteGuard::try_commit`.
+    #[track_caller]
+    pub(crate) fn publish_if_current(&self, base: usize, version: u64, neo: T) -> Result<u64, CommitError<T>> {
+        let neo = self.check(neo).map_err(CommitError::Rejected)?;
+        if let Err(backlog) = self.admit() {
+            return Err(CommitError::Rejected(self.backpressure(backlog, neo)));
+        }
+        let mut neo = Some(Box::new(neo));
+        let mut published = version;
+        // The address alone could be that of a newer value reusing a freed allocation, which
+        // can't be freed before its version was superseded though
+        let attempt = self.try_modify(
+            |cur| {
+                if cur as *const T as usize == base && self.raw.version() == version {
+                    Ok(neo.take().expect("only taken once"))
+                } else {
+                    Err(())
+                }
+            },
+            |_| published = self.raw.version(),
+        );
+        let staged = match attempt {
+            Modify::Published => return Ok(published),
+            Modify::Frozen(neo) => return Err(CommitError::Rejected(self.frozen(*neo))),
+            Modify::Conflict(neo) => *neo,
+            Modify::Aborted(()) => *neo.expect("handed to `try_modify` only on a match"),
+        };
+        #[cfg(feature = "metrics")]
+        self.failed(Failure::Conflict);
+        let (current, version) = self.read_versioned();
+        Err(CommitError::Conflict(CommitConflict { staged, current, version }))
+    }
+    /// Publishes `f(current)`, re-running `f` on the newer value whenever another writer got there
+    /// first, so no concurrent publish is ever lost; `f` may therefore be called several times.
+    /// Returns a clone of the value that was published.
+    #[track_caller]
+    pub fn update_with(&self, mut f: impl FnMut(&T) -> T) -> Result<T, PublishError<T>> {
+        let mut published = None;
+        self.modify_retrying(|cur| self.check(f(cur)).map(Box::new), |neo| published = Some(neo.clone()))?;
+        Ok(published.unwrap())
+    }
+    /// Like [`Rcu::update_with`], `f` changing a clone of the current value in place, returning what
+    /// the successful call of `f` returned instead of a clone of the value published.
+    #[track_caller]
+    pub(crate) fn update_mut<R>(&self, mut f: impl FnMut(&mut T) -> R) -> Result<R, PublishError<T>> {
+        let mut out = None;
+        self.modify_retrying(
+            |cur| {
+                let mut next = cur.clone();
+                out = Some(f(&mut next));
+                self.check(next).map(Box::new)
+            },
+            |_| {},
+        )?;
+        Ok(out.unwrap())
+    }
+    /// Publishes what `f` computes from the current value, re-running it on the newer value whenever
+    /// another writer got there first. `on_published` runs with the value that sticks.
+    #[track_caller]
+    fn modify_retrying(
+        &self,
+        mut f: impl FnMut(&T) -> Result<Box<T>, PublishError<T>>,
+        mut on_published: impl FnMut(&T),
+    ) -> Result<(), PublishError<T>> {
+        if let Err(backlog) = self.admit() {
+            // Handed back as the value that would have been published
+            let neo = self.read_with(&mut f)?;
+            return Err(self.backpressure(backlog, *neo));
+        }
+        let mut conflicts = 0;
+        loop {
+            match self.try_modify(&mut f, &mut on_published) {
+                Modify::Published => return Ok(()),
+                Modify::Conflict(_) => {
+                    self.raw.waiter().wait(conflicts);
+                    conflicts += 1;
+                }
+                Modify::Frozen(neo) => return Err(self.frozen(*neo)),
+                Modify::Aborted(err) => return Err(err),
+            }
+        }
+    }
+    /// Applies every op in order to a single clone of the current value and publishes the result
+    /// once, bumping the version and waking subscribers once for the whole batch. Returns a clone of
+    /// the value that was published. An empty batch publishes nothing and returns the current value.
+    ///
+    /// Like [`Rcu::update_with`], whenever another writer got there first all ops are applied again,
+    /// from a fresh clone of the newer value, so each op may run several times and must not have side
+    /// effects beyond the value it is given. Ops of different types can be boxed as
+    /// `Box<dyn FnMut(&mut T)>`.
+    #[track_caller]
+    pub fn update_batch<F: FnMut(&mut T)>(&self, ops: impl IntoIterator<Item = F>) -> Result<T, PublishError<T>> {
+        let mut ops: Vec<F> = ops.into_iter().collect();
+        if ops.is_empty() {
+            return Ok(self.read());
+        }
+        self.update_with(|cur| {
+            let mut next = cur.clone();
+            ops.iter_mut().for_each(|op| op(&mut next));
+            next
+        })
+    }
+    /// Publishes `new`, and whenever another writer got there first calls `merge(winner, mine)` with
+    /// the value that writer published and the value that lost to it, `new` or the result of the
+    /// last call, retrying with the merged value until it sticks. A publish racing this call, from
+    /// the moment it was made, is never overwritten without being merged; one landing before it is,
+    /// so compute `new` from the value current right before the call. Returns a clone of the value
+    /// that was finally published.
+    ///
+    /// `merge` isn't called at all when the first attempt sticks, and is called once per lost race
+    /// after that, which may be many times under contention, so it must not have side effects
+    /// beyond the value it returns. A winner whose publish is still in progress is waited for rather
+    /// than merged repeatedly.
+    ///
+    /// If an invariant refuses `new` or a merged value, the `Rcu` is frozen or its backlog is past a
+    /// limit failing publishes, nothing is published and this returns a clone of the current value,
+    /// like [`Rcu::update`] returning false.
+    #[track_caller]
+    pub fn update_or_merge(&self, new: T, mut merge: impl FnMut(&T, T) -> T) -> T {
+        if let Err(backlog) = self.admit() {
+            // Only recorded, the current value is what is published
+            let _ = self.backpressure(backlog, new);
+            return self.read();
+        }
+        let base = self.version();
+        let mut mine = Some(new);
+        let mut raced = false;
+        let mut published = None;
+        let mut conflicts = 0;
+        loop {
+            let attempt = self.try_modify(
+                |winner| {
+                    let mine = mine.take().expect("handed back by every conflict");
+                    let next = if raced || self.version() != base { merge(winner, mine) } else { mine };
+                    self.check(next).map(Box::new)
+                },
+                |neo| published = Some(neo.clone()),
+            );
+            match attempt {
+                Modify::Published => return published.unwrap(),
+                Modify::Conflict(lost) => mine = Some(*lost),
+                Modify::Frozen(lost) => {
+                    let _ = self.frozen(*lost);
+                    return self.read();
+                }
+                Modify::Aborted(_) => return self.read(),
+            }
+            raced = true;
+            self.raw.waiter().wait(conflicts);
+            conflicts += 1;
+        }
+    }
+    /// Like [`Rcu::update`], but awaits readers of the replaced value instead of blocking for them:
+    /// `new` is published when the future is first polled, which then stays pending until those
+    /// readers are done and the replaced value is freed, the last of them to exit waking the task.
+    /// Dropping the future before that leaves freeing the replaced value to a later grace period.
+    ///
+    /// Readers are never held back meanwhile, not even with the `reclaim-counted` engine, so an
+    /// unbroken stream of overlapping readers can keep the future pending.
+    #[cfg(feature = "async")]
+    pub async fn update_async(&self, new: T) -> bool {
+        let Ok(new) = self.check(new) else {
+            return false;
+        };
+        if self.admit_async().await.is_err() {
+            return false;
+        }
+        match self.raw.publish_detached(Box::new(new), |neo| self.published(neo, |_| {})) {
+            Ok(reclamation) => {
+                self.changed.notify();
+                reclamation.await;
+                true
+            }
+            Err(refused) => {
+                self.refused(refused);
+                false
+            }
+        }
+    }
+    /// Like [`Rcu::update_with`], awaiting readers of the replaced value like [`Rcu::update_async`].
+    /// While another publish is in flight the task yields instead of spinning.
+    #[cfg(feature = "async")]
+    pub async fn update_with_async(&self, mut f: impl FnMut(&T) -> T) -> Result<T, PublishError<T>> {
+        if let Err(backlog) = self.admit_async().await {
+            let neo = self.check(self.read_with(&mut f))?;
+            return Err(self.backpressure(backlog, neo));
+        }
+        let mut published = None;
+        loop {
+            if self.raw.is_settled() {
+                let attempt = self.raw.try_modify_detached(
+                    |cur| self.check(f(cur)).map(Box::new),
+                    |neo| self.published(neo, |neo| published = Some(neo.clone())),
+                );
+                match attempt {
+                    Ok(reclamation) => {
+                        self.changed.notify();
+                        reclamation.await;
+                        return Ok(published.unwrap());
+                    }
+                    Err(Modify::Published | Modify::Conflict(_)) => {}
+                    Err(Modify::Frozen(neo)) => return Err(self.frozen(*neo)),
+                    Err(Modify::Aborted(err)) => return Err(err),
+                }
+            }
+            YieldNow::default().await;
+        }
+    }
+    /// Publishes the value in `staged`, already checked, like [`Rcu::update_async`] but without
+    /// awaiting anything: leaves it staged and returns None if it would have to wait for the
+    /// backlog to shrink or for a publish in flight to complete, see `RcuSink`.
+    #[cfg(feature = "async")]
+    pub(crate) fn publish_staged(&self, staged: &mut Option<T>) -> Result<Option<Reclamation<'_, T>>, PublishError<T>> {
+        let Some(new) = staged.take() else {
+            return Ok(None);
+        };
+        match self.admit_before(Some(Instant::now())) {
+            Ok(true) => {}
+            Ok(false) => {
+                *staged = Some(new);
+                return Ok(None);
+            }
+            Err(backlog) => return Err(self.backpressure(backlog, new)),
+        }
+        match self.raw.publish_detached(Box::new(new), |neo| self.published(neo, |_| {})) {
+            Ok(reclamation) => {
+                self.changed.notify();
+                Ok(Some(reclamation))
+            }
+            Err(Refused::InFlight(new)) => {
+                *staged = Some(*new);
+                Ok(None)
+            }
+            Err(Refused::Frozen(new)) => Err(self.frozen(*new)),
+        }
+    }
+    /// Installs an invariant every value has to satisfy before it is published, by any of the
+    /// publishing methods. A value refused by any invariant is never visible to readers, leaves
+    /// the current value in place and does not bump the version; `Err(reason)` is reported back
+    /// as [`PublishError::Rejected`]. Invariants compose, a value has to pass all of them, checked
+    /// in installation order. The current value is not re-checked.
+    pub fn set_invariant(&self, f: impl Fn(&T) -> Result<(), String> + Send + Sync + 'static) {
+        self.invariants.add(f)
+    }
+    /// Registers `f` to be called with every value published from now on.
+    ///
+    /// Hooks run synchronously on the publishing thread, right after the new value became visible
+    /// to readers and before the publishing call returns, in registration order. While they run no
+    /// other publish can succeed, so:
+    ///
+    /// - a hook calling `update` on the same `Rcu` always gets `false` back, it never deadlocks;
+    /// - a hook must not call [`Rcu::set`], [`Rcu::update_with`] or [`Rcu::update_or_merge`] on the
+    ///   same `Rcu`, they wait for the publish the hook is part of to complete and would never return;
+    /// - a hook may register or remove hooks (including itself), which takes effect from the next publish;
+    /// - a panicking hook is contained: the remaining hooks still run, the publish still succeeds
+    ///   and the `Rcu` remains fully usable.
+    ///
+    /// A hook registered concurrently with a publish may or may not be called for that publish.
+    pub fn on_update(&self, f: impl Fn(&T) + Send + Sync + 'static) -> HookId {
+        self.hooks.add(f)
+    }
+    /// Like [`Rcu::on_update`], `f` also getting the version the value was published as.
+    pub(crate) fn on_update_versioned(&self, f: impl Fn(&T, u64) + Send + Sync + 'static) -> HookId {
+        self.hooks.add_versioned(f)
+    }
+    /// Removes a hook registered with [`Rcu::on_update`]. Returns false if it was already removed.
+    pub fn remove_hook(&self, id: HookId) -> bool {
+        self.hooks.remove(id)
+    }
+    /// Every publish goes through here (or `publish_box_before`), `try_modify` or the async updates,
+    /// `on_published` runs like a hook.
+    fn publish_box(&self, neo: Box<T>, on_published: impl FnOnce(&T)) -> Result<(), Refused<T>> {
+        self.publish_box_before(neo, None, on_published).map(|_| ())
+    }
+    /// `publish_box` giving up on waiting for readers at `deadline`, see `RawRcu::publish_before`.
+    fn publish_box_before(
+        &self,
+        neo: Box<T>,
+        deadline: Option<Instant>,
+        on_published: impl FnOnce(&T),
+    ) -> Result<bool, Refused<T>> {
+        let in_time = self.raw.publish_before(neo, deadline, |neo| self.published(neo, on_published))?;
+        self.changed.notify();
+        Ok(in_time)
+    }
+    /// Publishes `neo` without excluding other writers, for an `Rcu` that can't refuse it: split by
+    /// `Rcu::into_single_writer`, which doesn't split one with invariants or limits, or frozen.
+    ///
+    /// # Safety
+    /// No other publish may ever run concurrently with this one.
+    pub(crate) unsafe fn publish_exclusive(&self, neo: Box<T>) {
+        self.raw.publish_exclusive(neo, |neo| self.published(neo, |_| {}));
+        self.changed.notify();
+    }
+    /// Publishes `neo`, waiting out publishes in flight until it sticks. Invariants are the caller's
+    /// business. Only fails, handing `neo` back, if the `Rcu` is frozen.
+    fn publish_settled(&self, mut neo: Box<T>) -> Result<(), Box<T>> {
+        loop {
+            match self.publish_box(neo, |_| {}) {
+                Ok(()) => return Ok(()),
+                Err(Refused::InFlight(back)) => neo = back,
+                Err(Refused::Frozen(back)) => return Err(back),
+            }
+            self.raw.wait_settled();
+        }
+    }
+    /// A single attempt of `RawRcu::try_modify`, with the same bookkeeping as `publish_box`.
+    #[track_caller]
+    fn try_modify<E>(&self, f: impl FnOnce(&T) -> Result<Box<T>, E>, on_published: impl FnOnce(&T)) -> Modify<T, E> {
+        let attempt = self.raw.try_modify(f, |neo| self.published(neo, on_published));
+        if let Modify::Published = attempt {
+            self.changed.notify();
+        }
+        attempt
+    }
+    /// Called with every value this `Rcu` publishes, before any other publish can replace it.
+    fn published(&self, neo: &T, on_published: impl FnOnce(&T)) {
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = self.raw.metrics() {
+            metrics.published();
+        }
+        self.head.published(self.raw.version(), self.raw.published_at());
+        #[cfg(feature = "audit")]
+        self.audit.record(neo, self.raw.version());
+        self.hooks.run(neo, self.raw.version());
+        on_published(neo);
+    }
+    /// Runs `value` past the invariants.
+    pub(crate) fn check(&self, value: T) -> Result<T, PublishError<T>> {
+        let checked = self.invariants.check(value);
+        #[cfg(feature = "metrics")]
+        if checked.is_err() {
+            self.failed(Failure::Rejected);
+        }
+        checked
+    }
+    /// Like `check`, for values that are only borrowed.
+    fn check_ref(&self, value: &T) -> Result<(), String> {
+        let checked = self.invariants.check_ref(value);
+        #[cfg(feature = "metrics")]
+        if checked.is_err() {
+            self.failed(Failure::Rejected);
+        }
+        checked
+    }
+    /// Records a call refused by `publish_box`, returning the value it handed back.
+    fn refused(&self, refused: Refused<T>) -> Box<T> {
+        match refused {
+            Refused::InFlight(value) => {
+                #[cfg(feature = "metrics")]
+                self.failed(Failure::Conflict);
+                value
+            }
+            Refused::Frozen(value) => {
+                #[cfg(feature = "metrics")]
+                self.failed(Failure::Frozen);
+                value
+            }
+        }
+    }
+    /// Records a call that ran out of time, returning the error to report.
+    fn expired(&self, value: T) -> DeadlineError<T> {
+        #[cfg(feature = "metrics")]
+        self.failed(Failure::Expired);
+        DeadlineError::Expired { value }
+    }
+    /// Records a call refused because the `Rcu` is frozen, returning the error to report.
+    fn frozen(&self, value: T) -> PublishError<T> {
+        #[cfg(feature = "metrics")]
+        self.failed(Failure::Frozen);
+        PublishError::Frozen { value }
+    }
+    /// Records a call that published nothing.
+    #[cfg(feature = "metrics")]
+    fn failed(&self, failure: Failure) {
+        if let Some(metrics) = self.raw.metrics() {
+            metrics.failed(failure);
+        }
+    }
+}
+
+unsafe impl<T> Send for Rcu<T> where T: Send + Sync + Clone {}
+unsafe impl<T> Sync for Rcu<T> where T: Send + Sync + Clone {}
+
+/// A struct for subscribing to a `Rcu`. May be useful when a thread only needs to read the current value of the
+/// `Rcu` and does not need have the ability to update.
+pub struct RcuSubscriber<'a, T: Clone> {
+    rcu: &'a Rcu<T>,
+    /// What the last `read` returned
+    view: Arc<View>,
+    /// Set if created with `subscribe_acked`
+    ack_id: Option<u64>,
+}
+
+impl<T: Clone> RcuSubscriber<'_, T> {
+    /// Read the data currently in the `Rcu` being subscribed to.
+    #[track_caller]
+    pub fn read(&self) -> T {
+        let value = self.rcu.raw.read_versioned(|value, version| {
+            self.view.record(version);
+            value.clone()
+        });
+        if self.ack_id.is_some() {
+            self.rcu.acks.observed();
+        }
+        value
+    }
+    /// How far the value last returned by `read` (or the one current at subscription time, before
+    /// the first read) is behind the latest publish.
+    pub fn staleness(&self) -> Staleness {
+        self.view.staleness()
+    }
+    /// Tracks [`RcuSubscriber::staleness`] without borrowing the subscriber, see
+    /// [`StalenessRegistry`](crate::StalenessRegistry).
+    pub fn staleness_handle(&self) -> StalenessHandle {
+        StalenessHandle::new(&self.view)
+    }
+    /// The id [`LaggingSubscribers`] lists the subscriber under if created with
+    /// [`Rcu::subscribe_acked`], ids count up from 0 in creation order per `Rcu`. None otherwise.
+    pub fn ack_id(&self) -> Option<u64> {
+        self.ack_id
+    }
+}
+
+impl<T: Clone> Drop for RcuSubscriber<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.ack_id {
+            self.rcu.acks.unregister(id);
+        }
+    }
+}
+
+unsafe impl<T> Send for RcuSubscriber<'_, T> where T: Send + Sync + Clone {}
+unsafe impl<T> Sync for RcuSubscriber<'_, T> where T: Send + Sync + Clone {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst};
+    use std::sync::{mpsc, Arc, Barrier, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::Rcu;
+    use crate::{DeadlineError, ReclaimStatus, SpinYield};
+
+    /// Counts its live instances in the counter it shares with the test.
+    struct Live(Arc<AtomicUsize>);
+
+    impl Live {
+        fn new(live: &Arc<AtomicUsize>) -> Self {
+            live.fetch_add(1, SeqCst);
+            Self(Arc::clone(live))
+        }
+    }
+
+    impl Clone for Live {
+        fn clone(&self) -> Self {
+            Self::new(&self.0)
+        }
+    }
+
+    impl Drop for Live {
+        fn drop(&mut self) {
+            self.0.fetch_sub(1, SeqCst);
+        }
+    }
+
+    #[test]
+    fn publishes_are_read_back_in_order() {
+        let rcu = Rcu::new(0);
+        assert_eq!(rcu.version(), 0);
+        for i in 1..=10 {
+            rcu.set(i).unwrap();
+            assert_eq!(rcu.read(), i);
+            assert_eq!(rcu.version(), i as u64);
+            assert_eq!(rcu.read_prev(), Some(i - 1));
+        }
+        assert!(rcu.update(11));
+        assert_eq!(*rcu.into_box(), 11);
+    }
+
+    #[test]
+    fn concurrent_updates_are_never_lost() {
+        let rcu = Rcu::new(0u64);
+        thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    for _ in 0..500 {
+                        rcu.update_with(|n| n + 1).unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(rcu.read(), 4000);
+        assert_eq!(rcu.version(), 4000);
+    }
+
+    #[test]
+    fn readers_only_ever_see_whole_values() {
+        let rcu = Rcu::new((0u64, 0u64));
+        let stop = AtomicBool::new(false);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let mut last = 0;
+                    while !stop.load(SeqCst) {
+                        let (n, double) = rcu.read_with(|&pair| pair);
+                        assert_eq!(double, 2 * n, "torn read");
+                        assert!(n >= last, "went back in time");
+                        last = n;
+                    }
+                });
+            }
+            for n in 1..=2000 {
+                rcu.set((n, 2 * n)).unwrap();
+            }
+            stop.store(true, SeqCst);
+        });
+    }
+
+    #[test]
+    fn merging_writers_keep_every_key() {
+        const WRITERS: usize = 8;
+        let rcu = Rcu::new(BTreeMap::new()).with_wait_strategy(SpinYield::default());
+        // Holds every first attempt back until all of them started, so all but one lose the race
+        let start = Arc::new(Barrier::new(WRITERS));
+        let first_attempts = Arc::new(AtomicUsize::new(0));
+        {
+            let (start, first_attempts) = (Arc::clone(&start), Arc::clone(&first_attempts));
+            rcu.set_invariant(move |_| {
+                if first_attempts.fetch_add(1, SeqCst) < WRITERS {
+                    start.wait();
+                }
+                Ok(())
+            });
+        }
+        let merges = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for writer in 0..WRITERS {
+                let (rcu, merges) = (&rcu, &merges);
+                s.spawn(move || {
+                    let mine = rcu.read_with(|map| {
+                        let mut mine = map.clone();
+                        mine.insert(writer, writer);
+                        mine
+                    });
+                    let merged = rcu.update_or_merge(mine, |winner, mut mine| {
+                        merges.fetch_add(1, SeqCst);
+                        mine.extend(winner.iter().map(|(&key, &writer)| (key, writer)));
+                        mine
+                    });
+                    assert_eq!(merged.get(&writer), Some(&writer), "published without its own key");
+                });
+            }
+        });
+        assert_eq!(rcu.read(), (0..WRITERS).map(|writer| (writer, writer)).collect::<BTreeMap<_, _>>());
+        assert!(merges.load(SeqCst) >= WRITERS - 1, "only merged {} times", merges.load(SeqCst));
+    }
+
+    #[test]
+    fn merging_is_only_for_lost_races() {
+        let rcu = Rcu::new(vec![1]);
+        let published = rcu.update_or_merge(vec![1, 2], |_, _| panic!("merged without a race"));
+        assert_eq!(published, vec![1, 2]);
+        assert_eq!(rcu.read(), vec![1, 2]);
+        // Refused values publish nothing, and hand back the value that stays current
+        rcu.freeze();
+        assert_eq!(rcu.update_or_merge(vec![3], |_, mine| mine), vec![1, 2]);
+        assert_eq!(rcu.version(), 1);
+    }
+
+    #[test]
+    fn every_displaced_value_is_freed() {
+        let live = Arc::new(AtomicUsize::new(0));
+        // Spinning writers would starve each other on few cores
+        let rcu = Rcu::new(Live::new(&live)).with_wait_strategy(SpinYield::default());
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..200 {
+                        rcu.read_with(|_| thread::yield_now());
+                        rcu.set(Live::new(&live)).ok().unwrap();
+                    }
+                });
+            }
+        });
+        rcu.barrier();
+        // The current value and the previous one kept for `read_prev`
+        assert_eq!(live.load(SeqCst), 2);
+        assert_eq!(rcu.outstanding_allocations(), 2);
+        drop(rcu);
+        assert_eq!(live.load(SeqCst), 0);
+    }
+
+    #[test]
+    fn deadline_publishes_report_whether_reclamation_was_deferred() {
+        let rcu = Rcu::new(0);
+        assert_eq!(rcu.update_with_deadline(1, Duration::from_secs(5)), Ok(ReclaimStatus::Done));
+        let guard = rcu.read_guard();
+        // Only engines waiting for readers in-line wait for the guard, and give up on it
+        let expected = if cfg!(any(feature = "reclaim-counted", feature = "fallback-lock")) { ReclaimStatus::Deferred } else { ReclaimStatus::Done };
+        assert_eq!(rcu.update_with_deadline(2, Duration::from_millis(20)), Ok(expected));
+        assert_eq!((*guard, rcu.read()), (1, 2));
+        drop(guard);
+        rcu.barrier();
+        assert_eq!(rcu.pending_retired(), 0);
+    }
+
+    #[test]
+    fn deadline_publishes_expire_behind_a_publish_in_flight() {
+        let rcu = Rcu::new(0);
+        let (entered, wait_entered) = mpsc::channel();
+        let (release, wait_release) = mpsc::channel::<()>();
+        let wait_release = Mutex::new(wait_release);
+        // Keeps the publish of 1 in flight until released
+        rcu.on_update(move |value| {
+            if *value == 1 {
+                entered.send(()).unwrap();
+                let _ = wait_release.lock().unwrap().recv();
+            }
+        });
+        thread::scope(|s| {
+            s.spawn(|| rcu.set(1).unwrap());
+            wait_entered.recv().unwrap();
+            let expired = rcu.update_with_deadline(2, Duration::from_millis(20));
+            assert_eq!(expired, Err(DeadlineError::Expired { value: 2 }));
+            drop(release);
+        });
+        assert_eq!(rcu.read(), 1);
+        assert_eq!(rcu.update_with_deadline(3, Duration::from_secs(5)), Ok(ReclaimStatus::Done));
+    }
+
+    #[test]
+    fn read_pair_never_mixes_two_publishes() {
+        const PUBLISHES: u64 = 2000;
+        // Every publish is the one before plus 1, so a mismatched pair shows
+        let rcu = Rcu::new(0u64).with_wait_strategy(SpinYield::default());
+        let pairs = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| {
+                    while rcu.read() < 2 * PUBLISHES {
+                        if let (cur, Some(prev)) = rcu.read_pair() {
+                            assert_eq!(prev + 1, cur, "paired with another publish's previous value");
+                            pairs.fetch_add(1, SeqCst);
+                        }
+                        thread::yield_now();
+                    }
+                });
+            }
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..PUBLISHES {
+                        rcu.update_with(|n| n + 1).unwrap();
+                        // Lets readers in between publishes even on a single core
+                        thread::yield_now();
+                    }
+                });
+            }
+        });
+        assert!(pairs.load(SeqCst) > 0);
+        assert_eq!(rcu.read_pair(), (2 * PUBLISHES, Some(2 * PUBLISHES - 1)));
+        assert_eq!(rcu.read_prev(), Some(2 * PUBLISHES - 1));
+    }
+
+    #[test]
+    fn reclaim_now_leaves_nothing_pending_once_readers_are_quiescent() {
+        let live = Arc::new(AtomicUsize::new(0));
+        let rcu = Rcu::new(Live::new(&live)).with_retire_batching(1000, Duration::from_secs(3600));
+        for _ in 0..10 {
+            rcu.set(Live::new(&live)).ok().unwrap();
+        }
+        // Everything displaced but the previous value is batched
+        assert_eq!(rcu.pending_retired(), 9);
+        rcu.reclaim_now();
+        assert_eq!(rcu.pending_retired(), 0);
+        assert_eq!(live.load(SeqCst), 2);
+
+        let guard = rcu.read_guard();
+        // Deferred by engines waiting for readers in-line, left to the engine by the others
+        assert!(rcu.update_with_deadline(Live::new(&live), Duration::from_millis(20)).is_ok());
+        assert!(rcu.update_with_deadline(Live::new(&live), Duration::from_millis(20)).is_ok());
+        drop(guard);
+        rcu.reclaim_now();
+        assert_eq!(rcu.pending_retired(), 0);
+        assert_eq!(live.load(SeqCst), 2);
+    }
+
+    #[test]
+    fn reclaim_now_never_frees_a_value_still_read() {
+        let live = Arc::new(AtomicUsize::new(0));
+        let rcu = Rcu::new((0, Live::new(&live))).with_retire_batching(1000, Duration::from_secs(3600));
+        let guard = rcu.read_guard();
+        // Displaces the guarded value, then retires it
+        rcu.set((1, Live::new(&live))).ok().unwrap();
+        rcu.set((2, Live::new(&live))).ok().unwrap();
+        assert_eq!(rcu.pending_retired(), 1);
+        rcu.reclaim_now();
+        assert_eq!(live.load(SeqCst), 3, "freed the value under the guard");
+        assert_eq!(rcu.pending_retired(), 1);
+        assert_eq!(guard.0, 0);
+        drop(guard);
+        rcu.reclaim_now();
+        assert_eq!(rcu.pending_retired(), 0);
+        assert_eq!(live.load(SeqCst), 2);
+    }
+
+
+    #[cfg(all(unix, not(feature = "fallback-lock")))]
+    #[test]
+    fn signal_handlers_read_whole_values_while_updates_run() {
+        use std::ptr;
+        use std::sync::atomic::AtomicPtr;
+
+        type Pair = (u64, u64);
+        /// The value the handler reads, null outside of the test
+        static TARGET: AtomicPtr<Rcu<Pair>> = AtomicPtr::new(ptr::null_mut());
+        static READS: AtomicUsize = AtomicUsize::new(0);
+        static TORN: AtomicUsize = AtomicUsize::new(0);
+
+        extern "C" fn handler(_: libc::c_int) {
+            // Safety: the test only clears it once no handler can run anymore
+            let Some(rcu) = (unsafe { TARGET.load(SeqCst).as_ref() }) else { return };
+            if !rcu.read_signal_safe(|&(a, b)| a == b) {
+                TORN.fetch_add(1, SeqCst);
+            }
+            READS.fetch_add(1, SeqCst);
+        }
+
+        // Safety: an all-zero `sigaction` is a valid empty one, filled in before it is used
+        let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+        action.sa_sigaction = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        // Safety: as above
+        let mut previous: libc::sigaction = unsafe { std::mem::zeroed() };
+        // Safety: both point to valid `sigaction`s, and the handler only does what is signal-safe
+        assert_eq!(unsafe { libc::sigaction(libc::SIGUSR1, &action, &mut previous) }, 0);
+
+        type New = fn(Pair) -> Rcu<Pair>;
+        #[cfg_attr(not(feature = "membarrier"), allow(unused_mut))]
+        let mut constructors: Vec<(&str, New)> = vec![("new", Rcu::new), ("new_biased", Rcu::new_biased)];
+        #[cfg(feature = "membarrier")]
+        constructors.push(("new_membarrier", Rcu::new_membarrier));
+        for (constructor, new) in constructors {
+            READS.store(0, SeqCst);
+            let rcu = new((0, 0)).with_wait_strategy(SpinYield::default());
+            TARGET.store(&rcu as *const Rcu<Pair> as *mut Rcu<Pair>, SeqCst);
+            let done = AtomicBool::new(false);
+            let (send_id, recv_id) = mpsc::channel();
+            thread::scope(|s| {
+                // Interrupted halfway through its own reads and publishes
+                let target = s.spawn(|| {
+                    // Safety: always safe to call
+                    send_id.send(unsafe { libc::pthread_self() }).unwrap();
+                    while !done.load(SeqCst) {
+                        rcu.read_with(|&(a, b)| assert_eq!(a, b, "{constructor}: torn read"));
+                        rcu.update_with(|&(a, b)| (a + 1, b + 1)).unwrap();
+                    }
+                });
+                s.spawn(|| {
+                    while !done.load(SeqCst) {
+                        rcu.update_with(|&(a, b)| (a + 1, b + 1)).unwrap();
+                        thread::yield_now();
+                    }
+                });
+                let id = recv_id.recv().unwrap();
+                for _ in 0..2000 {
+                    // Safety: `target` runs until `done` is set below
+                    assert_eq!(unsafe { libc::pthread_kill(id, libc::SIGUSR1) }, 0);
+                    thread::yield_now();
+                }
+                done.store(true, SeqCst);
+                // A handler runs before its thread goes on, so none is left once it is joined
+                target.join().unwrap();
+            });
+            TARGET.store(ptr::null_mut(), SeqCst);
+            assert!(READS.load(SeqCst) > 0, "{constructor}: the handler never ran");
+        }
+        assert_eq!(TORN.load(SeqCst), 0, "a handler read a torn value");
+        // Safety: as above
+        assert_eq!(unsafe { libc::sigaction(libc::SIGUSR1, &previous, ptr::null_mut()) }, 0);
+    }
+
+    #[test]
+    fn snapshot_iterations_each_see_exactly_one_version() {
+        // Every element of a version holds its number
+        let rcu = Rcu::new(vec![0u64; 64]).with_wait_strategy(SpinYield::default());
+        let done = AtomicBool::new(false);
+        let iterations = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    while !done.load(SeqCst) {
+                        let mut seen = rcu.iter_snapshot();
+                        let first = seen.next().unwrap();
+                        assert!(seen.all(|n| n == first), "iter_snapshot mixed versions");
+                        let mut elements = Vec::new();
+                        rcu.for_each(|&n| {
+                            elements.push(n);
+                            // Gives writers a chance to publish halfway through
+                            if elements.len() == 32 {
+                                thread::yield_now();
+                            }
+                        });
+                        assert_eq!(elements.len(), 64);
+                        assert!(elements.iter().all(|&n| n == elements[0]), "for_each mixed versions");
+                        iterations.fetch_add(1, SeqCst);
+                    }
+                });
+            }
+            s.spawn(|| {
+                for n in 1..=500 {
+                    rcu.set(vec![n; 64]).unwrap();
+                    thread::yield_now();
+                }
+                done.store(true, SeqCst);
+            });
+        });
+        assert!(iterations.load(SeqCst) > 0, "no iteration raced the writer");
+        assert!(rcu.iter_snapshot().eq([500; 64]));
+    }
+
+    #[test]
+    fn a_batch_publishes_once_whatever_its_length() {
+        let rcu = Rcu::new(Vec::new());
+        let (tx, rx) = mpsc::channel();
+        rcu.on_update(move |list: &Vec<u32>| tx.send(list.clone()).unwrap());
+        let published = rcu.update_batch((0..10).map(|n| move |list: &mut Vec<u32>| list.push(n))).unwrap();
+        assert_eq!(published, (0..10).collect::<Vec<_>>());
+        assert_eq!(rcu.version(), 1, "more than one publish for a batch");
+        // Hooks only ever saw the whole batch applied
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), [published]);
+        let empty: [fn(&mut Vec<u32>); 0] = [];
+        assert_eq!(rcu.update_batch(empty).unwrap().len(), 10);
+        assert_eq!(rcu.version(), 1, "an empty batch published");
+    }
+
+    #[test]
+    fn batches_racing_a_writer_apply_whole_and_lose_nothing() {
+        // Batches push runs of 4 numbers from a million up, the other writer single numbers below
+        let rcu = Rcu::new(Vec::<u64>::new()).with_wait_strategy(SpinYield::default());
+        thread::scope(|s| {
+            s.spawn(|| {
+                for n in 0..200 {
+                    let ops = (0..4).map(|i| move |list: &mut Vec<u64>| list.push(1_000_000 + n * 4 + i));
+                    rcu.update_batch(ops).unwrap();
+                    thread::yield_now();
+                }
+            });
+            s.spawn(|| {
+                for n in 0..200 {
+                    rcu.update_with(|list| list.iter().copied().chain([n]).collect()).unwrap();
+                    thread::yield_now();
+                }
+            });
+            s.spawn(|| {
+                // No reader ever sees a batch half applied
+                for _ in 0..1000 {
+                    rcu.read_with(|list| {
+                        let batched = list.iter().filter(|&&n| n >= 1_000_000).count();
+                        assert!(batched.is_multiple_of(4), "{batched} batched numbers");
+                    });
+                    thread::yield_now();
+                }
+            });
+        });
+        assert_eq!(rcu.version(), 400, "a publish per batch and per update");
+        let list = rcu.read();
+        let (batched, single): (Vec<u64>, Vec<u64>) = list.iter().partition(|&&n| n >= 1_000_000);
+        assert_eq!(batched, (1_000_000..1_000_800).collect::<Vec<_>>(), "batches lost or reordered ops");
+        assert_eq!(single, (0..200).collect::<Vec<_>>(), "the racing writer's updates were lost");
+    }
+
+    #[test]
+    fn read_fresh_tells_fresh_values_from_stale_ones() {
+        let rcu = Rcu::new(0);
+        let before = Instant::now();
+        rcu.set(1).unwrap();
+        let after = Instant::now();
+        let published = rcu.last_updated();
+        assert!(before <= published && published <= after, "stamped outside of its publish");
+        assert_eq!(rcu.read_fresh(Duration::from_secs(60)).unwrap(), 1);
+        thread::sleep(Duration::from_millis(30));
+        let stale = rcu.read_fresh(Duration::from_millis(10)).unwrap_err();
+        assert_eq!(stale.value, 1);
+        assert!(stale.age >= Duration::from_millis(30), "aged {:?}", stale.age);
+        assert!(rcu.age() >= stale.age);
+    }
+
+    #[test]
+    fn read_fresh_ages_are_those_of_the_value_read() {
+        // Each value carries when its publish started, the writer logs when each one ended
+        let rcu = Rcu::new((0usize, Instant::now())).with_wait_strategy(SpinYield::default());
+        let mut ended = vec![Instant::now()];
+        let done = AtomicBool::new(false);
+        let reads = thread::scope(|s| {
+            let readers: Vec<_> = (0..2)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut reads = Vec::new();
+                        while !done.load(SeqCst) {
+                            let start = Instant::now();
+                            // Every value is stale, for its age to be handed out
+                            let stale = rcu.read_fresh(Duration::ZERO).unwrap_err();
+                            let end = Instant::now();
+                            reads.push((stale.value, start - stale.age, end - stale.age));
+                            thread::yield_now();
+                        }
+                        reads
+                    })
+                })
+                .collect();
+            for n in 1..=300 {
+                rcu.set((n, Instant::now())).unwrap();
+                ended.push(Instant::now());
+                thread::yield_now();
+            }
+            done.store(true, SeqCst);
+            readers.into_iter().flat_map(|reader| reader.join().unwrap()).collect::<Vec<_>>()
+        });
+        assert!(!reads.is_empty());
+        // The stamp lies between the two estimates, and within the publish of the value read
+        for ((n, started), earliest, latest) in reads.into_iter().filter(|((n, _), _, _)| *n > 0) {
+            assert!(latest >= started, "value {n} stamped before its publish started");
+            assert!(earliest <= ended[n], "value {n} stamped after its publish ended");
+        }
+    }
+
+    #[cfg(all(feature = "reclaim-counted", not(feature = "fallback-lock")))]
+    #[test]
+    fn only_preferring_writers_holds_readers_back_under_sustained_writes() {
+        use crate::Preference;
+
+        /// How long the slow reader stays in, and out, of its critical sections.
+        const HOLD: Duration = Duration::from_millis(2);
+
+        /// The 99th percentile of a fast reader's latency while a writer publishes nonstop and a
+        /// slow reader makes each of its grace periods last.
+        fn p99(preference: Preference) -> Duration {
+            let rcu = Rcu::new(0u64).with_wait_strategy(SpinYield::default()).with_preference(preference);
+            let stop = AtomicBool::new(false);
+            let mut latencies = thread::scope(|s| {
+                s.spawn(|| {
+                    while !stop.load(SeqCst) {
+                        rcu.read_with(|_| thread::sleep(HOLD));
+                        thread::sleep(HOLD);
+                    }
+                });
+                s.spawn(|| {
+                    for value in 1.. {
+                        if stop.load(SeqCst) {
+                            break;
+                        }
+                        rcu.set(value).unwrap();
+                    }
+                });
+                let mut latencies = Vec::new();
+                let start = Instant::now();
+                while start.elapsed() < Duration::from_millis(300) {
+                    let read = Instant::now();
+                    rcu.read_with(|_| ());
+                    latencies.push(read.elapsed());
+                    thread::yield_now();
+                }
+                stop.store(true, SeqCst);
+                latencies
+            });
+            latencies.sort_unstable();
+            latencies[latencies.len() * 99 / 100]
+        }
+
+        let (reader_preferred, writer_preferred) = (p99(Preference::ReaderPreferred), p99(Preference::WriterPreferred));
+        assert!(reader_preferred < HOLD / 2, "readers waited for the slow reader: p99 {reader_preferred:?}");
+        assert!(writer_preferred >= HOLD / 2, "readers weren't held back: p99 {writer_preferred:?}");
+    }
+}
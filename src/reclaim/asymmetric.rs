@@ -0,0 +1,442 @@
+//! Membarrier-assisted reads, with the `membarrier` feature, after liburcu's memb flavor.
+//!
+//! Every thread reading a value gets a slot of its own, and marks its critical sections in it with
+//! plain stores separated from its loads of the value by compiler fences only: no read-modify-write
+//! and no hardware fence on the read path. The writer pays for the ordering instead, with a
+//! process-wide barrier (`membarrier(2)`) on each side of its wait for readers:
+//!
+//! - after unpublishing a value, the first barrier makes every slot marked before it visible, and
+//!   any reader marking its slot after it is guaranteed to load the new value;
+//! - once the slots it waits for are clear, the second barrier completes whatever those readers
+//!   loaded before clearing them, so the old value can be freed.
+//!
+//! Slots are marked with the grace period their critical section entered in, only ever advanced
+//! by writers, so a writer never waits for readers that entered after it started waiting.
+
+use std::cell::RefCell;
+use std::mem;
+use std::sync::atomic::{compiler_fence, AtomicUsize, Ordering::{Relaxed, SeqCst}};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::Waker;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::wakers::{self, Wakers};
+use super::{barrier, try_lock_freeing, Retired, Watchdog};
+use crate::wait::Waiter;
+
+/// Checks of the slots a writer spins through before it sleeps until a reader exits, like liburcu
+const ACTIVE_ATTEMPTS: u32 = 100;
+/// Longest a writer sleeps before checking again regardless, so it notices its deadline
+const SLEEP: Duration = Duration::from_millis(1);
+
+/// Where one thread marks its critical sections on one engine.
+#[derive(Default)]
+struct Slot {
+    /// Nesting depth of the thread's critical sections, only ever written by that thread
+    depth: AtomicUsize,
+    /// The grace period the thread's outermost critical section entered in, 0 outside of one
+    period: AtomicUsize,
+}
+
+thread_local! {
+    /// This thread's slot on every engine it read from, by engine id
+    static SLOTS: RefCell<Vec<(usize, Arc<Slot>)>> = const { RefCell::new(Vec::new()) };
+}
+
+pub(crate) struct Asymmetric {
+    /// Keys this engine's slots in `SLOTS`
+    id: usize,
+    /// The grace period readers entering now mark their slot with, never 0
+    period: AtomicUsize,
+    /// The slot of every thread that read, kept until that thread exited
+    slots: Mutex<Vec<Arc<Slot>>>,
+    /// Reads from signal handlers, which can't use their thread's slot: the code they interrupted
+    /// may be halfway through marking it, or registering it
+    signal_readers: AtomicUsize,
+    /// Allocations whose writer stopped waiting at its deadline, freed by the next full grace period
+    deferred: Mutex<Vec<Retired>>,
+    /// Held while allocations taken out of `deferred` are waited for, see `try_lock_freeing`
+    freeing: Mutex<()>,
+    watchdog: Watchdog,
+    /// Writers and tasks waiting for slots to clear
+    wakers: Wakers,
+}
+
+impl Asymmetric {
+    /// None if the process-wide barrier isn't available, or `fallback-lock` rules it out, the
+    /// caller then falls back to the regular engine.
+    pub(crate) fn new() -> Option<Self> {
+        Self::if_available(barrier::available())
+    }
+
+    /// Like `new`, given whether the process-wide barrier is available.
+    fn if_available(available: bool) -> Option<Self> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        (!cfg!(feature = "fallback-lock") && available).then(|| Self {
+            id: NEXT_ID.fetch_add(1, Relaxed),
+            period: AtomicUsize::new(1),
+            slots: Default::default(),
+            signal_readers: Default::default(),
+            deferred: Default::default(),
+            freeing: Default::default(),
+            watchdog: Default::default(),
+            wakers: Default::default(),
+        })
+    }
+
+    /// Enters a critical section, returning the token to exit it with.
+    pub(crate) fn enter(&self) -> usize {
+        let slot = self.slot();
+        let depth = slot.depth.load(Relaxed);
+        slot.depth.store(depth + 1, Relaxed);
+        if depth == 0 {
+            slot.period.store(self.period.load(Relaxed), Relaxed);
+            // Keeps the mark ahead of the loads of the value, the first barrier of
+            // `wait_for_readers` does the rest
+            compiler_fence(SeqCst);
+        }
+        slot as *const Slot as usize
+    }
+
+    /// Exits the critical section `token` was handed out for, on the thread that entered it.
+    pub(crate) fn exit(&self, token: usize) {
+        // Safety: `token` is this thread's slot, which `SLOTS` keeps alive until the thread exits
+        let slot = unsafe { &*(token as *const Slot) };
+        let depth = slot.depth.load(Relaxed) - 1;
+        slot.depth.store(depth, Relaxed);
+        if depth == 0 {
+            // Keeps the loads of the value ahead of clearing the mark, the second barrier of
+            // `wait_for_readers` does the rest
+            compiler_fence(SeqCst);
+            slot.period.store(0, Relaxed);
+            // Either this sees a writer waiting, or its barrier makes it see the slot clear
+            compiler_fence(SeqCst);
+            self.wakers.wake();
+            self.watchdog.waiter().notify();
+        }
+    }
+
+    /// Enters a read from a signal handler, see `Reclaim::enter_signal_safe`.
+    pub(crate) fn enter_signal_safe(&self) -> usize {
+        // Writers wait for every signal reader, whenever it entered
+        self.signal_readers.fetch_add(1, SeqCst);
+        0
+    }
+
+    /// Exits a read entered by `enter_signal_safe`.
+    pub(crate) fn exit_signal_safe(&self) {
+        // Writers sleeping for readers wake up on their own regularly, nothing to wake here
+        self.signal_readers.fetch_sub(1, SeqCst);
+    }
+
+    /// This thread's slot, registered on its first read.
+    fn slot(&self) -> &Slot {
+        let slot = SLOTS.with(|slots| {
+            if let Some((_, slot)) = slots.borrow().iter().find(|(id, _)| *id == self.id) {
+                return Arc::as_ptr(slot);
+            }
+            let mut slots = slots.borrow_mut();
+            // Forget the slots of dropped engines, which are the only ones still holding them
+            slots.retain(|(_, slot)| Arc::strong_count(slot) > 1);
+            let slot = Arc::new(Slot::default());
+            self.slots.lock().unwrap_or_else(PoisonError::into_inner).push(Arc::clone(&slot));
+            let ptr = Arc::as_ptr(&slot);
+            slots.push((self.id, slot));
+            ptr
+        });
+        // Safety: `SLOTS` keeps the slot alive until this thread exits, and it is only ever used
+        // by this thread while `self` is borrowed
+        unsafe { &*slot }
+    }
+
+    /// Starts a grace period, returning the period readers active now are older than. Must follow
+    /// the unpublish of the values it is for.
+    fn start(&self) -> usize {
+        // Either a reader's mark is visible from here on, or its loads see what was unpublished gone
+        barrier::heavy();
+        self.period.fetch_add(1, SeqCst) + 1
+    }
+
+    /// Number of threads in a critical section entered before grace period `period` started, and
+    /// of reads from signal handlers.
+    fn active(&self, period: usize) -> usize {
+        let slots = self.slots.lock().unwrap_or_else(PoisonError::into_inner);
+        let readers = slots
+            .iter()
+            .filter(|slot| {
+                let entered = slot.period.load(Relaxed);
+                entered != 0 && entered < period
+            })
+            .count();
+        readers + self.signal_readers.load(SeqCst)
+    }
+
+    /// Waits for the readers active now to exit, giving up at `deadline`. Returns whether they did.
+    fn wait_for_readers(&self, deadline: Option<Instant>) -> bool {
+        // Threads that exited are only held onto by `slots`
+        self.slots.lock().unwrap_or_else(PoisonError::into_inner).retain(|slot| Arc::strong_count(slot) > 1);
+        let period = self.start();
+        let waker = wakers::unpark_current();
+        let mut attempts = 0u32;
+        // The readers waited for may have been preempted, spinning would only keep them from running,
+        // so unless told otherwise this parks like liburcu does on top of the strategy's spinning
+        let parks = self.watchdog.waiter().is_default();
+        let waiting = || {
+            attempts += 1;
+            if parks && attempts > ACTIVE_ATTEMPTS && !self.drained_or_register(period, &waker) {
+                thread::park_timeout(SLEEP);
+            }
+            self.active(period) > 0
+        };
+        let drained = self.watchdog.wait_while(waiting, || self.active(period), deadline);
+        if drained {
+            // Completes everything those readers did before clearing their slot
+            barrier::heavy();
+        }
+        drained
+    }
+
+    /// Frees `retired`, and what was deferred before, once readers drained, unless that
+    /// didn't happen by `deadline`, see `Counted::retire_with`.
+    unsafe fn retire_with(&self, retired: impl IntoIterator<Item = Retired>, deadline: Option<Instant>) -> bool {
+        let freeing = try_lock_freeing(&self.freeing);
+        let deferred = if freeing.is_some() {
+            mem::take(&mut *self.deferred.lock().unwrap_or_else(PoisonError::into_inner))
+        } else {
+            Vec::new()
+        };
+        let drained = self.wait_for_readers(deadline);
+        // As in `Counted::retire_with`, allocates nothing unless deferring
+        let batch = deferred.into_iter().chain(retired);
+        if drained {
+            for retired in batch {
+                retired.reclaim();
+            }
+        } else {
+            self.deferred.lock().unwrap_or_else(PoisonError::into_inner).extend(batch);
+        }
+        drained
+    }
+
+    /// See `Reclaim::retire`.
+    pub(crate) unsafe fn retire(&self, retired: Retired) {
+        self.retire_with([retired], None);
+    }
+
+    /// See `Reclaim::retire_before`.
+    pub(crate) unsafe fn retire_before(&self, retired: Retired, deadline: Instant) -> bool {
+        self.retire_with([retired], Some(deadline))
+    }
+
+    /// See `Reclaim::retire_batch`.
+    pub(crate) unsafe fn retire_batch(&self, batch: Vec<Retired>, deadline: Option<Instant>) -> bool {
+        self.retire_with(batch, deadline)
+    }
+
+    /// See `Reclaim::synchronize`.
+    pub(crate) fn synchronize(&self) {
+        self.wait_for_readers(None);
+    }
+
+    /// See `Reclaim::barrier`.
+    pub(crate) fn barrier(&self) {
+        let _freeing = self.freeing.lock().unwrap_or_else(PoisonError::into_inner);
+        let deferred = mem::take(&mut *self.deferred.lock().unwrap_or_else(PoisonError::into_inner));
+        self.wait_for_readers(None);
+        for retired in deferred {
+            // Safety: unpublished before the readers waited for drained
+            unsafe { retired.reclaim() };
+        }
+    }
+
+    /// See `Reclaim::set_waiter`.
+    pub(crate) fn set_waiter(&mut self, waiter: Waiter) {
+        self.watchdog.set_waiter(waiter);
+    }
+
+    /// See `Reclaim::start_grace_period`.
+    #[cfg(feature = "async")]
+    pub(crate) fn start_grace_period(&self) -> usize {
+        self.start()
+    }
+
+    /// See `Reclaim::poll_grace_period`.
+    #[cfg(feature = "async")]
+    pub(crate) fn poll_grace_period(&self, period: usize, waker: &Waker) -> bool {
+        if !self.drained_or_register(period, waker) {
+            return false;
+        }
+        barrier::heavy();
+        true
+    }
+
+    /// Whether the readers of grace period `period` are gone, registering `waker` to be woken by
+    /// the next reader exit if not.
+    fn drained_or_register(&self, period: usize, waker: &Waker) -> bool {
+        if self.active(period) == 0 {
+            return true;
+        }
+        self.wakers.register(waker);
+        // Either a reader exiting from now on sees the waker, or this sees its slot clear
+        barrier::heavy();
+        self.active(period) == 0
+    }
+
+    /// See `Reclaim::pending`.
+    pub(crate) fn pending(&self) -> usize {
+        self.deferred.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    /// See `Reclaim::watchdog`.
+    #[cfg(feature = "diagnostics")]
+    pub(crate) fn watchdog(&self) -> &Watchdog {
+        &self.watchdog
+    }
+}
+
+impl Drop for Asymmetric {
+    fn drop(&mut self) {
+        let deferred = self.deferred.get_mut().unwrap_or_else(PoisonError::into_inner);
+        for retired in deferred.drain(..) {
+            // Safety: we have exclusive access, so there are no readers left
+            unsafe { retired.reclaim() }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::Asymmetric;
+    use crate::reclaim::{Engine, Reclaim, ReadLock, Retired};
+    use crate::{Rcu, SpinYield};
+
+    /// Counts its live instances in the counter it shares with the test.
+    struct Live(Arc<AtomicUsize>);
+
+    impl Live {
+        fn new(live: &Arc<AtomicUsize>) -> Self {
+            live.fetch_add(1, SeqCst);
+            Self(Arc::clone(live))
+        }
+    }
+
+    impl Clone for Live {
+        fn clone(&self) -> Self {
+            Self::new(&self.0)
+        }
+    }
+
+    impl Drop for Live {
+        fn drop(&mut self) {
+            self.0.fetch_sub(1, SeqCst);
+        }
+    }
+
+    /// Counts its drops in the counter it shares with the test.
+    struct Dropped(Arc<AtomicUsize>);
+
+    impl Drop for Dropped {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, SeqCst);
+        }
+    }
+
+    /// Two halves published together, torn if a reader ever sees them differ.
+    #[derive(Clone)]
+    struct Pair {
+        a: u64,
+        b: u64,
+        _live: Live,
+    }
+
+    #[cfg(all(target_os = "linux", feature = "membarrier"))]
+    #[test]
+    fn readers_see_whole_values_while_writers_free_the_displaced_ones() {
+        if !cfg!(feature = "fallback-lock") {
+            assert!(Asymmetric::new().is_some(), "membarrier is unavailable, nothing would be stressed");
+        }
+        let live = Arc::new(AtomicUsize::new(0));
+        let rcu = Rcu::new_membarrier(Pair { a: 0, b: 0, _live: Live::new(&live) })
+            .with_wait_strategy(SpinYield::default());
+        let done = AtomicBool::new(false);
+        let reads = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let mut last = 0;
+                    while !done.load(SeqCst) {
+                        let seen = rcu.read_with(|pair| {
+                            assert_eq!(pair.a, pair.b, "torn read");
+                            pair.a
+                        });
+                        // Each writer only ever adds, so a reader never goes back
+                        assert!(seen >= last, "went back from {last} to {seen}");
+                        last = seen;
+                        reads.fetch_add(1, SeqCst);
+                        thread::yield_now();
+                    }
+                });
+            }
+            let writers: Vec<_> = (0..2)
+                .map(|_| {
+                    s.spawn(|| {
+                        for _ in 0..500 {
+                            let next = |pair: &Pair| Pair { a: pair.a + 1, b: pair.b + 1, _live: Live::new(&live) };
+                            assert!(rcu.update_with(next).is_ok(), "publish failed");
+                            thread::yield_now();
+                        }
+                    })
+                })
+                .collect();
+            for writer in writers {
+                writer.join().unwrap();
+            }
+            done.store(true, SeqCst);
+        });
+        assert!(reads.load(SeqCst) > 0, "no reads raced the writers");
+        assert_eq!(rcu.read_with(|pair| pair.a), 1000);
+        rcu.barrier();
+        // The current value and the previous one kept for `read_prev`
+        assert_eq!(live.load(SeqCst), 2, "displaced values outlived their readers");
+        drop(rcu);
+        assert_eq!(live.load(SeqCst), 0, "leaked on drop");
+    }
+
+    #[test]
+    fn without_the_barrier_values_get_an_engine_of_their_own() {
+        assert!(Asymmetric::if_available(false).is_none(), "asymmetric engine without the barrier");
+        let engine = Engine::asymmetric_or_own(Asymmetric::if_available(false));
+        assert!(matches!(engine, Engine::Own(_)), "not the regular engine");
+
+        // Which still waits for its readers
+        let drops = Arc::new(AtomicUsize::new(0));
+        let (entered, wait_entered) = mpsc::channel();
+        let (exit, wait_exit) = mpsc::channel::<()>();
+        let engine = &engine;
+        thread::scope(|s| {
+            s.spawn(move || {
+                let _lock = ReadLock::new(engine);
+                entered.send(()).unwrap();
+                let _ = wait_exit.recv();
+            });
+            wait_entered.recv().unwrap();
+            // Waits for the reader in-line with `reclaim-counted`, hence the thread of its own
+            s.spawn(|| {
+                let retired = Retired::new(Box::into_raw(Box::new(Dropped(Arc::clone(&drops)))));
+                // Safety: never published, so no reader entering later can observe it
+                unsafe { engine.retire(retired) }
+            });
+            thread::sleep(Duration::from_millis(50));
+            assert_eq!(drops.load(SeqCst), 0, "freed under an active reader");
+            drop(exit);
+        });
+        engine.barrier();
+        assert_eq!(drops.load(SeqCst), 1, "never freed");
+    }
+}
@@ -0,0 +1,41 @@
+//! Process-wide memory barriers, with `membarrier(2)`: the side of a handshake that runs rarely
+//! pays for a full barrier on every thread, so the side that runs all the time only needs a
+//! compiler fence. Only available on Linux.
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::sync::OnceLock;
+
+    const MEMBARRIER_CMD_PRIVATE_EXPEDITED: libc::c_int = 1 << 3;
+    const MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED: libc::c_int = 1 << 4;
+
+    /// Whether `heavy` can be used, registering the process for it on the first call.
+    pub(crate) fn available() -> bool {
+        static REGISTERED: OnceLock<bool> = OnceLock::new();
+        *REGISTERED.get_or_init(|| {
+            // Safety: the command takes no pointers
+            unsafe { libc::syscall(libc::SYS_membarrier, MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED, 0, 0) == 0 }
+        })
+    }
+
+    /// Executes a full memory barrier on every running thread of the process.
+    pub(crate) fn heavy() {
+        // Safety: the command takes no pointers
+        let ret = unsafe { libc::syscall(libc::SYS_membarrier, MEMBARRIER_CMD_PRIVATE_EXPEDITED, 0, 0) };
+        // Callers only rely on this once `available` said so, and the registration is for good
+        assert_eq!(ret, 0, "membarrier failed");
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    pub(crate) fn available() -> bool {
+        false
+    }
+
+    pub(crate) fn heavy() {
+        unreachable!("nothing relies on a process-wide barrier where it isn't available")
+    }
+}
+
+pub(crate) use imp::{available, heavy};
@@ -0,0 +1,347 @@
+//! Biasing an engine towards the thread that created it.
+//!
+//! While biased, the creating thread (the owner) marks its read-side critical sections with plain
+//! stores into its own flag rather than registering with the engine, and frees values it unpublishes
+//! right away instead of waiting for a grace period: no other thread can be reading, since every
+//! other thread revokes the bias before touching the engine.
+//!
+//! Revoking is a handshake: the revoking thread clears the owner, forces a full memory barrier on
+//! every thread of the process (`membarrier(2)`), then waits for the owner's flag to clear. The
+//! owner sets its flag and re-checks that it still owns the engine with only a compiler fence in
+//! between; the process-wide barrier is what orders the two sides, so either the owner sees the
+//! revocation, or the revoking thread sees the flag and waits. Once revoked, every thread, the
+//! owner included, goes through the engine from then on.
+//!
+//! Only supported on Linux, where `membarrier` is; elsewhere values are never biased.
+//!
+//! The handshake is model-checked with [loom](https://docs.rs/loom), which can't model
+//! `membarrier` and so checks it with the full fences the process-wide barrier stands for on both
+//! sides:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test --release --lib reclaim::biased
+//! ```
+
+use std::cell::{Cell, UnsafeCell};
+use std::mem;
+#[cfg(not(loom))]
+use std::sync::atomic::{compiler_fence, AtomicBool, AtomicUsize};
+use std::sync::atomic::Ordering::{Acquire, Relaxed, Release, SeqCst};
+
+#[cfg(loom)]
+use loom::sync::atomic::{fence, AtomicBool, AtomicUsize};
+
+use super::{barrier, Retired};
+use crate::wait::Waiter;
+
+/// Handed out by `Engine::enter` for a critical section entered through the bias, never a token
+/// of one of the engines.
+pub(crate) const BIASED: usize = usize::MAX;
+/// Handed out by `Engine::enter_signal_safe` for a read through the bias from a handler that
+/// interrupted the owner while its flag was set
+pub(crate) const BIASED_SIGNAL_ACTIVE: usize = usize::MAX - 1;
+/// Like `BIASED_SIGNAL_ACTIVE`, while the owner's flag was clear
+pub(crate) const BIASED_SIGNAL_IDLE: usize = usize::MAX - 2;
+
+/// A process-unique id for the current thread, never 0.
+fn thread_id() -> usize {
+    static NEXT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(1);
+    #[cfg(not(loom))]
+    thread_local! {
+        static ID: Cell<usize> = const { Cell::new(0) };
+    }
+    #[cfg(loom)]
+    loom::thread_local! {
+        static ID: Cell<usize> = Cell::new(0);
+    }
+    ID.with(|id| {
+        if id.get() == 0 {
+            id.set(NEXT.fetch_add(1, Relaxed));
+        }
+        id.get()
+    })
+}
+
+/// The owner's side of the handshake, ordered against the revoking thread's by `heavy_barrier`.
+fn light_barrier() {
+    #[cfg(not(loom))]
+    compiler_fence(SeqCst);
+    #[cfg(loom)]
+    fence(SeqCst);
+}
+
+/// The revoking thread's side of the handshake, a full barrier on every thread of the process.
+fn heavy_barrier() {
+    #[cfg(not(loom))]
+    barrier::heavy();
+    #[cfg(loom)]
+    fence(SeqCst);
+}
+
+pub(crate) struct Bias {
+    /// The thread the engine is biased towards, 0 once revoked
+    owner: AtomicUsize,
+    /// The thread that created the engine, the only one to ever write `depth`, `active` and `deferred`
+    creator: usize,
+    /// Set once the revocation completed, so nobody relies on the bias anymore
+    revoked: AtomicBool,
+    /// Nesting depth of the owner's biased critical sections
+    depth: AtomicUsize,
+    /// Whether the owner is inside a biased critical section or freeing a value through the bias
+    active: AtomicBool,
+    /// Values the owner unpublished inside its own biased critical sections, freed once it exits them
+    deferred: UnsafeCell<Vec<Retired>>,
+    /// How a revoking thread waits for the owner's flag to clear
+    waiter: Waiter,
+}
+
+// Safety: `deferred` is only ever touched by the creating thread, see `Bias::creator`
+unsafe impl Sync for Bias {}
+
+impl Bias {
+    /// A bias towards the current thread, None if biasing isn't supported on this system, or
+    /// `fallback-lock` rules it out.
+    pub(crate) fn new() -> Option<Self> {
+        (!cfg!(feature = "fallback-lock") && (cfg!(loom) || barrier::available())).then(|| Self {
+            owner: AtomicUsize::new(thread_id()),
+            creator: thread_id(),
+            revoked: AtomicBool::new(false),
+            depth: AtomicUsize::new(0),
+            active: AtomicBool::new(false),
+            deferred: UnsafeCell::new(Vec::new()),
+            waiter: Waiter::default(),
+        })
+    }
+
+    /// Enters a biased critical section if called by the owner while it still holds the bias,
+    /// returning whether it did; any other thread revokes the bias first.
+    pub(crate) fn enter(&self) -> bool {
+        if self.revoked.load(Acquire) {
+            return false;
+        }
+        let me = thread_id();
+        if me != self.creator {
+            self.revoke();
+            return false;
+        }
+        let depth = self.depth.load(Relaxed);
+        // Nested inside a biased critical section, which already protects everything (the
+        // revocation can't complete before it exits) even if the bias was revoked meanwhile
+        if depth > 0 || self.claim(me) {
+            self.depth.store(depth + 1, Relaxed);
+            return true;
+        }
+        false
+    }
+
+    /// Like `enter`, for a read from a signal handler, which may have interrupted the owner
+    /// anywhere, even halfway through `enter` or `exit`: leaves `depth` and `deferred` alone and
+    /// only sets the owner's flag for the duration of the read. Returns the flag to restore with
+    /// `exit_signal_safe` if the read goes through the bias, None if it must use the engine.
+    pub(crate) fn enter_signal_safe(&self) -> Option<bool> {
+        if self.revoked.load(Acquire) {
+            return None;
+        }
+        let me = thread_id();
+        if me != self.creator {
+            // Only waits for the owner's current biased critical section, on another thread
+            self.revoke();
+            return None;
+        }
+        let active = self.active.load(Relaxed);
+        // Inside a biased critical section of the interrupted code, which can't exit before the
+        // handler returns
+        if self.depth.load(Relaxed) > 0 {
+            return Some(active);
+        }
+        self.active.store(true, Relaxed);
+        // As in `claim`
+        light_barrier();
+        if self.owner.load(Relaxed) == me {
+            return Some(active);
+        }
+        self.active.store(active, Release);
+        None
+    }
+
+    /// Exits a read entered by `enter_signal_safe`, restoring the owner's flag to `active`.
+    pub(crate) fn exit_signal_safe(&self, active: bool) {
+        self.active.store(active, Release);
+    }
+
+    /// Exits a critical section entered by `enter` returning true.
+    pub(crate) fn exit(&self) {
+        let depth = self.depth.load(Relaxed) - 1;
+        self.depth.store(depth, Relaxed);
+        if depth == 0 {
+            // Safety: only the creator gets here
+            let deferred = mem::take(unsafe { &mut *self.deferred.get() });
+            // Still inside the claim: whatever a thread revoking meanwhile reads once it's released
+            // was published after these were unpublished
+            for retired in deferred {
+                // Safety: no other thread could read while the owner held the bias, and the owner
+                // has exited every critical section that could still observe them
+                unsafe { retired.reclaim() }
+            }
+            self.active.store(false, Release);
+            self.waiter.notify();
+        }
+    }
+
+    /// Frees `retired` through the bias if called by the owner while it still holds it, right away
+    /// or once the owner exits its biased critical sections. Otherwise hands it back, for the
+    /// engine to deal with; any other thread revokes the bias first.
+    ///
+    /// # Safety
+    /// As for `Reclaim::retire`.
+    pub(crate) unsafe fn retire(&self, retired: Retired) -> Option<Retired> {
+        if self.revoked.load(Acquire) {
+            return Some(retired);
+        }
+        let me = thread_id();
+        if me != self.creator {
+            self.revoke();
+            return Some(retired);
+        }
+        if self.depth.load(Relaxed) > 0 {
+            // The owner may still be reading it, the engine doesn't know about those readers
+            // Safety: only the creator gets here
+            unsafe { &mut *self.deferred.get() }.push(retired);
+            return None;
+        }
+        if !self.claim(me) {
+            return Some(retired);
+        }
+        retired.reclaim();
+        self.active.store(false, Release);
+        self.waiter.notify();
+        None
+    }
+
+    pub(crate) fn set_waiter(&mut self, waiter: Waiter) {
+        self.waiter = waiter;
+    }
+
+    /// Revokes the bias first if called by a thread other than the owner, before it uses the engine.
+    pub(crate) fn touch(&self) {
+        if !self.revoked.load(Acquire) && thread_id() != self.creator {
+            self.revoke();
+        }
+    }
+
+    /// Sets the owner's flag if `me` still owns the engine, returning whether it does.
+    fn claim(&self, me: usize) -> bool {
+        self.active.store(true, Relaxed);
+        // Pairs with the process-wide barrier in `revoke`
+        light_barrier();
+        if self.owner.load(Relaxed) == me {
+            return true;
+        }
+        self.active.store(false, Release);
+        false
+    }
+
+    /// Takes the bias away from the owner for good, waiting until it no longer relies on it.
+    fn revoke(&self) {
+        self.owner.store(0, Relaxed);
+        // Either the owner's next claim sees the store above, or this sees its flag
+        heavy_barrier();
+        #[cfg(not(loom))]
+        self.waiter.wait_while(|| self.active.load(Acquire), None);
+        #[cfg(loom)]
+        while self.active.load(Acquire) {
+            loom::thread::yield_now();
+        }
+        self.revoked.store(true, Release);
+    }
+}
+
+#[cfg(all(loom, test))]
+mod loom_model {
+    use loom::cell::UnsafeCell;
+    use loom::sync::Arc;
+    use loom::thread;
+
+    use super::*;
+
+    /// Stands for a value the owner reads through the bias: loom reports reading it while another
+    /// thread frees it, which only the handshake rules out.
+    struct Value(UnsafeCell<u64>);
+
+    impl Value {
+        fn read(&self) -> u64 {
+            // Safety: loom checks the accesses, which is the point
+            self.0.with(|value| unsafe { *value })
+        }
+    }
+
+    /// Frees `value`, as far as loom can tell.
+    unsafe fn free(value: *mut Value) {
+        (*value).0.with_mut(|value| *value = 0);
+    }
+
+    #[test]
+    fn revoking_waits_for_the_owners_reads() {
+        loom::model(|| {
+            let bias = Arc::new(Bias::new().expect("biasing is always modelled"));
+            let value = Arc::new(Value(UnsafeCell::new(1)));
+            let other = {
+                let (bias, value) = (Arc::clone(&bias), Arc::clone(&value));
+                thread::spawn(move || {
+                    bias.touch();
+                    // The bias is gone, so freeing what the owner read through it must be safe
+                    unsafe { free(Arc::as_ptr(&value) as *mut Value) };
+                })
+            };
+            if bias.enter() {
+                assert_eq!(value.read(), 1);
+                bias.exit();
+            }
+            other.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn the_owner_frees_through_the_bias_only_while_nobody_else_reads() {
+        loom::model(|| {
+            let bias = Arc::new(Bias::new().expect("biasing is always modelled"));
+            let value = Arc::new(Value(UnsafeCell::new(1)));
+            let other = {
+                let (bias, value) = (Arc::clone(&bias), Arc::clone(&value));
+                thread::spawn(move || {
+                    // Doesn't get the bias, and reads through the engine once it revoked it
+                    assert!(!bias.enter());
+                    value.read();
+                })
+            };
+            let retired = Retired::with_deleter(Arc::as_ptr(&value) as *mut Value, free);
+            // Safety: as far as the model goes, `value` is unpublished; what is handed back would be
+            // left to the engine, which waits for the other thread's read
+            unsafe { bias.retire(retired) };
+            other.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn values_deferred_inside_the_owners_reads_are_freed_once_safe() {
+        loom::model(|| {
+            let bias = Arc::new(Bias::new().expect("biasing is always modelled"));
+            let value = Arc::new(Value(UnsafeCell::new(1)));
+            let other = {
+                let (bias, value) = (Arc::clone(&bias), Arc::clone(&value));
+                thread::spawn(move || {
+                    assert!(!bias.enter());
+                    value.read();
+                })
+            };
+            if bias.enter() {
+                assert_eq!(value.read(), 1);
+                let retired = Retired::with_deleter(Arc::as_ptr(&value) as *mut Value, free);
+                // Safety: as above; freed by `exit` while the owner still holds the bias
+                unsafe { bias.retire(retired) };
+                bias.exit();
+            }
+            other.join().unwrap();
+        });
+    }
+}
@@ -0,0 +1,297 @@
+use std::mem;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering::{Acquire, Release, SeqCst}};
+use std::sync::{Mutex, PoisonError, TryLockError};
+#[cfg(feature = "async")]
+use std::task::Waker;
+use std::time::Instant;
+
+#[cfg(feature = "async")]
+use super::Wakers;
+use super::{try_lock_freeing, Reclaim, Retired, Watchdog};
+use crate::wait::{Preference, Waiter};
+
+/// The original scheme: reader counters, with writers waiting in-line for them to drain before
+/// freeing the previous value. Readers count themselves in one of two counters, selected by a phase
+/// which grace periods not holding new readers back flip, so that they only wait for the readers of
+/// the phase they flipped away from, see `Preference`.
+#[derive(Default)]
+pub(crate) struct Counted {
+    /// Active readers, indexed by the phase they entered in
+    readers: [AtomicU32; 2],
+    /// The phase readers entering now count themselves in
+    phase: AtomicUsize,
+    /// Serializes grace periods which flip `phase`
+    flipping: Mutex<()>,
+    /// Number of threads currently waiting for readers to drain while holding new ones back. A
+    /// count rather than a flag since values in an `RcuDomain` share their engine, and so their writers
+    writers: AtomicU32,
+    /// Grace periods completed while holding new readers back, for readers waiting out exactly one
+    completed: AtomicU64,
+    /// Whether writers hold new readers back while waiting
+    preference: Preference,
+    /// Allocations whose writer stopped waiting at its deadline, freed by the next full grace period
+    deferred: Mutex<Vec<Retired>>,
+    /// Held while allocations taken out of `deferred` are waited for, see `try_lock_freeing`
+    freeing: Mutex<()>,
+    watchdog: Watchdog,
+    /// Tasks waiting for the readers to drop to 0
+    #[cfg(feature = "async")]
+    wakers: Wakers,
+}
+
+/// A reader counting itself in, split at its atomic steps so that the proofs in `reclaim::proofs`
+/// can interleave them with grace periods; `register` takes them back to back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Registration {
+    /// About to load the phase
+    Start,
+    /// Loaded the phase, about to count itself in it
+    Loaded(usize),
+    /// Counted in the phase, about to check it didn't flip meanwhile
+    Counted(usize),
+    /// Registered in the phase
+    Registered(usize),
+}
+
+impl Counted {
+    /// Waits for active readers to drain, giving up at `deadline`. Returns whether they drained.
+    fn wait_for_readers(&self, deadline: Option<Instant>) -> bool {
+        if self.preference == Preference::WriterPreferred {
+            // Pause new readers so they can't starve us; paused, every reader is waited for
+            self.writers.fetch_add(1, Release);
+            let drained = self.watchdog.wait_while(|| self.active() > 0, || self.active(), deadline);
+            self.writers.fetch_sub(1, Release);
+            // Readers may be waiting for the pause to be lifted
+            self.watchdog.waiter().notify();
+            return drained;
+        }
+        // Only one grace period can flip at a time, or readers of the phase waited for could keep
+        // entering after the next flip
+        let mut flipping = None;
+        let locked = self.watchdog.wait_while(
+            || {
+                flipping = match self.flipping.try_lock() {
+                    Ok(guard) => Some(guard),
+                    Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+                    Err(TryLockError::WouldBlock) => None,
+                };
+                flipping.is_none()
+            },
+            || self.active(),
+            deadline,
+        );
+        if !locked {
+            return false;
+        }
+        let fair = self.preference == Preference::PhaseFair;
+        if fair {
+            // Pause readers arriving from now on until this grace period completed, no longer
+            self.writers.fetch_add(1, Release);
+        }
+        let old = self.flip();
+        let drained = self.watchdog.wait_while(
+            || !self.drained(old),
+            || self.readers[old].load(SeqCst) as usize,
+            deadline,
+        );
+        if fair {
+            self.completed.fetch_add(1, Release);
+            self.writers.fetch_sub(1, Release);
+            self.watchdog.waiter().notify();
+        }
+        drained
+    }
+
+    /// Makes readers entering from now on count themselves in the other phase, see `register`,
+    /// returning the phase whose readers the grace period waits for.
+    pub(super) fn flip(&self) -> usize {
+        self.phase.fetch_xor(1, SeqCst)
+    }
+
+    /// Whether no reader is counted in `phase`.
+    pub(super) fn drained(&self, phase: usize) -> bool {
+        self.readers[phase].load(SeqCst) == 0
+    }
+
+    /// Number of readers counted in `phase`.
+    #[cfg(kani)]
+    pub(super) fn counted_in(&self, phase: usize) -> u32 {
+        self.readers[phase].load(SeqCst)
+    }
+
+    /// Number of active readers, in either phase.
+    pub(super) fn active(&self) -> usize {
+        self.readers.iter().map(|readers| readers.load(SeqCst) as usize).sum()
+    }
+
+    /// Counts a reader in the current phase, returning the phase.
+    fn register(&self) -> usize {
+        let mut registration = Registration::Start;
+        loop {
+            registration = self.register_step(registration);
+            if let Registration::Registered(phase) = registration {
+                return phase;
+            }
+        }
+    }
+
+    /// Takes the next step of counting a reader in.
+    #[inline]
+    pub(super) fn register_step(&self, registration: Registration) -> Registration {
+        match registration {
+            Registration::Start => Registration::Loaded(self.phase.load(SeqCst)),
+            Registration::Loaded(phase) => {
+                self.readers[phase].fetch_add(1, SeqCst);
+                Registration::Counted(phase)
+            }
+            // Only count as registered if the phase didn't flip meanwhile, otherwise a writer may
+            // already have seen its readers drained and moved past them
+            Registration::Counted(phase) if self.phase.load(SeqCst) == phase => Registration::Registered(phase),
+            Registration::Counted(phase) => {
+                self.release(phase);
+                Registration::Start
+            }
+            Registration::Registered(_) => registration,
+        }
+    }
+
+    /// Uncounts a reader of `phase`.
+    pub(super) fn release(&self, phase: usize) {
+        if self.readers[phase].fetch_sub(1, SeqCst) == 1 {
+            self.watchdog.waiter().notify();
+            #[cfg(feature = "async")]
+            self.wakers.wake();
+        }
+    }
+
+    /// Retires `retired`, also freeing what was deferred before unless another thread is waiting
+    /// for that already, unless readers didn't drain by `deadline`.
+    unsafe fn retire_with(&self, retired: impl IntoIterator<Item = Retired>, deadline: Option<Instant>) -> bool {
+        // Only what was deferred before the wait started is covered by it
+        let freeing = try_lock_freeing(&self.freeing);
+        let deferred = if freeing.is_some() {
+            mem::take(&mut *self.deferred.lock().unwrap_or_else(PoisonError::into_inner))
+        } else {
+            Vec::new()
+        };
+        let drained = self.wait_for_readers(deadline);
+        // Chained rather than collected, so that retiring allocates nothing unless deferring
+        let batch = deferred.into_iter().chain(retired);
+        if drained {
+            for retired in batch {
+                retired.reclaim();
+            }
+        } else {
+            self.deferred.lock().unwrap_or_else(PoisonError::into_inner).extend(batch);
+        }
+        drained
+    }
+}
+
+impl Reclaim for Counted {
+    fn enter(&self) -> usize {
+        // Check if a thread is currently in the process of writing
+        // Acquire matches the Release from `self.wait_for_readers`
+        match self.preference {
+            Preference::WriterPreferred => {
+                let waiter = self.watchdog.waiter();
+                waiter.wait_while(|| self.writers.load(Acquire) > 0, None);
+            }
+            Preference::PhaseFair if self.writers.load(Acquire) > 0 => {
+                // Only waits out the grace period in progress, even if another one starts right after
+                let completed = self.completed.load(Acquire);
+                let waiter = self.watchdog.waiter();
+                let paused = || self.writers.load(Acquire) > 0 && self.completed.load(Acquire) == completed;
+                waiter.wait_while(paused, None);
+            }
+            Preference::PhaseFair | Preference::ReaderPreferred => {}
+        }
+        self.register()
+    }
+
+    fn exit(&self, phase: usize) {
+        self.release(phase);
+    }
+
+    fn enter_signal_safe(&self) -> usize {
+        // Never paused: the writer pausing readers may be the thread the handler interrupted.
+        // Pausing only keeps writers from starving, it plays no part in ordering
+        self.register()
+    }
+
+    fn exit_signal_safe(&self, phase: usize) {
+        self.readers[phase].fetch_sub(1, SeqCst);
+    }
+
+    unsafe fn retire(&self, retired: Retired) {
+        // From this point on we know no new threads will read the retired data,
+        // therefore, once the readers waited for are 0, we can deallocate it, since
+        // any thread that was reading from it has finished reading.
+        self.retire_with([retired], None);
+    }
+
+    unsafe fn retire_before(&self, retired: Retired, deadline: Instant) -> bool {
+        self.retire_with([retired], Some(deadline))
+    }
+
+    unsafe fn retire_batch(&self, batch: Vec<Retired>, deadline: Option<Instant>) -> bool {
+        self.retire_with(batch, deadline)
+    }
+
+    fn synchronize(&self) {
+        self.wait_for_readers(None);
+    }
+
+    fn barrier(&self) {
+        let _freeing = self.freeing.lock().unwrap_or_else(PoisonError::into_inner);
+        let deferred = mem::take(&mut *self.deferred.lock().unwrap_or_else(PoisonError::into_inner));
+        self.wait_for_readers(None);
+        for retired in deferred {
+            // Safety: unpublished before the readers waited for drained
+            unsafe { retired.reclaim() };
+        }
+    }
+
+    fn set_waiter(&mut self, waiter: Waiter) {
+        self.watchdog.set_waiter(waiter);
+    }
+
+    fn set_preference(&mut self, preference: Preference) {
+        self.preference = preference;
+    }
+
+    #[cfg(feature = "async")]
+    fn start_grace_period(&self) -> usize {
+        0
+    }
+
+    #[cfg(feature = "async")]
+    fn poll_grace_period(&self, _ticket: usize, waker: &Waker) -> bool {
+        // New readers aren't paused, a task must not stall the writer's executor spinning for them,
+        // so a constant stream of overlapping readers can keep this pending
+        if self.active() == 0 {
+            return true;
+        }
+        self.wakers.register(waker);
+        self.active() == 0
+    }
+
+    fn pending(&self) -> usize {
+        self.deferred.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    #[cfg(feature = "diagnostics")]
+    fn watchdog(&self) -> &Watchdog {
+        &self.watchdog
+    }
+}
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        let deferred = self.deferred.get_mut().unwrap_or_else(PoisonError::into_inner);
+        for retired in deferred.drain(..) {
+            // Safety: we have exclusive access, so there are no readers left
+            unsafe { retired.reclaim() }
+        }
+    }
+}
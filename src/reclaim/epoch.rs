@@ -0,0 +1,171 @@
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+#[cfg(feature = "async")]
+use std::task::Waker;
+use std::time::Instant;
+
+#[cfg(feature = "async")]
+use super::Wakers;
+use super::{try_lock_freeing, Reclaim, Retired, Watchdog};
+use crate::wait::Waiter;
+
+/// A locked list of retired allocations, tagged with the epoch they were retired in.
+type Guard<'a> = MutexGuard<'a, Vec<(usize, Retired)>>;
+
+/// Epoch-based scheme: readers register in one of two counters selected by the parity of the
+/// global epoch, so writers only need the *previous* epoch's readers to drain before the epoch
+/// can advance. An allocation retired during epoch `e` is freed once the epoch reaches `e + 2`.
+/// Neither readers nor writers ever wait on each other.
+#[derive(Default)]
+pub(crate) struct Epoch {
+    /// The current epoch, only ever increases
+    epoch: AtomicUsize,
+    /// Active readers, indexed by the parity of the epoch they registered in
+    readers: [AtomicUsize; 2],
+    /// Unpublished allocations tagged with the epoch they were retired in
+    retired: Mutex<Vec<(usize, Retired)>>,
+    /// Held while allocations taken out of `retired` are freed, see `try_lock_freeing`. Holds
+    /// them meanwhile, keeping its capacity for the next ones
+    freeing: Mutex<Vec<(usize, Retired)>>,
+    watchdog: Watchdog,
+    /// Tasks waiting for the epoch to advance, woken whenever one of `readers` drops to 0
+    #[cfg(feature = "async")]
+    wakers: Wakers,
+}
+
+impl Epoch {
+    /// Advances the epoch if no reader from the previous epoch is still active.
+    fn try_advance(&self) {
+        let epoch = self.epoch.load(SeqCst);
+        if self.readers[(epoch + 1) & 1].load(SeqCst) == 0 {
+            let _ = self.epoch.compare_exchange(epoch, epoch + 1, SeqCst, SeqCst);
+        }
+    }
+
+    /// Tags `retired` with the current epoch, freeing whatever became eligible.
+    unsafe fn retire_all(&self, retired: impl IntoIterator<Item = Retired>) {
+        let epoch = self.epoch.load(SeqCst);
+        let mut list = self.retired.lock().unwrap_or_else(PoisonError::into_inner);
+        list.extend(retired.into_iter().map(|retired| (epoch, retired)));
+        // Two advances are needed before anything retired in the current epoch is eligible
+        self.try_advance();
+        self.try_advance();
+        let Some(freeing) = try_lock_freeing(&self.freeing) else {
+            return;
+        };
+        self.reclaim_eligible(list, freeing);
+    }
+
+    /// Frees what in `list` was retired two epochs ago or earlier, moving it to `freeing` first.
+    unsafe fn reclaim_eligible(&self, mut list: Guard<'_>, mut freeing: Guard<'_>) {
+        let current = self.epoch.load(SeqCst);
+        freeing.extend(list.extract_if(.., |(retired_in, _)| *retired_in + 2 <= current));
+        drop(list);
+        for (_, retired) in freeing.drain(..) {
+            retired.reclaim();
+        }
+    }
+}
+
+impl Reclaim for Epoch {
+    fn enter(&self) -> usize {
+        loop {
+            let epoch = self.epoch.load(SeqCst);
+            let idx = epoch & 1;
+            self.readers[idx].fetch_add(1, SeqCst);
+            // Only count as registered if the epoch did not move while we were registering,
+            // otherwise a writer may already have checked our counter and moved past it
+            if self.epoch.load(SeqCst) == epoch {
+                return idx;
+            }
+            self.readers[idx].fetch_sub(1, SeqCst);
+        }
+    }
+
+    fn exit(&self, idx: usize) {
+        if self.readers[idx].fetch_sub(1, SeqCst) == 1 {
+            self.watchdog.waiter().notify();
+            #[cfg(feature = "async")]
+            self.wakers.wake();
+        }
+    }
+
+    fn exit_signal_safe(&self, idx: usize) {
+        self.readers[idx].fetch_sub(1, SeqCst);
+    }
+
+    unsafe fn retire(&self, retired: Retired) {
+        self.retire_all([retired]);
+    }
+
+    unsafe fn retire_batch(&self, batch: Vec<Retired>, _deadline: Option<Instant>) -> bool {
+        self.retire_all(batch);
+        true
+    }
+
+    fn synchronize(&self) {
+        // Readers active now registered in the current epoch or the one before, both have drained
+        // once the epoch moved on twice
+        let target = self.epoch.load(SeqCst) + 2;
+        self.watchdog.wait_while(
+            || {
+                self.try_advance();
+                self.epoch.load(SeqCst) < target
+            },
+            || self.readers.iter().map(|readers| readers.load(SeqCst)).sum(),
+            None,
+        );
+    }
+
+    fn barrier(&self) {
+        let freeing = self.freeing.lock().unwrap_or_else(PoisonError::into_inner);
+        // Whatever was retired before is eligible once the epoch moved on twice
+        self.synchronize();
+        let list = self.retired.lock().unwrap_or_else(PoisonError::into_inner);
+        // Safety: as `retire_all` frees
+        unsafe { self.reclaim_eligible(list, freeing) };
+    }
+
+    fn set_waiter(&mut self, waiter: Waiter) {
+        self.watchdog.set_waiter(waiter);
+    }
+
+    #[cfg(feature = "async")]
+    fn start_grace_period(&self) -> usize {
+        // As for `synchronize`
+        self.epoch.load(SeqCst) + 2
+    }
+
+    #[cfg(feature = "async")]
+    fn poll_grace_period(&self, target: usize, waker: &Waker) -> bool {
+        let advanced = || {
+            self.try_advance();
+            self.try_advance();
+            self.epoch.load(SeqCst) >= target
+        };
+        if advanced() {
+            return true;
+        }
+        self.wakers.register(waker);
+        advanced()
+    }
+
+    fn pending(&self) -> usize {
+        self.retired.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    #[cfg(feature = "diagnostics")]
+    fn watchdog(&self) -> &Watchdog {
+        &self.watchdog
+    }
+}
+
+impl Drop for Epoch {
+    fn drop(&mut self) {
+        let list = self.retired.get_mut().unwrap_or_else(PoisonError::into_inner);
+        for (_, retired) in list.drain(..) {
+            // Safety: we have exclusive access, so there are no readers left
+            unsafe { retired.reclaim() }
+        }
+    }
+}
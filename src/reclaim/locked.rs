@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+#[cfg(feature = "async")]
+use std::task::Waker;
+use std::time::Instant;
+
+#[cfg(feature = "async")]
+use super::Wakers;
+use super::{try_lock_freeing, Reclaim, Retired, Watchdog};
+use crate::wait::{Preference, Waiter};
+
+/// With the `fallback-lock` feature, in place of the selected engine: readers and writers keep
+/// track of each other under a plain `Mutex`, with none of the atomic protocols of the other
+/// engines, for when those must be ruled out, at the cost of every read taking the lock twice.
+/// Writers wait in-line for readers, as with `reclaim-counted`.
+///
+/// Every grace period starts a new generation, and waits for the readers that entered in the
+/// generations before. Readers entering meanwhile count in the new one, so whatever the
+/// `Preference`, they are never waited for.
+#[derive(Default)]
+pub(crate) struct Locked {
+    state: Mutex<State>,
+    /// Reads from signal handlers, which must not take the lock: the code they interrupted may hold
+    /// it. Every grace period waits for them
+    signal_readers: AtomicUsize,
+    /// Whether writers hold new readers back while waiting
+    preference: Preference,
+    /// Allocations whose writer stopped waiting at its deadline, freed by the next full grace period
+    deferred: Mutex<Vec<Retired>>,
+    /// Held while allocations taken out of `deferred` are waited for, see `try_lock_freeing`
+    freeing: Mutex<()>,
+    watchdog: Watchdog,
+    /// Tasks waiting for readers to exit
+    #[cfg(feature = "async")]
+    wakers: Wakers,
+}
+
+#[derive(Default)]
+struct State {
+    /// The generation readers entering now count in
+    generation: usize,
+    /// Active readers by the generation they entered in, generations without any left out
+    readers: BTreeMap<usize, usize>,
+    /// Writers waiting for readers while holding new ones back
+    writers: usize,
+    /// Grace periods completed while holding new readers back, for readers waiting out exactly one
+    completed: u64,
+}
+
+impl State {
+    /// Number of active readers that entered in `generation` or before.
+    fn readers_since(&self, generation: usize) -> usize {
+        self.readers.range(..=generation).map(|(_, readers)| readers).sum()
+    }
+}
+
+impl Locked {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Starts a grace period, returning the generation whose readers, and those before, it waits for.
+    fn start(&self) -> usize {
+        let mut state = self.state();
+        state.generation += 1;
+        state.generation - 1
+    }
+
+    /// Number of readers a grace period started as `generation` waits for.
+    fn waited_for(&self, generation: usize) -> usize {
+        self.state().readers_since(generation) + self.signal_readers.load(SeqCst)
+    }
+
+    /// Waits for active readers to exit, giving up at `deadline`. Returns whether they did.
+    fn wait_for_readers(&self, deadline: Option<Instant>) -> bool {
+        let holds_back = self.preference != Preference::ReaderPreferred;
+        if holds_back {
+            self.state().writers += 1;
+        }
+        let generation = self.start();
+        let active = || self.waited_for(generation);
+        let drained = self.watchdog.wait_while(|| active() > 0, active, deadline);
+        if holds_back {
+            let mut state = self.state();
+            state.writers -= 1;
+            state.completed += 1;
+            drop(state);
+            // Readers may be waiting for the pause to be lifted
+            self.watchdog.waiter().notify();
+        }
+        drained
+    }
+
+    /// Retires `retired` like `Counted::retire_with` does.
+    unsafe fn retire_with(&self, retired: impl IntoIterator<Item = Retired>, deadline: Option<Instant>) -> bool {
+        let freeing = try_lock_freeing(&self.freeing);
+        let deferred = if freeing.is_some() {
+            mem::take(&mut *self.deferred.lock().unwrap_or_else(PoisonError::into_inner))
+        } else {
+            Vec::new()
+        };
+        let drained = self.wait_for_readers(deadline);
+        // As in `Counted::retire_with`, allocates nothing unless deferring
+        let batch = deferred.into_iter().chain(retired);
+        if drained {
+            for retired in batch {
+                retired.reclaim();
+            }
+        } else {
+            self.deferred.lock().unwrap_or_else(PoisonError::into_inner).extend(batch);
+        }
+        drained
+    }
+
+    /// Wakes writers and tasks waiting for readers to exit, once the last reader of a generation did.
+    fn exited(&self) {
+        self.watchdog.waiter().notify();
+        #[cfg(feature = "async")]
+        self.wakers.wake();
+    }
+}
+
+impl Reclaim for Locked {
+    fn enter(&self) -> usize {
+        match self.preference {
+            Preference::WriterPreferred => {
+                self.watchdog.waiter().wait_while(|| self.state().writers > 0, None);
+            }
+            Preference::PhaseFair => {
+                // Only waits out the grace period in progress, even if another one starts right after
+                let completed = self.state().completed;
+                let paused = || {
+                    let state = self.state();
+                    state.writers > 0 && state.completed == completed
+                };
+                self.watchdog.waiter().wait_while(paused, None);
+            }
+            Preference::ReaderPreferred => {}
+        }
+        let mut state = self.state();
+        let generation = state.generation;
+        *state.readers.entry(generation).or_default() += 1;
+        generation
+    }
+
+    fn exit(&self, generation: usize) {
+        let mut state = self.state();
+        let readers = state.readers.get_mut(&generation).expect("exited a generation without readers");
+        *readers -= 1;
+        if *readers == 0 {
+            state.readers.remove(&generation);
+            drop(state);
+            self.exited();
+        }
+    }
+
+    fn enter_signal_safe(&self) -> usize {
+        self.signal_readers.fetch_add(1, SeqCst);
+        0
+    }
+
+    fn exit_signal_safe(&self, _token: usize) {
+        self.signal_readers.fetch_sub(1, SeqCst);
+    }
+
+    unsafe fn retire(&self, retired: Retired) {
+        self.retire_with([retired], None);
+    }
+
+    unsafe fn retire_before(&self, retired: Retired, deadline: Instant) -> bool {
+        self.retire_with([retired], Some(deadline))
+    }
+
+    unsafe fn retire_batch(&self, batch: Vec<Retired>, deadline: Option<Instant>) -> bool {
+        self.retire_with(batch, deadline)
+    }
+
+    fn synchronize(&self) {
+        self.wait_for_readers(None);
+    }
+
+    fn barrier(&self) {
+        let _freeing = self.freeing.lock().unwrap_or_else(PoisonError::into_inner);
+        let deferred = mem::take(&mut *self.deferred.lock().unwrap_or_else(PoisonError::into_inner));
+        self.wait_for_readers(None);
+        for retired in deferred {
+            // Safety: unpublished before the readers waited for exited
+            unsafe { retired.reclaim() };
+        }
+    }
+
+    fn set_waiter(&mut self, waiter: Waiter) {
+        self.watchdog.set_waiter(waiter);
+    }
+
+    fn set_preference(&mut self, preference: Preference) {
+        self.preference = preference;
+    }
+
+    #[cfg(feature = "async")]
+    fn start_grace_period(&self) -> usize {
+        self.start()
+    }
+
+    #[cfg(feature = "async")]
+    fn poll_grace_period(&self, generation: usize, waker: &Waker) -> bool {
+        // Never holds readers back, so a constant stream of signal handler reads can keep this pending
+        if self.waited_for(generation) == 0 {
+            return true;
+        }
+        self.wakers.register(waker);
+        self.waited_for(generation) == 0
+    }
+
+    fn pending(&self) -> usize {
+        self.deferred.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    #[cfg(feature = "diagnostics")]
+    fn watchdog(&self) -> &Watchdog {
+        &self.watchdog
+    }
+}
+
+impl Drop for Locked {
+    fn drop(&mut self) {
+        let deferred = self.deferred.get_mut().unwrap_or_else(PoisonError::into_inner);
+        for retired in deferred.drain(..) {
+            // Safety: we have exclusive access, so there are no readers left
+            unsafe { retired.reclaim() }
+        }
+    }
+}
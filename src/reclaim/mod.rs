@@ -0,0 +1,567 @@
+//! Grace-period detection and reclamation engines.
+//!
+//! Exactly one engine is compiled in, selected by one of the mutually exclusive cargo features
+//! `reclaim-counted` (the default), `reclaim-retire-list` or `reclaim-epoch`. Every engine implements
+//! [`Reclaim`], which is the only thing the rest of the crate talks to, so the public API is identical
+//! no matter which strategy is selected.
+//!
+//! With the `fallback-lock` feature, whichever engine was selected is replaced by one tracking
+//! readers under a plain `Mutex`, see `locked`, and neither `Rcu::new_biased` nor
+//! `Rcu::new_membarrier` use their own read paths: a baseline to rule the atomic protocols out with,
+//! along with the lock-based publication protocol it comes with, see `raw`.
+//!
+//! On `wasm32` without the `atomics` target feature there are no threads to wait for, so whichever
+//! engine was selected is replaced by one that never waits, see `single_thread`.
+
+#[cfg(not(any(
+    feature = "reclaim-counted",
+    feature = "reclaim-retire-list",
+    feature = "reclaim-epoch",
+    feature = "fallback-lock",
+)))]
+compile_error!(
+    "no reclamation strategy selected: enable exactly one of the `reclaim-counted`, \
+     `reclaim-retire-list` or `reclaim-epoch` features, or `fallback-lock`"
+);
+
+#[cfg(any(
+    all(feature = "reclaim-counted", feature = "reclaim-retire-list"),
+    all(feature = "reclaim-counted", feature = "reclaim-epoch"),
+    all(feature = "reclaim-retire-list", feature = "reclaim-epoch"),
+))]
+compile_error!(
+    "multiple reclamation strategies selected: enable exactly one of the `reclaim-counted`, \
+     `reclaim-retire-list` or `reclaim-epoch` features (use `default-features = false` to drop the \
+     default `reclaim-counted`)"
+);
+
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+#[cfg(feature = "async")]
+use std::task::Waker;
+use std::time::Instant;
+
+use crate::wait::{Preference, Waiter};
+
+#[cfg(all(feature = "membarrier", target_os = "linux"))]
+mod asymmetric;
+// Only the asymmetric engine relies on it under loom, which models the bias's barriers itself
+#[cfg_attr(loom, allow(dead_code, unused_imports))]
+mod barrier;
+mod biased;
+#[cfg(any(feature = "async", all(feature = "membarrier", target_os = "linux")))]
+mod wakers;
+// Mostly unused by the engine for targets without threads, which never waits
+#[cfg_attr(all(target_arch = "wasm32", not(target_feature = "atomics")), allow(dead_code))]
+mod watchdog;
+
+#[cfg(all(feature = "membarrier", target_os = "linux"))]
+use asymmetric::Asymmetric;
+use biased::{Bias, BIASED, BIASED_SIGNAL_ACTIVE, BIASED_SIGNAL_IDLE};
+#[cfg(feature = "diagnostics")]
+pub use watchdog::StallReport;
+// Used by the engines that wait, and by the diagnostics
+#[cfg(any(
+    feature = "diagnostics",
+    all(
+        any(feature = "reclaim-counted", feature = "reclaim-retire-list", feature = "reclaim-epoch", feature = "fallback-lock"),
+        not(all(target_arch = "wasm32", not(target_feature = "atomics"))),
+    ),
+))]
+pub(crate) use watchdog::Watchdog;
+#[cfg(feature = "async")]
+pub(crate) use wakers::Wakers;
+
+// Targets without threads, where waiting for a reader can only ever hang, always use a
+// non-waiting engine in place of the selected one
+#[cfg(all(feature = "reclaim-counted", not(feature = "fallback-lock"), not(all(target_arch = "wasm32", not(target_feature = "atomics")))))]
+mod counted;
+#[cfg(all(feature = "reclaim-epoch", not(feature = "fallback-lock"), not(all(target_arch = "wasm32", not(target_feature = "atomics")))))]
+mod epoch;
+#[cfg(all(feature = "reclaim-retire-list", not(feature = "fallback-lock"), not(all(target_arch = "wasm32", not(target_feature = "atomics")))))]
+mod retire_list;
+#[cfg(all(feature = "fallback-lock", not(all(target_arch = "wasm32", not(target_feature = "atomics")))))]
+mod locked;
+// Also built by the tests, whose own run anywhere
+#[cfg(any(test, all(target_arch = "wasm32", not(target_feature = "atomics"))))]
+mod single_thread;
+// Model-checked proofs of the selected engine, only built by `cargo kani`
+#[cfg(all(kani, not(feature = "fallback-lock")))]
+mod proofs;
+#[cfg(test)]
+mod tests;
+
+#[cfg(all(feature = "reclaim-counted", not(feature = "fallback-lock"), not(all(target_arch = "wasm32", not(target_feature = "atomics")))))]
+pub(crate) use counted::Counted as Reclaimer;
+#[cfg(all(feature = "reclaim-epoch", not(feature = "fallback-lock"), not(all(target_arch = "wasm32", not(target_feature = "atomics")))))]
+pub(crate) use epoch::Epoch as Reclaimer;
+#[cfg(all(feature = "reclaim-retire-list", not(feature = "fallback-lock"), not(all(target_arch = "wasm32", not(target_feature = "atomics")))))]
+pub(crate) use retire_list::RetireList as Reclaimer;
+#[cfg(all(feature = "fallback-lock", not(all(target_arch = "wasm32", not(target_feature = "atomics")))))]
+pub(crate) use locked::Locked as Reclaimer;
+#[cfg(all(target_arch = "wasm32", not(target_feature = "atomics")))]
+pub(crate) use single_thread::SingleThread as Reclaimer;
+
+/// The boundary between the publication logic in `Rcu` and the engine deciding when an
+/// unpublished allocation may be freed.
+pub(crate) trait Reclaim: Default + Send + Sync {
+    /// Marks the start of a read-side critical section. Any pointer loaded after this returns
+    /// stays valid until the matching `exit`. The returned token is engine-specific and must be
+    /// handed back to `exit`.
+    fn enter(&self) -> usize;
+
+    /// Marks the end of the read-side critical section started by the matching `enter`.
+    fn exit(&self, token: usize);
+
+    /// Like `enter`, only using async-signal-safe operations and never waiting for anything the
+    /// thread it interrupted could be doing, see `Rcu::read_signal_safe`.
+    fn enter_signal_safe(&self) -> usize {
+        self.enter()
+    }
+
+    /// Like `exit`, for a critical section entered by `enter_signal_safe`. Never wakes tasks.
+    fn exit_signal_safe(&self, token: usize) {
+        self.exit(token)
+    }
+
+    /// Hands over an allocation that has been unpublished, to be freed once no reader can
+    /// still be observing it. Engines are free to do this in-line or defer it.
+    ///
+    /// # Safety
+    /// The allocation must already be unreachable for readers entering after this call, and
+    /// must not be retired twice.
+    unsafe fn retire(&self, retired: Retired);
+
+    /// Like `retire`, giving up on waiting for readers at `deadline`: returns false if the
+    /// allocation couldn't be freed by then, it is then left for a later grace period to free.
+    /// Engines that never wait free or queue it as `retire` does and always return true.
+    ///
+    /// # Safety
+    /// As for `retire`.
+    unsafe fn retire_before(&self, retired: Retired, _deadline: Instant) -> bool {
+        self.retire(retired);
+        true
+    }
+
+    /// Like `retire_before` for every allocation of `batch` at once, or `retire` without a
+    /// deadline, waiting for readers once rather than once per allocation. Returns whether all of
+    /// them were dealt with in time.
+    ///
+    /// # Safety
+    /// As for `retire`, for each of them.
+    unsafe fn retire_batch(&self, batch: Vec<Retired>, deadline: Option<Instant>) -> bool {
+        let mut in_time = true;
+        for retired in batch {
+            match deadline {
+                None => self.retire(retired),
+                Some(deadline) => in_time &= self.retire_before(retired, deadline),
+            }
+        }
+        in_time
+    }
+
+    /// Waits until every read-side critical section active when this is called has exited.
+    fn synchronize(&self);
+
+    /// Frees every allocation retired before this call, waiting for the readers that may still
+    /// observe them however long that takes, and for frees another thread is in the middle of: where
+    /// `synchronize` only waits for readers, this leaves nothing retired before pending on return.
+    fn barrier(&self);
+
+    /// Makes the engine wait and notify waiters through `waiter` from now on. Engines that never
+    /// wait ignore it.
+    fn set_waiter(&mut self, _waiter: Waiter) {}
+
+    /// Makes the engine hold readers back while waiting as `preference` says. Engines that never
+    /// hold readers back ignore it.
+    fn set_preference(&mut self, _preference: Preference) {}
+
+    /// Starts a grace period waited for with `poll_grace_period` instead of `synchronize`, returning
+    /// the engine-specific ticket to poll it with.
+    #[cfg(feature = "async")]
+    fn start_grace_period(&self) -> usize;
+
+    /// Whether every read-side critical section active when `ticket`'s grace period started has
+    /// exited. If not, `waker` is woken once a reader exit may have changed that. Unlike
+    /// `synchronize`, never holds new readers back.
+    #[cfg(feature = "async")]
+    fn poll_grace_period(&self, ticket: usize, waker: &Waker) -> bool;
+
+    /// Number of allocations retired but not freed yet.
+    fn pending(&self) -> usize;
+
+    /// Observes this engine's waits for readers.
+    #[cfg(feature = "diagnostics")]
+    fn watchdog(&self) -> &Watchdog;
+}
+
+/// Locks `freeing`, held by engines from taking allocations out of their queue until freeing them
+/// or putting them back, unless another thread holds it: that one frees what is queued meanwhile.
+/// Never blocks, so a value freed while it is held may retire values of its own.
+/// `Reclaim::barrier` blocks on it instead, waiting for whoever took allocations queued before.
+/// Engines may keep what they are freeing in it, so that its capacity is reused.
+#[cfg_attr(all(target_arch = "wasm32", not(target_feature = "atomics")), allow(dead_code))]
+fn try_lock_freeing<F>(freeing: &Mutex<F>) -> Option<MutexGuard<'_, F>> {
+    match freeing.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+/// The engine of one RCU-managed value: its own, possibly biased towards the thread that created
+/// it (see `biased`), or shared by every member of an `RcuDomain`. With the `membarrier` feature, it
+/// may also track readers in per-thread slots (see `asymmetric`) rather than through its own engine,
+/// which then never has readers.
+pub(crate) enum Engine {
+    Own(Reclaimer),
+    Biased(Reclaimer, Bias),
+    Shared(Arc<Reclaimer>),
+    #[cfg(all(feature = "membarrier", target_os = "linux"))]
+    Asymmetric(Reclaimer, Box<Asymmetric>),
+}
+
+impl Engine {
+    /// An engine of its own, biased towards the current thread where supported.
+    pub(crate) fn biased() -> Self {
+        match Bias::new() {
+            Some(bias) => Engine::Biased(Reclaimer::default(), bias),
+            None => Engine::Own(Reclaimer::default()),
+        }
+    }
+
+    /// An engine of its own whose readers only ever use plain stores, where supported.
+    #[cfg(feature = "membarrier")]
+    pub(crate) fn membarrier() -> Self {
+        #[cfg(target_os = "linux")]
+        return Self::asymmetric_or_own(Asymmetric::new());
+        #[cfg(not(target_os = "linux"))]
+        Engine::Own(Reclaimer::default())
+    }
+
+    /// An engine tracking readers with `asymmetric`, or of its own if there is none.
+    #[cfg(all(feature = "membarrier", target_os = "linux"))]
+    fn asymmetric_or_own(asymmetric: Option<Asymmetric>) -> Self {
+        match asymmetric {
+            Some(asymmetric) => Engine::Asymmetric(Reclaimer::default(), Box::new(asymmetric)),
+            None => Engine::Own(Reclaimer::default()),
+        }
+    }
+
+    /// Frees `retired` through the bias if this thread holds it, see `Bias::retire`, handing it back
+    /// otherwise, for the caller to wait for the engine's readers before freeing it.
+    ///
+    /// # Safety
+    /// As for `Reclaim::retire`.
+    #[cfg(feature = "async")]
+    pub(crate) unsafe fn retire_biased(&self, retired: Retired) -> Option<Retired> {
+        match self {
+            Engine::Biased(_, bias) => bias.retire(retired),
+            _ => Some(retired),
+        }
+    }
+
+    /// Frees what was retired before and no reader can observe anymore, waiting for readers until
+    /// `deadline` if the engine waits, returning whether everything retired could be freed in time.
+    pub(crate) fn reclaim_before(&self, deadline: Option<Instant>) -> bool {
+        // Safety: an empty batch retires nothing, only what was retired before is freed
+        unsafe {
+            match self {
+                #[cfg(all(feature = "membarrier", target_os = "linux"))]
+                Engine::Asymmetric(_, asymmetric) => asymmetric.retire_batch(Vec::new(), deadline),
+                _ => (**self).retire_batch(Vec::new(), deadline),
+            }
+        }
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::Own(Reclaimer::default())
+    }
+}
+
+impl Deref for Engine {
+    type Target = Reclaimer;
+
+    fn deref(&self) -> &Reclaimer {
+        match self {
+            Engine::Own(reclaimer) | Engine::Biased(reclaimer, _) => reclaimer,
+            #[cfg(all(feature = "membarrier", target_os = "linux"))]
+            Engine::Asymmetric(reclaimer, _) => reclaimer,
+            Engine::Shared(reclaimer) => reclaimer,
+        }
+    }
+}
+
+impl Reclaim for Engine {
+    fn enter(&self) -> usize {
+        match self {
+            Engine::Biased(_, bias) if bias.enter() => BIASED,
+            #[cfg(all(feature = "membarrier", target_os = "linux"))]
+            Engine::Asymmetric(_, asymmetric) => asymmetric.enter(),
+            _ => (**self).enter(),
+        }
+    }
+
+    fn exit(&self, token: usize) {
+        match self {
+            Engine::Biased(_, bias) if token == BIASED => bias.exit(),
+            #[cfg(all(feature = "membarrier", target_os = "linux"))]
+            Engine::Asymmetric(_, asymmetric) => asymmetric.exit(token),
+            _ => (**self).exit(token),
+        }
+    }
+
+    fn enter_signal_safe(&self) -> usize {
+        match self {
+            Engine::Biased(_, bias) => match bias.enter_signal_safe() {
+                Some(true) => BIASED_SIGNAL_ACTIVE,
+                Some(false) => BIASED_SIGNAL_IDLE,
+                None => (**self).enter_signal_safe(),
+            },
+            #[cfg(all(feature = "membarrier", target_os = "linux"))]
+            Engine::Asymmetric(_, asymmetric) => asymmetric.enter_signal_safe(),
+            _ => (**self).enter_signal_safe(),
+        }
+    }
+
+    fn exit_signal_safe(&self, token: usize) {
+        match self {
+            Engine::Biased(_, bias) if token == BIASED_SIGNAL_ACTIVE => bias.exit_signal_safe(true),
+            Engine::Biased(_, bias) if token == BIASED_SIGNAL_IDLE => bias.exit_signal_safe(false),
+            #[cfg(all(feature = "membarrier", target_os = "linux"))]
+            Engine::Asymmetric(_, asymmetric) => asymmetric.exit_signal_safe(),
+            _ => (**self).exit_signal_safe(token),
+        }
+    }
+
+    unsafe fn retire(&self, retired: Retired) {
+        let retired = match self {
+            Engine::Biased(_, bias) => bias.retire(retired),
+            #[cfg(all(feature = "membarrier", target_os = "linux"))]
+            Engine::Asymmetric(_, asymmetric) => return asymmetric.retire(retired),
+            _ => Some(retired),
+        };
+        if let Some(retired) = retired {
+            (**self).retire(retired);
+        }
+    }
+
+    unsafe fn retire_before(&self, retired: Retired, deadline: Instant) -> bool {
+        let retired = match self {
+            Engine::Biased(_, bias) => bias.retire(retired),
+            #[cfg(all(feature = "membarrier", target_os = "linux"))]
+            Engine::Asymmetric(_, asymmetric) => return asymmetric.retire_before(retired, deadline),
+            _ => Some(retired),
+        };
+        retired.is_none_or(|retired| (**self).retire_before(retired, deadline))
+    }
+
+    unsafe fn retire_batch(&self, batch: Vec<Retired>, deadline: Option<Instant>) -> bool {
+        let batch = match self {
+            Engine::Biased(_, bias) => batch.into_iter().filter_map(|retired| bias.retire(retired)).collect(),
+            #[cfg(all(feature = "membarrier", target_os = "linux"))]
+            Engine::Asymmetric(_, asymmetric) => return asymmetric.retire_batch(batch, deadline),
+            _ => batch,
+        };
+        batch.is_empty() || (**self).retire_batch(batch, deadline)
+    }
+
+    fn set_waiter(&mut self, waiter: Waiter) {
+        match self {
+            Engine::Own(reclaimer) => reclaimer.set_waiter(waiter),
+            Engine::Biased(reclaimer, bias) => {
+                bias.set_waiter(waiter.clone());
+                reclaimer.set_waiter(waiter);
+            }
+            #[cfg(all(feature = "membarrier", target_os = "linux"))]
+            Engine::Asymmetric(reclaimer, asymmetric) => {
+                asymmetric.set_waiter(waiter.clone());
+                reclaimer.set_waiter(waiter);
+            }
+            // Waits the way its domain does
+            Engine::Shared(_) => {}
+        }
+    }
+
+    fn set_preference(&mut self, preference: Preference) {
+        match self {
+            // The bias and the asymmetric engine never hold readers back, the engine's own readers
+            // are those of other threads, or signal handlers
+            Engine::Own(reclaimer) | Engine::Biased(reclaimer, _) => reclaimer.set_preference(preference),
+            #[cfg(all(feature = "membarrier", target_os = "linux"))]
+            Engine::Asymmetric(reclaimer, _) => reclaimer.set_preference(preference),
+            Engine::Shared(_) => {}
+        }
+    }
+
+    fn synchronize(&self) {
+        match self {
+            Engine::Biased(_, bias) => bias.touch(),
+            #[cfg(all(feature = "membarrier", target_os = "linux"))]
+            Engine::Asymmetric(_, asymmetric) => return asymmetric.synchronize(),
+            _ => {}
+        }
+        (**self).synchronize();
+    }
+
+    fn barrier(&self) {
+        match self {
+            // Revoking waits for the owner to free what it deferred through the bias
+            Engine::Biased(_, bias) => bias.touch(),
+            #[cfg(all(feature = "membarrier", target_os = "linux"))]
+            Engine::Asymmetric(_, asymmetric) => return asymmetric.barrier(),
+            _ => {}
+        }
+        (**self).barrier();
+    }
+
+    #[cfg(feature = "async")]
+    fn start_grace_period(&self) -> usize {
+        match self {
+            Engine::Biased(_, bias) => bias.touch(),
+            #[cfg(all(feature = "membarrier", target_os = "linux"))]
+            Engine::Asymmetric(_, asymmetric) => return asymmetric.start_grace_period(),
+            _ => {}
+        }
+        (**self).start_grace_period()
+    }
+
+    #[cfg(feature = "async")]
+    fn poll_grace_period(&self, ticket: usize, waker: &Waker) -> bool {
+        match self {
+            #[cfg(all(feature = "membarrier", target_os = "linux"))]
+            Engine::Asymmetric(_, asymmetric) => asymmetric.poll_grace_period(ticket, waker),
+            _ => (**self).poll_grace_period(ticket, waker),
+        }
+    }
+
+    fn pending(&self) -> usize {
+        match self {
+            #[cfg(all(feature = "membarrier", target_os = "linux"))]
+            Engine::Asymmetric(_, asymmetric) => asymmetric.pending(),
+            _ => (**self).pending(),
+        }
+    }
+
+    #[cfg(feature = "diagnostics")]
+    fn watchdog(&self) -> &Watchdog {
+        match self {
+            #[cfg(all(feature = "membarrier", target_os = "linux"))]
+            Engine::Asymmetric(_, asymmetric) => asymmetric.watchdog(),
+            _ => (**self).watchdog(),
+        }
+    }
+}
+
+/// RAII form of a read-side critical section, so a panicking reader never leaves the engine
+/// believing it is still active.
+pub(crate) struct ReadLock<'a, R: Reclaim = Reclaimer> {
+    reclaimer: &'a R,
+    token: usize,
+    /// Id of the entry location recorded with the watchdog
+    #[cfg(all(feature = "diagnostics", debug_assertions))]
+    tracked: u64,
+}
+
+impl<'a, R: Reclaim> ReadLock<'a, R> {
+    /// Enters a read-side critical section on `reclaimer`, exited when the returned value is dropped.
+    #[track_caller]
+    pub(crate) fn new(reclaimer: &'a R) -> Self {
+        #[cfg(all(feature = "diagnostics", debug_assertions))]
+        let tracked = reclaimer.watchdog().enter(std::panic::Location::caller());
+        let token = reclaimer.enter();
+        Self {
+            reclaimer,
+            token,
+            #[cfg(all(feature = "diagnostics", debug_assertions))]
+            tracked,
+        }
+    }
+}
+
+impl ReadLock<'_> {
+    /// Whether this critical section was entered on `reclaimer`.
+    pub(crate) fn is_on(&self, reclaimer: &Reclaimer) -> bool {
+        std::ptr::eq(self.reclaimer, reclaimer)
+    }
+}
+
+impl<R: Reclaim> Drop for ReadLock<'_, R> {
+    fn drop(&mut self) {
+        self.reclaimer.exit(self.token);
+        #[cfg(all(feature = "diagnostics", debug_assertions))]
+        self.reclaimer.watchdog().exit(self.tracked);
+    }
+}
+
+/// Like `ReadLock`, for a read-side critical section entered with `Reclaim::enter_signal_safe`.
+/// Isn't tracked by the watchdog, which takes locks.
+pub(crate) struct SignalSafeLock<'a> {
+    reclaimer: &'a Engine,
+    token: usize,
+}
+
+impl<'a> SignalSafeLock<'a> {
+    pub(crate) fn new(reclaimer: &'a Engine) -> Self {
+        Self {
+            reclaimer,
+            token: reclaimer.enter_signal_safe(),
+        }
+    }
+}
+
+impl Drop for SignalSafeLock<'_> {
+    fn drop(&mut self) {
+        self.reclaimer.exit_signal_safe(self.token);
+    }
+}
+
+/// A type-erased allocation waiting to be freed.
+pub(crate) struct Retired {
+    ptr: *mut (),
+    drop_fn: unsafe fn(*mut ()),
+}
+
+impl Retired {
+    /// Wraps a pointer obtained from `Box::into_raw`.
+    pub(crate) fn new<T>(ptr: *mut T) -> Self {
+        unsafe fn drop_box<T>(ptr: *mut ()) {
+            drop(Box::from_raw(ptr as *mut T));
+        }
+        Self {
+            ptr: ptr as *mut (),
+            drop_fn: drop_box::<T>,
+        }
+    }
+
+    /// Wraps `value`, to be dropped once reclaimed.
+    #[cfg_attr(not(any(feature = "fallback-lock", test)), allow(dead_code))]
+    pub(crate) fn owned<V>(value: V) -> Self {
+        Self::new(Box::into_raw(Box::new(value)))
+    }
+
+    /// Wraps `ptr`, to be freed by calling `deleter` on it.
+    pub(crate) fn with_deleter<N>(ptr: *mut N, deleter: unsafe fn(*mut N)) -> Self {
+        Self {
+            ptr: ptr as *mut (),
+            // Safety: pointers to sized types are ABI-compatible, so calling `deleter` through
+            // this signature is equivalent to calling it directly
+            drop_fn: unsafe { std::mem::transmute::<unsafe fn(*mut N), unsafe fn(*mut ())>(deleter) },
+        }
+    }
+
+    /// Frees the allocation.
+    ///
+    /// # Safety
+    /// No reader may still hold a reference into the allocation.
+    pub(crate) unsafe fn reclaim(self) {
+        (self.drop_fn)(self.ptr)
+    }
+}
+
+// Safety: a `Retired` is only ever created from values owned by an `Rcu<T>`, which is only `Send` and
+// `Sync` when `T: Send + Sync`, or handed to `RcuDomain::retire`, whose callers guarantee the same, so
+// moving the pointer to whichever thread reclaims it is sound.
+unsafe impl Send for Retired {}
@@ -0,0 +1,247 @@
+//! [Kani](https://model-checking.github.io/kani/) proof harnesses for the protocol between readers,
+//! publishes and the selected engine, checked for every interleaving of their steps up to a bound
+//! rather than for the interleavings a test happens to hit. For each engine they prove that:
+//!
+//! - the pointer a reader dereferences is one a publish made with `Box::into_raw`, and that value
+//!   wasn't freed yet, nor is it until the reader exits,
+//! - every displaced value is freed at most once, and exactly once after the engine is dropped,
+//! - with `reclaim-counted`, the reader counter of each phase always equals the number of readers
+//!   counted in it, so it never underflows.
+//!
+//! Readers and the writer are modelled as state machines taking one atomic step at a time, picked
+//! nondeterministically, on a single thread: what is checked is the protocol, not the atomics
+//! themselves, which are sequentially consistent throughout. With `reclaim-counted` the steps are
+//! those of `Counted::register_step`, `Counted::flip` and `Counted::drained`, which the engine
+//! itself is made of, since its grace periods block until readers exit and so can't be stepped
+//! through from one thread. The other engines never block and are driven through `Reclaim`.
+//!
+//! # Running
+//!
+//! ```sh
+//! cargo install --locked kani-verifier && cargo kani setup
+//! cargo kani
+//! cargo kani --no-default-features --features reclaim-retire-list
+//! cargo kani --no-default-features --features reclaim-epoch
+//! ```
+//!
+//! The first checks `reclaim-counted`, the default, the others the remaining engines; add
+//! `--harness <name>` to check one harness only. `STEPS` bounds how many steps are interleaved:
+//! raising it checks longer runs, at the cost of verification time growing quickly.
+
+use std::sync::atomic::{AtomicPtr, AtomicU8, Ordering::SeqCst};
+
+#[cfg(feature = "reclaim-counted")]
+use super::counted::{Counted, Registration};
+#[cfg(not(feature = "reclaim-counted"))]
+use super::{Reclaim, Reclaimer};
+use super::Retired;
+
+/// Values published over a run, the first one being the initial value.
+const VALUES: usize = 3;
+/// Readers reading concurrently with the writer.
+const READERS: usize = 2;
+/// Steps taken by the readers and the writer altogether.
+const STEPS: usize = 12;
+
+/// How many times each published value was freed, by index.
+static FREED: [AtomicU8; VALUES] = [const { AtomicU8::new(0) }; VALUES];
+
+/// A published value, knowing its own index to be checked against the one it was published as.
+struct Value {
+    index: usize,
+}
+
+/// Frees a value, checking it wasn't freed before.
+unsafe fn free(value: *mut Value) {
+    let value = Box::from_raw(value);
+    let freed = FREED[value.index].fetch_add(1, SeqCst);
+    assert!(freed == 0, "a value was freed twice");
+}
+
+/// The values of a run, each an allocation made with `Box::into_raw`.
+struct Values([*mut Value; VALUES]);
+
+impl Values {
+    fn new() -> Self {
+        Self(std::array::from_fn(|index| Box::into_raw(Box::new(Value { index }))))
+    }
+
+    fn ptr(&self, index: usize) -> *mut Value {
+        self.0[index]
+    }
+
+    /// The index of the value `ptr` points to, which must be one of them.
+    fn index_of(&self, ptr: *mut Value) -> usize {
+        let index = self.0.iter().position(|&value| value == ptr);
+        index.expect("a reader loaded a pointer no publish made")
+    }
+
+    /// Loads the current value from `slot` as a reader does, returning its index.
+    fn load(&self, slot: &AtomicPtr<Value>) -> usize {
+        let index = self.index_of(slot.load(SeqCst));
+        assert!(FREED[index].load(SeqCst) == 0, "a reader loaded a freed value");
+        index
+    }
+
+    /// Dereferences the value at `index`, which a reader loaded and still holds.
+    fn read(&self, index: usize) {
+        assert!(FREED[index].load(SeqCst) == 0, "a value was freed while a reader held it");
+        // Safety: just checked it wasn't freed
+        let value = unsafe { &*self.ptr(index) };
+        assert!(value.index == index);
+    }
+
+    /// The value at `index`, to be retired.
+    fn retired(&self, index: usize) -> Retired {
+        Retired::with_deleter(self.ptr(index), free)
+    }
+}
+
+/// A nondeterministic index below `bound`.
+fn any_below(bound: usize) -> usize {
+    let index: usize = kani::any();
+    kani::assume(index < bound);
+    index
+}
+
+/// A reader of the counted engine, reading over and over.
+#[cfg(feature = "reclaim-counted")]
+#[derive(Clone, Copy)]
+enum CountedReader {
+    Registering(Registration),
+    /// Registered in the phase, about to load the value
+    Registered(usize),
+    /// Registered in the phase, holding the value at the index
+    Holding(usize, usize),
+}
+
+#[cfg(feature = "reclaim-counted")]
+impl CountedReader {
+    /// The phase whose counter counts the reader, if any.
+    fn counted_in(self) -> Option<usize> {
+        match self {
+            CountedReader::Registering(Registration::Counted(phase) | Registration::Registered(phase))
+            | CountedReader::Registered(phase)
+            | CountedReader::Holding(phase, _) => Some(phase),
+            CountedReader::Registering(Registration::Start | Registration::Loaded(_)) => None,
+        }
+    }
+}
+
+/// What the writer of the counted engine waits for before freeing the value it displaced.
+#[cfg(feature = "reclaim-counted")]
+#[derive(Clone, Copy)]
+enum GracePeriod {
+    /// Every reader, as with `Preference::WriterPreferred`
+    All,
+    /// The readers of the phase it flipped away from, as with the other preferences
+    Phase(usize),
+}
+
+/// The writer of the counted engine, publishing the values in turn.
+#[cfg(feature = "reclaim-counted")]
+#[derive(Clone, Copy)]
+enum CountedWriter {
+    /// About to publish the value at the index
+    Publishing(usize),
+    /// Waiting to free the value at the first index, then to publish the one at the second
+    Waiting(GracePeriod, usize, usize),
+}
+
+#[cfg(feature = "reclaim-counted")]
+#[kani::proof]
+#[kani::unwind(13)]
+fn counted_readers_only_see_live_values() {
+    let counted = Counted::default();
+    let values = Values::new();
+    let slot = AtomicPtr::new(values.ptr(0));
+    let mut readers = [CountedReader::Registering(Registration::Start); READERS];
+    let mut writer = CountedWriter::Publishing(1);
+    for _ in 0..STEPS {
+        if kani::any() {
+            writer = match writer {
+                CountedWriter::Publishing(next) if next < VALUES => {
+                    let displaced = values.index_of(slot.swap(values.ptr(next), SeqCst));
+                    let grace_period = if kani::any() { GracePeriod::All } else { GracePeriod::Phase(counted.flip()) };
+                    CountedWriter::Waiting(grace_period, displaced, next + 1)
+                }
+                CountedWriter::Publishing(next) => CountedWriter::Publishing(next),
+                CountedWriter::Waiting(grace_period, displaced, next) => {
+                    let drained = match grace_period {
+                        GracePeriod::All => counted.active() == 0,
+                        GracePeriod::Phase(phase) => counted.drained(phase),
+                    };
+                    if drained {
+                        // Safety: how the engine frees what it retires, once readers drained
+                        unsafe { values.retired(displaced).reclaim() };
+                        CountedWriter::Publishing(next)
+                    } else {
+                        CountedWriter::Waiting(grace_period, displaced, next)
+                    }
+                }
+            };
+        } else {
+            let reader = any_below(READERS);
+            readers[reader] = match readers[reader] {
+                CountedReader::Registering(registration) => match counted.register_step(registration) {
+                    Registration::Registered(phase) => CountedReader::Registered(phase),
+                    registration => CountedReader::Registering(registration),
+                },
+                CountedReader::Registered(phase) => CountedReader::Holding(phase, values.load(&slot)),
+                CountedReader::Holding(phase, index) => {
+                    values.read(index);
+                    counted.release(phase);
+                    CountedReader::Registering(Registration::Start)
+                }
+            };
+        }
+        for phase in 0..2 {
+            let counted_in = readers.iter().filter(|reader| reader.counted_in() == Some(phase)).count();
+            assert!(counted.counted_in(phase) as usize == counted_in, "a reader counter is off");
+        }
+    }
+}
+
+#[cfg(not(feature = "reclaim-counted"))]
+#[kani::proof]
+#[kani::unwind(13)]
+fn readers_only_see_live_values() {
+    let engine = Reclaimer::default();
+    let values = Values::new();
+    let slot = AtomicPtr::new(values.ptr(0));
+    // The token of each reader inside a read-side critical section, with the value it holds
+    let mut readers: [Option<(usize, usize)>; READERS] = [None; READERS];
+    let mut published = 1;
+    for _ in 0..STEPS {
+        if kani::any() {
+            if published < VALUES {
+                let displaced = values.index_of(slot.swap(values.ptr(published), SeqCst));
+                // Safety: unreachable for readers entering from now on, and retired once
+                unsafe { engine.retire(values.retired(displaced)) };
+                published += 1;
+            }
+        } else {
+            let reader = any_below(READERS);
+            readers[reader] = match readers[reader] {
+                None => {
+                    let token = engine.enter();
+                    Some((token, values.load(&slot)))
+                }
+                Some((token, index)) => {
+                    values.read(index);
+                    engine.exit(token);
+                    None
+                }
+            };
+        }
+    }
+    for (token, index) in readers.into_iter().flatten() {
+        values.read(index);
+        engine.exit(token);
+    }
+    drop(engine);
+    for (index, freed) in FREED.iter().enumerate() {
+        let displaced = index < published - 1;
+        assert!(freed.load(SeqCst) == u8::from(displaced), "a displaced value wasn't freed once");
+    }
+}
@@ -0,0 +1,135 @@
+use std::mem;
+use std::sync::atomic::{AtomicU32, Ordering::SeqCst};
+use std::sync::{Mutex, PoisonError};
+#[cfg(feature = "async")]
+use std::task::Waker;
+use std::time::Instant;
+
+#[cfg(feature = "async")]
+use super::Wakers;
+use super::{try_lock_freeing, Reclaim, Retired, Watchdog};
+use crate::wait::Waiter;
+
+/// Readers are never gated; writers push the previous value onto a list, and the whole list is
+/// freed whenever a writer observes that no reader is active.
+#[derive(Default)]
+pub(crate) struct RetireList {
+    /// Holds the count of the current number of readers
+    cur_readers: AtomicU32,
+    /// Allocations that have been unpublished but may still be observed by a reader
+    retired: Mutex<Vec<Retired>>,
+    /// Held while allocations taken out of `retired` are freed, see `try_lock_freeing`. Holds
+    /// them meanwhile, the two lists trading places so neither loses its capacity
+    freeing: Mutex<Vec<Retired>>,
+    watchdog: Watchdog,
+    /// Tasks waiting for `cur_readers` to drop to 0
+    #[cfg(feature = "async")]
+    wakers: Wakers,
+}
+
+impl RetireList {
+    /// Queues `retired`, freeing the whole list if no reader is active.
+    unsafe fn retire_all(&self, retired: impl IntoIterator<Item = Retired>) {
+        let mut list = self.retired.lock().unwrap_or_else(PoisonError::into_inner);
+        list.extend(retired);
+        // Everything in the list was unpublished before this load, so if no reader is active
+        // now, none can still be looking at any of it
+        if self.cur_readers.load(SeqCst) > 0 {
+            return;
+        }
+        let Some(mut freeing) = try_lock_freeing(&self.freeing) else {
+            return;
+        };
+        mem::swap(&mut *list, &mut *freeing);
+        drop(list);
+        for retired in freeing.drain(..) {
+            retired.reclaim();
+        }
+    }
+}
+
+impl Reclaim for RetireList {
+    fn enter(&self) -> usize {
+        self.cur_readers.fetch_add(1, SeqCst);
+        0
+    }
+
+    fn exit(&self, _token: usize) {
+        if self.cur_readers.fetch_sub(1, SeqCst) == 1 {
+            self.watchdog.waiter().notify();
+            #[cfg(feature = "async")]
+            self.wakers.wake();
+        }
+    }
+
+    fn exit_signal_safe(&self, _token: usize) {
+        self.cur_readers.fetch_sub(1, SeqCst);
+    }
+
+    unsafe fn retire(&self, retired: Retired) {
+        self.retire_all([retired]);
+    }
+
+    unsafe fn retire_batch(&self, batch: Vec<Retired>, _deadline: Option<Instant>) -> bool {
+        self.retire_all(batch);
+        true
+    }
+
+    fn synchronize(&self) {
+        // Readers are never held back, so a constant stream of them can keep this waiting
+        self.watchdog.wait_while(
+            || self.cur_readers.load(SeqCst) > 0,
+            || self.cur_readers.load(SeqCst) as usize,
+            None,
+        );
+    }
+
+    fn barrier(&self) {
+        let mut freeing = self.freeing.lock().unwrap_or_else(PoisonError::into_inner);
+        // Readers entering from now on can't observe anything in the list, those active now are
+        // waited for
+        mem::swap(&mut *self.retired.lock().unwrap_or_else(PoisonError::into_inner), &mut *freeing);
+        self.synchronize();
+        for retired in freeing.drain(..) {
+            // Safety: unpublished before the readers waited for exited
+            unsafe { retired.reclaim() };
+        }
+    }
+
+    fn set_waiter(&mut self, waiter: Waiter) {
+        self.watchdog.set_waiter(waiter);
+    }
+
+    #[cfg(feature = "async")]
+    fn start_grace_period(&self) -> usize {
+        0
+    }
+
+    #[cfg(feature = "async")]
+    fn poll_grace_period(&self, _ticket: usize, waker: &Waker) -> bool {
+        if self.cur_readers.load(SeqCst) == 0 {
+            return true;
+        }
+        self.wakers.register(waker);
+        self.cur_readers.load(SeqCst) == 0
+    }
+
+    fn pending(&self) -> usize {
+        self.retired.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    #[cfg(feature = "diagnostics")]
+    fn watchdog(&self) -> &Watchdog {
+        &self.watchdog
+    }
+}
+
+impl Drop for RetireList {
+    fn drop(&mut self) {
+        let list = self.retired.get_mut().unwrap_or_else(PoisonError::into_inner);
+        for retired in list.drain(..) {
+            // Safety: we have exclusive access, so there are no readers left
+            unsafe { retired.reclaim() }
+        }
+    }
+}
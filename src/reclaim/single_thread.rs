@@ -0,0 +1,193 @@
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::sync::{Mutex, PoisonError};
+#[cfg(feature = "async")]
+use std::task::Waker;
+
+#[cfg(feature = "async")]
+use super::Wakers;
+#[cfg(feature = "diagnostics")]
+use super::Watchdog;
+use super::{Reclaim, Retired};
+
+/// For targets without threads: the only readers that can be active while a value is retired are
+/// further up the writer's own stack (a publish from inside `read_with`, or a hook), and they can't
+/// be waited for. So nothing ever waits: retired values are freed right away when no reader is
+/// active, otherwise by the exit of the outermost reader.
+#[derive(Default)]
+pub(crate) struct SingleThread {
+    /// Nesting depth of read-side critical sections
+    readers: AtomicUsize,
+    /// Allocations retired while a reader was active
+    retired: Mutex<Vec<Retired>>,
+    /// Never has anything to report, only there for `Reclaim::watchdog`
+    #[cfg(feature = "diagnostics")]
+    watchdog: Watchdog,
+    /// Tasks waiting for the outermost reader to exit
+    #[cfg(feature = "async")]
+    wakers: Wakers,
+}
+
+impl SingleThread {
+    fn reclaim_retired(&self) {
+        let eligible = mem::take(&mut *self.retired.lock().unwrap_or_else(PoisonError::into_inner));
+        for retired in eligible {
+            // Safety: there are no other threads, and no reader left on this one
+            unsafe { retired.reclaim() }
+        }
+    }
+}
+
+impl Reclaim for SingleThread {
+    fn enter(&self) -> usize {
+        self.readers.fetch_add(1, Relaxed);
+        0
+    }
+
+    fn exit(&self, _token: usize) {
+        if self.readers.fetch_sub(1, Relaxed) == 1 {
+            self.reclaim_retired();
+            #[cfg(feature = "async")]
+            self.wakers.wake();
+        }
+    }
+
+    unsafe fn retire(&self, retired: Retired) {
+        self.retired.lock().unwrap_or_else(PoisonError::into_inner).push(retired);
+        if self.readers.load(Relaxed) == 0 {
+            self.reclaim_retired();
+        }
+    }
+
+    fn synchronize(&self) {
+        // Any active reader is further up our own stack and can't exit before we return
+    }
+
+    fn barrier(&self) {
+        // Values retired inside a reader further up our own stack are freed once it exits
+        if self.readers.load(Relaxed) == 0 {
+            self.reclaim_retired();
+        }
+    }
+
+    #[cfg(feature = "async")]
+    fn start_grace_period(&self) -> usize {
+        0
+    }
+
+    #[cfg(feature = "async")]
+    fn poll_grace_period(&self, _ticket: usize, waker: &Waker) -> bool {
+        // A reader further up the stack of the task polling, which exits once it returns pending
+        if self.readers.load(Relaxed) == 0 {
+            return true;
+        }
+        self.wakers.register(waker);
+        false
+    }
+
+    fn pending(&self) -> usize {
+        self.retired.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    #[cfg(feature = "diagnostics")]
+    fn watchdog(&self) -> &Watchdog {
+        &self.watchdog
+    }
+}
+
+impl Drop for SingleThread {
+    fn drop(&mut self) {
+        self.reclaim_retired();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    use std::sync::Arc;
+
+    use super::SingleThread;
+    use crate::reclaim::{Reclaim, ReadLock, Retired};
+
+    /// Counts its drops in the counter it shares with the test.
+    struct Tracked(Arc<AtomicUsize>);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, SeqCst);
+        }
+    }
+
+    fn retired(drops: &Arc<AtomicUsize>) -> Retired {
+        Retired::new(Box::into_raw(Box::new(Tracked(Arc::clone(drops)))))
+    }
+
+    #[test]
+    fn retiring_without_readers_frees_right_away() {
+        let engine = SingleThread::default();
+        let drops = Arc::new(AtomicUsize::new(0));
+        // Safety: never published, so no reader can observe it
+        unsafe { engine.retire(retired(&drops)) };
+        assert_eq!(drops.load(SeqCst), 1, "not freed right away");
+        assert_eq!(engine.pending(), 0);
+    }
+
+    #[test]
+    fn retiring_inside_readers_frees_on_the_outermost_exit() {
+        let engine = SingleThread::default();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let outer = ReadLock::new(&engine);
+        let inner = ReadLock::new(&engine);
+        // Safety: as above
+        unsafe { engine.retire(retired(&drops)) };
+        // Neither waits for the readers up the stack, which would never exit
+        engine.synchronize();
+        engine.barrier();
+        assert_eq!(engine.pending(), 1, "freed under an active reader");
+        drop(inner);
+        assert_eq!(drops.load(SeqCst), 0, "freed on an inner exit");
+        drop(outer);
+        assert_eq!(drops.load(SeqCst), 1, "not freed on the outermost exit");
+        assert_eq!(engine.pending(), 0);
+    }
+
+    #[test]
+    fn dropping_the_engine_frees_what_is_pending() {
+        let engine = SingleThread::default();
+        let drops = Arc::new(AtomicUsize::new(0));
+        engine.enter();
+        // Safety: as above
+        unsafe { engine.retire(retired(&drops)) };
+        // A reader that never exited, e.g. a leaked guard
+        drop(engine);
+        assert_eq!(drops.load(SeqCst), 1, "leaked on drop");
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn grace_periods_end_with_the_outermost_reader() {
+        use std::sync::atomic::AtomicBool;
+        use std::task::{Wake, Waker};
+
+        /// Records that it was woken.
+        struct Flag(AtomicBool);
+
+        impl Wake for Flag {
+            fn wake(self: Arc<Self>) {
+                self.0.store(true, SeqCst);
+            }
+        }
+
+        let engine = SingleThread::default();
+        let flag = Arc::new(Flag(AtomicBool::new(false)));
+        let waker = Waker::from(Arc::clone(&flag));
+        let period = engine.start_grace_period();
+        assert!(engine.poll_grace_period(period, &waker), "pending without a reader");
+        let lock = ReadLock::new(&engine);
+        let period = engine.start_grace_period();
+        assert!(!engine.poll_grace_period(period, &waker), "ended under an active reader");
+        drop(lock);
+        assert!(flag.0.load(SeqCst), "the reader's exit woke nothing");
+        assert!(engine.poll_grace_period(period, &waker), "pending after the reader exited");
+    }
+}
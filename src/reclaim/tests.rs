@@ -0,0 +1,261 @@
+//! The contract of `Reclaim`, checked against whichever engine the cargo features selected: run
+//! `cargo test` once per backend, e.g. `cargo test --no-default-features --features reclaim-epoch`.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use super::{Engine, Reclaim, Reclaimer, ReadLock, Retired};
+
+/// The engine the features select, as `Reclaimer` should name it.
+#[cfg(feature = "fallback-lock")]
+const SELECTED: &str = "Locked";
+#[cfg(all(feature = "reclaim-counted", not(feature = "fallback-lock")))]
+const SELECTED: &str = "Counted";
+#[cfg(all(feature = "reclaim-retire-list", not(feature = "fallback-lock")))]
+const SELECTED: &str = "RetireList";
+#[cfg(all(feature = "reclaim-epoch", not(feature = "fallback-lock")))]
+const SELECTED: &str = "Epoch";
+
+/// Long enough for a grace period that wrongly ignores a reader to have ended.
+const SETTLE: Duration = Duration::from_millis(50);
+
+/// Counts its drops in the counter it shares with the test.
+struct Tracked(Arc<AtomicUsize>);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, SeqCst);
+    }
+}
+
+fn retired(drops: &Arc<AtomicUsize>) -> Retired {
+    Retired::new(Box::into_raw(Box::new(Tracked(Arc::clone(drops)))))
+}
+
+/// Every flavour of engine a value may get, each checked on its own.
+fn engines() -> Vec<(&'static str, Engine)> {
+    #[cfg_attr(not(feature = "membarrier"), allow(unused_mut))]
+    let mut engines = vec![
+        ("own", Engine::Own(Reclaimer::default())),
+        ("biased", Engine::biased()),
+        ("shared", Engine::Shared(Arc::default())),
+    ];
+    #[cfg(feature = "membarrier")]
+    engines.push(("membarrier", Engine::membarrier()));
+    engines
+}
+
+/// Runs `f` with a reader of `engine` active on another thread, which exits once `f` returns.
+fn with_reader(engine: &Engine, f: impl FnOnce()) {
+    let (entered, wait_entered) = mpsc::channel();
+    let (exit, wait_exit) = mpsc::channel::<()>();
+    thread::scope(|s| {
+        s.spawn(move || {
+            let _lock = ReadLock::new(engine);
+            entered.send(()).unwrap();
+            let _ = wait_exit.recv();
+        });
+        wait_entered.recv().unwrap();
+        f();
+        drop(exit);
+    });
+}
+
+#[test]
+fn features_select_the_engine() {
+    let name = std::any::type_name::<Reclaimer>();
+    assert!(name.ends_with(SELECTED), "{name} isn't the {SELECTED} engine");
+}
+
+#[test]
+fn retired_allocations_outlive_readers() {
+    for (flavour, engine) in engines() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let engine = &engine;
+        thread::scope(|s| {
+            with_reader(engine, || {
+                // Waits for the reader in-line with `reclaim-counted`, hence the thread of its own
+                s.spawn(|| {
+                    // Safety: never published, so no reader entering later can observe it
+                    unsafe { engine.retire(retired(&drops)) }
+                });
+                thread::sleep(SETTLE);
+                assert_eq!(drops.load(SeqCst), 0, "{flavour}: freed under an active reader");
+            });
+        });
+        engine.barrier();
+        assert_eq!(drops.load(SeqCst), 1, "{flavour}: never freed");
+        assert_eq!(engine.pending(), 0, "{flavour}: still pending after a barrier");
+    }
+}
+
+#[test]
+fn synchronize_waits_for_active_readers() {
+    for (flavour, engine) in engines() {
+        let done = AtomicBool::new(false);
+        let engine = &engine;
+        thread::scope(|s| {
+            with_reader(engine, || {
+                s.spawn(|| {
+                    engine.synchronize();
+                    done.store(true, SeqCst);
+                });
+                thread::sleep(SETTLE);
+                assert!(!done.load(SeqCst), "{flavour}: synchronize ignored an active reader");
+            });
+        });
+        assert!(done.load(SeqCst), "{flavour}: synchronize outlived the scope");
+    }
+}
+
+#[test]
+fn readers_entering_later_are_not_waited_for() {
+    for (_, engine) in engines() {
+        drop(ReadLock::new(&engine));
+        // Would hang if the reader that exited were still waited for
+        engine.synchronize();
+        let drops = Arc::new(AtomicUsize::new(0));
+        // Safety: never published
+        unsafe { engine.retire(retired(&drops)) };
+        engine.barrier();
+        assert_eq!(drops.load(SeqCst), 1);
+    }
+}
+
+#[test]
+fn barrier_frees_every_batch() {
+    for (flavour, engine) in engines() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        for _ in 0..10 {
+            // Safety: never published
+            unsafe { engine.retire_batch((0..10).map(|_| retired(&drops)).collect(), None) };
+        }
+        engine.barrier();
+        assert_eq!(drops.load(SeqCst), 100, "{flavour}: the barrier left allocations behind");
+        assert_eq!(engine.pending(), 0);
+    }
+}
+
+/// Runs `f` on a thread of its own, failing the test if it hasn't returned within `LIMIT`.
+fn finishes(flavour: &str, f: impl FnOnce() + Send + 'static) {
+    const LIMIT: Duration = Duration::from_secs(20);
+    let (done, wait_done) = mpsc::channel();
+    thread::spawn(move || {
+        f();
+        done.send(()).unwrap();
+    });
+    assert!(wait_done.recv_timeout(LIMIT).is_ok(), "{flavour}: the barrier deadlocked");
+}
+
+#[test]
+fn barriers_return_with_nothing_retired() {
+    for (flavour, engine) in engines() {
+        finishes(flavour, move || {
+            engine.barrier();
+            engine.barrier();
+            assert_eq!(engine.pending(), 0);
+        });
+    }
+}
+
+#[test]
+fn concurrent_barriers_free_everything_retired_before_them() {
+    for (flavour, engine) in engines() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let check = Arc::clone(&drops);
+        finishes(flavour, move || {
+            let stop = AtomicBool::new(false);
+            thread::scope(|s| {
+                // Leaves gaps between its critical sections, which the retire list's barrier needs
+                s.spawn(|| {
+                    while !stop.load(SeqCst) {
+                        drop(ReadLock::new(&engine));
+                        thread::yield_now();
+                    }
+                });
+                for _ in 0..3 {
+                    s.spawn(|| {
+                        while !stop.load(SeqCst) {
+                            engine.barrier();
+                            thread::yield_now();
+                        }
+                    });
+                }
+                let retirers: Vec<_> = (0..2)
+                    .map(|_| {
+                        s.spawn(|| {
+                            let mine = Arc::new(AtomicUsize::new(0));
+                            for round in 1..=50 {
+                                // Safety: never published
+                                unsafe { engine.retire(retired(&mine)) };
+                                if round % 10 == 0 {
+                                    engine.barrier();
+                                    assert_eq!(mine.load(SeqCst), round, "returned before freeing what came before");
+                                }
+                            }
+                            mine.load(SeqCst)
+                        })
+                    })
+                    .collect();
+                for retirer in retirers {
+                    drops.fetch_add(retirer.join().unwrap(), SeqCst);
+                }
+                stop.store(true, SeqCst);
+            });
+            engine.barrier();
+        });
+        assert_eq!(check.load(SeqCst), 100, "{flavour}: the barriers left allocations behind");
+    }
+}
+
+#[test]
+fn retiring_from_many_threads_frees_everything() {
+    for (flavour, engine) in engines() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let stop = AtomicBool::new(false);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    while !stop.load(SeqCst) {
+                        let _lock = ReadLock::new(&engine);
+                        thread::yield_now();
+                    }
+                });
+            }
+            let retirers: Vec<_> = (0..4)
+                .map(|_| {
+                    s.spawn(|| {
+                        for _ in 0..250 {
+                            // Safety: never published
+                            unsafe { engine.retire(retired(&drops)) };
+                        }
+                    })
+                })
+                .collect();
+            for retirer in retirers {
+                retirer.join().unwrap();
+            }
+            stop.store(true, SeqCst);
+        });
+        engine.barrier();
+        assert_eq!(drops.load(SeqCst), 1000, "{flavour}: allocations leaked");
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn grace_periods_can_be_polled() {
+    use std::task::Waker;
+
+    for (flavour, engine) in engines() {
+        let engine = &engine;
+        with_reader(engine, || {
+            let ticket = engine.start_grace_period();
+            assert!(!engine.poll_grace_period(ticket, Waker::noop()), "{flavour}: ignored a reader");
+        });
+        let ticket = engine.start_grace_period();
+        assert!(engine.poll_grace_period(ticket, Waker::noop()), "{flavour}: no readers left");
+    }
+}
@@ -0,0 +1,54 @@
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Wake, Waker};
+use std::thread::{self, Thread};
+
+/// Tasks waiting for readers to exit, see `Reclaim::poll_grace_period`, or threads, see
+/// `unpark_current`. Readers only take the lock when somebody waits.
+#[derive(Default)]
+pub(crate) struct Wakers {
+    /// The length of `wakers`
+    waiting: AtomicUsize,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl Wakers {
+    /// Wakes `waker` with the next call to `wake`. The caller must check what it waits for again
+    /// afterwards: a reader exiting before it registered won't wake it.
+    pub(crate) fn register(&self, waker: &Waker) {
+        let mut wakers = self.wakers.lock().unwrap_or_else(PoisonError::into_inner);
+        if !wakers.iter().any(|registered| registered.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+        // Either the reader's `wake` sees this, or the caller's check sees that reader gone
+        self.waiting.store(wakers.len(), SeqCst);
+    }
+
+    /// Wakes every registered task, called by readers whose exit may have ended a grace period.
+    pub(crate) fn wake(&self) {
+        if self.waiting.load(SeqCst) == 0 {
+            return;
+        }
+        let wakers = {
+            let mut wakers = self.wakers.lock().unwrap_or_else(PoisonError::into_inner);
+            self.waiting.store(0, SeqCst);
+            mem::take(&mut *wakers)
+        };
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+/// A waker unparking the current thread, for threads sleeping until readers exit.
+#[cfg_attr(not(all(feature = "membarrier", target_os = "linux")), allow(dead_code))]
+pub(crate) fn unpark_current() -> Waker {
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    Waker::from(Arc::new(Unpark(thread::current())))
+}
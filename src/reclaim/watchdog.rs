@@ -0,0 +1,240 @@
+//! Reporting grace-period waits that take suspiciously long, with the `diagnostics` feature.
+//! Without it the watchdog only waits, through the engine's `WaitStrategy`.
+
+#[cfg(all(feature = "diagnostics", debug_assertions))]
+use std::collections::HashMap;
+#[cfg(feature = "diagnostics")]
+use std::panic::{self, AssertUnwindSafe, Location};
+#[cfg(feature = "diagnostics")]
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+#[cfg(feature = "diagnostics")]
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+#[cfg(feature = "diagnostics")]
+use std::time::Duration;
+
+use crate::wait::Waiter;
+
+/// Describes a grace-period wait that exceeded the threshold set with `Rcu::set_stall_threshold`.
+#[cfg(feature = "diagnostics")]
+#[derive(Clone, Debug)]
+pub struct StallReport {
+    /// Readers active when the threshold was exceeded
+    pub readers: usize,
+    /// How long the writer had been waiting for them at that point
+    pub waited: Duration,
+    /// Where the read-side critical sections active at that point were entered, only tracked in
+    /// debug builds; empty otherwise
+    pub reader_locations: Vec<&'static Location<'static>>,
+}
+
+#[cfg(feature = "diagnostics")]
+type StallCallback = Arc<dyn Fn(&StallReport) + Send + Sync>;
+
+/// Watches the waits of one engine.
+#[cfg(feature = "diagnostics")]
+#[derive(Default)]
+pub(crate) struct Watchdog {
+    /// In nanoseconds, 0 while disarmed
+    threshold: AtomicU64,
+    /// The most recent report
+    report: Mutex<Option<StallReport>>,
+    callback: Mutex<Option<StallCallback>>,
+    /// Where each active reader entered, by tracking id
+    #[cfg(debug_assertions)]
+    readers: Mutex<HashMap<u64, &'static Location<'static>>>,
+    #[cfg(debug_assertions)]
+    next_reader: AtomicU64,
+    /// How the engine's waits wait
+    waiter: Waiter,
+}
+
+#[cfg(feature = "diagnostics")]
+impl Watchdog {
+    /// Waits while `waiting` returns true, reporting once if that lasts longer than the threshold.
+    /// `readers` counts the readers being waited for. Gives up at `deadline`, returning false.
+    pub(crate) fn wait_while(
+        &self,
+        mut waiting: impl FnMut() -> bool,
+        readers: impl Fn() -> usize,
+        deadline: Option<Instant>,
+    ) -> bool {
+        let threshold = self.threshold.load(Relaxed);
+        if threshold == 0 {
+            return self.waiter.wait_while(waiting, deadline);
+        }
+        let start = Instant::now();
+        let mut reported = false;
+        let mut attempt = 0u32;
+        while waiting() {
+            self.waiter.wait(attempt);
+            attempt = attempt.wrapping_add(1);
+            // Keep reading the clock off the spin path
+            if attempt.is_multiple_of(1024) {
+                let now = Instant::now();
+                if !reported && now.duration_since(start).as_nanos() >= threshold as u128 {
+                    reported = true;
+                    self.report(readers(), now.duration_since(start));
+                }
+                if deadline.is_some_and(|deadline| now >= deadline) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn report(&self, readers: usize, waited: Duration) {
+        let report = StallReport {
+            readers,
+            waited,
+            reader_locations: self.reader_locations(),
+        };
+        *self.report.lock().unwrap_or_else(PoisonError::into_inner) = Some(report.clone());
+        let callback = self.callback.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        if let Some(callback) = callback {
+            // Purely observational, a panicking callback must not disturb the writer
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| callback(&report)));
+        }
+    }
+
+    /// Arms the watchdog, or disarms it with None.
+    pub(crate) fn set_threshold(&self, threshold: Option<Duration>) {
+        let nanos = threshold.map_or(0, |threshold| (threshold.as_nanos() as u64).max(1));
+        self.threshold.store(nanos, Relaxed);
+    }
+
+    pub(crate) fn set_callback(&self, callback: StallCallback) {
+        *self.callback.lock().unwrap_or_else(PoisonError::into_inner) = Some(callback);
+    }
+
+    pub(crate) fn last_report(&self) -> Option<StallReport> {
+        self.report.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Where the active readers entered, only tracked in debug builds; empty otherwise.
+    pub(crate) fn reader_locations(&self) -> Vec<&'static Location<'static>> {
+        #[cfg(debug_assertions)]
+        return self.readers.lock().unwrap_or_else(PoisonError::into_inner).values().copied().collect();
+        #[cfg(not(debug_assertions))]
+        Vec::new()
+    }
+
+    /// Records where a reader entered, returning the id to hand to `exit`.
+    #[cfg(debug_assertions)]
+    pub(crate) fn enter(&self, location: &'static Location<'static>) -> u64 {
+        let id = self.next_reader.fetch_add(1, Relaxed);
+        self.readers.lock().unwrap_or_else(PoisonError::into_inner).insert(id, location);
+        id
+    }
+
+    #[cfg(debug_assertions)]
+    pub(crate) fn exit(&self, id: u64) {
+        self.readers.lock().unwrap_or_else(PoisonError::into_inner).remove(&id);
+    }
+}
+
+#[cfg(not(feature = "diagnostics"))]
+#[derive(Default)]
+pub(crate) struct Watchdog {
+    /// How the engine's waits wait
+    waiter: Waiter,
+}
+
+#[cfg(not(feature = "diagnostics"))]
+impl Watchdog {
+    /// Waits while `waiting` returns true. Gives up at `deadline`, returning false.
+    pub(crate) fn wait_while(
+        &self,
+        waiting: impl FnMut() -> bool,
+        _readers: impl Fn() -> usize,
+        deadline: Option<Instant>,
+    ) -> bool {
+        self.waiter.wait_while(waiting, deadline)
+    }
+}
+
+impl Watchdog {
+    /// How the engine waits, for its waits outside of `wait_while` and to notify waiters.
+    pub(crate) fn waiter(&self) -> &Waiter {
+        &self.waiter
+    }
+
+    pub(crate) fn set_waiter(&mut self, waiter: Waiter) {
+        self.waiter = waiter;
+    }
+}
+
+#[cfg(all(test, feature = "diagnostics"))]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::StallReport;
+    use crate::reclaim::{Reclaim, Reclaimer, ReadLock};
+
+    const THRESHOLD: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn a_parked_reader_gets_the_waiting_writer_reported() {
+        let engine = Reclaimer::default();
+        let watchdog = engine.watchdog();
+        watchdog.set_threshold(Some(THRESHOLD));
+        let (reported, wait_reported) = mpsc::channel::<StallReport>();
+        watchdog.set_callback(Arc::new(move |report| reported.send(report.clone()).unwrap()));
+        let (entered, wait_entered) = mpsc::channel();
+        let (exit, wait_exit) = mpsc::channel::<()>();
+        let synchronized = AtomicBool::new(false);
+        let engine = &engine;
+        let report = thread::scope(|s| {
+            s.spawn(move || {
+                let _lock = ReadLock::new(engine);
+                entered.send(()).unwrap();
+                // Parked until the writer waiting for it got reported
+                let _ = wait_exit.recv();
+            });
+            wait_entered.recv().unwrap();
+            s.spawn(|| {
+                engine.synchronize();
+                synchronized.store(true, SeqCst);
+            });
+            let report = wait_reported.recv_timeout(Duration::from_secs(10)).expect("the stall was never reported");
+            assert!(!synchronized.load(SeqCst), "the writer went on under an active reader");
+            drop(exit);
+            report
+        });
+        assert!(synchronized.load(SeqCst), "the writer never went on");
+        assert_eq!(report.readers, 1);
+        assert!(report.waited >= THRESHOLD, "reported after {:?}", report.waited);
+        if cfg!(debug_assertions) {
+            assert_eq!(report.reader_locations.len(), 1, "{:?}", report.reader_locations);
+            assert_eq!(report.reader_locations[0].file(), file!());
+        }
+        let last = watchdog.last_report().expect("the report wasn't kept");
+        assert_eq!(last.readers, 1);
+    }
+
+    #[test]
+    fn waits_are_only_reported_once_armed() {
+        let engine = Reclaimer::default();
+        let watchdog = engine.watchdog();
+        watchdog.set_callback(Arc::new(|_| panic!("reported while disarmed")));
+        let (exit, wait_exit) = mpsc::channel::<()>();
+        let engine = &engine;
+        thread::scope(|s| {
+            let (entered, wait_entered) = mpsc::channel();
+            s.spawn(move || {
+                let _lock = ReadLock::new(engine);
+                entered.send(()).unwrap();
+                let _ = wait_exit.recv();
+            });
+            wait_entered.recv().unwrap();
+            s.spawn(|| engine.synchronize());
+            thread::sleep(THRESHOLD * 5);
+            drop(exit);
+        });
+        assert!(watchdog.last_report().is_none(), "reported while disarmed");
+    }
+}
@@ -0,0 +1,315 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::sync::{Arc, Condvar, Mutex, PoisonError, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::{PublishError, Rcu};
+
+impl<T: Clone + Send + Sync + 'static> Rcu<T> {
+    /// Spawns a thread calling `f` every `interval`, publishing what it returns like [`Rcu::set`]
+    /// does, unless it returns None. The first call happens right away.
+    ///
+    /// Calls are scheduled at fixed points in time, so a slow call delays the next one rather than
+    /// every later one, and missed calls are skipped rather than made up for. A panic in `f` is
+    /// caught and counted, see [`RefresherHandle::panics`], and the refresher carries on. Values an
+    /// invariant rejects aren't published and are dropped. The thread exits once the handle is
+    /// dropped or stopped, once the `Rcu` is frozen, or once every other `Arc` of it was dropped.
+    pub fn refresh_every(
+        self: &Arc<Self>,
+        interval: Duration,
+        mut f: impl FnMut() -> Option<T> + Send + 'static,
+    ) -> RefresherHandle {
+        RefresherHandle::spawn(Arc::downgrade(self), interval, move |rcu| f().map(|value| rcu.set(value)))
+    }
+    /// Like [`Rcu::refresh_every`], also skipping values equal to the current one, which then
+    /// never bump the version nor wake subscribers.
+    pub fn refresh_changed_every(
+        self: &Arc<Self>,
+        interval: Duration,
+        mut f: impl FnMut() -> Option<T> + Send + 'static,
+    ) -> RefresherHandle
+    where
+        T: PartialEq,
+    {
+        RefresherHandle::spawn(Arc::downgrade(self), interval, move |rcu| {
+            let value = f()?;
+            let changed = rcu.read_with(|current| *current != value);
+            changed.then(|| rcu.set(value))
+        })
+    }
+}
+
+/// What the refresher thread and its handle share.
+#[derive(Default)]
+struct Shared {
+    stopped: Mutex<bool>,
+    wake: Condvar,
+    /// Values published
+    refreshes: AtomicU64,
+    /// Calls that panicked
+    panics: AtomicU64,
+}
+
+/// Controls a refresher started with [`Rcu::refresh_every`]. Dropping it stops the refresher, like
+/// [`RefresherHandle::stop`] does.
+pub struct RefresherHandle {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RefresherHandle {
+    /// Runs `refresh` on the `Rcu` every `interval`, on a thread of its own. `refresh` returns
+    /// the outcome of its publish, None if it didn't publish.
+    fn spawn<T: Clone + Send + Sync + 'static>(
+        rcu: Weak<Rcu<T>>,
+        interval: Duration,
+        mut refresh: impl FnMut(&Rcu<T>) -> Option<Result<(), PublishError<T>>> + Send + 'static,
+    ) -> Self {
+        let shared = Arc::new(Shared::default());
+        let thread = thread::Builder::new()
+            .name("rcu-refresher".into())
+            .spawn({
+                let shared = Arc::clone(&shared);
+                move || {
+                    let mut next = Instant::now();
+                    loop {
+                        let Some(rcu) = rcu.upgrade() else {
+                            return;
+                        };
+                        match panic::catch_unwind(AssertUnwindSafe(|| refresh(&rcu))) {
+                            Ok(Some(Ok(()))) => {
+                                shared.refreshes.fetch_add(1, Relaxed);
+                            }
+                            Ok(Some(Err(PublishError::Frozen { .. }))) => return,
+                            Ok(Some(Err(PublishError::Rejected { .. } | PublishError::Backpressure { .. })) | None) => {}
+                            Err(_) => {
+                                shared.panics.fetch_add(1, Relaxed);
+                            }
+                        }
+                        // Don't keep the `Rcu` alive while sleeping
+                        drop(rcu);
+                        next += interval;
+                        let now = Instant::now();
+                        if next < now {
+                            next = now;
+                        }
+                        if shared.sleep_until(next) {
+                            return;
+                        }
+                    }
+                }
+            })
+            .expect("failed to spawn the refresher thread");
+        Self {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    /// Stops the refresher, waiting for a call in progress to return.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    /// Number of values the refresher published so far.
+    pub fn refreshes(&self) -> u64 {
+        self.shared.refreshes.load(Relaxed)
+    }
+
+    /// Number of calls of the refresher's closure that panicked so far.
+    pub fn panics(&self) -> u64 {
+        self.shared.panics.load(Relaxed)
+    }
+
+    /// Whether the refresher thread exited, stopped or because its `Rcu` is gone or frozen.
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    fn shutdown(&mut self) {
+        *self.shared.stopped.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        self.shared.wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            // The closure's panics are caught, only a stop from the refresher itself could fail
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+impl Shared {
+    /// Sleeps until `deadline`, returning early with true if the refresher was stopped.
+    fn sleep_until(&self, deadline: Instant) -> bool {
+        let mut stopped = self.stopped.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            if *stopped {
+                return true;
+            }
+            let Some(left) = deadline.checked_duration_since(Instant::now()).filter(|left| !left.is_zero()) else {
+                return false;
+            };
+            stopped = self.wake.wait_timeout(stopped, left).unwrap_or_else(PoisonError::into_inner).0;
+        }
+    }
+}
+
+impl Drop for RefresherHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    use std::sync::mpsc;
+
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_millis(10);
+
+    /// Long enough that a refresher only wakes up early if stopped.
+    const NEVER: Duration = Duration::from_secs(3600);
+
+    /// Counts the calls of a refresher's closure in `calls`, reporting each on `started` as it starts.
+    fn counter(calls: &Arc<AtomicUsize>, started: mpsc::Sender<usize>) -> impl FnMut() -> usize + Send + 'static {
+        let calls = Arc::clone(calls);
+        move || {
+            let call = calls.fetch_add(1, SeqCst);
+            let _ = started.send(call);
+            call
+        }
+    }
+
+    #[test]
+    fn calls_are_scheduled_every_interval_from_the_first() {
+        let rcu = Arc::new(Rcu::new(0));
+        let (called, wait_called) = mpsc::channel();
+        let start = Instant::now();
+        let refresher = rcu.refresh_every(INTERVAL, move || {
+            let _ = called.send(Instant::now());
+            Some(1)
+        });
+        let times: Vec<_> = (0..5).map(|_| wait_called.recv().unwrap()).collect();
+        refresher.stop();
+        assert!(times[0] - start < INTERVAL * 100, "the first call should happen right away");
+        for (call, time) in times.iter().enumerate() {
+            assert!(*time - times[0] >= INTERVAL * call as u32, "call {call} came early");
+        }
+        assert_eq!(rcu.read(), 1);
+    }
+
+    #[test]
+    fn nones_are_not_published() {
+        let rcu = Arc::new(Rcu::new(0));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (started, wait_started) = mpsc::channel();
+        let mut call = counter(&calls, started);
+        let refresher = rcu.refresh_every(INTERVAL, move || {
+            let call = call();
+            call.is_multiple_of(2).then_some(call)
+        });
+        for _ in 0..6 {
+            wait_started.recv().unwrap();
+        }
+        refresher.stop();
+        let calls = calls.load(SeqCst);
+        assert_eq!(rcu.version(), calls.div_ceil(2) as u64, "{calls} calls");
+        assert_eq!(rcu.read(), (calls - 1) / 2 * 2);
+    }
+
+    #[test]
+    fn unchanged_values_are_not_published() {
+        let rcu = Arc::new(Rcu::new(0));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (started, wait_started) = mpsc::channel();
+        let mut call = counter(&calls, started);
+        // 0, 0, 0, 1, 1, 1, 2...: only every third call brings something new, the first not even
+        let refresher = rcu.refresh_changed_every(INTERVAL, move || Some(call() / 3));
+        for _ in 0..9 {
+            wait_started.recv().unwrap();
+        }
+        refresher.stop();
+        let calls = calls.load(SeqCst);
+        assert_eq!(rcu.version(), ((calls - 1) / 3) as u64, "{calls} calls");
+        assert_eq!(rcu.read(), (calls - 1) / 3);
+    }
+
+    #[test]
+    fn panics_are_counted_and_the_refresher_carries_on() {
+        let rcu = Arc::new(Rcu::new(0));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (started, wait_started) = mpsc::channel();
+        let mut call = counter(&calls, started);
+        let refresher = rcu.refresh_every(INTERVAL, move || {
+            let call = call();
+            assert!(call.is_multiple_of(2), "odd call");
+            Some(call)
+        });
+        for _ in 0..6 {
+            wait_started.recv().unwrap();
+        }
+        // Counted once they return, so only match the calls when none is in progress
+        let (panics, refreshes, made) = loop {
+            let (panics, refreshes) = (refresher.panics(), refresher.refreshes());
+            let made = calls.load(SeqCst) as u64;
+            if panics + refreshes == made {
+                break (panics, refreshes, made);
+            }
+            thread::yield_now();
+        };
+        assert_eq!((panics, refreshes), (made / 2, made.div_ceil(2)));
+        assert!(!refresher.is_finished());
+        refresher.stop();
+        assert_eq!(rcu.version(), (calls.load(SeqCst) as u64).div_ceil(2));
+    }
+
+    #[test]
+    fn stopping_wakes_the_refresher_and_ends_its_calls() {
+        let rcu = Arc::new(Rcu::new(0));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (started, wait_started) = mpsc::channel();
+        let mut call = counter(&calls, started);
+        let refresher = rcu.refresh_every(NEVER, move || Some(call()));
+        wait_started.recv().unwrap();
+        let stopping = Instant::now();
+        refresher.stop();
+        assert!(stopping.elapsed() < NEVER / 2, "stop waited for the next call");
+        thread::sleep(INTERVAL * 5);
+        assert_eq!(calls.load(SeqCst), 1, "called after being stopped");
+    }
+
+    #[test]
+    fn dropping_the_handle_stops_the_refresher() {
+        let rcu = Arc::new(Rcu::new(0));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (started, wait_started) = mpsc::channel();
+        let mut call = counter(&calls, started);
+        let refresher = rcu.refresh_every(INTERVAL, move || Some(call()));
+        wait_started.recv().unwrap();
+        drop(refresher);
+        let after_drop = calls.load(SeqCst);
+        thread::sleep(INTERVAL * 5);
+        assert_eq!(calls.load(SeqCst), after_drop, "called after the handle was dropped");
+    }
+
+    #[test]
+    fn refreshers_exit_with_their_rcu() {
+        let rcu = Arc::new(Rcu::new(0));
+        let refresher = rcu.refresh_every(INTERVAL, || Some(1));
+        drop(rcu);
+        while !refresher.is_finished() {
+            thread::sleep(INTERVAL);
+        }
+
+        let rcu = Arc::new(Rcu::new(0));
+        let refresher = rcu.refresh_every(INTERVAL, || Some(1));
+        rcu.freeze();
+        while !refresher.is_finished() {
+            thread::sleep(INTERVAL);
+        }
+        assert!(rcu.version() <= 1);
+    }
+}
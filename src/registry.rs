@@ -0,0 +1,232 @@
+use std::any::{self, Any};
+use std::collections::BTreeMap;
+#[cfg(feature = "serde")]
+use std::io;
+use std::ops::Deref;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Instant;
+
+use crate::error::LookupError;
+use crate::Rcu;
+
+/// What an `Rcu` of any value type offers without knowing the type, for code managing many
+/// differently typed values alike, see [`RcuRegistry`]. Implemented for every `Rcu<T>` whose
+/// values can be shared across threads, and with the `serde` feature serialized.
+pub trait DynRcu: Any + Send + Sync {
+    /// See [`Rcu::version`].
+    fn version(&self) -> u64;
+    /// See [`Rcu::last_updated`].
+    fn last_updated(&self) -> Instant;
+    /// The name of the value type, as `std::any::type_name` gives it.
+    fn type_name(&self) -> &'static str;
+    /// Writes the current value to `out` as JSON.
+    #[cfg(feature = "serde")]
+    fn serialize_snapshot(&self, out: &mut dyn io::Write) -> io::Result<()>;
+}
+
+#[cfg(not(feature = "serde"))]
+impl<T: Clone + Send + Sync + 'static> DynRcu for Rcu<T> {
+    fn version(&self) -> u64 {
+        Rcu::version(self)
+    }
+    fn last_updated(&self) -> Instant {
+        Rcu::last_updated(self)
+    }
+    fn type_name(&self) -> &'static str {
+        any::type_name::<T>()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Clone + Send + Sync + serde::Serialize + 'static> DynRcu for Rcu<T> {
+    fn version(&self) -> u64 {
+        Rcu::version(self)
+    }
+    fn last_updated(&self) -> Instant {
+        Rcu::last_updated(self)
+    }
+    fn type_name(&self) -> &'static str {
+        any::type_name::<T>()
+    }
+    fn serialize_snapshot(&self, out: &mut dyn io::Write) -> io::Result<()> {
+        self.read_with(|value| serde_json::to_writer(out, value)).map_err(io::Error::from)
+    }
+}
+
+/// `Rcu`s of different value types by name, e.g. every part of a service's configuration, to be
+/// listed and inspected through [`DynRcu`] or looked up by their concrete type.
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use rcu_rust::{Rcu, RcuRegistry};
+/// let registry = RcuRegistry::new();
+/// registry.register("routes", Arc::new(Rcu::new(vec!["/".to_string()])));
+/// registry.register("max_connections", Arc::new(Rcu::new(1024u32)));
+/// for (name, rcu) in registry.iter() {
+///     println!("{name}: {} v{}", rcu.type_name(), rcu.version());
+/// }
+/// let routes = registry.get::<Vec<String>>("routes").unwrap();
+/// ```
+#[derive(Default)]
+pub struct RcuRegistry {
+    entries: RwLock<BTreeMap<String, Arc<dyn DynRcu>>>,
+}
+
+impl RcuRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Registers `rcu` as `name`, returning the one registered as `name` before, if any.
+    pub fn register(&self, name: impl Into<String>, rcu: Arc<impl DynRcu>) -> Option<Arc<dyn DynRcu>> {
+        self.entries.write().unwrap_or_else(PoisonError::into_inner).insert(name.into(), rcu)
+    }
+    /// Removes the `Rcu` registered as `name`, returning it.
+    pub fn remove(&self, name: &str) -> Option<Arc<dyn DynRcu>> {
+        self.entries.write().unwrap_or_else(PoisonError::into_inner).remove(name)
+    }
+    /// The `Rcu` registered as `name`, whatever its value type.
+    pub fn get_dyn(&self, name: &str) -> Option<Arc<dyn DynRcu>> {
+        self.entries.read().unwrap_or_else(PoisonError::into_inner).get(name).cloned()
+    }
+    /// The `Rcu<T>` registered as `name`, None if there is none or it holds another type, see
+    /// [`RcuRegistry::try_get`].
+    pub fn get<T: Clone + Send + Sync + 'static>(&self, name: &str) -> Option<TypedHandle<T>> {
+        self.try_get(name).ok()
+    }
+    /// Like [`RcuRegistry::get`], telling a missing name apart from a value of another type.
+    pub fn try_get<T: Clone + Send + Sync + 'static>(&self, name: &str) -> Result<TypedHandle<T>, LookupError> {
+        let rcu = self.get_dyn(name).ok_or_else(|| LookupError::Missing { name: name.into() })?;
+        let found = rcu.type_name();
+        let rcu: Arc<dyn Any + Send + Sync> = rcu;
+        rcu.downcast::<Rcu<T>>()
+            .map(|rcu| TypedHandle { rcu })
+            .map_err(|_| LookupError::WrongType {
+                name: name.into(),
+                expected: any::type_name::<T>(),
+                found,
+            })
+    }
+    /// Every registered `Rcu` with its name, in name order. Iterates over a snapshot, registering
+    /// meanwhile doesn't affect it.
+    pub fn iter(&self) -> impl Iterator<Item = (String, Arc<dyn DynRcu>)> {
+        let entries = self.entries.read().unwrap_or_else(PoisonError::into_inner);
+        let snapshot: Vec<_> = entries.iter().map(|(name, rcu)| (name.clone(), Arc::clone(rcu))).collect();
+        snapshot.into_iter()
+    }
+    /// The number of registered `Rcu`s.
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap_or_else(PoisonError::into_inner).len()
+    }
+    /// Whether nothing is registered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// An `Rcu<T>` found in an [`RcuRegistry`], dereferencing to it. Keeps it alive even once
+/// removed from the registry.
+pub struct TypedHandle<T: Clone> {
+    rcu: Arc<Rcu<T>>,
+}
+
+impl<T: Clone> TypedHandle<T> {
+    /// The shared `Rcu` itself.
+    pub fn into_arc(self) -> Arc<Rcu<T>> {
+        self.rcu
+    }
+}
+
+impl<T: Clone> Clone for TypedHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            rcu: Arc::clone(&self.rcu),
+        }
+    }
+}
+
+impl<T: Clone> Deref for TypedHandle<T> {
+    type Target = Rcu<T>;
+
+    fn deref(&self) -> &Rcu<T> {
+        &self.rcu
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A registry of a `u32`, a `Vec<String>` and a `BTreeMap<String, u64>`.
+    fn registry() -> RcuRegistry {
+        let registry = RcuRegistry::new();
+        registry.register("max_connections", Arc::new(Rcu::new(1024_u32)));
+        registry.register("routes", Arc::new(Rcu::new(vec!["/".to_string()])));
+        registry.register("quotas", Arc::new(Rcu::new(BTreeMap::from([("free".to_string(), 10_u64)]))));
+        registry
+    }
+
+    #[test]
+    fn rcus_of_several_types_are_listed_alike() {
+        let registry = registry();
+        assert_eq!(registry.len(), 3);
+        let listed: Vec<_> = registry.iter().map(|(name, rcu)| (name, rcu.type_name())).collect();
+        let expected = [
+            ("max_connections", any::type_name::<u32>()),
+            ("quotas", any::type_name::<BTreeMap<String, u64>>()),
+            ("routes", any::type_name::<Vec<String>>()),
+        ];
+        assert_eq!(listed, expected.map(|(name, type_name)| (name.to_string(), type_name)));
+        // Registering a name again replaces the `Rcu`, whatever its type
+        let replaced = registry.register("max_connections", Arc::new(Rcu::new(1024_u64))).unwrap();
+        assert_eq!(replaced.type_name(), any::type_name::<u32>());
+        assert_eq!(registry.get::<u64>("max_connections").unwrap().read(), 1024);
+        assert_eq!(registry.remove("routes").unwrap().type_name(), any::type_name::<Vec<String>>());
+        assert_eq!(registry.len(), 2);
+    }
+
+    #[test]
+    fn publishes_through_a_typed_handle_show_through_the_dyn_interface() {
+        let registry = registry();
+        let routes = registry.get::<Vec<String>>("routes").unwrap();
+        let dyn_routes = registry.get_dyn("routes").unwrap();
+        let version = dyn_routes.version();
+        let before = dyn_routes.last_updated();
+        routes.set(vec!["/".to_string(), "/health".to_string()]).unwrap();
+        assert_eq!(dyn_routes.version(), version + 1);
+        assert!(dyn_routes.last_updated() >= before);
+        // Back from the dyn interface to the very same `Rcu`
+        let rcu: Arc<dyn Any + Send + Sync> = dyn_routes;
+        let rcu = rcu.downcast::<Rcu<Vec<String>>>().ok().unwrap();
+        assert!(Arc::ptr_eq(&rcu, &routes.clone().into_arc()));
+        assert_eq!(rcu.read(), ["/", "/health"]);
+        #[cfg(feature = "serde")]
+        for (name, json) in [
+            ("max_connections", "1024"),
+            ("routes", r#"["/","/health"]"#),
+            ("quotas", r#"{"free":10}"#),
+        ] {
+            let mut out = Vec::new();
+            registry.get_dyn(name).unwrap().serialize_snapshot(&mut out).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn looking_up_another_type_is_a_clean_error() {
+        let registry = registry();
+        assert!(registry.get::<u64>("max_connections").is_none());
+        let error = registry.try_get::<u64>("max_connections").err().unwrap();
+        let wrong_type = LookupError::WrongType {
+            name: "max_connections".to_string(),
+            expected: any::type_name::<u64>(),
+            found: any::type_name::<u32>(),
+        };
+        assert_eq!(error, wrong_type);
+        assert_eq!(error.to_string(), r#"the rcu registered as "max_connections" holds u32, not u64"#);
+        let missing = registry.try_get::<u32>("min_connections").err().unwrap();
+        assert_eq!(missing, LookupError::Missing { name: "min_connections".to_string() });
+        // Nothing changed: the right type still finds it
+        assert_eq!(registry.get::<u32>("max_connections").unwrap().read(), 1024);
+    }
+}
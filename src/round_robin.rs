@@ -0,0 +1,215 @@
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+use crate::Rcu;
+
+/// The endpoints of an `RcuRoundRobin`, published together with what picking needs.
+#[derive(Clone)]
+struct Endpoints<T> {
+    entries: Vec<T>,
+    /// For weighted endpoints, the sum of the weights of each entry and those before it
+    cumulative: Option<Vec<u64>>,
+}
+
+impl<T> Endpoints<T> {
+    fn unweighted(entries: Vec<T>) -> Self {
+        Self { entries, cumulative: None }
+    }
+
+    fn weighted(weighted: Vec<(T, u32)>) -> Self {
+        let mut total = 0;
+        let (entries, cumulative) = weighted
+            .into_iter()
+            .map(|(entry, weight)| {
+                total += u64::from(weight);
+                (entry, total)
+            })
+            .unzip();
+        Self {
+            entries,
+            cumulative: Some(cumulative),
+        }
+    }
+
+    /// The entry the `n`th pick lands on, None if there is none to pick.
+    fn nth(&self, n: usize) -> Option<&T> {
+        match &self.cumulative {
+            None if self.entries.is_empty() => None,
+            None => Some(&self.entries[n % self.entries.len()]),
+            Some(cumulative) => {
+                let total = *cumulative.last().filter(|&&total| total > 0)?;
+                let slot = n as u64 % total;
+                // The first entry whose weights reach past the slot, never one of weight 0
+                Some(&self.entries[cumulative.partition_point(|&reached| reached <= slot)])
+            }
+        }
+    }
+}
+
+/// Round-robin selection over a list of endpoints behind an `Rcu`, for picking a backend on every
+/// request from a list which rarely changes: a pick reads the list in place, without cloning it,
+/// and advances a cursor shared by every thread picking, so picks are spread evenly over the
+/// endpoints however many threads pick.
+///
+/// The cursor is taken modulo the list as published at the time of each pick, so picks go on
+/// across a publish, a shrinking list included, and are spread evenly over the new endpoints from
+/// then on. With weights, see [`RcuRoundRobin::weighted`], picks are spread in proportion to them,
+/// the picks of one endpoint following each other within every round over the weights.
+///
+/// ```
+/// # use rcu_rust::RcuRoundRobin;
+/// let (a, b, c) = ("10.0.0.1", "10.0.0.2", "10.0.0.3");
+/// let backends = RcuRoundRobin::new(vec![a, b, c]);
+/// let backend = backends.pick().ok_or("no backend")?;
+/// assert_eq!(backend, a);
+/// backends.set_endpoints(vec![a, c]);
+/// assert_eq!(backends.len(), 2);
+/// # Ok::<(), &str>(())
+/// ```
+pub struct RcuRoundRobin<T: Clone> {
+    endpoints: Rcu<Endpoints<T>>,
+    /// Number of picks so far, wrapping around
+    cursor: AtomicUsize,
+}
+
+impl<T: Clone> RcuRoundRobin<T> {
+    /// Creates a round robin over `endpoints`, picked in turn.
+    pub fn new(endpoints: Vec<T>) -> Self {
+        Self::from_endpoints(Endpoints::unweighted(endpoints))
+    }
+    /// Creates a round robin over weighted endpoints, each picked in proportion to its weight:
+    /// out of every round of as many picks as the weights sum to, an endpoint is picked as many
+    /// times as its weight. Endpoints of weight 0 are never picked.
+    pub fn weighted(endpoints: Vec<(T, u32)>) -> Self {
+        Self::from_endpoints(Endpoints::weighted(endpoints))
+    }
+    fn from_endpoints(endpoints: Endpoints<T>) -> Self {
+        Self {
+            endpoints: Rcu::new(endpoints),
+            cursor: AtomicUsize::new(0),
+        }
+    }
+    /// A clone of the next endpoint, None if there are none, or only ones of weight 0.
+    #[track_caller]
+    pub fn pick(&self) -> Option<T> {
+        self.pick_with(T::clone)
+    }
+    /// Runs `f` on the next endpoint without cloning it, see [`Rcu::read_with`], None if there are
+    /// none to pick.
+    #[track_caller]
+    pub fn pick_with<R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        let n = self.cursor.fetch_add(1, Relaxed);
+        self.endpoints.read_with(|endpoints| endpoints.nth(n).map(f))
+    }
+    /// Publishes `endpoints` in place of the current ones, picked in turn from the next pick on.
+    #[track_caller]
+    pub fn set_endpoints(&self, endpoints: Vec<T>) {
+        self.publish(Endpoints::unweighted(endpoints));
+    }
+    /// Publishes weighted endpoints in place of the current ones, see [`RcuRoundRobin::weighted`].
+    #[track_caller]
+    pub fn set_weighted(&self, endpoints: Vec<(T, u32)>) {
+        self.publish(Endpoints::weighted(endpoints));
+    }
+    #[track_caller]
+    fn publish(&self, endpoints: Endpoints<T>) {
+        // No invariants nor limits are ever set on the private `Rcu`, nor is it frozen
+        let _ = self.endpoints.set(endpoints);
+    }
+    /// A copy of the current endpoints, without their weights.
+    #[track_caller]
+    pub fn endpoints(&self) -> Vec<T> {
+        self.endpoints.read_with(|endpoints| endpoints.entries.clone())
+    }
+    /// Number of current endpoints, those of weight 0 included.
+    #[track_caller]
+    pub fn len(&self) -> usize {
+        self.endpoints.read_with(|endpoints| endpoints.entries.len())
+    }
+    /// Whether there are no endpoints.
+    #[track_caller]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::hash::Hash;
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use std::thread;
+
+    use super::*;
+
+    const PICKERS: usize = 4;
+
+    /// How often each endpoint was picked by `PICKERS` threads picking `picks` times each.
+    fn spread<T: Clone + Eq + Hash + Send + Sync>(rr: &RcuRoundRobin<T>, picks: usize) -> HashMap<T, usize> {
+        let counts: Vec<Vec<T>> = thread::scope(|s| {
+            let pick_all = || (0..picks).map(|_| rr.pick().unwrap()).collect();
+            let pickers: Vec<_> = (0..PICKERS).map(|_| s.spawn(pick_all)).collect();
+            pickers.into_iter().map(|picker| picker.join().unwrap()).collect()
+        });
+        let mut spread = HashMap::new();
+        for picked in counts.into_iter().flatten() {
+            *spread.entry(picked).or_default() += 1;
+        }
+        spread
+    }
+
+    #[test]
+    fn picks_are_spread_evenly_over_the_endpoints() {
+        let rr = RcuRoundRobin::new(vec!["a", "b", "c", "d", "e"]);
+        let spread = spread(&rr, 1000);
+        assert_eq!(spread, HashMap::from([("a", 800), ("b", 800), ("c", 800), ("d", 800), ("e", 800)]));
+    }
+
+    #[test]
+    fn picks_are_spread_in_proportion_to_the_weights() {
+        let rr = RcuRoundRobin::weighted(vec![("a", 1), ("b", 2), ("never", 0), ("c", 5)]);
+        let spread = spread(&rr, 2000);
+        assert_eq!(spread, HashMap::from([("a", 1000), ("b", 2000), ("c", 5000)]));
+    }
+
+    #[test]
+    fn picks_racing_replacements_only_pick_from_a_published_list() {
+        // List `n` has `n` endpoints, numbered `10 * n` onwards, the empty one included
+        let list = |n: usize| (0..n).map(|i| 10 * n + i).collect::<Vec<_>>();
+        let rr = RcuRoundRobin::new(list(5));
+        let done = AtomicBool::new(false);
+        thread::scope(|s| {
+            for _ in 0..PICKERS {
+                s.spawn(|| {
+                    while !done.load(SeqCst) {
+                        if let Some(picked) = rr.pick() {
+                            let n = picked / 10;
+                            assert!((1..=5).contains(&n) && picked % 10 < n, "picked {picked} from no list");
+                        }
+                        thread::yield_now();
+                    }
+                });
+            }
+            for n in (0..500).map(|i| [5, 1, 0, 3, 2][i % 5]) {
+                rr.set_endpoints(list(n));
+                thread::yield_now();
+            }
+            done.store(true, SeqCst);
+        });
+        // Fair again right after the last replacement
+        rr.set_endpoints(list(2));
+        assert_eq!(spread(&rr, 50), HashMap::from([(20, 100), (21, 100)]));
+    }
+
+    #[test]
+    fn nothing_to_pick_picks_nothing() {
+        let rr = RcuRoundRobin::<u32>::new(Vec::new());
+        assert_eq!(rr.pick(), None);
+        rr.set_endpoints(vec![1]);
+        assert_eq!(rr.pick(), Some(1));
+        rr.set_endpoints(Vec::new());
+        assert_eq!((rr.pick(), rr.pick_with(|_| ())), (None, None));
+        rr.set_weighted(vec![(1, 0), (2, 0)]);
+        assert_eq!(rr.pick(), None);
+        assert!(RcuRoundRobin::weighted(Vec::<(u32, u32)>::new()).pick().is_none());
+    }
+}
@@ -0,0 +1,190 @@
+use std::cell::UnsafeCell;
+use std::mem::{align_of, size_of, MaybeUninit};
+use std::sync::atomic::{fence, AtomicU8, AtomicUsize, Ordering::{Acquire, Relaxed, Release}};
+
+/// A seqlock: the small-`Copy`-value counterpart to `Rcu`. Writers bump a sequence number to odd,
+/// overwrite the value in place and bump it back to even; readers copy the value out and retry if
+/// the sequence was odd or changed meanwhile. No allocation, no grace period and no per-reader
+/// state, at the price of readers retrying (and, under a constant stream of writes, starving)
+/// instead of ever blocking writers.
+///
+/// A reader may copy out a torn mix of two writes, but the sequence check discards it before it
+/// is ever turned into a `T`. Copying reads every byte of the value as an integer, which is why
+/// payloads must be [`NoPadding`]: a padded value, whose padding bytes are uninitialized, is
+/// refused.
+///
+/// ```compile_fail
+/// # use rcu_rust::RcuSeq;
+/// // `(u8, u32)` has three bytes of padding
+/// let seq = RcuSeq::new((1u8, 2u32));
+/// ```
+pub struct RcuSeq<T: NoPadding> {
+    /// Even when no write is in progress, odd while one is
+    seq: AtomicUsize,
+    data: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// A `Copy` type every byte of which is initialized in every value of it: no padding, no unions,
+/// no `MaybeUninit`. Implemented for the primitive numbers, `bool`, `char` and arrays of them.
+///
+/// # Safety
+/// Every value of the type must have all of its `size_of::<Self>()` bytes initialized. For a
+/// struct, that is one with `#[repr(C)]` or `#[repr(transparent)]` whose fields are all
+/// `NoPadding` and leave no gap between them nor at the end.
+///
+/// ```
+/// # use rcu_rust::{NoPadding, RcuSeq};
+/// #[derive(Clone, Copy)]
+/// #[repr(C)]
+/// struct Position {
+///     x: f32,
+///     y: f32,
+/// }
+/// // Safety: two `f32`s, with no gap between them nor at the end
+/// unsafe impl NoPadding for Position {}
+///
+/// let position = RcuSeq::new(Position { x: 0.0, y: 0.0 });
+/// position.write(Position { x: 1.0, y: 2.0 });
+/// assert_eq!(position.read().y, 2.0);
+/// ```
+pub unsafe trait NoPadding: Copy {}
+
+macro_rules! no_padding {
+    ($($ty:ty),*) => {
+        // Safety: primitives without padding
+        $(unsafe impl NoPadding for $ty {})*
+    };
+}
+
+no_padding!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, bool, char);
+
+// Safety: arrays have no padding between their elements
+unsafe impl<T: NoPadding, const N: usize> NoPadding for [T; N] {}
+
+impl<T: NoPadding> RcuSeq<T> {
+    /// Creates a new `RcuSeq` holding `value`.
+    pub fn new(value: T) -> Self {
+        Self {
+            seq: AtomicUsize::new(0),
+            data: UnsafeCell::new(MaybeUninit::new(value)),
+        }
+    }
+    /// Returns a copy of the current value, retrying while a write is in progress.
+    pub fn read(&self) -> T {
+        let mut out = MaybeUninit::<T>::uninit();
+        loop {
+            let before = self.seq.load(Acquire);
+            if before & 1 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            // Safety: both pointers are valid for `size_of::<T>()` bytes and suitably aligned
+            unsafe { atomic_copy(self.data.get() as *const T, out.as_mut_ptr()) };
+            // Keeps the copy above from being reordered after the check below
+            fence(Acquire);
+            if self.seq.load(Relaxed) == before {
+                // Safety: the sequence did not move, so no write overlapped the copy and it is a
+                // whole value written by `new` or `write`
+                return unsafe { out.assume_init() };
+            }
+        }
+    }
+    /// Overwrites the value in place, waiting for a concurrent write to finish first.
+    pub fn write(&self, value: T) {
+        let seq = loop {
+            let seq = self.seq.load(Relaxed);
+            if seq & 1 == 0 && self.seq.compare_exchange_weak(seq, seq + 1, Acquire, Relaxed).is_ok() {
+                break seq;
+            }
+            std::hint::spin_loop();
+        };
+        // Keeps the writes below from being reordered before the sequence turned odd
+        fence(Release);
+        // Safety: both pointers are valid for `size_of::<T>()` bytes and suitably aligned, and the
+        // odd sequence excludes other writers
+        unsafe { atomic_copy(&value, self.data.get() as *mut T) };
+        self.seq.store(seq + 2, Release);
+    }
+}
+
+/// Copies a `T` using relaxed atomic accesses only, so a copy racing a write is merely a garbage
+/// value rather than a data race. Word-sized accesses are used when the layout of `T` allows it.
+/// Sound only because `T` is `NoPadding`: every byte loaded as an integer is initialized.
+///
+/// # Safety
+/// `src` and `dst` must be valid and aligned for a `T`.
+unsafe fn atomic_copy<T: NoPadding>(src: *const T, dst: *mut T) {
+    if align_of::<T>() >= align_of::<usize>() && size_of::<T>().is_multiple_of(size_of::<usize>()) {
+        let (src, dst) = (src as *const AtomicUsize, dst as *mut AtomicUsize);
+        for i in 0..size_of::<T>() / size_of::<usize>() {
+            (*dst.add(i)).store((*src.add(i)).load(Relaxed), Relaxed);
+        }
+    } else {
+        let (src, dst) = (src as *const AtomicU8, dst as *mut AtomicU8);
+        for i in 0..size_of::<T>() {
+            (*dst.add(i)).store((*src.add(i)).load(Relaxed), Relaxed);
+        }
+    }
+}
+
+unsafe impl<T> Send for RcuSeq<T> where T: Send + NoPadding {}
+unsafe impl<T> Sync for RcuSeq<T> where T: Send + NoPadding {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use std::thread;
+
+    use super::*;
+
+    /// Checks that readers racing `writes` writes of `value(i)` only ever see whole values, in order.
+    fn never_torn<T: NoPadding + Send + Sync + std::fmt::Debug>(value: fn(u16) -> T, index: fn(&T) -> Option<u16>) {
+        const WRITES: u16 = 20_000;
+        let seq = RcuSeq::new(value(0));
+        let done = AtomicBool::new(false);
+        thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    let mut last = 0;
+                    while !done.load(SeqCst) {
+                        let read = seq.read();
+                        let i = index(&read).unwrap_or_else(|| panic!("torn read {read:?}"));
+                        assert!(i >= last, "read {i} after {last}");
+                        last = i;
+                    }
+                });
+            }
+            for i in 1..=WRITES {
+                seq.write(value(i));
+                if i % 64 == 0 {
+                    thread::yield_now();
+                }
+            }
+            done.store(true, SeqCst);
+        });
+        assert_eq!(index(&seq.read()), Some(WRITES));
+    }
+
+    /// The index every element of `values` holds, if they agree.
+    fn agreed<E: Copy + PartialEq>(values: &[E]) -> Option<E> {
+        values.iter().all(|value| *value == values[0]).then_some(values[0])
+    }
+
+    #[test]
+    fn word_sized_copies_are_never_torn() {
+        never_torn(|i| [u64::from(i); 16], |values| agreed(values).map(|i| i as u16));
+    }
+
+    #[test]
+    fn byte_sized_copies_are_never_torn() {
+        never_torn(|i| [i.to_le_bytes(); 7], |values| agreed(values).map(u16::from_le_bytes));
+    }
+
+    #[test]
+    fn single_threaded_reads_see_the_last_write() {
+        let seq = RcuSeq::new('a');
+        assert_eq!(seq.read(), 'a');
+        seq.write('b');
+        assert_eq!(seq.read(), 'b');
+    }
+}
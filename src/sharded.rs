@@ -0,0 +1,264 @@
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+
+use crate::{PublishError, Rcu};
+
+/// A hash map split into a fixed number of shards, each an independently published `HashMap`
+/// behind an `Rcu` of its own: a change copies only the shard of its key, where one of an
+/// [`RcuHashMap`](crate::RcuHashMap) copies the whole map, so changes cost roughly the number of
+/// shards times less. Lookups hash the key to its shard and read it like an `RcuHashMap` does,
+/// hashing it twice, once to pick the shard and once in it.
+///
+/// The number of shards is fixed at construction, pick it for the largest size the map will
+/// reach: shards grow without bound and changes get slower as they do, and there is no resharding.
+/// With too many shards, whole-map operations such as [`ShardedRcuMap::len`] and
+/// [`ShardedRcuMap::snapshot`] pay for visiting each of them. Operations spanning shards aren't
+/// atomic: they see or change one shard at a time while others may change meanwhile, and
+/// [`ShardedRcuMap::extend`] publishes once per shard it touches.
+///
+/// ```
+/// # use rcu_rust::ShardedRcuMap;
+/// #[derive(Clone)]
+/// struct Session {
+///     user: u64,
+/// }
+/// let sessions = ShardedRcuMap::new(64);
+/// sessions.insert(7, Session { user: 42 }).ok().unwrap();
+/// let user = sessions.read_with(&7, |session| session.map(|session| session.user));
+/// assert_eq!(user, Some(42));
+/// ```
+pub struct ShardedRcuMap<K: Clone, V: Clone, S: Clone = RandomState> {
+    shards: Box<[Rcu<HashMap<K, V, S>>]>,
+    /// Picks the shard of a key, every shard hashing with a clone of it
+    hasher: S,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> ShardedRcuMap<K, V> {
+    /// Creates an empty map of `shards` shards.
+    ///
+    /// # Panics
+    /// If `shards` is 0.
+    pub fn new(shards: usize) -> Self {
+        Self::with_hasher(shards, RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, S: BuildHasher + Clone> ShardedRcuMap<K, V, S> {
+    /// Like [`ShardedRcuMap::new`], hashing keys with `hasher`.
+    ///
+    /// # Panics
+    /// If `shards` is 0.
+    pub fn with_hasher(shards: usize, hasher: S) -> Self {
+        assert!(shards > 0, "a ShardedRcuMap needs at least one shard");
+        let shards = (0..shards).map(|_| Rcu::new(HashMap::with_hasher(hasher.clone()))).collect();
+        Self { shards, hasher }
+    }
+    /// The number of shards.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+    /// The `Rcu` of every shard, in shard order, for everything else an `Rcu` does, e.g.
+    /// subscribing to a shard or setting invariants on it.
+    pub fn shards(&self) -> &[Rcu<HashMap<K, V, S>>] {
+        &self.shards
+    }
+    /// The shard `key` belongs to.
+    pub fn shard_of<Q>(&self, key: &Q) -> &Rcu<HashMap<K, V, S>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        &self.shards[self.index_of(key)]
+    }
+    /// A clone of the value of `key`, if any.
+    #[track_caller]
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.read_with(key, |value| value.cloned())
+    }
+    /// Runs `f` on the value of `key`, None if it has none, without cloning it.
+    #[track_caller]
+    pub fn read_with<Q, R>(&self, key: &Q, f: impl FnOnce(Option<&V>) -> R) -> R
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard_of(key).read_with(|shard| f(shard.get(key)))
+    }
+    /// Whether `key` has a value.
+    #[track_caller]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard_of(key).read_with(|shard| shard.contains_key(key))
+    }
+    /// The number of entries, summed over the shards one at a time.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.read_with(HashMap::len)).sum()
+    }
+    /// Whether no shard has entries.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| shard.read_with(HashMap::is_empty))
+    }
+    /// Sets the value of `key`, returning the one it replaces. Copies the shard of `key` only.
+    pub fn insert(&self, key: K, value: V) -> Result<Option<V>, PublishError<HashMap<K, V, S>>> {
+        self.shard_of(&key).update_mut(|shard| shard.insert(key.clone(), value.clone()))
+    }
+    /// Removes the value of `key`, returning it. Publishes nothing if there was none.
+    pub fn remove<Q>(&self, key: &Q) -> Result<Option<V>, PublishError<HashMap<K, V, S>>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let shard = self.shard_of(key);
+        if !shard.read_with(|shard| shard.contains_key(key)) {
+            return Ok(None);
+        }
+        shard.update_mut(|shard| shard.remove(key))
+    }
+    /// Publishes a copy of the shard of `key` changed by `f`, returning what `f` returned, e.g. to
+    /// change a value depending on what it was. Like every change, `f` runs again on a copy of the
+    /// newer shard whenever another writer got there first. `f` must only insert keys of that
+    /// shard, such as `key` itself: lookups of others look in their own shard and never find them.
+    #[track_caller]
+    pub fn update_shard<Q, R>(
+        &self,
+        key: &Q,
+        f: impl FnMut(&mut HashMap<K, V, S>) -> R,
+    ) -> Result<R, PublishError<HashMap<K, V, S>>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard_of(key).update_mut(f)
+    }
+    /// Sets the values of every key of `entries`, later ones winning, with one publish per shard
+    /// they touch. Stops at the first shard that refuses its change, the shards before it keeping
+    /// theirs.
+    pub fn extend(&self, entries: impl IntoIterator<Item = (K, V)>) -> Result<(), PublishError<HashMap<K, V, S>>> {
+        let mut by_shard: Vec<Vec<(K, V)>> = (0..self.shards.len()).map(|_| Vec::new()).collect();
+        for (key, value) in entries {
+            let index = self.index_of(&key);
+            by_shard[index].push((key, value));
+        }
+        for (shard, entries) in self.shards.iter().zip(by_shard) {
+            if !entries.is_empty() {
+                shard.update_mut(|shard| shard.extend(entries.iter().cloned()))?;
+            }
+        }
+        Ok(())
+    }
+    /// Removes every entry, one shard at a time.
+    pub fn clear(&self) -> Result<(), PublishError<HashMap<K, V, S>>> {
+        for shard in self.shards.iter() {
+            if !shard.read_with(HashMap::is_empty) {
+                shard.update_mut(HashMap::clear)?;
+            }
+        }
+        Ok(())
+    }
+    /// A copy of every entry in a single map, copying the shards one at a time.
+    pub fn snapshot(&self) -> HashMap<K, V, S> {
+        let mut map = HashMap::with_hasher(self.hasher.clone());
+        for shard in self.shards.iter() {
+            shard.read_with(|shard| map.extend(shard.iter().map(|(key, value)| (key.clone(), value.clone()))));
+        }
+        map
+    }
+    /// The index of the shard `key` belongs to.
+    fn index_of<Q: Hash + ?Sized>(&self, key: &Q) -> usize {
+        // The shards' maps index their buckets by the low bits of the same hash and tag them with
+        // the top ones, picking shards by the bits in between keeps each shard's keys spread out
+        (self.hasher.hash_one(key) >> 32) as usize % self.shards.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use std::thread;
+
+    use super::*;
+
+    const WRITERS: usize = 4;
+
+    #[test]
+    fn writers_racing_on_one_shard_lose_nothing() {
+        let map = ShardedRcuMap::new(8);
+        let first = &map.shards()[0];
+        let keys: Vec<u64> = (0..).filter(|key| ptr::eq(map.shard_of(key), first)).take(401).collect();
+        let (counter, keys) = (keys[0], &keys[1..]);
+        map.insert(counter, 0).unwrap();
+        thread::scope(|s| {
+            for chunk in keys.chunks(keys.len() / WRITERS) {
+                let map = &map;
+                s.spawn(move || {
+                    for &key in chunk {
+                        assert_eq!(map.insert(key, key * 2).unwrap(), None);
+                        // Every writer also bumps the same value of the shard
+                        map.update_shard(&counter, |shard| *shard.get_mut(&counter).unwrap() += 1).unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(first.read_with(HashMap::len), keys.len() + 1);
+        assert!(map.shards()[1..].iter().all(|shard| shard.read_with(HashMap::is_empty)));
+        assert_eq!(map.get(&counter), Some(keys.len() as u64), "a bump was lost");
+        for key in keys {
+            assert_eq!(map.get(key), Some(key * 2));
+        }
+    }
+
+    #[test]
+    fn writers_across_shards_lose_nothing_and_readers_see_whole_values() {
+        const KEYS: u64 = 1000;
+
+        let map = ShardedRcuMap::new(16);
+        let done = AtomicBool::new(false);
+        thread::scope(|s| {
+            s.spawn(|| {
+                while !done.load(SeqCst) {
+                    for key in (0..KEYS * WRITERS as u64).step_by(7) {
+                        if let Some(value) = map.get(&key) {
+                            assert_eq!(value, key * 2, "read a value never inserted");
+                        }
+                    }
+                    thread::yield_now();
+                }
+            });
+            let writers: Vec<_> = (0..WRITERS as u64)
+                .map(|writer| {
+                    let map = &map;
+                    s.spawn(move || {
+                        for key in (writer * KEYS..).take(KEYS as usize) {
+                            map.insert(key, key * 2).unwrap();
+                            // Removes every tenth key again, of whichever shard
+                            if key % 10 == 0 {
+                                assert_eq!(map.remove(&key).unwrap(), Some(key * 2));
+                            }
+                        }
+                    })
+                })
+                .collect();
+            for writer in writers {
+                writer.join().unwrap();
+            }
+            done.store(true, SeqCst);
+        });
+        let kept = (0..KEYS * WRITERS as u64).filter(|key| key % 10 != 0);
+        let expected: HashMap<_, _> = kept.map(|key| (key, key * 2)).collect();
+        assert_eq!(map.snapshot(), expected);
+        // Each key is in its own shard only
+        for (index, shard) in map.shards().iter().enumerate() {
+            shard.read_with(|shard| assert!(shard.keys().all(|key| map.index_of(key) == index)));
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{Rcu, RcuSubscriber};
+
+impl<T: Clone> Rcu<T> {
+    /// Splits the `Rcu` into its only writer and a handle for readers. With a single writer
+    /// guaranteed by the type system, publishing is a plain exchange of the pointer followed by the
+    /// grace period: no compare-exchange, no exclusion and no way to fail.
+    ///
+    /// Hooks keep running with every publish. As [`SingleWriter::publish`] can't fail, an `Rcu`
+    /// that could refuse a value is handed back, boxed: one with invariants, see
+    /// [`Rcu::set_invariant`], with limits on its backlog, see [`Rcu::with_max_pending_retired`]
+    /// and [`Rcu::with_max_pending_bytes`], or frozen.
+    ///
+    /// ```
+    /// # use rcu_rust::Rcu;
+    /// let Ok((mut writer, reader)) = Rcu::new(0).into_single_writer() else { unreachable!() };
+    /// writer.publish(1);
+    /// assert_eq!(reader.read(), 1);
+    ///
+    /// let checked = Rcu::new(0);
+    /// checked.set_invariant(|n| if *n >= 0 { Ok(()) } else { Err(format!("{n} is negative")) });
+    /// assert!(checked.into_single_writer().is_err());
+    /// ```
+    pub fn into_single_writer(self) -> Result<(SingleWriter<T>, RcuReader<T>), Box<Self>> {
+        if self.invariants.is_empty() && !self.limits.is_set() && !self.is_frozen() {
+            let rcu = Arc::new(self);
+            Ok((SingleWriter { rcu: Arc::clone(&rcu) }, RcuReader { rcu }))
+        } else {
+            Err(Box::new(self))
+        }
+    }
+}
+
+/// The only writer of an `Rcu`, created with [`Rcu::into_single_writer`]. Not `Clone`, and every
+/// publish takes `&mut self`, so publishes can never race.
+pub struct SingleWriter<T: Clone> {
+    rcu: Arc<Rcu<T>>,
+}
+
+impl<T: Clone> SingleWriter<T> {
+    /// Publishes `value`, waiting for readers of the value it replaces like [`Rcu::set`] does.
+    pub fn publish(&mut self, value: T) {
+        self.publish_box(Box::new(value));
+    }
+    /// Like [`SingleWriter::publish`], handing the allocation over as is.
+    pub fn publish_box(&mut self, value: Box<T>) {
+        // Safety: this is the only writer, and `&mut self` keeps it from publishing concurrently
+        unsafe { self.rcu.publish_exclusive(value) }
+    }
+    /// Publishes `f(current)`. As nobody else can publish, `current` is still the current value
+    /// when the result is published, and `f` runs exactly once.
+    pub fn publish_with(&mut self, f: impl FnOnce(&T) -> T) {
+        let value = self.rcu.read_with(|current| Box::new(f(current)));
+        self.publish_box(value);
+    }
+    /// The number of publishes since the `Rcu` was created, including those before it was split.
+    pub fn version(&self) -> u64 {
+        self.rcu.version()
+    }
+    /// Another handle for readers.
+    pub fn reader(&self) -> RcuReader<T> {
+        RcuReader { rcu: Arc::clone(&self.rcu) }
+    }
+}
+
+/// Reads an `Rcu` split with [`Rcu::into_single_writer`]. Cheap to clone, each clone reading the
+/// same value.
+pub struct RcuReader<T: Clone> {
+    rcu: Arc<Rcu<T>>,
+}
+
+impl<T: Clone> Clone for RcuReader<T> {
+    fn clone(&self) -> Self {
+        Self { rcu: Arc::clone(&self.rcu) }
+    }
+}
+
+impl<T: Clone> RcuReader<T> {
+    /// See [`Rcu::read`].
+    #[track_caller]
+    pub fn read(&self) -> T {
+        self.rcu.read()
+    }
+    /// See [`Rcu::read_with`].
+    #[track_caller]
+    pub fn read_with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        self.rcu.read_with(f)
+    }
+    /// See [`Rcu::read_versioned`].
+    #[track_caller]
+    pub fn read_versioned(&self) -> (T, u64) {
+        self.rcu.read_versioned()
+    }
+    /// See [`Rcu::version`].
+    pub fn version(&self) -> u64 {
+        self.rcu.version()
+    }
+    /// See [`Rcu::wait_for_change`].
+    pub fn wait_for_change(&self, version: u64) -> u64 {
+        self.rcu.wait_for_change(version)
+    }
+    /// See [`Rcu::wait_for_change_timeout`].
+    pub fn wait_for_change_timeout(&self, version: u64, timeout: Duration) -> Option<u64> {
+        self.rcu.wait_for_change_timeout(version, timeout)
+    }
+    /// See [`Rcu::subscribe`].
+    pub fn subscribe(&self) -> RcuSubscriber<'_, T> {
+        self.rcu.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst};
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::{PublishError, Rcu, SpinYield};
+
+    #[test]
+    fn values_that_could_be_refused_are_not_split() {
+        let checked = Rcu::new(0);
+        checked.set_invariant(|n| if *n >= 0 { Ok(()) } else { Err(format!("{n} is negative")) });
+        let Err(checked) = checked.into_single_writer() else { panic!("split despite an invariant") };
+        assert!(matches!(checked.set(-1), Err(PublishError::Rejected { value: -1, .. })));
+
+        assert!(Rcu::new(0).with_max_pending_retired(4).into_single_writer().is_err());
+        assert!(Rcu::new(0).with_max_pending_bytes(64, |_| 8).into_single_writer().is_err());
+
+        let frozen = Rcu::new(0);
+        frozen.freeze();
+        let Err(frozen) = frozen.into_single_writer() else { panic!("split while frozen") };
+        assert_eq!(frozen.frozen_ref(), Some(&0));
+    }
+
+    #[test]
+    fn hooks_run_with_every_publish() {
+        let rcu = Rcu::new(0);
+        let seen = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&seen);
+        rcu.on_update(move |_| {
+            counted.fetch_add(1, SeqCst);
+        });
+        let Ok((mut writer, reader)) = rcu.into_single_writer() else { unreachable!() };
+        writer.publish(1);
+        writer.publish_with(|n| n + 1);
+        assert_eq!(seen.load(SeqCst), 2);
+        assert_eq!((reader.read(), writer.version()), (2, 2));
+    }
+
+    #[test]
+    fn readers_are_unaffected() {
+        const PUBLISHES: u64 = 10_000;
+        let rcu = Rcu::new(0).with_wait_strategy(SpinYield::default());
+        let Ok((mut writer, reader)) = rcu.into_single_writer() else { unreachable!() };
+        let done = AtomicBool::new(false);
+        thread::scope(|s| {
+            for _ in 0..2 {
+                let reader = reader.clone();
+                let done = &done;
+                s.spawn(move || {
+                    let mut last = 0;
+                    while !done.load(SeqCst) {
+                        let (value, version) = reader.read_versioned();
+                        // Every publish is the number of publishes so far
+                        assert_eq!(value, version);
+                        assert!(version >= last, "went back from {last} to {version}");
+                        last = version;
+                    }
+                });
+            }
+            for i in 1..=PUBLISHES {
+                writer.publish(i);
+            }
+            done.store(true, SeqCst);
+        });
+        assert_eq!(reader.read_versioned(), (PUBLISHES, PUBLISHES));
+    }
+}
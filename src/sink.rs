@@ -0,0 +1,193 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering::{Acquire, Release}};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+
+use crate::reclaim::Retired;
+use crate::Rcu;
+
+pub(crate) type Sink<T> = Arc<dyn Fn(T) + Send + Sync>;
+
+/// Where the values displaced from one `RawRcu` go once their grace period is over, if anywhere.
+pub(crate) struct RetireSink<T> {
+    /// Whether `sink` is set, so retiring doesn't take the lock when it isn't
+    installed: AtomicBool,
+    sink: Mutex<Option<Sink<T>>>,
+}
+
+/// A displaced value on its way to the sink that was installed when it was retired.
+#[cfg_attr(feature = "fallback-lock", allow(dead_code))]
+struct Sunk<T> {
+    value: *mut T,
+    sink: Sink<T>,
+}
+
+/// Hands the value to the sink instead of dropping it, once the engine reclaims it.
+#[cfg_attr(feature = "fallback-lock", allow(dead_code))]
+unsafe fn deliver_sunk<T>(sunk: *mut Sunk<T>) {
+    let sunk = Box::from_raw(sunk);
+    deliver(&sunk.sink, *Box::from_raw(sunk.value));
+}
+
+/// Moves `value` into `sink`.
+pub(crate) fn deliver<T>(sink: &Sink<T>, value: T) {
+    // Runs wherever the engine reclaims, a panicking sink must not disturb it
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| sink(value)));
+}
+
+impl<T> RetireSink<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            installed: AtomicBool::new(false),
+            sink: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn set(&self, sink: Option<Sink<T>>) {
+        let mut cur = self.sink.lock().unwrap_or_else(PoisonError::into_inner);
+        self.installed.store(sink.is_some(), Release);
+        *cur = sink;
+    }
+
+    /// The sink installed now, if any.
+    pub(crate) fn get(&self) -> Option<Sink<T>> {
+        if self.installed.load(Acquire) {
+            self.sink.lock().unwrap_or_else(PoisonError::into_inner).clone()
+        } else {
+            None
+        }
+    }
+
+    /// Wraps the displaced `ptr` for the engine: reclaiming it moves the value into the sink
+    /// installed now, or drops it if there is none.
+    #[cfg_attr(feature = "fallback-lock", allow(dead_code))]
+    pub(crate) fn retired(&self, ptr: *mut T) -> Retired {
+        match self.get() {
+            Some(sink) => Retired::with_deleter(Box::into_raw(Box::new(Sunk { value: ptr, sink })), deliver_sunk::<T>),
+            None => Retired::new(ptr),
+        }
+    }
+}
+
+impl<T: Clone> Rcu<T> {
+    /// Moves every value displaced from now on into `sink` once no reader can observe it anymore,
+    /// instead of dropping it: whatever its destructor costs is then paid wherever `sink` drops it,
+    /// rather than by the publishing thread. Replaces any sink installed before.
+    ///
+    /// `sink` runs wherever the engine reclaims: on the publishing thread with the
+    /// `reclaim-counted` engine, on whichever publish ends the grace period with the others. Values
+    /// arrive exactly once, in the order they were retired in. A value retired before the sink was
+    /// replaced or removed still goes to the sink installed at the time. A panicking sink is
+    /// contained, the value is lost.
+    pub fn set_retire_sink(&self, sink: impl Fn(T) + Send + Sync + 'static) {
+        self.raw.set_retire_sink(Some(Arc::new(sink)));
+    }
+    /// Removes the sink installed with [`Rcu::set_retire_sink`], displaced values are dropped in-line again.
+    pub fn remove_retire_sink(&self) {
+        self.raw.set_retire_sink(None);
+    }
+    /// Like [`Rcu::set_retire_sink`], sending displaced values through `tx`. They are dropped
+    /// in-line if its receiver is gone.
+    pub fn retire_into(&self, tx: Sender<T>)
+    where
+        T: Send + 'static,
+    {
+        self.set_retire_sink(move |value| {
+            let _ = tx.send(value);
+        });
+    }
+    /// Drops displaced values on a background thread from now on, see [`Rcu::retire_into`]. The
+    /// thread exits once the sink is replaced or removed, or the `Rcu` is dropped, and every value
+    /// sent before that was dropped.
+    pub fn spawn_reclaimer(&self) -> JoinHandle<()>
+    where
+        T: Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel::<T>();
+        let reclaimer = thread::Builder::new()
+            .name("rcu-reclaimer".into())
+            .spawn(move || rx.into_iter().for_each(drop))
+            .expect("failed to spawn the reclaimer thread");
+        self.retire_into(tx);
+        reclaimer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+
+    use crate::Rcu;
+
+    /// Logs the name of the thread it is dropped on.
+    struct OnDrop(Arc<Mutex<Vec<Option<String>>>>);
+
+    impl Clone for OnDrop {
+        fn clone(&self) -> Self {
+            Self(Arc::clone(&self.0))
+        }
+    }
+
+    impl Drop for OnDrop {
+        fn drop(&mut self) {
+            self.0.lock().unwrap().push(thread::current().name().map(String::from));
+        }
+    }
+
+    #[test]
+    fn channel_sinks_get_every_displaced_value_once_in_retirement_order() {
+        let rcu = Rcu::new(0u64);
+        let (tx, rx) = mpsc::channel();
+        rcu.retire_into(tx);
+        for i in 1..=100 {
+            rcu.set(i).unwrap();
+        }
+        rcu.barrier();
+        // The last two are still current and previous
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), (0..99).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn channel_sinks_get_every_value_displaced_by_racing_writers_once() {
+        let rcu = Rcu::new(0u64);
+        let (tx, rx) = mpsc::channel();
+        rcu.retire_into(tx);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..250 {
+                        rcu.update_with(|n| n + 1).unwrap();
+                    }
+                });
+            }
+        });
+        rcu.barrier();
+        let mut sunk: Vec<_> = rx.try_iter().collect();
+        sunk.sort_unstable();
+        assert_eq!(sunk, (0..999).collect::<Vec<_>>(), "lost or duplicated displaced values");
+    }
+
+    #[test]
+    fn spawn_reclaimer_drops_displaced_values_on_its_thread_until_the_sink_goes() {
+        let drops = Arc::new(Mutex::new(Vec::new()));
+        let rcu = Rcu::new(OnDrop(Arc::clone(&drops)));
+        let reclaimer = rcu.spawn_reclaimer();
+        for _ in 0..10 {
+            assert!(rcu.set(OnDrop(Arc::clone(&drops))).is_ok());
+        }
+        // Values still waiting for their grace period would keep the reclaimer's channel open
+        rcu.barrier();
+        rcu.remove_retire_sink();
+        // Only exits once it dropped everything sent
+        reclaimer.join().unwrap();
+        // The last two are still current and previous
+        let reclaimer = Some("rcu-reclaimer".to_string());
+        assert_eq!(*drops.lock().unwrap(), vec![reclaimer.clone(); 9]);
+        drop(rcu);
+        let drops = drops.lock().unwrap();
+        assert_eq!(drops.len(), 11, "dropping the Rcu lost values");
+        assert!(drops[9..].iter().all(|name| *name != reclaimer), "dropped on the removed reclaimer");
+    }
+}
@@ -0,0 +1,284 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering::{Acquire, Relaxed, Release}};
+use std::sync::{Mutex, PoisonError};
+
+use crate::raw::{RawReadGuard, RawRcu};
+use crate::reclaim::Retired;
+
+/// A stable handle to an entry of an [`RcuSlab`]. Once the entry is removed the key never finds
+/// anything again, even after its slot is reused by a later insert.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SlabKey {
+    index: u32,
+    generation: u32,
+}
+
+/// An inserted value, together with the generation of its slot it was inserted in.
+struct Entry<T> {
+    generation: u32,
+    value: T,
+}
+
+/// One published version of the slot table. Entries aren't owned by the table they are in, but
+/// by the slab, so displaced tables free nothing but themselves.
+struct Table<T> {
+    slots: Box<[AtomicPtr<Entry<T>>]>,
+}
+
+impl<T> Table<T> {
+    fn with_len(len: usize) -> Self {
+        Self {
+            slots: (0..len).map(|_| AtomicPtr::new(ptr::null_mut())).collect(),
+        }
+    }
+}
+
+/// What only writers touch.
+#[derive(Default)]
+struct Writer {
+    /// The generation of every slot, bumped when its entry is removed
+    generations: Vec<u32>,
+    /// Slots whose entry was removed, reused before the table grows
+    free: Vec<u32>,
+}
+
+/// An arena handing out generational [`SlabKey`]s, for values which are looked up far more often
+/// than they are inserted or removed. Lookups are wait-free RCU reads: one load of the slot table
+/// and one of the slot, yielding a guard which keeps the entry alive for as long as it is held,
+/// even if it is removed meanwhile.
+///
+/// Inserts and removals are serialized. An insert stores the new entry in a free slot in place,
+/// and only copies the slot table, publishing the copy, when it has to grow, doubling it. A
+/// removal clears the slot in place and retires the entry. Both may wait for readers as publishes
+/// do (with the `reclaim-counted` engine), so neither must be called while holding a guard of the
+/// same slab.
+///
+/// Generations are 32 bits wide: a key only finds a reused slot's new entry by mistake if that slot
+/// was reused exactly 2³² times in between.
+pub struct RcuSlab<T> {
+    table: RawRcu<Table<T>>,
+    /// Serializes inserts and removals
+    writer: Mutex<Writer>,
+    /// Entries are only referenced through raw pointers
+    _values: PhantomData<Box<Entry<T>>>,
+}
+
+impl<T> RcuSlab<T> {
+    /// Creates an empty slab.
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+    /// Creates an empty slab with room for `capacity` entries before its slot table has to grow.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            table: RawRcu::new(Box::new(Table::with_len(capacity))),
+            writer: Mutex::default(),
+            _values: PhantomData,
+        }
+    }
+    /// Inserts `value`, returning the key to look it up with.
+    pub fn insert(&self, value: T) -> SlabKey {
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let index = match writer.free.pop() {
+            Some(index) => index,
+            None => {
+                let index = writer.generations.len();
+                let index = u32::try_from(index).expect("more than u32::MAX slab entries");
+                writer.generations.push(0);
+                index
+            }
+        };
+        let generation = writer.generations[index as usize];
+        let entry = Box::into_raw(Box::new(Entry { generation, value }));
+        let stored = self.table.read_with(|table| {
+            let slot = table.slots.get(index as usize)?;
+            slot.store(entry, Release);
+            Some(())
+        });
+        if stored.is_none() {
+            self.grow(index as usize, entry);
+        }
+        SlabKey { index, generation }
+    }
+    /// Publishes a copy of the table with room for at least `index + 1` slots, `entry` stored at
+    /// `index`. Only called by writers.
+    fn grow(&self, index: usize, entry: *mut Entry<T>) {
+        let table = self.table.read_with(|table| {
+            let grown = Table::with_len((index + 1).max(table.slots.len() * 2).max(4));
+            for (slot, copy) in table.slots.iter().zip(grown.slots.iter()) {
+                copy.store(slot.load(Relaxed), Relaxed);
+            }
+            grown.slots[index].store(entry, Relaxed);
+            grown
+        });
+        // Safety: only writers publish, and they are serialized by `self.writer`
+        unsafe { self.table.publish_exclusive(Box::new(table), |_| {}) };
+    }
+    /// The entry `key` was handed out for, unless it was removed. The guard keeps it alive until it
+    /// is dropped; it is a read-side critical section, see [`RcuReadGuard`](crate::RcuReadGuard).
+    #[track_caller]
+    pub fn get(&self, key: SlabKey) -> Option<SlabGuard<'_, T>> {
+        let table = self.table.read_guard();
+        let entry = table.slots.get(key.index as usize)?.load(Acquire);
+        // Safety: entries are only freed a grace period after they were unlinked, and the guard
+        // keeps this one from ending
+        let found = unsafe { entry.as_ref() }.is_some_and(|entry| entry.generation == key.generation);
+        found.then_some(SlabGuard {
+            entry,
+            _table: table,
+        })
+    }
+    /// Whether `key`'s entry hasn't been removed.
+    pub fn contains_key(&self, key: SlabKey) -> bool {
+        self.get(key).is_some()
+    }
+    /// Removes `key`'s entry, returning false if it was already removed. Lookups starting once this
+    /// is called don't find it anymore; the entry is freed once those in flight are done with it,
+    /// like a value displaced by a publish is.
+    pub fn remove(&self, key: SlabKey) -> bool {
+        let entry = {
+            let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+            let Some(generation) = writer.generations.get_mut(key.index as usize) else {
+                return false;
+            };
+            if *generation != key.generation {
+                return false;
+            }
+            let slot = key.index as usize;
+            let entry = self.table.read_with(|table| table.slots[slot].swap(ptr::null_mut(), Relaxed));
+            *generation = generation.wrapping_add(1);
+            writer.free.push(key.index);
+            entry
+        };
+        // Unlinked from the current table, and any displaced one which could still hold it is only
+        // read by readers that entered before, so the grace period covers every reader that may have
+        // found it. Outside of the lock, other writers may go on meanwhile
+        // Safety: only this call unlinked the entry
+        unsafe { self.table.retire(Retired::new(entry)) };
+        true
+    }
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        let writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        writer.generations.len() - writer.free.len()
+    }
+    /// Whether the slab holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Default for RcuSlab<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for RcuSlab<T> {
+    fn drop(&mut self) {
+        self.table.read_with(|table| {
+            for slot in table.slots.iter() {
+                let entry = slot.load(Relaxed);
+                if !entry.is_null() {
+                    // Safety: we own `self`, so there are no readers left, and the entry is only
+                    // reachable from this table, displaced ones never being read again
+                    drop(unsafe { Box::from_raw(entry) });
+                }
+            }
+        });
+    }
+}
+
+// Safety: values are shared between readers and dropped by whichever thread reclaims them
+unsafe impl<T: Send + Sync> Send for RcuSlab<T> {}
+unsafe impl<T: Send + Sync> Sync for RcuSlab<T> {}
+
+/// An entry of an [`RcuSlab`], kept alive until the guard is dropped, see [`RcuSlab::get`].
+pub struct SlabGuard<'a, T> {
+    /// Into the entry `_table` keeps from being freed
+    entry: *const Entry<T>,
+    _table: RawReadGuard<'a, Table<T>>,
+}
+
+impl<T> Deref for SlabGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Safety: the read-side critical section `self._table` holds keeps the entry alive
+        unsafe { &(*self.entry).value }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SlabGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::RcuSlab;
+    use crate::wait::Waiter;
+    use crate::SpinYield;
+
+    #[test]
+    fn lookups_racing_removals_find_whole_entries_or_nothing() {
+        const KEYS: u64 = 500;
+        let mut slab = RcuSlab::new();
+        // Spinning writers would starve readers on few cores
+        slab.table.set_waiter(Waiter::new(Arc::new(SpinYield::default())));
+        let keys: Vec<_> = (0..KEYS).map(|n| (n, slab.insert(vec![n; 16]))).collect();
+        let done = AtomicBool::new(false);
+        thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| {
+                    while !done.load(SeqCst) {
+                        for &(n, key) in &keys {
+                            if let Some(entry) = slab.get(key) {
+                                thread::yield_now();
+                                // Still whole after yielding to the remover
+                                assert!(entry.iter().all(|&m| m == n), "entry of {n} changed under its guard");
+                            }
+                        }
+                    }
+                });
+            }
+            for &(_, key) in &keys {
+                assert!(slab.remove(key));
+                assert!(slab.get(key).is_none(), "found right after its removal");
+                thread::yield_now();
+            }
+            done.store(true, SeqCst);
+        });
+        assert!(slab.is_empty());
+        assert!(keys.iter().all(|&(_, key)| !slab.contains_key(key) && !slab.remove(key)));
+    }
+
+    #[test]
+    fn stale_keys_never_find_the_entry_reusing_their_slot() {
+        let slab = RcuSlab::new();
+        let kept = slab.insert(u32::MAX);
+        let mut stale = Vec::new();
+        let mut key = slab.insert(0);
+        for n in 1..10_000 {
+            assert!(slab.remove(key));
+            stale.push(key);
+            key = slab.insert(n);
+            assert_eq!(key.index, stale[0].index, "the freed slot wasn't reused");
+            assert_eq!(slab.get(key).as_deref(), Some(&n));
+            assert!(slab.get(*stale.last().unwrap()).is_none(), "a stale key found the new entry");
+        }
+        assert!(stale.iter().all(|&stale| slab.get(stale).is_none() && !slab.remove(stale)));
+        assert_eq!(slab.get(kept).as_deref(), Some(&u32::MAX));
+        assert_eq!(slab.len(), 2);
+        // Churning a single slot never grew the table
+        assert_eq!(slab.table.read_with(|table| table.slots.len()), 4);
+    }
+}
@@ -0,0 +1,231 @@
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+
+use crate::error::{PromoteError, StaleStage};
+use crate::raw::RawRcu;
+use crate::Rcu;
+
+/// Identifies a value staged with [`Rcu::stage`], to promote or roll back with. Stale once
+/// another value is staged on the same `Rcu`.
+#[derive(Debug, PartialEq, Eq)]
+#[must_use = "the value stays staged until promoted or rolled back with its handle"]
+pub struct StagedVersion {
+    /// The `id` of the `Rcu` the value was staged on
+    rcu: u64,
+    stage: u64,
+}
+
+impl StagedVersion {
+    /// The number of the stage, counting from 1 for the first value staged on its `Rcu`.
+    pub fn stage(&self) -> u64 {
+        self.stage
+    }
+}
+
+/// The candidate value of an `Rcu`, see `Rcu::stage`.
+pub(crate) struct Staging<T> {
+    /// A `RawRcu` of its own, so that staged reads and their reclamation never involve the stable
+    /// value. Created by the first stage
+    slot: OnceLock<RawRcu<Option<T>>>,
+    /// Held by every publish to `slot`, and by promotes for their whole publish
+    stages: Mutex<Stages>,
+}
+
+#[derive(Default)]
+struct Stages {
+    /// The number of the last stage, 0 before the first
+    latest: u64,
+    /// Whether the value of the last stage is still staged, neither promoted nor rolled back
+    staged: bool,
+}
+
+impl Stages {
+    /// Fails unless `staged` is the handle of the value staged on the `Rcu` with `id`.
+    fn check(&self, id: u64, staged: &StagedVersion) -> Result<(), StaleStage> {
+        if staged.rcu == id && staged.stage == self.latest && self.staged {
+            Ok(())
+        } else {
+            Err(StaleStage {
+                stage: staged.stage,
+                latest: self.latest,
+            })
+        }
+    }
+}
+
+impl<T> Staging<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            slot: OnceLock::new(),
+            stages: Mutex::default(),
+        }
+    }
+
+    fn stages(&self) -> MutexGuard<'_, Stages> {
+        self.stages.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Publishes `value` to the slot, which only ever happens with `stages` locked.
+    fn publish(&self, _stages: &mut Stages, value: Option<T>) {
+        let mut value = Some(value);
+        let slot = self.slot.get_or_init(|| RawRcu::new(Box::new(value.take().unwrap())));
+        if let Some(value) = value {
+            // Safety: every publish to the slot holds `stages`
+            unsafe { slot.publish_exclusive(Box::new(value), |_| {}) };
+        }
+    }
+
+}
+
+impl<T: Clone> Rcu<T> {
+    /// Stages `value` as a candidate for the next publish, for rolling out a risky change in two
+    /// steps: readers opting in with [`Rcu::read_staged`] see it from now on, while every other read
+    /// keeps seeing the current value, until [`Rcu::promote`] publishes it for all of them, or
+    /// [`Rcu::rollback`] withdraws it. Staging a value while another one is staged supersedes it,
+    /// the handle of the older one going stale.
+    ///
+    /// Staging isn't publishing: it neither runs invariants nor hooks, nor bumps the version, all of
+    /// which only happens once the value is promoted. The staged value is held by an RCU of its own
+    /// and reclaimed apart from the current value: replacing or withdrawing it waits for the reads
+    /// of it in progress as publishes wait for reads of the current value, and publishes never wait
+    /// for reads of it.
+    ///
+    /// ```
+    /// # use rcu_rust::Rcu;
+    /// # fn error_rate_of_canaries() -> f64 { 0.0 }
+    /// # let threshold = 0.01;
+    /// let config = Rcu::new("stable");
+    /// let canary = config.stage("candidate");
+    /// assert_eq!(config.read_staged(), "candidate");
+    /// assert_eq!(config.read(), "stable");
+    /// if error_rate_of_canaries() < threshold {
+    ///     config.promote(canary)?;
+    /// } else {
+    ///     config.rollback(canary)?;
+    /// }
+    /// assert_eq!(config.read(), "candidate");
+    /// # Ok::<(), rcu_rust::PromoteError<&str>>(())
+    /// ```
+    pub fn stage(&self, value: T) -> StagedVersion {
+        let mut stages = self.staging.stages();
+        self.staging.publish(&mut stages, Some(value));
+        stages.latest += 1;
+        stages.staged = true;
+        StagedVersion {
+            rcu: self.id,
+            stage: stages.latest,
+        }
+    }
+    /// Like [`Rcu::read`], reading the staged value instead if there is one, see [`Rcu::stage`].
+    #[track_caller]
+    pub fn read_staged(&self) -> T {
+        self.read_staged_with(T::clone)
+    }
+    /// Like [`Rcu::read_with`], running `f` on the staged value instead if there is one, see
+    /// [`Rcu::stage`]. A read racing with a promote sees the promoted value either way: it is only
+    /// withdrawn once it is the current value.
+    #[track_caller]
+    pub fn read_staged_with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        if let Some(slot) = self.staging.slot.get() {
+            let staged = slot.read_guard();
+            if let Some(value) = &*staged {
+                return f(value);
+            }
+        }
+        self.read_with(f)
+    }
+    /// Publishes the value staged with `staged` like [`Rcu::set`], then withdraws it, so that every
+    /// read sees it from now on, staged reads included. Readers of the staged value are never taken
+    /// back to the previous one: it is published before it is withdrawn.
+    ///
+    /// Fails with [`PromoteError::Stale`], changing nothing, if another value was staged since. If
+    /// publishing fails, the value is withdrawn anyway and handed back in
+    /// [`PromoteError::Rejected`]. Like `set`, must not be called from a hook on the same `Rcu`.
+    #[track_caller]
+    pub fn promote(&self, staged: StagedVersion) -> Result<(), PromoteError<T>> {
+        let mut stages = self.staging.stages();
+        stages.check(self.id, &staged)?;
+        let slot = self.staging.slot.get().expect("a value was staged");
+        let value = slot.read_with(|staged| staged.clone()).expect("the value is still staged");
+        let published = self.set(value);
+        stages.staged = false;
+        self.staging.publish(&mut stages, None);
+        published.map_err(PromoteError::Rejected)
+    }
+    /// Withdraws the value staged with `staged`, staged reads seeing the current value again from
+    /// now on. Fails, changing nothing, if another value was staged since.
+    pub fn rollback(&self, staged: StagedVersion) -> Result<(), StaleStage> {
+        let mut stages = self.staging.stages();
+        stages.check(self.id, &staged)?;
+        stages.staged = false;
+        self.staging.publish(&mut stages, None);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use std::thread;
+
+    use crate::{PromoteError, Rcu, SpinYield, StaleStage};
+
+    #[test]
+    fn staged_and_stable_reads_stay_consistent_through_promotes_and_rollbacks() {
+        const ROUNDS: u64 = 300;
+        // Round `r` stages `10 * r + 1`, promoted on even rounds and rolled back on odd ones
+        let rcu = Rcu::new(0u64).with_wait_strategy(SpinYield::default());
+        let done = AtomicBool::new(false);
+        thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| {
+                    let mut last = 0;
+                    while !done.load(SeqCst) {
+                        let stable = rcu.read();
+                        assert!(stable == 0 || (stable / 10).is_multiple_of(2), "read {stable}, which was rolled back");
+                        assert!(stable >= last, "went back from {last} to {stable}");
+                        last = stable;
+                        let staged = rcu.read_staged();
+                        // A promoted value is published before it is withdrawn
+                        assert!(staged >= stable, "staged read {staged} behind the stable {stable}");
+                        thread::yield_now();
+                    }
+                });
+            }
+            for round in 1..=ROUNDS {
+                let staged = rcu.stage(10 * round + 1);
+                assert_eq!(rcu.read_staged(), 10 * round + 1);
+                thread::yield_now();
+                if round.is_multiple_of(2) {
+                    rcu.promote(staged).unwrap();
+                } else {
+                    rcu.rollback(staged).unwrap();
+                }
+                assert_eq!(rcu.read_staged(), rcu.read());
+            }
+            done.store(true, SeqCst);
+        });
+        assert_eq!(rcu.read(), 10 * ROUNDS + 1);
+        assert_eq!(rcu.version(), ROUNDS / 2);
+    }
+
+    #[test]
+    fn staging_again_makes_older_handles_stale() {
+        let rcu = Rcu::new("stable");
+        let first = rcu.stage("first");
+        let second = rcu.stage("second");
+        let third = rcu.stage("third");
+        assert_eq!((first.stage(), second.stage(), third.stage()), (1, 2, 3));
+        assert_eq!(rcu.read_staged(), "third");
+        assert_eq!(rcu.rollback(first), Err(StaleStage { stage: 1, latest: 3 }));
+        assert_eq!(rcu.promote(second), Err(PromoteError::Stale(StaleStage { stage: 2, latest: 3 })));
+        // The stale handles changed nothing
+        assert_eq!((rcu.read(), rcu.read_staged(), rcu.version()), ("stable", "third", 0));
+        rcu.promote(third).unwrap();
+        assert_eq!((rcu.read(), rcu.read_staged(), rcu.version()), ("third", "third", 1));
+
+        let other = Rcu::new("other");
+        let foreign = other.stage("foreign");
+        assert!(rcu.rollback(foreign).is_err(), "took the handle of another Rcu");
+        assert_eq!((other.read_staged(), rcu.read_staged()), ("foreign", "third"));
+    }
+}
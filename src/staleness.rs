@@ -0,0 +1,228 @@
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError, Weak};
+use std::time::{Duration, Instant};
+
+/// When the clock of `now` started.
+fn start() -> Instant {
+    static START: OnceLock<Instant> = OnceLock::new();
+    *START.get_or_init(Instant::now)
+}
+
+/// Nanoseconds since the first call, a clock cheap to store atomically.
+pub(crate) fn now() -> u64 {
+    start().elapsed().as_nanos() as u64
+}
+
+/// The `Instant` a reading of `now` was taken at.
+pub(crate) fn instant(nanos: u64) -> Instant {
+    start() + Duration::from_nanos(nanos)
+}
+
+/// The latest publish of an `Rcu`, shared with the views of its subscribers so their staleness can
+/// be computed without borrowing the `Rcu`.
+pub(crate) struct Head {
+    version: AtomicU64,
+    /// When `version` was published, see `now`
+    published_at: AtomicU64,
+}
+
+impl Head {
+    /// Nothing published since `created_at`, see `now`.
+    pub(crate) fn new(created_at: u64) -> Arc<Self> {
+        Arc::new(Self {
+            version: AtomicU64::new(0),
+            published_at: AtomicU64::new(created_at),
+        })
+    }
+
+    /// Records that `version` was just published, at `published_at`, see `now`.
+    pub(crate) fn published(&self, version: u64, published_at: u64) {
+        self.published_at.store(published_at, Relaxed);
+        self.version.store(version, Relaxed);
+    }
+}
+
+/// The version of the last value a subscriber handed out, and when it was published.
+pub(crate) struct View {
+    head: Arc<Head>,
+    version: AtomicU64,
+    published_at: AtomicU64,
+}
+
+impl View {
+    /// A view of the latest publish of `head`.
+    pub(crate) fn new(head: &Arc<Head>) -> Arc<Self> {
+        let view = Arc::new(Self {
+            head: Arc::clone(head),
+            version: AtomicU64::new(0),
+            published_at: AtomicU64::new(0),
+        });
+        view.record(head.version.load(Relaxed));
+        view
+    }
+
+    /// Records that the subscriber just handed out `version`. Taken as published when the latest
+    /// publish was, which it is unless a publish is racing with the read.
+    pub(crate) fn record(&self, version: u64) {
+        self.published_at.store(self.head.published_at.load(Relaxed), Relaxed);
+        self.version.store(version, Relaxed);
+    }
+
+    pub(crate) fn staleness(&self) -> Staleness {
+        let version = self.version.load(Relaxed);
+        let versions_behind = self.head.version.load(Relaxed).saturating_sub(version);
+        let age = if versions_behind == 0 {
+            Duration::ZERO
+        } else {
+            let behind = self.head.published_at.load(Relaxed).saturating_sub(self.published_at.load(Relaxed));
+            Duration::from_nanos(behind)
+        };
+        Staleness {
+            version,
+            versions_behind,
+            age,
+        }
+    }
+}
+
+/// How far behind its `Rcu` a subscriber's view is, see `RcuSubscriber::staleness`. Computed from
+/// relaxed loads, so the numbers may trail a publish happening at the same time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Staleness {
+    /// The version of the last value the subscriber handed out
+    pub version: u64,
+    /// Publishes since that one
+    pub versions_behind: u64,
+    /// How much earlier that value was published than the latest one, zero if up to date
+    pub age: Duration,
+}
+
+/// Tracks the staleness of a subscriber without borrowing it, e.g. from a monitoring thread.
+/// Obtained from the subscriber's `staleness_handle`, see [`StalenessRegistry`].
+#[derive(Clone)]
+pub struct StalenessHandle {
+    view: Weak<View>,
+}
+
+impl StalenessHandle {
+    pub(crate) fn new(view: &Arc<View>) -> Self {
+        Self { view: Arc::downgrade(view) }
+    }
+    /// The subscriber's current staleness, None once it was dropped.
+    pub fn staleness(&self) -> Option<Staleness> {
+        self.view.upgrade().map(|view| view.staleness())
+    }
+}
+
+/// A named set of subscribers whose staleness is reported together, e.g. one per worker. Dropped
+/// subscribers leave the set on their own.
+#[derive(Default)]
+pub struct StalenessRegistry {
+    entries: Mutex<Vec<(String, StalenessHandle)>>,
+}
+
+impl StalenessRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds the subscriber tracked by `handle`, reported as `name`.
+    pub fn register(&self, name: impl Into<String>, handle: StalenessHandle) {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner).push((name.into(), handle));
+    }
+    /// The staleness of every registered subscriber still alive, in registration order.
+    pub fn report(&self) -> Vec<(String, Staleness)> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut report = Vec::with_capacity(entries.len());
+        entries.retain(|(name, handle)| match handle.staleness() {
+            Some(staleness) => {
+                report.push((name.clone(), staleness));
+                true
+            }
+            None => false,
+        });
+        report
+    }
+    /// The registered subscriber furthest behind, by versions and then by age, None if there is none.
+    pub fn worst(&self) -> Option<(String, Staleness)> {
+        self.report()
+            .into_iter()
+            .max_by_key(|(_, staleness)| (staleness.versions_behind, staleness.age))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::Rcu;
+
+    /// Time between publishes, so each one is measurably older than the next
+    const GAP: Duration = Duration::from_millis(10);
+    const PUBLISHES: u64 = 3;
+
+    fn up_to_date(version: u64) -> Staleness {
+        Staleness {
+            version,
+            versions_behind: 0,
+            age: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn a_lagging_subscriber_is_behind_by_the_publishes_and_time_it_missed() {
+        let rcu = Rcu::new(0);
+        let (fresh, lagging) = (rcu.subscribe(), rcu.subscribe());
+        let first = rcu.version();
+        assert_eq!((fresh.staleness(), lagging.staleness()), (up_to_date(first), up_to_date(first)));
+        let started = Instant::now();
+        for value in 1..=PUBLISHES {
+            thread::sleep(GAP);
+            rcu.set(value).unwrap();
+            assert_eq!(fresh.read(), value);
+            assert_eq!(fresh.staleness(), up_to_date(rcu.version()));
+        }
+        let staleness = lagging.staleness();
+        assert_eq!((staleness.version, staleness.versions_behind), (first, PUBLISHES));
+        assert!(staleness.age >= GAP * PUBLISHES as u32, "{:?} old, for {PUBLISHES} missed publishes", staleness.age);
+        assert!(staleness.age <= started.elapsed() + GAP, "older than the publishes it missed");
+        // Catching up
+        assert_eq!(lagging.read(), PUBLISHES);
+        assert_eq!(lagging.staleness(), up_to_date(rcu.version()));
+    }
+
+    #[test]
+    fn the_registry_reports_every_live_subscriber_and_the_worst() {
+        let rcu = Rcu::new(0);
+        let registry = StalenessRegistry::new();
+        let subscribers = [rcu.subscribe(), rcu.subscribe(), rcu.subscribe()];
+        let dropped = rcu.subscribe();
+        for (name, subscriber) in ["fresh", "behind", "furthest"].iter().zip(&subscribers) {
+            registry.register(*name, subscriber.staleness_handle());
+        }
+        let handle = dropped.staleness_handle();
+        registry.register("dropped", handle.clone());
+        drop(dropped);
+        assert_eq!(handle.staleness(), None);
+        assert_eq!(registry.worst().map(|(_, staleness)| staleness.versions_behind), Some(0));
+        // Each subscriber stops reading one publish earlier than the one before it
+        for value in 1..=PUBLISHES {
+            thread::sleep(GAP);
+            rcu.set(value).unwrap();
+            for subscriber in &subscribers[..subscribers.len().min((PUBLISHES - value + 1) as usize)] {
+                subscriber.read();
+            }
+        }
+        let report = registry.report();
+        let names: Vec<_> = report.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["fresh", "behind", "furthest"]);
+        let behind: Vec<_> = report.iter().map(|(_, staleness)| staleness.versions_behind).collect();
+        assert_eq!(behind, [0, 1, 2]);
+        assert!(report[2].1.age > report[1].1.age && report[1].1.age >= GAP, "ages out of line: {report:?}");
+        let (name, worst) = registry.worst().unwrap();
+        assert_eq!((name.as_str(), worst), ("furthest", report[2].1));
+        assert_eq!(Some(worst), subscribers[2].staleness_handle().staleness());
+    }
+}
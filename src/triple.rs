@@ -0,0 +1,237 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::Arc;
+
+use crate::raw::RawRcu;
+
+struct Slot<T> {
+    value: UnsafeCell<T>,
+    /// Consumers currently holding this slot
+    readers: AtomicUsize,
+}
+
+struct Shared<T> {
+    /// Only ever grows, to two more slots than the most consumers alive at once, so indices stay
+    /// valid
+    slots: RawRcu<Vec<Arc<Slot<T>>>>,
+    /// Index of the most recently published slot
+    latest: AtomicUsize,
+    /// Consumers alive
+    consumers: AtomicUsize,
+}
+
+impl<T> Shared<T> {
+    fn slot(&self, idx: usize) -> Arc<Slot<T>> {
+        self.slots.read_with(|slots| Arc::clone(&slots[idx]))
+    }
+}
+
+/// A triple buffer for a single producer publishing snapshots to any number of consumers that
+/// only care about the latest one. The producer writes into a free slot and marks it as the latest,
+/// consumers switch to the latest slot when they ask for it; neither side ever waits for the other,
+/// and the producer never allocates.
+///
+/// Every consumer holds on to one slot, so there are always two more slots than consumers (three for
+/// the first one), which guarantees the producer a free slot. [`TripleConsumer::clone`] only adds a
+/// slot when more consumers are alive than ever before: the slots of dropped consumers are reused,
+/// and never freed before the buffer itself.
+pub struct TripleBuffer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Clone> TripleBuffer<T> {
+    /// Creates a triple buffer initially holding `value` in every slot.
+    pub fn new(value: T) -> Self {
+        let slots = (0..3)
+            .map(|_| {
+                Arc::new(Slot {
+                    value: UnsafeCell::new(value.clone()),
+                    readers: AtomicUsize::new(0),
+                })
+            })
+            .collect();
+        Self {
+            shared: Arc::new(Shared {
+                slots: RawRcu::new(Box::new(slots)),
+                latest: AtomicUsize::new(0),
+                consumers: AtomicUsize::new(1),
+            }),
+        }
+    }
+    /// Splits the buffer into its producer and first consumer.
+    pub fn split(self) -> (TripleProducer<T>, TripleConsumer<T>) {
+        let held = self.shared.slot(0);
+        held.readers.fetch_add(1, SeqCst);
+        let consumer = TripleConsumer {
+            shared: Arc::clone(&self.shared),
+            held,
+            held_idx: 0,
+        };
+        (TripleProducer { shared: self.shared }, consumer)
+    }
+}
+
+/// The writing half of a [`TripleBuffer`].
+pub struct TripleProducer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> TripleProducer<T> {
+    /// Publishes `value` as the latest snapshot.
+    pub fn publish(&mut self, value: T) {
+        self.publish_with(|slot| *slot = value)
+    }
+    /// Lets `f` write the next snapshot in place, reusing the storage of a free slot, and publishes
+    /// it. The slot holds some older snapshot when `f` is called, `f` is responsible for bringing
+    /// all of it up to date.
+    pub fn publish_with(&mut self, f: impl FnOnce(&mut T)) {
+        let latest = self.shared.latest.load(SeqCst);
+        // A consumer only ever switches to the latest slot, so it is seen holding at most one other
+        // slot on the way; with two more slots than consumers, one is always free
+        let (idx, slot) = self.shared.slots.read_with(|slots| {
+            slots
+                .iter()
+                .enumerate()
+                .find(|(idx, slot)| *idx != latest && slot.readers.load(SeqCst) == 0)
+                .map(|(idx, slot)| (idx, Arc::clone(slot)))
+                .expect("two more slots than consumers")
+        });
+        // Safety: the slot isn't the latest and no consumer holds it; a consumer that registers
+        // on it from now on will see it isn't the latest and won't read it until we publish it
+        f(unsafe { &mut *slot.value.get() });
+        self.shared.latest.store(idx, SeqCst);
+    }
+}
+
+/// A reading half of a [`TripleBuffer`], cloning it adds a consumer.
+pub struct TripleConsumer<T> {
+    shared: Arc<Shared<T>>,
+    held: Arc<Slot<T>>,
+    held_idx: usize,
+}
+
+impl<T> TripleConsumer<T> {
+    /// The latest published snapshot. Always a whole snapshot, the producer never writes into a slot
+    /// held by a consumer.
+    pub fn latest(&mut self) -> &T {
+        if self.shared.latest.load(SeqCst) != self.held_idx {
+            self.held.readers.fetch_sub(1, SeqCst);
+            loop {
+                let idx = self.shared.latest.load(SeqCst);
+                let slot = self.shared.slot(idx);
+                slot.readers.fetch_add(1, SeqCst);
+                // The producer may have picked the slot before our registration, but then it can
+                // only be the latest again once it is completely written
+                if self.shared.latest.load(SeqCst) == idx {
+                    self.held = slot;
+                    self.held_idx = idx;
+                    break;
+                }
+                slot.readers.fetch_sub(1, SeqCst);
+            }
+        }
+        // Safety: the producer never writes into a slot we are registered on
+        unsafe { &*self.held.value.get() }
+    }
+}
+
+impl<T: Clone> Clone for TripleConsumer<T> {
+    fn clone(&self) -> Self {
+        let consumers = self.shared.consumers.fetch_add(1, SeqCst) + 1;
+        if self.shared.slots.read_with(Vec::len) < consumers + 2 {
+            self.shared.slots.modify(|slots| {
+                let mut slots = slots.clone();
+                while slots.len() < consumers + 2 {
+                    // Safety: the producer never writes into a slot we are registered on
+                    let value = unsafe { (*self.held.value.get()).clone() };
+                    slots.push(Arc::new(Slot {
+                        value: UnsafeCell::new(value),
+                        readers: AtomicUsize::new(0),
+                    }));
+                }
+                slots
+            });
+        }
+        // Starts out on our slot, so it only ever holds one of its own once there is room for it
+        self.held.readers.fetch_add(1, SeqCst);
+        Self {
+            shared: Arc::clone(&self.shared),
+            held: Arc::clone(&self.held),
+            held_idx: self.held_idx,
+        }
+    }
+}
+
+impl<T> Drop for TripleConsumer<T> {
+    fn drop(&mut self) {
+        self.held.readers.fetch_sub(1, SeqCst);
+        self.shared.consumers.fetch_sub(1, SeqCst);
+    }
+}
+
+unsafe impl<T> Send for Slot<T> where T: Send + Sync {}
+unsafe impl<T> Sync for Slot<T> where T: Send + Sync {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use std::thread;
+
+    use super::TripleBuffer;
+
+    #[test]
+    fn consumers_only_see_whole_snapshots_in_order() {
+        const PUBLISHES: u64 = 20_000;
+        let (mut producer, consumer) = TripleBuffer::new([0u64; 8]).split();
+        let done = AtomicBool::new(false);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                let mut consumer = consumer.clone();
+                let done = &done;
+                s.spawn(move || {
+                    let mut last = 0;
+                    while !done.load(SeqCst) {
+                        let snapshot = *consumer.latest();
+                        assert!(snapshot.iter().all(|&n| n == snapshot[0]), "torn snapshot {snapshot:?}");
+                        assert!(snapshot[0] >= last, "went back from {last} to {}", snapshot[0]);
+                        last = snapshot[0];
+                        thread::yield_now();
+                    }
+                });
+            }
+            for n in 1..=PUBLISHES {
+                producer.publish_with(|slot| slot.fill(n));
+            }
+            done.store(true, SeqCst);
+        });
+        let mut consumer = consumer;
+        assert_eq!(*consumer.latest(), [PUBLISHES; 8]);
+    }
+
+    #[test]
+    fn dropped_consumers_leave_their_slots_for_the_next_ones() {
+        let (mut producer, mut consumer) = TripleBuffer::new(0).split();
+        for n in 1..=1000 {
+            let mut clone = consumer.clone();
+            producer.publish(n);
+            assert_eq!(*clone.latest(), n);
+            drop(clone);
+            assert_eq!(*consumer.latest(), n);
+        }
+        // The first consumer and one clone at a time
+        assert_eq!(producer.shared.slots.read_with(Vec::len), 4);
+
+        let mut clones: Vec<_> = (0..8).map(|_| consumer.clone()).collect();
+        for n in 1001..=1100 {
+            producer.publish(n);
+            for clone in &mut clones {
+                assert_eq!(*clone.latest(), n);
+            }
+        }
+        drop(clones);
+        for _ in 0..100 {
+            drop(consumer.clone());
+        }
+        assert_eq!(producer.shared.slots.read_with(Vec::len), 11);
+    }
+}
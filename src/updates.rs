@@ -0,0 +1,167 @@
+use std::iter::FusedIterator;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::Rcu;
+
+impl<T: Clone> Rcu<T> {
+    /// A blocking iterator over the values published from now on, see [`UpdateIter`].
+    pub fn iter_updates(&self) -> UpdateIter<'_, T> {
+        UpdateIter {
+            rcu: self,
+            version: self.version(),
+        }
+    }
+    /// Like [`Rcu::iter_updates`], owning a reference to the `Rcu`, e.g. to be moved to a thread.
+    pub fn iter_updates_owned(self: &Arc<Self>) -> OwnedUpdateIter<T> {
+        OwnedUpdateIter {
+            version: self.version(),
+            rcu: Arc::clone(self),
+        }
+    }
+    /// A clone of the current value if it is newer than `version`, moving `version` on to it,
+    /// waiting at most `timeout` for one. None once frozen with nothing newer.
+    fn next_update(&self, version: &mut u64, timeout: Option<Duration>) -> Option<T> {
+        let last = *version;
+        self.wait_until(|| self.version() > last || self.is_frozen(), timeout);
+        if self.version() == last {
+            return None;
+        }
+        let (value, current) = self.read_versioned();
+        *version = current;
+        Some(value)
+    }
+}
+
+/// Yields clones of the values a `Rcu` publishes, created with [`Rcu::iter_updates`]. `next` blocks
+/// until a version newer than the last one yielded (or the one current at creation, before the
+/// first) is published, then yields the current value.
+///
+/// Updates are coalesced: if several publishes happened since the last `next`, only the latest
+/// value is yielded, intermediate versions are never seen. Versions yielded thus strictly increase.
+/// The iterator ends once the `Rcu` is frozen, after yielding the final value if it wasn't yet.
+pub struct UpdateIter<'a, T: Clone> {
+    rcu: &'a Rcu<T>,
+    /// The version of the last value yielded
+    version: u64,
+}
+
+impl<T: Clone> UpdateIter<'_, T> {
+    /// Like `next`, giving up after `timeout` and returning None, for loops that also need to do
+    /// periodic work. It also returns None once the iterator ended, see [`UpdateIter::is_closed`].
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<T> {
+        self.rcu.next_update(&mut self.version, Some(timeout))
+    }
+    /// The version of the last value yielded, or the one current at creation before the first.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+    /// Whether the iterator ended: the `Rcu` is frozen and its final value was yielded.
+    pub fn is_closed(&self) -> bool {
+        self.rcu.is_frozen() && self.rcu.version() == self.version
+    }
+}
+
+impl<T: Clone> Iterator for UpdateIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rcu.next_update(&mut self.version, None)
+    }
+}
+
+impl<T: Clone> FusedIterator for UpdateIter<'_, T> {}
+
+/// Like [`UpdateIter`], owning a reference to its `Rcu`, created with [`Rcu::iter_updates_owned`].
+pub struct OwnedUpdateIter<T: Clone> {
+    rcu: Arc<Rcu<T>>,
+    /// The version of the last value yielded
+    version: u64,
+}
+
+impl<T: Clone> OwnedUpdateIter<T> {
+    /// See [`UpdateIter::next_timeout`].
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<T> {
+        self.rcu.next_update(&mut self.version, Some(timeout))
+    }
+    /// See [`UpdateIter::version`].
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+    /// See [`UpdateIter::is_closed`].
+    pub fn is_closed(&self) -> bool {
+        self.rcu.is_frozen() && self.rcu.version() == self.version
+    }
+}
+
+impl<T: Clone> Iterator for OwnedUpdateIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rcu.next_update(&mut self.version, None)
+    }
+}
+
+impl<T: Clone> FusedIterator for OwnedUpdateIter<T> {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use crate::Rcu;
+
+    #[test]
+    fn consumers_see_increasing_versions_until_the_producer_freezes() {
+        let rcu = Arc::new(Rcu::new(0u64));
+        let mut updates = rcu.iter_updates_owned();
+        let consumer = thread::spawn(move || {
+            let mut seen = Vec::new();
+            while let Some(value) = updates.next() {
+                // Each value is published as the version of its number
+                assert_eq!(updates.version(), value, "yielded with another version");
+                seen.push(value);
+            }
+            assert!(updates.is_closed());
+            seen
+        });
+        for n in 1..=1000 {
+            rcu.set(n).unwrap();
+            if n % 100 == 0 {
+                thread::yield_now();
+            }
+        }
+        rcu.freeze();
+        let seen = consumer.join().unwrap();
+        assert!(seen.windows(2).all(|pair| pair[0] < pair[1]), "versions didn't strictly increase");
+        assert_eq!(seen.last(), Some(&1000), "the final value wasn't yielded");
+    }
+
+    #[test]
+    fn next_timeout_gives_up_or_yields_what_is_published_meanwhile() {
+        let rcu = Rcu::new(0);
+        let mut updates = rcu.iter_updates();
+        let start = Instant::now();
+        assert_eq!(updates.next_timeout(Duration::from_millis(20)), None);
+        assert!(start.elapsed() >= Duration::from_millis(20), "gave up after {:?}", start.elapsed());
+        assert!(!updates.is_closed(), "timing out closed the iterator");
+        thread::scope(|s| {
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(20));
+                rcu.set(1).unwrap();
+            });
+            assert_eq!(updates.next_timeout(Duration::from_secs(10)), Some(1));
+        });
+        // Coalesces what was published since
+        rcu.set(2).unwrap();
+        rcu.set(3).unwrap();
+        assert_eq!(updates.next_timeout(Duration::ZERO), Some(3));
+        rcu.freeze();
+        let start = Instant::now();
+        assert_eq!(updates.next_timeout(Duration::from_secs(10)), None);
+        assert!(start.elapsed() < Duration::from_secs(5), "waited for a frozen Rcu");
+        assert!(updates.is_closed());
+        assert_eq!(updates.next(), None);
+    }
+}
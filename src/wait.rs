@@ -0,0 +1,339 @@
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+/// How a thread waits for a condition other threads will change: readers to exit a grace period,
+/// a writer to lift the pause of the `reclaim-counted` engine, a publish in flight to complete, a
+/// new version to be published. Waits call [`wait`](Self::wait) between checks of their condition,
+/// and threads changing such a condition call [`notify`](Self::notify).
+///
+/// Set with [`Rcu::with_wait_strategy`](crate::Rcu::with_wait_strategy) or
+/// [`RcuDomain::with_wait_strategy`](crate::RcuDomain::with_wait_strategy). Unless one is set,
+/// waits spin like [`Spin`] does, dispatched statically.
+pub trait WaitStrategy: Send + Sync {
+    /// Called once per failed check of the condition, `attempt` counting the earlier ones of the
+    /// same wait. May return at any time, the condition is checked again either way.
+    fn wait(&self, attempt: u32);
+
+    /// Called after changing a condition a thread could be waiting for. Only called from paths
+    /// which may block, never from reads of signal handlers, so strategies sleeping in `wait` must
+    /// still check their condition again in bounded time.
+    fn notify(&self) {}
+}
+
+/// Spins on the CPU for every attempt, for threads pinned to cores of their own. The default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Spin;
+
+impl WaitStrategy for Spin {
+    #[inline]
+    fn wait(&self, _attempt: u32) {
+        std::hint::spin_loop();
+    }
+}
+
+/// Spins for the first `spins` attempts, then yields to the scheduler for every further one.
+#[derive(Clone, Copy, Debug)]
+pub struct SpinYield {
+    pub spins: u32,
+}
+
+impl Default for SpinYield {
+    fn default() -> Self {
+        Self { spins: 100 }
+    }
+}
+
+impl WaitStrategy for SpinYield {
+    fn wait(&self, attempt: u32) {
+        if attempt < self.spins {
+            std::hint::spin_loop();
+        } else {
+            thread::yield_now();
+        }
+    }
+}
+
+/// Spins for the first `spins` attempts, then parks the thread until notified, or at most for
+/// `timeout` per attempt: a notification racing with the thread parking may be missed.
+#[derive(Debug)]
+pub struct Park {
+    pub spins: u32,
+    pub timeout: Duration,
+    /// The length of `parked`
+    waiting: AtomicUsize,
+    parked: Mutex<Vec<Thread>>,
+}
+
+impl Park {
+    /// Parks after `spins` attempts, for at most `timeout` at a time.
+    pub fn new(spins: u32, timeout: Duration) -> Self {
+        Self {
+            spins,
+            timeout,
+            waiting: AtomicUsize::new(0),
+            parked: Mutex::default(),
+        }
+    }
+}
+
+impl Default for Park {
+    /// Parks after 100 attempts, for at most a millisecond at a time.
+    fn default() -> Self {
+        Self::new(100, Duration::from_millis(1))
+    }
+}
+
+impl WaitStrategy for Park {
+    fn wait(&self, attempt: u32) {
+        if attempt < self.spins {
+            std::hint::spin_loop();
+            return;
+        }
+        {
+            let mut parked = self.parked.lock().unwrap_or_else(PoisonError::into_inner);
+            let current = thread::current();
+            if !parked.iter().any(|thread| thread.id() == current.id()) {
+                parked.push(current);
+            }
+            self.waiting.store(parked.len(), SeqCst);
+        }
+        thread::park_timeout(self.timeout);
+    }
+
+    fn notify(&self) {
+        if self.waiting.load(SeqCst) == 0 {
+            return;
+        }
+        let parked = {
+            let mut parked = self.parked.lock().unwrap_or_else(PoisonError::into_inner);
+            self.waiting.store(0, SeqCst);
+            std::mem::take(&mut *parked)
+        };
+        parked.iter().for_each(Thread::unpark);
+    }
+}
+
+/// Whether readers are held back while a writer waits for a grace period. Only the
+/// `reclaim-counted` engine ever holds readers back, the others always prefer readers.
+///
+/// Holding readers back means a thread already inside a read-side critical section must not enter
+/// another one on the same engine, e.g. by reading the same value from inside `Rcu::read_with`, or
+/// two members of a domain: a writer starting to wait in between would wait for the outer one,
+/// which waits for the writer. Only `ReaderPreferred` allows it.
+///
+/// Set with [`Rcu::with_preference`](crate::Rcu::with_preference) or
+/// [`RcuDomain::with_preference`](crate::RcuDomain::with_preference).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Preference {
+    /// Readers are never held back. Writers still can't be starved by a constant stream of
+    /// overlapping readers: a grace period only waits for those that entered before it started,
+    /// but grace periods are serialized, so under sustained writes reclamation lags further behind.
+    ReaderPreferred,
+    /// Readers arriving while a writer waits are held back until no writer waits anymore, so the
+    /// grace period completes as soon as the readers already in are done. A constant stream of
+    /// writers can starve readers. The default.
+    #[default]
+    WriterPreferred,
+    /// Like `WriterPreferred`, except that readers are held back at most until the grace period in
+    /// progress when they arrived completes, even if another writer waits right after: readers and
+    /// writers take turns in batches, and neither can starve the other.
+    PhaseFair,
+}
+
+/// The strategy of one engine or value: `Spin` unless another one was set, which is then called
+/// dynamically.
+#[derive(Clone, Default)]
+pub(crate) struct Waiter(Option<Arc<dyn WaitStrategy>>);
+
+impl Waiter {
+    pub(crate) fn new(strategy: Arc<dyn WaitStrategy>) -> Self {
+        Self(Some(strategy))
+    }
+
+    /// Whether no strategy was set.
+    pub(crate) fn is_default(&self) -> bool {
+        self.0.is_none()
+    }
+
+    #[inline]
+    pub(crate) fn wait(&self, attempt: u32) {
+        match &self.0 {
+            None => Spin.wait(attempt),
+            Some(strategy) => strategy.wait(attempt),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn notify(&self) {
+        if let Some(strategy) = &self.0 {
+            strategy.notify();
+        }
+    }
+
+    /// Waits while `waiting` returns true, giving up at `deadline` and returning false.
+    pub(crate) fn wait_while(&self, mut waiting: impl FnMut() -> bool, deadline: Option<Instant>) -> bool {
+        let mut attempt = 0u32;
+        while waiting() {
+            self.wait(attempt);
+            attempt = attempt.wrapping_add(1);
+            // Keep reading the clock off the spin path
+            if attempt.is_multiple_of(1024) && deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::atomic::Ordering::Relaxed;
+    use std::sync::{mpsc, Barrier};
+
+    use super::*;
+    use crate::Rcu;
+
+    /// Counts the waits of every thread by name, and the notifications, yielding to wait.
+    #[derive(Default)]
+    struct Recording {
+        waits: Mutex<HashMap<String, usize>>,
+        notifies: AtomicUsize,
+    }
+
+    impl WaitStrategy for Arc<Recording> {
+        fn wait(&self, _attempt: u32) {
+            let name = thread::current().name().unwrap_or_default().to_string();
+            *self.waits.lock().unwrap().entry(name).or_default() += 1;
+            thread::yield_now();
+        }
+
+        fn notify(&self) {
+            self.notifies.fetch_add(1, Relaxed);
+        }
+    }
+
+    impl Recording {
+        fn waited(&self, name: &str) -> bool {
+            self.waits.lock().unwrap().contains_key(name)
+        }
+
+        /// Blocks until the thread `name` waited through the strategy.
+        fn until_waited(&self, name: &str) {
+            while !self.waited(name) {
+                thread::yield_now();
+            }
+        }
+    }
+
+    fn named<'s, R: Send + 's>(
+        s: &'s thread::Scope<'s, '_>,
+        name: &str,
+        f: impl FnOnce() -> R + Send + 's,
+    ) -> thread::ScopedJoinHandle<'s, R> {
+        thread::Builder::new().name(name.into()).spawn_scoped(s, f).unwrap()
+    }
+
+    #[test]
+    fn waits_for_new_versions_go_through_the_strategy() {
+        let recording = Arc::new(Recording::default());
+        let rcu = Rcu::new(0).with_wait_strategy(Arc::clone(&recording));
+        thread::scope(|s| {
+            let subscriber = named(s, "subscriber", || rcu.wait_for_change(0));
+            recording.until_waited("subscriber");
+            let notifies = recording.notifies.load(Relaxed);
+            rcu.set(1).unwrap();
+            assert!(recording.notifies.load(Relaxed) > notifies, "the publish notified nobody");
+            assert_eq!(subscriber.join().unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn waits_for_readers_go_through_the_strategy() {
+        let recording = Arc::new(Recording::default());
+        let rcu = Rcu::new(0).with_wait_strategy(Arc::clone(&recording));
+        let (entered, wait_entered) = mpsc::channel();
+        let (exit, wait_exit) = mpsc::channel::<()>();
+        thread::scope(|s| {
+            let rcu = &rcu;
+            named(s, "reader", move || {
+                rcu.read_with(|_| {
+                    entered.send(()).unwrap();
+                    let _ = wait_exit.recv();
+                })
+            });
+            wait_entered.recv().unwrap();
+            // The second publish retires the value read, engines that don't wait for its readers
+            // then do in the barrier
+            let writer = named(s, "writer", || {
+                rcu.set(1).unwrap();
+                rcu.set(2).unwrap();
+                rcu.barrier();
+            });
+            recording.until_waited("writer");
+            drop(exit);
+            writer.join().unwrap();
+        });
+        assert_eq!(rcu.read(), 2);
+    }
+
+    #[test]
+    fn conflicting_writers_back_off_through_the_strategy() {
+        let recording = Arc::new(Recording::default());
+        let rcu = Rcu::new(0).with_wait_strategy(Arc::clone(&recording));
+        let raced = Barrier::new(2);
+        let retries = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for name in ["first", "second"] {
+                named(s, name, || {
+                    let mut attempts = 0;
+                    rcu.update_with(|value| {
+                        attempts += 1;
+                        // Both compute from the same value, so one of them loses the race
+                        if attempts == 1 {
+                            raced.wait();
+                        } else {
+                            retries.fetch_add(1, SeqCst);
+                        }
+                        value + 1
+                    })
+                    .unwrap();
+                });
+            }
+        });
+        assert_eq!(rcu.read(), 2);
+        assert!(retries.load(SeqCst) > 0, "nobody lost the race");
+        assert!(recording.waited("first") || recording.waited("second"), "the loser didn't back off");
+    }
+
+    #[cfg(all(feature = "reclaim-counted", not(feature = "fallback-lock")))]
+    #[test]
+    fn readers_held_back_by_a_writer_wait_through_the_strategy() {
+        let recording = Arc::new(Recording::default());
+        let rcu = Rcu::new(0).with_wait_strategy(Arc::clone(&recording));
+        let (entered, wait_entered) = mpsc::channel();
+        let (exit, wait_exit) = mpsc::channel::<()>();
+        thread::scope(|s| {
+            let rcu = &rcu;
+            named(s, "reader", move || {
+                rcu.read_with(|_| {
+                    entered.send(()).unwrap();
+                    let _ = wait_exit.recv();
+                })
+            });
+            wait_entered.recv().unwrap();
+            // The second publish waits for the reader of the value it retires
+            named(s, "writer", || {
+                rcu.set(1).unwrap();
+                rcu.set(2).unwrap();
+            });
+            recording.until_waited("writer");
+            let late = named(s, "late reader", || rcu.read());
+            recording.until_waited("late reader");
+            drop(exit);
+            assert_eq!(late.join().unwrap(), 2);
+        });
+    }
+}
@@ -0,0 +1,248 @@
+use std::ffi::OsString;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use ::notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::error::WatchError;
+use crate::{PublishError, Rcu};
+
+/// How long a file must go without change events before it is re-read, editors saving a file in
+/// several steps.
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+type ErrorCallback<E> = Arc<dyn Fn(&WatchError<E>) + Send + Sync>;
+
+/// The `Rcu` fed by a watcher, with the guard keeping it running.
+type Watched<T, E> = (Arc<Rcu<T>>, WatcherGuard<E>);
+
+/// What the watcher thread receives.
+enum Message {
+    Changed(::notify::Result<Event>),
+    Stop,
+}
+
+/// What the watcher thread and its guard share.
+struct Shared<E> {
+    on_error: Mutex<Option<ErrorCallback<E>>>,
+    /// Values published
+    reloads: AtomicU64,
+    /// Errors reported
+    errors: AtomicU64,
+    /// Calls of `parse` that panicked
+    panics: AtomicU64,
+}
+
+/// Reads the file at `path`, parses its contents with `parse` and publishes the result to a new
+/// `Rcu`, then keeps doing so whenever the file changes, until the returned guard is dropped.
+///
+/// Changes are noticed through the [`notify`](https://docs.rs/notify) crate, watching the
+/// directory holding the file so editors replacing it rather than writing it in place are noticed
+/// too. A burst of change events is waited out before the file is re-read, and contents identical
+/// to those last read aren't published again. If the file can't be read or parsed, the current
+/// value is kept and the error passed to the callback set with [`WatcherGuard::on_error`]. A panic
+/// in `parse` is caught and counted, see [`WatcherGuard::panics`], and the watcher carries on.
+///
+/// Fails if the file can't be read or parsed at first, or can't be watched. The watcher thread
+/// exits once the guard is dropped or stopped, once the `Rcu` is frozen, or once every `Arc` of it
+/// was dropped.
+///
+/// ```
+/// # use std::fs;
+/// # use rcu_rust::watch_file;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let path = std::env::temp_dir().join(format!("watch_file_doc_{}.txt", std::process::id()));
+/// fs::write(&path, "42")?;
+/// let (config, watcher) = watch_file(&path, |bytes| String::from_utf8_lossy(bytes).trim().parse::<u32>())?;
+/// assert_eq!(config.read(), 42);
+/// watcher.stop();
+/// fs::remove_file(&path)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn watch_file<T, E>(
+    path: impl AsRef<Path>,
+    parse: impl Fn(&[u8]) -> Result<T, E> + Send + 'static,
+) -> Result<Watched<T, E>, WatchError<E>>
+where
+    T: Clone + Send + Sync + 'static,
+    E: 'static,
+{
+    let path = path.as_ref().to_path_buf();
+    let contents = fs::read(&path).map_err(WatchError::Io)?;
+    let rcu = Arc::new(Rcu::new(parse(&contents).map_err(WatchError::Parse)?));
+    let guard = WatcherGuard::spawn(Arc::downgrade(&rcu), path, contents, parse)?;
+    Ok((rcu, guard))
+}
+
+/// Keeps the file watcher started with [`watch_file`] running. Dropping it stops the watcher, like
+/// [`WatcherGuard::stop`] does.
+pub struct WatcherGuard<E> {
+    shared: Arc<Shared<E>>,
+    stop: Sender<Message>,
+    watcher: Option<RecommendedWatcher>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<E: 'static> WatcherGuard<E> {
+    /// Watches `path`, whose `contents` were published already, publishing to `rcu` what `parse`
+    /// makes of it whenever it changes.
+    fn spawn<T: Clone + Send + Sync + 'static>(
+        rcu: Weak<Rcu<T>>,
+        path: PathBuf,
+        contents: Vec<u8>,
+        parse: impl Fn(&[u8]) -> Result<T, E> + Send + 'static,
+    ) -> Result<Self, WatchError<E>> {
+        let name = path.file_name().map(OsString::from).unwrap_or_default();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = ::notify::recommended_watcher({
+            let sender = sender.clone();
+            move |event| {
+                let _ = sender.send(Message::Changed(event));
+            }
+        })
+        .map_err(WatchError::Watch)?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive).map_err(WatchError::Watch)?;
+        let shared = Arc::new(Shared {
+            on_error: Mutex::new(None),
+            reloads: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            panics: AtomicU64::new(0),
+        });
+        let thread = thread::Builder::new()
+            .name("rcu-watcher".into())
+            .spawn({
+                let shared = Arc::clone(&shared);
+                move || {
+                    let mut last = contents;
+                    while wait_for_change(&receiver, &name, &shared) {
+                        let Some(rcu) = rcu.upgrade() else {
+                            return;
+                        };
+                        let parsed = match fs::read(&path) {
+                            Ok(contents) if contents == last => continue,
+                            Ok(contents) => {
+                                last = contents;
+                                match panic::catch_unwind(AssertUnwindSafe(|| parse(&last))) {
+                                    Ok(parsed) => parsed.map_err(WatchError::Parse),
+                                    Err(_) => {
+                                        shared.panics.fetch_add(1, Relaxed);
+                                        continue;
+                                    }
+                                }
+                            }
+                            Err(err) => Err(WatchError::Io(err)),
+                        };
+                        match parsed.map(|value| rcu.set(value)) {
+                            Ok(Ok(())) => {
+                                shared.reloads.fetch_add(1, Relaxed);
+                            }
+                            Ok(Err(PublishError::Frozen { .. })) => return,
+                            Ok(Err(PublishError::Rejected { .. } | PublishError::Backpressure { .. })) => {}
+                            Err(err) => shared.report(&err),
+                        }
+                    }
+                }
+            })
+            .expect("failed to spawn the watcher thread");
+        Ok(Self {
+            shared,
+            stop: sender,
+            watcher: Some(watcher),
+            thread: Some(thread),
+        })
+    }
+
+    /// Sets a callback to be called with every error reading, parsing or watching the file,
+    /// replacing the previous one. It runs on the watcher thread; a panic in it is ignored.
+    pub fn on_error(&self, f: impl Fn(&WatchError<E>) + Send + Sync + 'static) {
+        *self.shared.on_error.lock().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(f));
+    }
+}
+
+impl<E> WatcherGuard<E> {
+    /// Stops the watcher, waiting for a reload in progress to complete.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    /// Number of values the watcher published so far, not counting the first one.
+    pub fn reloads(&self) -> u64 {
+        self.shared.reloads.load(Relaxed)
+    }
+
+    /// Number of errors reading, parsing or watching the file so far.
+    pub fn errors(&self) -> u64 {
+        self.shared.errors.load(Relaxed)
+    }
+
+    /// Number of calls of `parse` that panicked so far, which keep the current value like errors.
+    pub fn panics(&self) -> u64 {
+        self.shared.panics.load(Relaxed)
+    }
+
+    fn shutdown(&mut self) {
+        // Stops the events before the thread, which may be waiting for them
+        drop(self.watcher.take());
+        let _ = self.stop.send(Message::Stop);
+        if let Some(thread) = self.thread.take() {
+            // Panics in `parse` are caught, the thread can't fail
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<E> Shared<E> {
+    fn report(&self, err: &WatchError<E>) {
+        self.errors.fetch_add(1, Relaxed);
+        let on_error = self.on_error.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        if let Some(on_error) = on_error {
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| on_error(err)));
+        }
+    }
+}
+
+/// Blocks until the file `name` changed and then went `DEBOUNCE` without changing again, returning
+/// false once stopped. Watch errors are reported meanwhile.
+fn wait_for_change<E>(receiver: &Receiver<Message>, name: &OsString, shared: &Shared<E>) -> bool {
+    let mut changed = false;
+    loop {
+        let message = if changed {
+            match receiver.recv_timeout(DEBOUNCE) {
+                Ok(message) => message,
+                Err(RecvTimeoutError::Timeout) => return true,
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+        } else {
+            match receiver.recv() {
+                Ok(message) => message,
+                Err(_) => return false,
+            }
+        };
+        match message {
+            Message::Stop => return false,
+            Message::Changed(Ok(event)) => {
+                // Reading the file generates access events of its own
+                changed |= !matches!(event.kind, EventKind::Access(_))
+                    && event.paths.iter().any(|path| path.file_name() == Some(name.as_os_str()));
+            }
+            Message::Changed(Err(err)) => shared.report(&WatchError::Watch(err)),
+        }
+    }
+}
+
+impl<E> Drop for WatcherGuard<E> {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
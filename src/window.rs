@@ -0,0 +1,322 @@
+use std::collections::VecDeque;
+use std::mem;
+use std::sync::Arc;
+
+use crate::{Rcu, RcuReader, SingleWriter};
+
+/// One generation of the samples of a window, oldest first. Full chunks are never changed again,
+/// so generations share them and a push only copies the chunk list and the tail.
+#[derive(Clone)]
+struct Window<T> {
+    capacity: usize,
+    /// Number of samples a chunk is sealed at, about the square root of the capacity, which keeps
+    /// both the chunk list and the tail copied by a push short
+    chunk_len: usize,
+    chunks: VecDeque<Arc<[T]>>,
+    /// Samples of the first chunk already out of the window
+    skip: usize,
+    /// Samples pushed since the last chunk was sealed
+    tail: Vec<T>,
+    /// Samples in the window
+    len: usize,
+}
+
+impl<T> Window<T> {
+    fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "a window needs to hold at least one sample");
+        let chunk_len = capacity.isqrt().max(1);
+        Self {
+            capacity,
+            chunk_len,
+            chunks: VecDeque::new(),
+            skip: 0,
+            tail: Vec::with_capacity(chunk_len),
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, sample: T) {
+        self.tail.push(sample);
+        if self.tail.len() == self.chunk_len {
+            let tail = mem::replace(&mut self.tail, Vec::with_capacity(self.chunk_len));
+            self.chunks.push_back(tail.into());
+        }
+        self.len += 1;
+        if self.len > self.capacity {
+            self.drop_oldest(self.len - self.capacity);
+        }
+    }
+
+    /// Moves the `count` oldest samples out of the window. The tail is always shorter than the
+    /// capacity, so they are all in chunks.
+    fn drop_oldest(&mut self, count: usize) {
+        self.len -= count;
+        self.skip += count;
+        while let Some(oldest) = self.chunks.front().filter(|oldest| oldest.len() <= self.skip) {
+            self.skip -= oldest.len();
+            self.chunks.pop_front();
+        }
+    }
+
+    /// The samples in the window, oldest first.
+    fn iter(&self) -> impl Iterator<Item = &T> {
+        self.chunks.iter().flat_map(|chunk| chunk.iter()).skip(self.skip).chain(&self.tail)
+    }
+}
+
+/// The last `capacity` samples pushed, behind an `Rcu`, for metrics read as a whole while samples
+/// keep coming in: every push publishes a new generation of the window, and readers always see one
+/// generation as a whole, never a sample moving in without the oldest one moving out.
+///
+/// Samples are kept in chunks of about the square root of the capacity, shared between
+/// generations once full, so a push copies the list of chunks and the chunk being filled, O(√N)
+/// for a window of N samples, rather than the whole window. Any number of threads may push, a push
+/// retrying on the newer window whenever another one got there first; with a single producer,
+/// [`RcuWindow::single_producer`] pushes without ever retrying.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use rcu_rust::RcuWindow;
+/// let latencies = RcuWindow::new(1024);
+/// for ms in [3, 12, 5] {
+///     latencies.push(Duration::from_millis(ms));
+/// }
+/// let slowest = latencies.fold(Duration::ZERO, |slowest, &latency| slowest.max(latency));
+/// assert_eq!(slowest, Duration::from_millis(12));
+/// ```
+pub struct RcuWindow<T: Clone> {
+    window: Rcu<Window<T>>,
+}
+
+impl<T: Clone> RcuWindow<T> {
+    /// Creates an empty window of the last `capacity` samples.
+    ///
+    /// # Panics
+    /// If `capacity` is 0.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            window: Rcu::new(Window::new(capacity)),
+        }
+    }
+    /// Creates an empty window of the last `capacity` samples, split into its only producer and a
+    /// handle for readers, see [`Rcu::into_single_writer`].
+    ///
+    /// # Panics
+    /// If `capacity` is 0.
+    pub fn single_producer(capacity: usize) -> (WindowProducer<T>, WindowReader<T>) {
+        let Ok((writer, reader)) = Rcu::new(Window::new(capacity)).into_single_writer() else {
+            unreachable!("a new `Rcu` has neither invariants nor limits, nor is it frozen")
+        };
+        (WindowProducer { writer }, WindowReader { reader })
+    }
+    /// Pushes `sample`, the oldest sample moving out of the window if it was full.
+    #[track_caller]
+    pub fn push(&self, sample: T) {
+        // No invariants nor limits are ever set on the private `Rcu`, nor is it frozen
+        let _ = self.window.update_mut(|window| window.push(sample.clone()));
+    }
+    /// Pushes every sample of `samples` with a single publish, so readers see all of them or none.
+    #[track_caller]
+    pub fn push_all(&self, samples: impl IntoIterator<Item = T>) {
+        let samples: Vec<T> = samples.into_iter().collect();
+        let _ = self.window.update_mut(|window| samples.iter().cloned().for_each(|sample| window.push(sample)));
+    }
+    /// A copy of the samples in the window, oldest first.
+    #[track_caller]
+    pub fn snapshot(&self) -> Vec<T> {
+        self.window.read_with(|window| window.iter().cloned().collect())
+    }
+    /// Folds the samples in the window, oldest first, into `init` with `f`, without copying them,
+    /// inside a read-side critical section, see [`Rcu::read_with`].
+    #[track_caller]
+    pub fn fold<A>(&self, init: A, f: impl FnMut(A, &T) -> A) -> A {
+        self.window.read_with(|window| window.iter().fold(init, f))
+    }
+    /// Number of samples in the window, up to its capacity.
+    #[track_caller]
+    pub fn len(&self) -> usize {
+        self.window.read_with(|window| window.len)
+    }
+    /// Whether no sample was pushed yet.
+    #[track_caller]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Number of samples the window holds once full.
+    #[track_caller]
+    pub fn capacity(&self) -> usize {
+        self.window.read_with(|window| window.capacity)
+    }
+}
+
+/// The only producer of a window created with [`RcuWindow::single_producer`]. Every push takes
+/// `&mut self`, so pushes can never race, and never retry.
+pub struct WindowProducer<T: Clone> {
+    writer: SingleWriter<Window<T>>,
+}
+
+impl<T: Clone> WindowProducer<T> {
+    /// See [`RcuWindow::push`].
+    pub fn push(&mut self, sample: T) {
+        self.writer.publish_with(|window| {
+            let mut window = window.clone();
+            window.push(sample);
+            window
+        });
+    }
+    /// See [`RcuWindow::push_all`].
+    pub fn push_all(&mut self, samples: impl IntoIterator<Item = T>) {
+        self.writer.publish_with(|window| {
+            let mut window = window.clone();
+            samples.into_iter().for_each(|sample| window.push(sample));
+            window
+        });
+    }
+    /// Another handle for readers.
+    pub fn reader(&self) -> WindowReader<T> {
+        WindowReader {
+            reader: self.writer.reader(),
+        }
+    }
+}
+
+/// Reads a window created with [`RcuWindow::single_producer`]. Cheap to clone, each clone reading
+/// the same window.
+pub struct WindowReader<T: Clone> {
+    reader: RcuReader<Window<T>>,
+}
+
+impl<T: Clone> Clone for WindowReader<T> {
+    fn clone(&self) -> Self {
+        Self {
+            reader: self.reader.clone(),
+        }
+    }
+}
+
+impl<T: Clone> WindowReader<T> {
+    /// See [`RcuWindow::snapshot`].
+    #[track_caller]
+    pub fn snapshot(&self) -> Vec<T> {
+        self.reader.read_with(|window| window.iter().cloned().collect())
+    }
+    /// See [`RcuWindow::fold`].
+    #[track_caller]
+    pub fn fold<A>(&self, init: A, f: impl FnMut(A, &T) -> A) -> A {
+        self.reader.read_with(|window| window.iter().fold(init, f))
+    }
+    /// See [`RcuWindow::len`].
+    #[track_caller]
+    pub fn len(&self) -> usize {
+        self.reader.read_with(|window| window.len)
+    }
+    /// See [`RcuWindow::is_empty`].
+    #[track_caller]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// See [`RcuWindow::capacity`].
+    #[track_caller]
+    pub fn capacity(&self) -> usize {
+        self.reader.read_with(|window| window.capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use std::thread;
+
+    use super::*;
+
+    /// Capacities filling whole chunks, leaving a partial tail, and of a single sample
+    const CAPACITIES: [usize; 3] = [16, 10, 1];
+    const PUSHES: usize = 2000;
+    const PRODUCERS: usize = 3;
+
+    /// Checks that `window` is a run of consecutive samples, as the last pushes of one producer are.
+    fn assert_consecutive(window: &[usize], capacity: usize) {
+        assert!(window.len() <= capacity, "{} samples in a window of {capacity}", window.len());
+        assert!(window.windows(2).all(|pair| pair[1] == pair[0] + 1), "not the last pushes: {window:?}");
+        if let Some(&newest) = window.last() {
+            assert_eq!(window.len(), capacity.min(newest + 1), "samples missing from {window:?}");
+        }
+    }
+
+    #[test]
+    fn single_producer_windows_are_the_last_pushes() {
+        for capacity in CAPACITIES {
+            let (mut producer, reader) = RcuWindow::single_producer(capacity);
+            let done = AtomicBool::new(false);
+            thread::scope(|s| {
+                for _ in 0..2 {
+                    let reader = reader.clone();
+                    let done = &done;
+                    s.spawn(move || {
+                        while !done.load(SeqCst) {
+                            assert_consecutive(&reader.snapshot(), capacity);
+                            thread::yield_now();
+                        }
+                    });
+                }
+                for sample in 0..PUSHES {
+                    producer.push(sample);
+                    if sample % 7 == 0 {
+                        thread::yield_now();
+                    }
+                }
+                done.store(true, SeqCst);
+            });
+            assert_eq!(reader.snapshot(), (PUSHES - capacity..PUSHES).collect::<Vec<_>>());
+            assert_eq!(reader.len(), capacity);
+        }
+    }
+
+    #[test]
+    fn multi_producer_windows_are_the_last_pushes() {
+        for capacity in CAPACITIES {
+            let window = RcuWindow::new(capacity);
+            let done = AtomicBool::new(false);
+            // Samples are `(producer, push)`, each producer's pushes a run of consecutive samples
+            let runs = |samples: Vec<(usize, usize)>| {
+                (0..PRODUCERS)
+                    .map(|producer| samples.iter().filter(|&&(p, _)| p == producer).map(|&(_, push)| push).collect())
+                    .collect::<Vec<Vec<usize>>>()
+            };
+            thread::scope(|s| {
+                s.spawn(|| {
+                    while !done.load(SeqCst) {
+                        let snapshot = window.snapshot();
+                        assert!(snapshot.len() <= capacity, "{} samples in a window of {capacity}", snapshot.len());
+                        for run in runs(snapshot) {
+                            assert!(run.windows(2).all(|pair| pair[1] == pair[0] + 1), "not the last pushes: {run:?}");
+                        }
+                        thread::yield_now();
+                    }
+                });
+                let producers: Vec<_> = (0..PRODUCERS)
+                    .map(|producer| {
+                        let window = &window;
+                        s.spawn(move || {
+                            for push in 0..PUSHES {
+                                window.push((producer, push));
+                                if push % 7 == 0 {
+                                    thread::yield_now();
+                                }
+                            }
+                        })
+                    })
+                    .collect();
+                producers.into_iter().for_each(|producer| producer.join().unwrap());
+                done.store(true, SeqCst);
+            });
+            // No push lost: the window is full, and every producer in it ends with its last push
+            let snapshot = window.snapshot();
+            assert_eq!(snapshot.len(), capacity);
+            for run in runs(snapshot).into_iter().filter(|run| !run.is_empty()) {
+                assert_eq!(run, (PUSHES - run.len()..PUSHES).collect::<Vec<_>>());
+            }
+            assert_eq!(window.len(), capacity);
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Weak};
+use std::thread::{self, JoinHandle};
+
+use crate::{PublishError, Rcu};
+
+/// A change sent to a writer pool.
+type Op<T> = Box<dyn FnOnce(&mut T) + Send>;
+
+/// What the publishing thread receives.
+enum Message<T> {
+    Op(Op<T>),
+    /// Sent by the handle: publish what is queued, then exit
+    Stop,
+}
+
+impl<T: Clone + Send + Sync + 'static> Rcu<T> {
+    /// Spawns a thread publishing the changes sent through the returned [`UpdateSender`], one
+    /// publish per change, so that writers on any number of threads enqueue their changes instead
+    /// of racing each other to publish. See [`Rcu::writer_pool_batched`].
+    pub fn writer_pool(self: &Arc<Self>) -> (UpdateSender<T>, PoolHandle) {
+        self.writer_pool_batched(1)
+    }
+    /// Like [`Rcu::writer_pool`], applying up to `max_batch` queued changes to the same copy of the
+    /// value before publishing it, which readers then see all at once.
+    ///
+    /// Changes are applied in the order they were sent, so in the order each sender sent them. The
+    /// publishing thread reads the current value, applies the changes to a copy of it and publishes
+    /// that, so it should be the only writer: a publish from elsewhere in between is overwritten.
+    ///
+    /// A panic in a change is caught and counted, see [`PoolHandle::panics`], and the batch it was
+    /// applied with is dropped rather than published half-changed. Values an invariant rejects
+    /// aren't published either. The thread exits once the handle is dropped or stopped, after
+    /// publishing what was sent before, once the `Rcu` is frozen, or once every other `Arc` of it
+    /// was dropped.
+    ///
+    /// # Panics
+    /// If `max_batch` is 0.
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use std::sync::Arc;
+    /// # use rcu_rust::Rcu;
+    /// let index = Arc::new(Rcu::new(HashMap::new()));
+    /// let (sender, pool) = index.writer_pool_batched(64);
+    /// sender.send(|index: &mut HashMap<&str, u32>| {
+    ///     index.insert("key", 1);
+    /// });
+    /// drop(sender);
+    /// pool.stop();
+    /// assert_eq!(index.read()["key"], 1);
+    /// ```
+    pub fn writer_pool_batched(self: &Arc<Self>, max_batch: usize) -> (UpdateSender<T>, PoolHandle) {
+        assert!(max_batch > 0, "a writer pool needs to apply at least one change per publish");
+        let (sender, receiver) = mpsc::channel();
+        let shared = Arc::new(Shared::default());
+        let rcu = Arc::downgrade(self);
+        let thread = thread::Builder::new()
+            .name("rcu-writer-pool".into())
+            .spawn({
+                let shared = Arc::clone(&shared);
+                move || publish_sent(rcu, receiver, max_batch, &shared)
+            })
+            .expect("failed to spawn the writer pool thread");
+        let handle = PoolHandle {
+            shared,
+            stop: Box::new({
+                let sender = sender.clone();
+                move || {
+                    let _ = sender.send(Message::Stop);
+                }
+            }),
+            thread: Some(thread),
+        };
+        (UpdateSender { sender }, handle)
+    }
+}
+
+/// Publishes the changes `receiver` gets to `rcu`, `max_batch` at a time, until stopped.
+fn publish_sent<T: Clone>(rcu: Weak<Rcu<T>>, receiver: Receiver<Message<T>>, max_batch: usize, shared: &Shared) {
+    let mut stopping = false;
+    loop {
+        // Once stopping, only what was queued before is left to publish
+        let first = if stopping { receiver.try_recv().ok() } else { receiver.recv().ok() };
+        let mut batch = match first {
+            Some(Message::Op(op)) => vec![op],
+            Some(Message::Stop) => {
+                stopping = true;
+                continue;
+            }
+            None => return,
+        };
+        while batch.len() < max_batch {
+            match receiver.try_recv() {
+                Ok(Message::Op(op)) => batch.push(op),
+                Ok(Message::Stop) => stopping = true,
+                Err(_) => break,
+            }
+        }
+        let Some(rcu) = rcu.upgrade() else {
+            return;
+        };
+        let ops = batch.len() as u64;
+        let mut value = rcu.read();
+        let applied = panic::catch_unwind(AssertUnwindSafe(|| batch.into_iter().for_each(|op| op(&mut value))));
+        if applied.is_err() {
+            shared.panics.fetch_add(1, Relaxed);
+            continue;
+        }
+        match rcu.set(value) {
+            Ok(()) => {
+                shared.publishes.fetch_add(1, Relaxed);
+                shared.applied.fetch_add(ops, Relaxed);
+            }
+            Err(PublishError::Frozen { .. }) => return,
+            Err(PublishError::Rejected { .. } | PublishError::Backpressure { .. }) => {}
+        }
+    }
+}
+
+/// Sends changes to the publishing thread of a writer pool, created with [`Rcu::writer_pool`].
+/// Clones send to the same pool.
+pub struct UpdateSender<T> {
+    sender: Sender<Message<T>>,
+}
+
+impl<T> UpdateSender<T> {
+    /// Queues `op` to be applied to the value and published, without waiting for it. Returns
+    /// false if the pool's thread exited, `op` is then dropped.
+    pub fn send(&self, op: impl FnOnce(&mut T) + Send + 'static) -> bool {
+        self.sender.send(Message::Op(Box::new(op))).is_ok()
+    }
+}
+
+impl<T> Clone for UpdateSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+/// What the publishing thread and its handle share.
+#[derive(Default)]
+struct Shared {
+    /// Values published
+    publishes: AtomicU64,
+    /// Changes published
+    applied: AtomicU64,
+    /// Batches dropped because a change panicked
+    panics: AtomicU64,
+}
+
+/// Controls the publishing thread of a writer pool started with [`Rcu::writer_pool`]. Dropping it
+/// stops the thread, like [`PoolHandle::stop`] does.
+pub struct PoolHandle {
+    shared: Arc<Shared>,
+    /// Tells the thread to stop, sending to it without knowing the value type
+    stop: Box<dyn Fn() + Send + Sync>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PoolHandle {
+    /// Stops the thread once it published every change sent before, waiting for it.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    /// Number of values the pool published so far.
+    pub fn publishes(&self) -> u64 {
+        self.shared.publishes.load(Relaxed)
+    }
+
+    /// Number of changes the values the pool published so far were made of.
+    pub fn applied(&self) -> u64 {
+        self.shared.applied.load(Relaxed)
+    }
+
+    /// Number of batches dropped so far because one of their changes panicked.
+    pub fn panics(&self) -> u64 {
+        self.shared.panics.load(Relaxed)
+    }
+
+    /// Whether the thread exited, stopped or because its `Rcu` is gone or frozen.
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    fn shutdown(&mut self) {
+        (self.stop)();
+        if let Some(thread) = self.thread.take() {
+            // Panics in changes are caught, only a stop from a change itself could fail
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+impl Drop for PoolHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Barrier;
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Long enough for a handle dropped on another thread to have told the pool to stop.
+    const SETTLE: Duration = Duration::from_millis(50);
+
+    /// Sends a change holding the publishing thread until the returned barrier is waited on, once
+    /// the thread started applying it.
+    fn hold(sender: &UpdateSender<Vec<usize>>) -> Arc<Barrier> {
+        let (started, release) = (Arc::new(Barrier::new(2)), Arc::new(Barrier::new(2)));
+        let (start, wait_release) = (Arc::clone(&started), Arc::clone(&release));
+        assert!(sender.send(move |_| {
+            start.wait();
+            wait_release.wait();
+        }));
+        started.wait();
+        release
+    }
+
+    #[test]
+    fn each_senders_changes_apply_in_the_order_sent() {
+        const SENDERS: usize = 4;
+        const CHANGES: usize = 100;
+
+        let rcu = Arc::new(Rcu::new(Vec::new()));
+        let (sender, pool) = rcu.writer_pool_batched(8);
+        thread::scope(|s| {
+            for id in 0..SENDERS {
+                let sender = sender.clone();
+                s.spawn(move || {
+                    for change in 0..CHANGES {
+                        assert!(sender.send(move |log: &mut Vec<(usize, usize)>| log.push((id, change))));
+                    }
+                });
+            }
+        });
+        pool.stop();
+        let log = rcu.read();
+        assert_eq!(log.len(), SENDERS * CHANGES);
+        for id in 0..SENDERS {
+            let sent: Vec<_> = log.iter().filter(|(sender, _)| *sender == id).map(|(_, change)| *change).collect();
+            assert_eq!(sent, (0..CHANGES).collect::<Vec<_>>(), "sender {id}'s changes out of order");
+        }
+    }
+
+    #[test]
+    fn queued_changes_are_published_max_batch_at_a_time() {
+        for (max_batch, publishes) in [(1, 11), (4, 4), (64, 2)] {
+            let rcu = Arc::new(Rcu::new(Vec::new()));
+            let (sender, pool) = rcu.writer_pool_batched(max_batch);
+            let release = hold(&sender);
+            for change in 0..10 {
+                sender.send(move |log: &mut Vec<usize>| log.push(change));
+            }
+            release.wait();
+            pool.stop();
+            assert_eq!(rcu.read(), (0..10).collect::<Vec<_>>());
+            assert_eq!(rcu.version(), publishes, "batches of {max_batch}");
+        }
+    }
+
+    #[test]
+    fn stopping_publishes_the_changes_still_queued() {
+        let stops: [fn(PoolHandle); 2] = [PoolHandle::stop, drop];
+        for stop in stops {
+            let rcu = Arc::new(Rcu::new(Vec::new()));
+            let (sender, pool) = rcu.writer_pool();
+            let release = hold(&sender);
+            for change in 0..50 {
+                sender.send(move |log: &mut Vec<usize>| log.push(change));
+            }
+            let stopping = thread::spawn(move || stop(pool));
+            thread::sleep(SETTLE);
+            release.wait();
+            stopping.join().unwrap();
+            assert_eq!(rcu.read(), (0..50).collect::<Vec<_>>());
+            assert!(!sender.send(|log| log.push(50)), "the pool's thread is still running");
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! The allocation-fallible paths, against a global allocator that fails an allocation on demand.
+//! Only allocations of the payload's size are counted and failed, any other failing would abort
+//! the process; only those of the current thread are, so that tests running in parallel don't
+//! disturb each other.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use rcu_rust::Rcu;
+
+/// A size nothing else allocates, so its allocations are those of boxes of it.
+type Payload = [u8; 12345];
+
+struct Failing;
+
+thread_local! {
+    /// The allocations of a `Payload` left before the one to fail, None for none to fail
+    static FAIL_IN: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+// Safety: defers to `System` for everything, except for failing on demand
+unsafe impl GlobalAlloc for Failing {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout == Layout::new::<Payload>() {
+            // A thread being torn down has no countdown anymore, and fails nothing
+            let fail = FAIL_IN
+                .try_with(|fail_in| match fail_in.get() {
+                    Some(0) => {
+                        fail_in.set(None);
+                        true
+                    }
+                    left => {
+                        fail_in.set(left.map(|left| left - 1));
+                        false
+                    }
+                })
+                .unwrap_or(false);
+            if fail {
+                return std::ptr::null_mut();
+            }
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static FAILING: Failing = Failing;
+
+/// Makes the `n`th next allocation of a `Payload` on this thread fail, counting from 1.
+fn fail_nth(n: usize) {
+    FAIL_IN.with(|fail_in| fail_in.set(Some(n - 1)));
+}
+
+#[test]
+fn try_new_hands_the_value_back() {
+    fail_nth(1);
+    let failed = Rcu::<Payload>::try_new([7; 12345]).err().expect("the allocation failed");
+    assert_eq!(failed.to_string(), "out of memory allocating a [u8; 12345]");
+    let rcu = Rcu::try_new(failed.into_value()).unwrap_or_else(|_| panic!("nothing fails anymore"));
+    assert_eq!(rcu.read_with(|value| value[0]), 7);
+}
+
+#[test]
+fn try_update_leaves_the_rcu_usable() {
+    let rcu: Rcu<Payload> = Rcu::new([0; 12345]);
+    fail_nth(2);
+    let mut failures = 0;
+    for i in 1..=3 {
+        let version = rcu.version();
+        match rcu.try_update([i; 12345]) {
+            Ok(published) => {
+                assert!(published, "nothing else publishes");
+                assert_eq!(rcu.read_with(|value| value[0]), i);
+            }
+            Err(failed) => {
+                failures += 1;
+                assert_eq!(i, 2, "failed the wrong allocation");
+                assert_eq!(failed.value[0], 2, "handed back another value");
+                // Left as it was
+                assert_eq!((rcu.read_with(|value| value[0]), rcu.version()), (1, version));
+            }
+        }
+    }
+    assert_eq!(failures, 1);
+    assert_eq!(rcu.read_with(|value| value[0]), 3);
+    // Every other path still works, and a failure leaves nothing behind for them to trip on
+    rcu.set([4; 12345]).unwrap();
+    assert_eq!(rcu.read_prev().map(|value| value[0]), Some(3));
+    rcu.barrier();
+    assert_eq!(rcu.outstanding_allocations(), 2);
+}
@@ -0,0 +1,167 @@
+//! Allocation counts of the paths that promise them, measured by a global allocator counting what
+//! the current thread allocates, so that tests running in parallel don't disturb each other. Not
+//! built with `fallback-lock`, which doesn't promise them: every value is moved into an `Arc`.
+#![cfg(not(feature = "fallback-lock"))]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::mem::MaybeUninit;
+use std::ptr;
+
+use rcu_rust::Rcu;
+
+struct Counting;
+
+thread_local! {
+    /// Allocations made by this thread so far
+    static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+}
+
+// Safety: defers to `System` for everything, only counting
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // A thread being torn down has no counter anymore, its allocations aren't measured
+        let _ = ALLOCATED.try_with(|allocated| allocated.set(allocated.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static COUNTING: Counting = Counting;
+
+/// Number of allocations `f` makes on this thread, along with what it returns.
+fn allocations<R>(f: impl FnOnce() -> R) -> (usize, R) {
+    let before = ALLOCATED.with(Cell::get);
+    let out = f();
+    (ALLOCATED.with(Cell::get) - before, out)
+}
+
+/// Big enough that a copy would show, small enough for the test thread's stack.
+type Payload = [u64; 512];
+
+fn address(rcu: &Rcu<Payload>) -> *const Payload {
+    rcu.read_with(|value| value as *const Payload)
+}
+
+#[test]
+fn from_box_reuses_the_allocation() {
+    let (by_value, _) = allocations(|| Rcu::new([1; 512]));
+    let boxed = Box::new([2; 512]);
+    let ptr = &*boxed as *const Payload;
+    let (by_box, rcu) = allocations(|| Rcu::from_box(boxed));
+    assert_eq!(by_box + 1, by_value, "boxing the value is the only allocation `from_box` saves");
+    assert_eq!(address(&rcu), ptr);
+}
+
+#[test]
+fn update_box_allocates_nothing() {
+    let rcu = Rcu::new([0; 512]);
+    // Anything allocated lazily by the first publishes is out of the way, and the lists of engines
+    // queueing displaced values have grown as long as they get
+    for i in 1..8 {
+        rcu.update_box(Box::new([i; 512])).unwrap();
+    }
+    for i in 8..100 {
+        let boxed = Box::new([i; 512]);
+        let ptr = &*boxed as *const Payload;
+        let (allocated, published) = allocations(|| rcu.update_box(boxed));
+        assert!(published.is_ok());
+        // Unless `diagnostics` keeps a record of the grace period
+        if !cfg!(feature = "diagnostics") {
+            assert_eq!(allocated, 0);
+        }
+        assert_eq!(address(&rcu), ptr);
+    }
+}
+
+#[test]
+fn a_refused_box_is_handed_back_untouched() {
+    let rcu: Rcu<Payload> = Rcu::new([0; 512]);
+    rcu.set_invariant(|value| if value[0] == 0 { Ok(()) } else { Err("nonzero".into()) });
+    let boxed = Box::new([1; 512]);
+    let ptr = &*boxed as *const Payload;
+    let refused = rcu.update_box(boxed).unwrap_err();
+    assert_eq!(&*refused as *const Payload, ptr);
+    assert_eq!(refused[0], 1);
+}
+
+#[test]
+fn into_box_hands_the_allocation_out() {
+    let rcu = Rcu::new([0; 512]);
+    rcu.update_box(Box::new([1; 512])).unwrap();
+    let ptr = address(&rcu);
+    let (allocated, boxed) = allocations(|| rcu.into_box());
+    assert_eq!(allocated, 0);
+    assert_eq!(&*boxed as *const Payload, ptr);
+    assert_eq!(boxed[0], 1);
+}
+
+/// Far too big for the test thread's stack, so that any copy through it would overflow it.
+type Huge = [u64; 1 << 20];
+
+fn zeroed(slot: &mut MaybeUninit<Huge>) -> &mut Huge {
+    // Safety: all zeroes is a valid array of integers
+    unsafe {
+        slot.as_mut_ptr().write_bytes(0, 1);
+        slot.assume_init_mut()
+    }
+}
+
+#[test]
+fn emplace_initializes_the_value_in_its_allocation() {
+    let mut slot_address = ptr::null();
+    let (by_emplace, rcu) = allocations(|| {
+        Rcu::emplace(|slot: &mut MaybeUninit<Huge>| {
+            slot_address = slot.as_ptr();
+            zeroed(slot)
+        })
+    });
+    assert_eq!(rcu.read_with(|value| value as *const Huge), slot_address);
+    let (by_box, _) = allocations(|| Rcu::from_box(Rcu::<Huge>::emplace_box(zeroed)));
+    assert_eq!(by_emplace, by_box, "the slot is the only allocation besides those of `from_box`");
+}
+
+#[test]
+fn update_emplace_publishes_the_slot_it_initialized() {
+    let rcu: Rcu<Huge> = Rcu::emplace(zeroed);
+    // As in `update_box_allocates_nothing`
+    for _ in 0..8 {
+        rcu.update_emplace(zeroed).unwrap();
+    }
+    for i in 1..20 {
+        let mut slot_address = ptr::null();
+        let (allocated, published) = allocations(|| {
+            rcu.update_emplace(|slot| {
+                slot_address = slot.as_ptr();
+                let value = zeroed(slot);
+                value[0] = i;
+                value
+            })
+        });
+        assert!(published.is_ok());
+        if !cfg!(feature = "diagnostics") {
+            assert_eq!(allocated, 1, "allocated more than the slot");
+        }
+        assert_eq!(rcu.read_with(|value| (value as *const Huge, value[0])), (slot_address, i));
+    }
+}
+
+#[test]
+fn emplaced_boxes_are_published_as_they_are() {
+    let rcu: Rcu<Huge> = Rcu::emplace(zeroed);
+    let boxed = Rcu::<Huge>::emplace_box(zeroed);
+    let ptr = &*boxed as *const Huge;
+    rcu.update_box(boxed).unwrap();
+    assert_eq!(rcu.read_with(|value| value as *const Huge), ptr);
+}
+
+#[test]
+#[should_panic(expected = "other than the slot")]
+fn emplace_refuses_references_to_anything_but_the_slot() {
+    let elsewhere: &'static mut Payload = Box::leak(Box::new([1; 512]));
+    let _ = Rcu::<Payload>::emplace(move |_slot| elsewhere);
+}
@@ -0,0 +1,170 @@
+//! The async publishing paths, driven by a single-threaded tokio runtime, where any blocking wait
+//! would stall every other task.
+#![cfg(feature = "async")]
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::{stream, StreamExt};
+use rcu_rust::{PublishError, Rcu};
+
+/// How long the slow reader holds on to the value it read
+const SLOW_READ: Duration = Duration::from_millis(200);
+
+/// Counts its drops.
+#[derive(Clone, Debug)]
+struct Tracked {
+    value: u64,
+    drops: Arc<AtomicUsize>,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, SeqCst);
+    }
+}
+
+/// An `Rcu` of `current` after `previous`: the value a publish replaces stays readable through
+/// `Rcu::read_prev`, so it is `previous` the next publish displaces and waits for the readers of.
+fn after<T: Clone>(previous: T, current: T) -> Arc<Rcu<T>> {
+    let rcu = Rcu::new(previous);
+    rcu.set(current).unwrap_or_else(|_| unreachable!("nothing refuses the value"));
+    Arc::new(rcu)
+}
+
+/// Starts a thread holding a read guard of `rcu` for `SLOW_READ`, returning once it holds it. The
+/// thread sets `released` right before it lets go of the guard.
+fn slow_reader<T>(rcu: &Arc<Rcu<T>>, released: &Arc<AtomicBool>) -> thread::JoinHandle<()>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    let (rcu, released) = (Arc::clone(rcu), Arc::clone(released));
+    let (reading, wait_reading) = mpsc::channel();
+    let reader = thread::spawn(move || {
+        let guard = rcu.read_guard();
+        reading.send(()).unwrap();
+        thread::sleep(SLOW_READ);
+        released.store(true, SeqCst);
+        drop(guard);
+    });
+    wait_reading.recv().unwrap();
+    reader
+}
+
+/// Spawns a task on the current runtime counting how many times it got to run, 1 ms apart.
+fn ticker() -> Arc<AtomicUsize> {
+    let ticks = Arc::new(AtomicUsize::new(0));
+    let counted = Arc::clone(&ticks);
+    tokio::spawn(async move {
+        loop {
+            counted.fetch_add(1, SeqCst);
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    });
+    ticks
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn other_tasks_run_while_update_async_awaits_a_slow_reader() {
+    let rcu = after(0u64, 1);
+    let released = Arc::new(AtomicBool::new(false));
+    let reader = slow_reader(&rcu, &released);
+    let ticks = ticker();
+    let start = Instant::now();
+    assert!(rcu.update_async(2).await);
+    // Only complete once the reader, that may still have been reading the displaced value, let go
+    assert!(released.load(SeqCst));
+    assert!(start.elapsed() >= SLOW_READ / 2, "returned after {:?}", start.elapsed());
+    assert_eq!(rcu.read(), 2);
+    let ticked = ticks.load(SeqCst);
+    assert!(ticked >= 10, "the other task only ran {ticked} times");
+    reader.join().unwrap();
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn other_tasks_run_while_update_with_async_awaits_a_slow_reader() {
+    let rcu = after(0u64, 1);
+    let released = Arc::new(AtomicBool::new(false));
+    let reader = slow_reader(&rcu, &released);
+    let ticks = ticker();
+    assert_eq!(rcu.update_with_async(|n| n * 10).await.unwrap(), 10);
+    assert!(released.load(SeqCst));
+    assert_eq!(rcu.read(), 10);
+    let ticked = ticks.load(SeqCst);
+    assert!(ticked >= 10, "the other task only ran {ticked} times");
+    reader.join().unwrap();
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn a_cancelled_update_leaves_freeing_to_a_later_grace_period() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let tracked = |value| Tracked {
+        value,
+        drops: Arc::clone(&drops),
+    };
+    let rcu = after(tracked(0), tracked(1));
+    let released = Arc::new(AtomicBool::new(false));
+    let reader = slow_reader(&rcu, &released);
+    let update = rcu.update_async(tracked(2));
+    assert!(tokio::time::timeout(Duration::from_millis(10), update).await.is_err(), "the reader is still there");
+    // Cancelled, but published all the same, and the displaced value may still be read
+    assert_eq!(rcu.read().value, 2);
+    assert!(!released.load(SeqCst));
+    // Every `tracked` dropped so far is a temporary clone
+    let dropped = drops.load(SeqCst);
+    reader.join().unwrap();
+    rcu.barrier();
+    assert_eq!(drops.load(SeqCst), dropped + 1, "the displaced value was not freed exactly once");
+}
+
+/// Records every value `rcu` publishes from now on.
+fn published<T: Clone + Send + Sync + 'static>(rcu: &Rcu<T>) -> Arc<Mutex<Vec<T>>> {
+    let published = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&published);
+    rcu.on_update(move |value| recorded.lock().unwrap().push(value.clone()));
+    published
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn a_forwarded_stream_publishes_every_item_in_order() {
+    let rcu = Rcu::new(0u64);
+    let published = published(&rcu);
+    stream::iter(1..=100).map(Ok).forward(rcu.sink()).await.unwrap();
+    assert_eq!(rcu.read(), 100, "the last item isn't the value");
+    assert_eq!(*published.lock().unwrap(), (1..=100).collect::<Vec<_>>());
+    // Closed once the stream ended
+    assert!(rcu.is_frozen());
+    assert!(rcu.set(101).is_err());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn a_latest_wins_sink_skips_what_is_sent_during_a_grace_period() {
+    let rcu = after(0u64, 1);
+    let published = published(&rcu);
+    let released = Arc::new(AtomicBool::new(false));
+    let reader = slow_reader(&rcu, &released);
+    let ticks = ticker();
+    stream::iter(2..=100).map(Ok).forward(rcu.sink().latest_wins()).await.unwrap();
+    assert!(released.load(SeqCst), "didn't wait for the grace period of the first publish");
+    assert_eq!(rcu.read(), 100);
+    let published = published.lock().unwrap();
+    assert_eq!(published.first(), Some(&2));
+    assert_eq!(published.last(), Some(&100));
+    assert!(published.len() < 99, "published every item: {published:?}");
+    let ticked = ticks.load(SeqCst);
+    assert!(ticked >= 10, "the other task only ran {ticked} times");
+    reader.join().unwrap();
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn a_refused_item_fails_the_forward() {
+    let rcu = Rcu::new(0u64);
+    rcu.set_invariant(|n| if *n < 3 { Ok(()) } else { Err(format!("{n} is too big")) });
+    let forwarded = stream::iter(1..=5).map(Ok).forward(rcu.sink()).await;
+    assert!(matches!(forwarded, Err(PublishError::Rejected { value: 3, .. })), "{forwarded:?}");
+    assert_eq!(rcu.read(), 2);
+    // Not closed, the stream never ended
+    assert!(!rcu.is_frozen());
+}
@@ -0,0 +1,92 @@
+//! The behaviour of the code `#[derive(RcuFields)]` generates.
+#![cfg(feature = "derive")]
+
+use std::sync::Barrier;
+use std::thread;
+use std::time::Duration;
+
+use rcu_rust::{PublishError, RcuFields};
+
+#[derive(Clone, Debug, PartialEq, RcuFields)]
+struct Config {
+    timeout: Duration,
+    #[rcu(rename = "urls")]
+    endpoints: Vec<String>,
+    #[rcu(skip)]
+    name: String,
+}
+
+fn config() -> Config {
+    Config {
+        timeout: Duration::from_secs(1),
+        endpoints: vec!["a".into()],
+        name: "svc".into(),
+    }
+}
+
+#[test]
+fn snapshot_of_from_round_trips() {
+    assert_eq!(ConfigRcu::from(config()).snapshot(), config());
+}
+
+#[test]
+fn setters_publish_to_their_field_only() {
+    let rcu = ConfigRcu::from(config());
+    rcu.set_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(rcu.timeout(), Duration::from_secs(5));
+    assert_eq!(rcu.urls(), ["a"]);
+    assert_eq!(rcu.timeout_rcu().version(), 1);
+    assert_eq!(rcu.urls_rcu().version(), 0);
+    rcu.set_urls(vec!["b".into(), "c".into()]).unwrap();
+    let snapshot = rcu.snapshot();
+    assert_eq!(snapshot.timeout, Duration::from_secs(5));
+    assert_eq!(snapshot.endpoints, ["b", "c"]);
+    assert_eq!(snapshot.name, "svc");
+}
+
+#[test]
+fn setters_go_through_the_field_rcu() {
+    let rcu = ConfigRcu::from(config());
+    rcu.urls_rcu().set_invariant(|urls| if urls.is_empty() { Err("no urls".into()) } else { Ok(()) });
+    let refused = rcu.set_urls(Vec::new());
+    assert!(matches!(refused, Err(PublishError::Rejected { .. })));
+    assert_eq!(rcu.urls(), ["a"]);
+}
+
+#[derive(Clone, RcuFields)]
+#[rcu(name = "Cells")]
+struct Pair<A> {
+    first: A,
+    second: A,
+}
+
+#[test]
+fn generic_structs_and_custom_names() {
+    let cells = Cells::from(Pair { first: 1u8, second: 2 });
+    cells.set_second(3).unwrap();
+    let pair = cells.snapshot();
+    assert_eq!((pair.first, pair.second), (1, 3));
+}
+
+#[test]
+fn fields_are_published_independently() {
+    let rcu = ConfigRcu::from(config());
+    let start = Barrier::new(2);
+    thread::scope(|s| {
+        s.spawn(|| {
+            start.wait();
+            for i in 0..100 {
+                rcu.set_timeout(Duration::from_millis(i)).unwrap();
+            }
+        });
+        s.spawn(|| {
+            start.wait();
+            for i in 0..100 {
+                rcu.set_urls(vec![i.to_string()]).unwrap();
+            }
+        });
+    });
+    assert_eq!(rcu.timeout(), Duration::from_millis(99));
+    assert_eq!(rcu.urls(), ["99"]);
+    assert_eq!((rcu.timeout_rcu().version(), rcu.urls_rcu().version()), (100, 100));
+}
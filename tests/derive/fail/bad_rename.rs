@@ -0,0 +1,9 @@
+use rcu_rust::RcuFields;
+
+#[derive(Clone, RcuFields)]
+struct Config {
+    #[rcu(rename = "not an ident")]
+    timeout: u64,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use rcu_rust::RcuFields;
+
+#[derive(Clone, RcuFields)]
+enum Mode {
+    Fast,
+    Safe,
+}
+
+fn main() {}
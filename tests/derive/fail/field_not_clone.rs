@@ -0,0 +1,10 @@
+use rcu_rust::RcuFields;
+
+struct Socket;
+
+#[derive(RcuFields)]
+struct Config {
+    socket: Socket,
+}
+
+fn main() {}
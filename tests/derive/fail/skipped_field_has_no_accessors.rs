@@ -0,0 +1,13 @@
+use rcu_rust::RcuFields;
+
+#[derive(Clone, RcuFields)]
+struct Config {
+    timeout: u64,
+    #[rcu(skip)]
+    name: String,
+}
+
+fn main() {
+    let config = ConfigRcu::from(Config { timeout: 1, name: "svc".into() });
+    config.set_name("other".into());
+}
@@ -0,0 +1,6 @@
+use rcu_rust::RcuFields;
+
+#[derive(Clone, RcuFields)]
+struct Empty;
+
+fn main() {}
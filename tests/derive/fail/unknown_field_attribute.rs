@@ -0,0 +1,9 @@
+use rcu_rust::RcuFields;
+
+#[derive(Clone, RcuFields)]
+struct Config {
+    #[rcu(hide)]
+    timeout: u64,
+}
+
+fn main() {}
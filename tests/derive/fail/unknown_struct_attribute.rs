@@ -0,0 +1,9 @@
+use rcu_rust::RcuFields;
+
+#[derive(Clone, RcuFields)]
+#[rcu(rename = "Cells")]
+struct Config {
+    timeout: u64,
+}
+
+fn main() {}
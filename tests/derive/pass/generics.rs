@@ -0,0 +1,19 @@
+use rcu_rust::RcuFields;
+
+#[derive(Clone, RcuFields)]
+#[rcu(name = "Slots")]
+pub struct Pair<A, B: Default> {
+    pub first: A,
+    #[rcu(rename = "second_value")]
+    pub second: B,
+    #[rcu(skip)]
+    pub label: &'static str,
+}
+
+fn main() {
+    let slots: Slots<u8, String> = Slots::from(Pair { first: 1, second: String::new(), label: "pair" });
+    slots.set_first(2).unwrap();
+    let _: String = slots.second_value();
+    let _: &rcu_rust::Rcu<String> = slots.second_value_rcu();
+    let _: Pair<u8, String> = slots.snapshot();
+}
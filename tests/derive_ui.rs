@@ -0,0 +1,10 @@
+//! The errors `#[derive(RcuFields)]` reports, and what compiles. Regenerate the expected output
+//! with `TRYBUILD=overwrite cargo test --features derive --test derive_ui`.
+#![cfg(feature = "derive")]
+
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/derive/pass/*.rs");
+    t.compile_fail("tests/derive/fail/*.rs");
+}
@@ -0,0 +1,173 @@
+//! Leak checks, measured by a global allocator counting the allocations the current thread makes
+//! and frees, so that tests running in parallel don't disturb each other. Every value owns an
+//! allocation of its own besides the one of its box, so a leaked value shows either way.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+use rcu_rust::{Rcu, RcuDomain};
+
+struct Counting;
+
+thread_local! {
+    /// Allocations made by this thread so far, less those it freed
+    static LIVE: Cell<isize> = const { Cell::new(0) };
+}
+
+// Safety: defers to `System` for everything, only counting
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // A thread being torn down has no counter anymore, its allocations aren't measured
+        let _ = LIVE.try_with(|live| live.set(live.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = LIVE.try_with(|live| live.set(live.get() - 1));
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static COUNTING: Counting = Counting;
+
+/// Allocations `f` leaves behind on this thread. `f` runs twice and only the second run counts, so
+/// that what is allocated once per thread, like the registration of a reader, isn't taken for a leak.
+fn leaked(mut f: impl FnMut()) -> isize {
+    f();
+    let before = LIVE.with(Cell::get);
+    f();
+    LIVE.with(Cell::get) - before
+}
+
+/// A value owning an allocation, whose clone panics if asked to.
+#[derive(Debug)]
+struct Payload {
+    data: Vec<u64>,
+    explosive: bool,
+}
+
+impl Payload {
+    fn new(n: u64) -> Self {
+        Payload {
+            data: vec![n; 8],
+            explosive: false,
+        }
+    }
+}
+
+impl Clone for Payload {
+    fn clone(&self) -> Self {
+        assert!(!self.explosive, "cloned an explosive payload");
+        Payload {
+            data: self.data.clone(),
+            explosive: false,
+        }
+    }
+}
+
+#[test]
+fn new_then_drop() {
+    assert_eq!(leaked(|| drop(Rcu::new(Payload::new(0)))), 0);
+}
+
+#[test]
+fn updates_then_drop() {
+    let leaks = leaked(|| {
+        let rcu = Rcu::new(Payload::new(0));
+        for i in 1..100 {
+            rcu.set(Payload::new(i)).unwrap();
+        }
+        rcu.barrier();
+        assert_eq!(rcu.outstanding_allocations(), 2, "more than the current and previous values");
+    });
+    assert_eq!(leaks, 0);
+}
+
+#[test]
+fn failed_updates() {
+    let leaks = leaked(|| {
+        let rcu = Rcu::new(Payload::new(0));
+        rcu.set_invariant(|payload| if payload.data[0] < 10 { Ok(()) } else { Err("too big".into()) });
+        rcu.set(Payload::new(1)).unwrap();
+        assert!(rcu.set(Payload::new(10)).is_err());
+        assert!(rcu.update_box(Box::new(Payload::new(11))).is_err());
+        assert!(rcu.update_with(|_| Payload::new(12)).is_err());
+        rcu.freeze();
+        assert!(rcu.set(Payload::new(2)).is_err());
+        assert_eq!(rcu.outstanding_allocations(), 2);
+    });
+    assert_eq!(leaks, 0);
+}
+
+#[test]
+fn panicking_clones() {
+    let leaks = leaked(|| {
+        let rcu = Rcu::new(Payload::new(0));
+        rcu.set(Payload {
+            explosive: true,
+            ..Payload::new(1)
+        })
+        .unwrap();
+        let updated = panic::catch_unwind(AssertUnwindSafe(|| rcu.update_with(Payload::clone)));
+        assert!(updated.is_err());
+        let staged = panic::catch_unwind(AssertUnwindSafe(|| drop(rcu.write_guard())));
+        assert!(staged.is_err());
+        // Still usable afterwards
+        rcu.set(Payload::new(2)).unwrap();
+    });
+    assert_eq!(leaks, 0);
+}
+
+#[test]
+fn into_box() {
+    let leaks = leaked(|| {
+        let rcu = Rcu::new(Payload::new(0));
+        rcu.set(Payload::new(1)).unwrap();
+        rcu.set(Payload::new(2)).unwrap();
+        assert_eq!(rcu.into_box().data[0], 2);
+    });
+    assert_eq!(leaks, 0);
+}
+
+#[test]
+fn dropped_with_a_retire_batch_held_back() {
+    let leaks = leaked(|| {
+        let rcu = Rcu::new(Payload::new(0)).with_retire_batching(1000, Duration::from_secs(3600));
+        for i in 1..10 {
+            rcu.set(Payload::new(i)).unwrap();
+        }
+        assert!(rcu.retire_batch_len() > 0);
+    });
+    assert_eq!(leaks, 0);
+}
+
+#[test]
+fn dropped_with_reclamation_deferred_past_a_deadline() {
+    let leaks = leaked(|| {
+        let rcu = Rcu::new(Payload::new(0));
+        rcu.set(Payload::new(1)).unwrap();
+        let guard = rcu.read_guard();
+        // May be deferred, the guard being a reader of the displaced value as far as some engines go
+        rcu.update_with_deadline(Payload::new(2), Duration::from_millis(1)).unwrap();
+        drop(guard);
+    });
+    assert_eq!(leaks, 0);
+}
+
+#[test]
+fn domain_members() {
+    let leaks = leaked(|| {
+        let domain = RcuDomain::new();
+        let members: Vec<_> = (0..4).map(|i| Rcu::new_in_domain(Payload::new(i), &domain)).collect();
+        for (i, member) in members.iter().enumerate() {
+            member.set(Payload::new(i as u64 + 10)).unwrap();
+            member.set(Payload::new(i as u64 + 20)).unwrap();
+        }
+        drop(members);
+        domain.barrier();
+    });
+    assert_eq!(leaks, 0);
+}
@@ -0,0 +1,61 @@
+//! Owned snapshots held across `.await`s by tasks of a tokio runtime, while publishes carry on.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rcu_rust::{OwnedSnapshot, Rcu};
+use tokio::sync::oneshot;
+
+/// Logs its drop.
+#[derive(Clone, Debug)]
+struct Logged {
+    value: u64,
+    dropped: Arc<Mutex<Vec<u64>>>,
+}
+
+impl Drop for Logged {
+    fn drop(&mut self) {
+        self.dropped.lock().unwrap().push(self.value);
+    }
+}
+
+#[test]
+fn snapshots_are_send_and_static() {
+    fn spawnable<S: Send + Sync + 'static>() {}
+    spawnable::<OwnedSnapshot<Vec<String>>>();
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn a_snapshot_held_across_awaits_keeps_only_its_own_value_alive() {
+    const PUBLISHES: u64 = 20;
+    let dropped = Arc::new(Mutex::new(Vec::new()));
+    let logged = |value| Logged {
+        value,
+        dropped: Arc::clone(&dropped),
+    };
+    let rcu = Arc::new(Rcu::new(logged(0)));
+    let (published, wait_published) = oneshot::channel();
+    let snapshot = rcu.read_owned();
+    let holder = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        wait_published.await.unwrap();
+        assert_eq!(snapshot.value, 0);
+        snapshot
+    });
+    for i in 1..=PUBLISHES {
+        rcu.set(logged(i)).unwrap();
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    rcu.barrier();
+    {
+        let dropped = dropped.lock().unwrap();
+        // Publishes weren't held up: every displaced value but the snapshot's was freed
+        assert_eq!(*dropped, (1..PUBLISHES - 1).collect::<Vec<_>>());
+    }
+    published.send(()).unwrap();
+    let snapshot = holder.await.unwrap();
+    assert_eq!(snapshot.value, 0, "the snapshot changed");
+    drop(snapshot);
+    assert_eq!(dropped.lock().unwrap().last(), Some(&0), "not freed once the snapshot was dropped");
+    assert_eq!(rcu.read_with(|current| current.value), PUBLISHES);
+}
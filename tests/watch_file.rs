@@ -0,0 +1,92 @@
+//! `watch_file` against real files in a temporary directory, rewritten the ways programs and
+//! editors do.
+#![cfg(feature = "watch")]
+
+use std::fs;
+use std::num::ParseIntError;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use rcu_rust::{watch_file, WatchError};
+
+/// How long a change may take to be noticed, debounced, read and published.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A directory of its own for each test, removed with everything in it once the test is done.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(test: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("rcu_rust_{test}_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn parse(bytes: &[u8]) -> Result<u32, ParseIntError> {
+    String::from_utf8_lossy(bytes).trim().parse()
+}
+
+#[test]
+fn rewriting_the_file_publishes_the_new_value() {
+    let dir = TempDir::new("rewriting");
+    let path = dir.0.join("value.txt");
+    fs::write(&path, "1").unwrap();
+    let (value, watcher) = watch_file(&path, parse).unwrap();
+    assert_eq!(value.read(), 1);
+
+    fs::write(&path, "2").unwrap();
+    assert!(value.wait_for_change_timeout(0, TIMEOUT).is_some(), "the rewrite went unnoticed");
+    assert_eq!(value.read(), 2);
+
+    // Editors write a new file and rename it over the old one
+    let version = value.version();
+    let saved = dir.0.join("value.txt.swp");
+    fs::write(&saved, "3").unwrap();
+    fs::rename(&saved, &path).unwrap();
+    assert!(value.wait_for_change_timeout(version, TIMEOUT).is_some(), "the replacement went unnoticed");
+    assert_eq!(value.read(), 3);
+    watcher.stop();
+    assert_eq!(value.version(), 2, "published the same contents twice");
+}
+
+#[test]
+fn unparsable_contents_keep_the_value_and_are_reported() {
+    let dir = TempDir::new("unparsable");
+    let path = dir.0.join("value.txt");
+    fs::write(&path, "1").unwrap();
+    let (value, watcher) = watch_file(&path, parse).unwrap();
+    let (errors, reported) = mpsc::channel();
+    watcher.on_error(move |err| {
+        let _ = errors.send(matches!(err, WatchError::Parse(_)));
+    });
+
+    fs::write(&path, "not a number").unwrap();
+    assert_eq!(reported.recv_timeout(TIMEOUT), Ok(true), "no parse error was reported");
+    assert_eq!((value.read(), value.version()), (1, 0));
+    assert_eq!(watcher.errors(), 1);
+
+    fs::write(&path, "4").unwrap();
+    assert!(value.wait_for_change_timeout(0, TIMEOUT).is_some(), "didn't recover from the error");
+    assert_eq!(value.read(), 4);
+}
+
+#[test]
+fn dropping_the_guard_stops_the_watcher() {
+    let dir = TempDir::new("dropping");
+    let path = dir.0.join("value.txt");
+    fs::write(&path, "1").unwrap();
+    let (value, watcher) = watch_file(&path, parse).unwrap();
+    drop(watcher);
+    fs::write(&path, "2").unwrap();
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!((value.read(), value.version()), (1, 0));
+}